@@ -0,0 +1,3 @@
+mod site_indexing;
+
+pub use site_indexing::*;
@@ -0,0 +1,210 @@
+use axum::{debug_handler, response::IntoResponse};
+use chrono::NaiveDateTime;
+use html_escape::encode_text;
+use http::{header::CONTENT_TYPE, StatusCode};
+use intercode_entities::{
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_variables, events, pages,
+};
+use intercode_graphql_core::query_data::QueryData;
+use intercode_liquid::url_builder::UrlBuilder;
+use intercode_server::QueryDataFromRequest;
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter, QueryOrder};
+use tracing::log::error;
+
+/// The CMS variable a site can use to replace the default robots.txt rules.  The Sitemap line is
+/// added either way, and hidden conventions ignore it.
+pub const ROBOTS_TXT_VARIABLE: &str = "robots_txt";
+
+const DEFAULT_ROBOTS_RULES: &str = "User-agent: *\nDisallow: /admin\n";
+const HIDDEN_ROBOTS_RULES: &str = "User-agent: *\nDisallow: /\n";
+
+struct SitemapEntry {
+  url: String,
+  last_modified: Option<NaiveDateTime>,
+}
+
+fn internal_error(err: DbErr) -> StatusCode {
+  error!("{}", err);
+  StatusCode::INTERNAL_SERVER_ERROR
+}
+
+fn absolute_url(url_builder: &UrlBuilder, path: &str) -> String {
+  url_builder
+    .absolute_url(path)
+    .unwrap_or_else(|_| path.to_string())
+}
+
+fn sitemap_xml(entries: &[SitemapEntry]) -> String {
+  let mut xml = String::from(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+  );
+
+  for entry in entries {
+    xml.push_str("  <url>\n");
+    xml.push_str(&format!("    <loc>{}</loc>\n", encode_text(&entry.url)));
+    if let Some(last_modified) = entry.last_modified {
+      xml.push_str(&format!(
+        "    <lastmod>{}</lastmod>\n",
+        last_modified.format("%Y-%m-%dT%H:%M:%SZ")
+      ));
+    }
+    xml.push_str("  </url>\n");
+  }
+
+  xml.push_str("</urlset>\n");
+  xml
+}
+
+fn robots_txt(hidden: bool, configured_rules: Option<&str>, sitemap_url: &str) -> String {
+  if hidden {
+    return HIDDEN_ROBOTS_RULES.to_string();
+  }
+
+  let rules = configured_rules
+    .map(str::trim)
+    .filter(|rules| !rules.is_empty())
+    .unwrap_or(DEFAULT_ROBOTS_RULES.trim());
+
+  format!("{}\n\nSitemap: {}\n", rules, sitemap_url)
+}
+
+async fn sitemap_entries(
+  query_data: &QueryData,
+  cms_parent: &CmsParent,
+) -> Result<Vec<SitemapEntry>, DbErr> {
+  let db = query_data.db();
+  let url_builder = UrlBuilder::for_cms_parent(db, cms_parent).await?;
+
+  let mut entries = cms_parent
+    .pages()
+    .filter(pages::Column::HiddenFromSearch.eq(false))
+    .order_by_asc(pages::Column::Slug)
+    .all(db)
+    .await?
+    .into_iter()
+    .filter_map(|page| {
+      page.slug.as_deref().map(|slug| SitemapEntry {
+        url: url_builder.page_url(slug),
+        last_modified: page.updated_at,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let Some(convention) = query_data.convention() else {
+    return Ok(entries);
+  };
+
+  if convention.schedule_is_public() {
+    entries.push(SitemapEntry {
+      url: absolute_url(&url_builder, "/events/schedule"),
+      last_modified: None,
+    });
+  }
+
+  if convention.event_list_is_public() {
+    entries.push(SitemapEntry {
+      url: absolute_url(&url_builder, "/events"),
+      last_modified: None,
+    });
+
+    let events = events::Entity::find()
+      .filter(events::Column::ConventionId.eq(convention.id))
+      .filter(events::Column::Status.eq("active"))
+      .order_by_asc(events::Column::Id)
+      .all(db)
+      .await?;
+    entries.extend(events.into_iter().map(|event| SitemapEntry {
+      url: absolute_url(&url_builder, &format!("/events/{}", event.id)),
+      last_modified: event.updated_at,
+    }));
+  }
+
+  Ok(entries)
+}
+
+/// A sitemap of the site's public pages, plus its event list and schedule if those are public.
+/// Hidden conventions don't get one.
+#[debug_handler]
+pub async fn sitemap(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+) -> Result<impl IntoResponse, StatusCode> {
+  if query_data
+    .convention()
+    .is_some_and(|convention| convention.hidden)
+  {
+    return Err(StatusCode::NOT_FOUND);
+  }
+
+  let entries = sitemap_entries(&query_data, query_data.cms_parent())
+    .await
+    .map_err(internal_error)?;
+
+  Ok(([(CONTENT_TYPE, "application/xml")], sitemap_xml(&entries)))
+}
+
+#[debug_handler]
+pub async fn robots(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+) -> Result<impl IntoResponse, StatusCode> {
+  let cms_parent = query_data.cms_parent();
+  let hidden = query_data
+    .convention()
+    .is_some_and(|convention| convention.hidden);
+
+  let configured_rules = cms_parent
+    .cms_variables()
+    .filter(cms_variables::Column::Key.eq(ROBOTS_TXT_VARIABLE))
+    .one(query_data.db())
+    .await
+    .map_err(internal_error)?
+    .and_then(|variable| variable.value)
+    .and_then(|value| value.as_str().map(str::to_string));
+
+  let sitemap_url = absolute_url(&UrlBuilder::new(cms_parent), "/sitemap.xml");
+
+  Ok((
+    [(CONTENT_TYPE, "text/plain")],
+    robots_txt(hidden, configured_rules.as_deref(), &sitemap_url),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn builds_sitemap_xml() {
+    let xml = sitemap_xml(&[
+      SitemapEntry {
+        url: "https://con.example.com/pages/faq?a=1&b=2".to_string(),
+        last_modified: NaiveDateTime::from_timestamp_opt(1_700_000_000, 0),
+      },
+      SitemapEntry {
+        url: "https://con.example.com/events".to_string(),
+        last_modified: None,
+      },
+    ]);
+
+    assert!(xml.contains("<loc>https://con.example.com/pages/faq?a=1&amp;b=2</loc>"));
+    assert!(xml.contains("<lastmod>2023-11-14T22:13:20Z</lastmod>"));
+    assert!(xml.contains("<loc>https://con.example.com/events</loc>\n  </url>"));
+  }
+
+  #[test]
+  fn hidden_sites_disallow_everything() {
+    assert_eq!(
+      robots_txt(
+        true,
+        Some("User-agent: *\nAllow: /"),
+        "https://con.example.com/sitemap.xml"
+      ),
+      HIDDEN_ROBOTS_RULES
+    );
+    assert_eq!(
+      robots_txt(false, None, "https://con.example.com/sitemap.xml"),
+      "User-agent: *\nDisallow: /admin\n\nSitemap: https://con.example.com/sitemap.xml\n"
+    );
+  }
+}
@@ -0,0 +1,107 @@
+//! Just enough of ActiveStorage to give imported CMS files uploads of their own.  Blobs are stored
+//! the way the Rails app stores them: S3 objects in AWS_S3_BUCKET for the "amazon" service.
+
+use std::env;
+
+use aws_config::{BehaviorVersion, SdkConfig};
+use chrono::NaiveDateTime;
+use intercode_entities::active_storage_blobs;
+use rand::Rng;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbErr};
+use tokio::sync::OnceCell;
+
+const S3_SERVICE_NAME: &str = "amazon";
+const BLOB_KEY_LENGTH: usize = 28;
+const BLOB_KEY_ALPHABET: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+static AWS_CONFIG: OnceCell<SdkConfig> = OnceCell::const_new();
+
+/// A new random blob key, in the same format ActiveStorage uses (28 lowercase base36 characters)
+fn generate_blob_key() -> String {
+  let mut rng = rand::thread_rng();
+  (0..BLOB_KEY_LENGTH)
+    .map(|_| BLOB_KEY_ALPHABET[rng.gen_range(0..BLOB_KEY_ALPHABET.len())] as char)
+    .collect()
+}
+
+/// Whether we know how to copy blobs stored with the given ActiveStorage service
+pub fn can_copy_blobs_in(service_name: &str) -> bool {
+  service_name == S3_SERVICE_NAME && env::var("AWS_S3_BUCKET").is_ok()
+}
+
+async fn copy_s3_object(source_key: &str, destination_key: &str) -> Result<(), DbErr> {
+  let bucket =
+    env::var("AWS_S3_BUCKET").map_err(|_| DbErr::Custom("AWS_S3_BUCKET is not set".to_string()))?;
+  let config = AWS_CONFIG
+    .get_or_init(|| aws_config::load_defaults(BehaviorVersion::latest()))
+    .await;
+
+  aws_sdk_s3::Client::new(config)
+    .copy_object()
+    .bucket(&bucket)
+    .copy_source(format!("{}/{}", bucket, source_key))
+    .key(destination_key)
+    .send()
+    .await
+    .map_err(|err| {
+      DbErr::Custom(format!(
+        "Couldn't copy upload {} to {}: {}",
+        source_key, destination_key, err
+      ))
+    })?;
+
+  Ok(())
+}
+
+/// Makes a new blob with the same contents as an existing one.  Unless `copy_object` is false
+/// (as it is for dry runs), the stored object is copied too.  The copy happens straight away,
+/// so if the transaction the new blob is saved in rolls back, the copied object is left behind
+/// unattached.
+pub async fn copy_blob<C: ConnectionTrait>(
+  db: &C,
+  blob: &active_storage_blobs::Model,
+  now: NaiveDateTime,
+  copy_object: bool,
+) -> Result<active_storage_blobs::Model, DbErr> {
+  if !can_copy_blobs_in(&blob.service_name) {
+    return Err(DbErr::Custom(format!(
+      "Can't copy uploads stored with the {} service",
+      blob.service_name
+    )));
+  }
+
+  let key = generate_blob_key();
+  if copy_object {
+    copy_s3_object(&blob.key, &key).await?;
+  }
+
+  active_storage_blobs::ActiveModel {
+    key: ActiveValue::Set(key),
+    filename: ActiveValue::Set(blob.filename.clone()),
+    content_type: ActiveValue::Set(blob.content_type.clone()),
+    metadata: ActiveValue::Set(blob.metadata.clone()),
+    service_name: ActiveValue::Set(blob.service_name.clone()),
+    byte_size: ActiveValue::Set(blob.byte_size),
+    checksum: ActiveValue::Set(blob.checksum.clone()),
+    created_at: ActiveValue::Set(now),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn generates_activestorage_style_keys() {
+    let key = generate_blob_key();
+
+    assert_eq!(key.len(), BLOB_KEY_LENGTH);
+    assert!(key
+      .chars()
+      .all(|c| c.is_ascii_digit() || c.is_ascii_lowercase()));
+    assert_ne!(key, generate_blob_key());
+  }
+}
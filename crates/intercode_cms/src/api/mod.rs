@@ -1,3 +1,4 @@
+pub mod mutations;
 pub mod objects;
 pub mod partial_objects;
 pub mod policies;
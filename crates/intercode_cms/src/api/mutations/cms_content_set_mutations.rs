@@ -0,0 +1,54 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::cms_content_sets::{CmsContentSetConflictStrategy, CmsContentSetImportReport};
+
+#[derive(InputObject)]
+pub struct ExportCmsContentSetInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The convention to export content from.  If not given, the root site's content is exported.
+  #[graphql(name = "convention_id")]
+  pub convention_id: Option<ID>,
+}
+
+#[derive(SimpleObject)]
+pub struct ExportCmsContentSetPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The content set, in the single-file JSON format
+  #[graphql(name = "content_set_json")]
+  pub content_set_json: String,
+}
+
+#[derive(InputObject)]
+pub struct ImportCmsContentSetInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The convention to import content into.  If not given, it goes into the root site.
+  #[graphql(name = "convention_id")]
+  pub convention_id: Option<ID>,
+  /// A content set in the single-file JSON format.  Either this or source_convention_id must be
+  /// given.
+  #[graphql(name = "content_set_json")]
+  pub content_set_json: Option<String>,
+  /// A convention to copy content from directly
+  #[graphql(name = "source_convention_id")]
+  pub source_convention_id: Option<ID>,
+  /// What to do with content that has the same name as existing content
+  #[graphql(name = "conflict_strategy")]
+  pub conflict_strategy: CmsContentSetConflictStrategy,
+  /// Report what the import would do without doing it
+  #[graphql(name = "dry_run", default = true)]
+  pub dry_run: bool,
+}
+
+#[derive(SimpleObject)]
+pub struct ImportCmsContentSetPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub report: CmsContentSetImportReport,
+}
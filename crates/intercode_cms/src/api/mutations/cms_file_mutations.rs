@@ -0,0 +1,57 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::api::objects::CmsFileType;
+
+#[derive(InputObject)]
+pub struct CreateCmsFileInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// An uploaded blob that isn't attached to anything yet
+  #[graphql(name = "blob_id")]
+  pub blob_id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateCmsFilePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_file")]
+  pub cms_file: CmsFileType,
+}
+
+#[derive(InputObject)]
+pub struct RenameCmsFileInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  pub filename: String,
+}
+
+#[derive(SimpleObject)]
+pub struct RenameCmsFilePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_file")]
+  pub cms_file: CmsFileType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsFileInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsFilePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_file")]
+  pub cms_file: CmsFileType,
+}
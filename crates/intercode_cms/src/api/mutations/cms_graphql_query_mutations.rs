@@ -0,0 +1,77 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+use intercode_entities::cms_graphql_queries;
+use sea_orm::ActiveValue;
+
+use crate::api::objects::CmsGraphqlQueryType;
+
+#[derive(InputObject)]
+pub struct CmsGraphqlQueryInput {
+  pub identifier: Option<String>,
+  pub query: Option<String>,
+  #[graphql(name = "admin_notes")]
+  pub admin_notes: Option<String>,
+}
+
+impl CmsGraphqlQueryInput {
+  /// Copies the fields that were given into the active model, leaving the rest alone
+  pub fn apply_to(self, active_model: &mut cms_graphql_queries::ActiveModel) {
+    if let Some(identifier) = self.identifier {
+      active_model.identifier = ActiveValue::Set(Some(identifier));
+    }
+    if let Some(query) = self.query {
+      active_model.query = ActiveValue::Set(Some(query));
+    }
+    if let Some(admin_notes) = self.admin_notes {
+      active_model.admin_notes = ActiveValue::Set(Some(admin_notes));
+    }
+  }
+}
+
+#[derive(InputObject)]
+pub struct CreateCmsGraphqlQueryInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub query: CmsGraphqlQueryInput,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateCmsGraphqlQueryPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub query: CmsGraphqlQueryType,
+}
+
+#[derive(InputObject)]
+pub struct UpdateCmsGraphqlQueryInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  pub query: CmsGraphqlQueryInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateCmsGraphqlQueryPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub query: CmsGraphqlQueryType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsGraphqlQueryInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsGraphqlQueryPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub query: CmsGraphqlQueryType,
+}
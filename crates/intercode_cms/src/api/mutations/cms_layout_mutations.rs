@@ -0,0 +1,87 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+use intercode_entities::cms_layouts;
+use sea_orm::ActiveValue;
+
+use crate::api::objects::CmsLayoutType;
+
+#[derive(InputObject)]
+pub struct CmsLayoutInput {
+  pub name: Option<String>,
+  pub content: Option<String>,
+  #[graphql(name = "navbar_classes")]
+  pub navbar_classes: Option<String>,
+  #[graphql(name = "admin_notes")]
+  pub admin_notes: Option<String>,
+}
+
+impl CmsLayoutInput {
+  /// Copies the fields that were given into the active model, leaving the rest alone
+  pub fn apply_to(self, active_model: &mut cms_layouts::ActiveModel) {
+    if let Some(name) = self.name {
+      active_model.name = ActiveValue::Set(Some(name));
+    }
+    if let Some(content) = self.content {
+      active_model.content = ActiveValue::Set(Some(content));
+    }
+    if let Some(navbar_classes) = self.navbar_classes {
+      active_model.navbar_classes = ActiveValue::Set(Some(navbar_classes));
+    }
+    if let Some(admin_notes) = self.admin_notes {
+      active_model.admin_notes = ActiveValue::Set(Some(admin_notes));
+    }
+  }
+}
+
+#[derive(InputObject)]
+pub struct CreateCmsLayoutInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_layout")]
+  pub cms_layout: CmsLayoutInput,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateCmsLayoutPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_layout")]
+  pub cms_layout: CmsLayoutType,
+}
+
+#[derive(InputObject)]
+pub struct UpdateCmsLayoutInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  #[graphql(name = "cms_layout")]
+  pub cms_layout: CmsLayoutInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateCmsLayoutPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_layout")]
+  pub cms_layout: CmsLayoutType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsLayoutInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsLayoutPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_layout")]
+  pub cms_layout: CmsLayoutType,
+}
@@ -0,0 +1,128 @@
+use async_graphql::{InputObject, MaybeUndefined, SimpleObject, ID};
+use intercode_entities::cms_navigation_items;
+use intercode_graphql_core::lax_id::LaxId;
+use sea_orm::ActiveValue;
+
+use crate::api::objects::CmsNavigationItemType;
+
+#[derive(InputObject)]
+pub struct CmsNavigationItemInput {
+  pub title: Option<String>,
+  pub position: Option<i32>,
+  /// The section this item goes in, or null to put it at the top level
+  #[graphql(name = "navigation_section_id")]
+  pub navigation_section_id: MaybeUndefined<ID>,
+  /// The page this item links to, or null if it's a section
+  #[graphql(name = "page_id")]
+  pub page_id: MaybeUndefined<ID>,
+}
+
+fn parse_maybe_undefined_id(
+  id: MaybeUndefined<ID>,
+) -> Result<Option<Option<i64>>, async_graphql::Error> {
+  Ok(match id {
+    MaybeUndefined::Value(id) => Some(Some(LaxId::parse(id)?)),
+    MaybeUndefined::Null => Some(None),
+    MaybeUndefined::Undefined => None,
+  })
+}
+
+impl CmsNavigationItemInput {
+  /// Copies the fields that were given into the active model, leaving the rest alone
+  pub fn apply_to(
+    self,
+    active_model: &mut cms_navigation_items::ActiveModel,
+  ) -> Result<(), async_graphql::Error> {
+    if let Some(title) = self.title {
+      active_model.title = ActiveValue::Set(Some(title));
+    }
+    if let Some(position) = self.position {
+      active_model.position = ActiveValue::Set(Some(position));
+    }
+    if let Some(navigation_section_id) = parse_maybe_undefined_id(self.navigation_section_id)? {
+      active_model.navigation_section_id = ActiveValue::Set(navigation_section_id);
+    }
+    if let Some(page_id) = parse_maybe_undefined_id(self.page_id)? {
+      active_model.page_id = ActiveValue::Set(page_id);
+    }
+    Ok(())
+  }
+}
+
+#[derive(InputObject)]
+pub struct CreateCmsNavigationItemInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_navigation_item")]
+  pub cms_navigation_item: CmsNavigationItemInput,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateCmsNavigationItemPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_navigation_item")]
+  pub cms_navigation_item: CmsNavigationItemType,
+}
+
+#[derive(InputObject)]
+pub struct UpdateCmsNavigationItemInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  #[graphql(name = "cms_navigation_item")]
+  pub cms_navigation_item: CmsNavigationItemInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateCmsNavigationItemPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_navigation_item")]
+  pub cms_navigation_item: CmsNavigationItemType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsNavigationItemInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsNavigationItemPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_navigation_item")]
+  pub cms_navigation_item: CmsNavigationItemType,
+}
+
+#[derive(InputObject)]
+pub struct UpdateCmsNavigationItemPositionInput {
+  pub id: ID,
+  pub position: i32,
+  #[graphql(name = "navigation_section_id")]
+  pub navigation_section_id: Option<ID>,
+}
+
+#[derive(InputObject)]
+pub struct SortCmsNavigationItemsInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "sort_items")]
+  pub sort_items: Vec<UpdateCmsNavigationItemPositionInput>,
+}
+
+#[derive(SimpleObject)]
+pub struct SortCmsNavigationItemsPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+}
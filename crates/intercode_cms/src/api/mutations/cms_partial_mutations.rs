@@ -0,0 +1,82 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+use intercode_entities::cms_partials;
+use sea_orm::ActiveValue;
+
+use crate::api::objects::CmsPartialType;
+
+#[derive(InputObject)]
+pub struct CmsPartialInput {
+  pub name: Option<String>,
+  pub content: Option<String>,
+  #[graphql(name = "admin_notes")]
+  pub admin_notes: Option<String>,
+}
+
+impl CmsPartialInput {
+  /// Copies the fields that were given into the active model, leaving the rest alone
+  pub fn apply_to(self, active_model: &mut cms_partials::ActiveModel) {
+    if let Some(name) = self.name {
+      active_model.name = ActiveValue::Set(name);
+    }
+    if let Some(content) = self.content {
+      active_model.content = ActiveValue::Set(Some(content));
+    }
+    if let Some(admin_notes) = self.admin_notes {
+      active_model.admin_notes = ActiveValue::Set(Some(admin_notes));
+    }
+  }
+}
+
+#[derive(InputObject)]
+pub struct CreateCmsPartialInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_partial")]
+  pub cms_partial: CmsPartialInput,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateCmsPartialPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_partial")]
+  pub cms_partial: CmsPartialType,
+}
+
+#[derive(InputObject)]
+pub struct UpdateCmsPartialInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  #[graphql(name = "cms_partial")]
+  pub cms_partial: CmsPartialInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateCmsPartialPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_partial")]
+  pub cms_partial: CmsPartialType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsPartialInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsPartialPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_partial")]
+  pub cms_partial: CmsPartialType,
+}
@@ -0,0 +1,46 @@
+use async_graphql::{InputObject, SimpleObject};
+
+use crate::api::objects::CmsVariableType;
+
+#[derive(InputObject)]
+pub struct CmsVariableInput {
+  pub key: String,
+  /// The variable's value, encoded as JSON
+  #[graphql(name = "value_json")]
+  pub value_json: String,
+}
+
+#[derive(InputObject)]
+pub struct SetCmsVariableInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_variable")]
+  pub cms_variable: CmsVariableInput,
+}
+
+#[derive(SimpleObject)]
+pub struct SetCmsVariablePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_variable")]
+  pub cms_variable: CmsVariableType,
+}
+
+#[derive(InputObject)]
+pub struct DeleteCmsVariableInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub key: String,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteCmsVariablePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "cms_variable")]
+  pub cms_variable: CmsVariableType,
+}
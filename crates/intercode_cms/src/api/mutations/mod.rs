@@ -0,0 +1,19 @@
+mod cms_content_set_mutations;
+mod cms_file_mutations;
+mod cms_graphql_query_mutations;
+mod cms_layout_mutations;
+mod cms_navigation_item_mutations;
+mod cms_partial_mutations;
+mod cms_variable_mutations;
+mod page_mutations;
+mod revert_cms_content_mutations;
+
+pub use cms_content_set_mutations::*;
+pub use cms_file_mutations::*;
+pub use cms_graphql_query_mutations::*;
+pub use cms_layout_mutations::*;
+pub use cms_navigation_item_mutations::*;
+pub use cms_partial_mutations::*;
+pub use cms_variable_mutations::*;
+pub use page_mutations::*;
+pub use revert_cms_content_mutations::*;
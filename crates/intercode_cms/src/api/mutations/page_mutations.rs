@@ -0,0 +1,127 @@
+use async_graphql::{InputObject, MaybeUndefined, SimpleObject, ID};
+use intercode_entities::pages;
+use intercode_graphql_core::lax_id::LaxId;
+use sea_orm::ActiveValue;
+
+use crate::api::objects::PageType;
+
+#[derive(InputObject)]
+pub struct PageInput {
+  pub name: Option<String>,
+  /// Defaults to a URL-friendly version of the name
+  pub slug: Option<String>,
+  pub content: Option<String>,
+  #[graphql(name = "admin_notes")]
+  pub admin_notes: Option<String>,
+  /// The layout to use, or null to use the default layout
+  #[graphql(name = "cms_layout_id")]
+  pub cms_layout_id: MaybeUndefined<ID>,
+  #[graphql(name = "skip_clickwrap_agreement")]
+  pub skip_clickwrap_agreement: Option<bool>,
+  #[graphql(name = "hidden_from_search")]
+  pub hidden_from_search: Option<bool>,
+}
+
+/// Turns a name into something usable in a URL, the way ActiveSupport's parameterize does for
+/// ASCII text
+pub fn parameterize(name: &str) -> String {
+  name
+    .to_lowercase()
+    .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join("-")
+}
+
+impl PageInput {
+  /// Copies the fields that were given into the active model, leaving the rest alone
+  pub fn apply_to(self, active_model: &mut pages::ActiveModel) -> Result<(), async_graphql::Error> {
+    if let Some(name) = self.name {
+      active_model.name = ActiveValue::Set(Some(name));
+    }
+    if let Some(slug) = self.slug {
+      active_model.slug = ActiveValue::Set(Some(slug));
+    }
+    if let Some(content) = self.content {
+      active_model.content = ActiveValue::Set(Some(content));
+    }
+    if let Some(admin_notes) = self.admin_notes {
+      active_model.admin_notes = ActiveValue::Set(Some(admin_notes));
+    }
+    match self.cms_layout_id {
+      MaybeUndefined::Value(id) => {
+        active_model.cms_layout_id = ActiveValue::Set(Some(LaxId::parse(id)?));
+      }
+      MaybeUndefined::Null => active_model.cms_layout_id = ActiveValue::Set(None),
+      MaybeUndefined::Undefined => {}
+    }
+    if let Some(skip_clickwrap_agreement) = self.skip_clickwrap_agreement {
+      active_model.skip_clickwrap_agreement = ActiveValue::Set(skip_clickwrap_agreement);
+    }
+    if let Some(hidden_from_search) = self.hidden_from_search {
+      active_model.hidden_from_search = ActiveValue::Set(hidden_from_search);
+    }
+    Ok(())
+  }
+}
+
+#[derive(InputObject)]
+pub struct CreatePageInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub page: PageInput,
+}
+
+#[derive(SimpleObject)]
+pub struct CreatePagePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub page: PageType,
+}
+
+#[derive(InputObject)]
+pub struct UpdatePageInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+  pub page: PageInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdatePagePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub page: PageType,
+}
+
+#[derive(InputObject)]
+pub struct DeletePageInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct DeletePagePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub page: PageType,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::parameterize;
+
+  #[test]
+  fn parameterizes_names_into_slugs() {
+    assert_eq!(parameterize("Hotel & Travel Info"), "hotel-travel-info");
+    assert_eq!(parameterize("  FAQ: 2024 edition!  "), "faq-2024-edition");
+    assert_eq!(parameterize("snake_case_stays"), "snake_case_stays");
+  }
+}
@@ -0,0 +1,23 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::api::objects::{CmsContentRevisionType, RevisionedCmsContentType};
+
+#[derive(InputObject)]
+pub struct RevertCmsContentInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The revision to restore the content to
+  #[graphql(name = "revision_id")]
+  pub revision_id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct RevertCmsContentPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub content: RevisionedCmsContentType,
+  /// The content's latest revision, which matches the restored content
+  pub revision: CmsContentRevisionType,
+}
@@ -0,0 +1,18 @@
+use async_graphql::SimpleObject;
+
+use crate::cms_revisions::DiffLine;
+
+use super::CmsContentRevisionType;
+
+#[derive(SimpleObject)]
+#[graphql(name = "CmsContentRevisionDiff")]
+pub struct CmsContentRevisionDiffType {
+  #[graphql(name = "from_revision")]
+  pub from_revision: CmsContentRevisionType,
+  #[graphql(name = "to_revision")]
+  pub to_revision: CmsContentRevisionType,
+  /// Fields other than the body that differ between the two revisions
+  #[graphql(name = "changed_fields")]
+  pub changed_fields: Vec<String>,
+  pub lines: Vec<DiffLine>,
+}
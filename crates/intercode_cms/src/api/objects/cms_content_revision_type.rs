@@ -0,0 +1,58 @@
+use async_graphql::*;
+use intercode_entities::{cms_content_revisions, users, UserNames};
+use intercode_graphql_core::{
+  model_backed_type, query_data::QueryData, scalars::DateScalar, scalars::JsonScalar,
+  ModelBackedType,
+};
+use sea_orm::EntityTrait;
+
+use crate::cms_revisions::{snapshot_body, CmsRevisionedContentKind};
+
+model_backed_type!(CmsContentRevisionType, cms_content_revisions::Model);
+
+#[Object(name = "CmsContentRevision")]
+impl CmsContentRevisionType {
+  async fn id(&self) -> ID {
+    self.model.id.into()
+  }
+
+  #[graphql(name = "author_name")]
+  async fn author_name(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+    let Some(author_id) = self.model.author_id else {
+      return Ok(None);
+    };
+    let query_data = ctx.data::<QueryData>()?;
+
+    Ok(
+      users::Entity::find_by_id(author_id)
+        .one(query_data.db())
+        .await?
+        .map(|user| user.name_without_nickname()),
+    )
+  }
+
+  /// The revisioned text: the content of a page, partial or layout, or a variable's value as JSON
+  async fn body(&self) -> String {
+    snapshot_body(&self.model.snapshot)
+  }
+
+  #[graphql(name = "content_id")]
+  async fn content_id(&self) -> ID {
+    self.model.content_id.into()
+  }
+
+  #[graphql(name = "content_type")]
+  async fn content_type(&self) -> Option<CmsRevisionedContentKind> {
+    CmsRevisionedContentKind::from_content_type(&self.model.content_type)
+  }
+
+  #[graphql(name = "created_at")]
+  async fn created_at(&self) -> Result<DateScalar> {
+    self.model.created_at.try_into()
+  }
+
+  /// All the fields saved in this revision
+  async fn snapshot(&self) -> JsonScalar {
+    JsonScalar(self.model.snapshot.clone())
+  }
+}
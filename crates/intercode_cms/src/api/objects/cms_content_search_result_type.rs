@@ -0,0 +1,14 @@
+use async_graphql::SimpleObject;
+
+use super::CmsContentType;
+
+#[derive(SimpleObject)]
+#[graphql(name = "CmsContentSearchResult")]
+pub struct CmsContentSearchResultType {
+  pub content: CmsContentType,
+  /// Higher is better.  Ranks only mean something relative to the other results of the same
+  /// search.
+  pub rank: f64,
+  /// An HTML excerpt of the content around the first match, with matches wrapped in <mark> tags
+  pub snippet: Option<String>,
+}
@@ -4,6 +4,7 @@ use async_graphql::*;
 use intercode_entities::cms_layouts;
 use intercode_graphql_core::{
   liquid_renderer::LiquidRenderer, model_backed_type, query_data::QueryData,
+  schema_data::SchemaData,
 };
 use intercode_liquid::{cms_parent_partial_source::PreloadPartialsStrategy, react_component_tag};
 use intercode_policies::{
@@ -45,6 +46,7 @@ impl CmsLayoutType {
     path: Option<String>,
   ) -> Result<Option<String>, Error> {
     let query_data = ctx.data::<QueryData>()?;
+    let schema_data = ctx.data::<SchemaData>()?;
     let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
 
     let cms_rendering_context = CmsRenderingContext::new(
@@ -57,13 +59,11 @@ impl CmsLayoutType {
       }),
       query_data,
       liquid_renderer.as_ref(),
-    );
+    )
+    .with_output_cache(&schema_data.cms_output_cache);
 
     cms_rendering_context
-      .render_liquid(
-        self.model.content.as_deref().unwrap_or(""),
-        Some(PreloadPartialsStrategy::ByLayout(&self.model)),
-      )
+      .render_layout_content(&self.model)
       .await
       .map(Some)
   }
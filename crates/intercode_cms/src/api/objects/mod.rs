@@ -1,3 +1,6 @@
+mod cms_content_revision_diff_type;
+mod cms_content_revision_type;
+mod cms_content_search_result_type;
 mod cms_content_type;
 mod cms_file_type;
 mod cms_graphql_query_type;
@@ -8,7 +11,11 @@ mod cms_variable_type;
 mod liquid_assign_type;
 mod notification_template_type;
 mod page_type;
+mod revisioned_cms_content_type;
 
+pub use cms_content_revision_diff_type::*;
+pub use cms_content_revision_type::*;
+pub use cms_content_search_result_type::*;
 pub use cms_content_type::*;
 pub use cms_file_type::*;
 pub use cms_graphql_query_type::*;
@@ -19,3 +26,4 @@ pub use cms_variable_type::*;
 pub use liquid_assign_type::*;
 pub use notification_template_type::*;
 pub use page_type::*;
+pub use revisioned_cms_content_type::*;
@@ -4,9 +4,8 @@ use async_graphql::*;
 use intercode_entities::pages;
 use intercode_graphql_core::{
   liquid_renderer::LiquidRenderer, load_one_by_model_id, loader_result_to_many, model_backed_type,
-  query_data::QueryData, ModelBackedType,
+  query_data::QueryData, schema_data::SchemaData, ModelBackedType,
 };
-use intercode_liquid::cms_parent_partial_source::PreloadPartialsStrategy;
 use intercode_policies::{
   AuthorizationInfo, ModelBackedTypeGuardablePolicy, Policy, ReadManageAction,
 };
@@ -44,18 +43,15 @@ impl PageType {
 
   #[graphql(name = "content_html")]
   async fn content_html(&self, ctx: &Context<'_>) -> Result<String, Error> {
-    if let Some(content) = &self.model.content {
+    if self.model.content.is_some() {
       let query_data = ctx.data::<QueryData>()?;
+      let schema_data = ctx.data::<SchemaData>()?;
       let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
       let cms_rendering_context =
-        CmsRenderingContext::new(object!({}), query_data, liquid_renderer.as_ref());
-
-      cms_rendering_context
-        .render_liquid(
-          content.as_str(),
-          Some(PreloadPartialsStrategy::ByPage(&self.model)),
-        )
-        .await
+        CmsRenderingContext::new(object!({}), query_data, liquid_renderer.as_ref())
+          .with_output_cache(&schema_data.cms_output_cache);
+
+      cms_rendering_context.render_page_content(&self.model).await
     } else {
       Ok("".to_string())
     }
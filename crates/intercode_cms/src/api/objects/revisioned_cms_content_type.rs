@@ -0,0 +1,12 @@
+use async_graphql::Union;
+
+use super::{CmsLayoutType, CmsPartialType, CmsVariableType, PageType};
+
+#[derive(Union)]
+#[graphql(name = "RevisionedCmsContent")]
+pub enum RevisionedCmsContentType {
+  Page(PageType),
+  Partial(CmsPartialType),
+  Layout(CmsLayoutType),
+  Variable(CmsVariableType),
+}
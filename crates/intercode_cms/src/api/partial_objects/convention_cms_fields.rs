@@ -12,10 +12,12 @@ use sea_orm::{ColumnTrait, QueryFilter};
 use crate::api::objects::{LiquidAssignType, NotificationTemplateType};
 use crate::{
   api::objects::{
-    CmsContentType, CmsFileType, CmsGraphqlQueryType, CmsLayoutType, CmsNavigationItemType,
-    CmsPartialType, CmsVariableType, PageType,
+    CmsContentRevisionDiffType, CmsContentRevisionType, CmsContentSearchResultType, CmsContentType,
+    CmsFileType, CmsGraphqlQueryType, CmsLayoutType, CmsNavigationItemType, CmsPartialType,
+    CmsVariableType, PageType,
   },
   cms_parent_implementation::CmsParentImplementation,
+  cms_revisions::CmsRevisionedContentKind,
   CmsRenderingContext,
 };
 
@@ -46,6 +48,26 @@ impl ConventionCmsFields {
       .map(Some)
   }
 
+  async fn cms_content_revisions(
+    &self,
+    ctx: &Context<'_>,
+    content_type: CmsRevisionedContentKind,
+    content_id: ID,
+  ) -> Result<Vec<CmsContentRevisionType>, Error> {
+    CmsParentImplementation::cms_content_revisions(self, ctx, content_type, content_id).await
+  }
+
+  /// A line-by-line comparison of two revisions of the same content
+  async fn cms_content_revision_diff(
+    &self,
+    ctx: &Context<'_>,
+    from_revision_id: ID,
+    to_revision_id: ID,
+  ) -> Result<CmsContentRevisionDiffType, Error> {
+    CmsParentImplementation::cms_content_revision_diff(self, ctx, from_revision_id, to_revision_id)
+      .await
+  }
+
   async fn cms_files(&self, ctx: &Context<'_>) -> Result<Vec<CmsFileType>, Error> {
     CmsParentImplementation::cms_files(self, ctx).await
   }
@@ -146,6 +168,15 @@ impl ConventionCmsFields {
     CmsParentImplementation::typeahead_search_cms_content(self, ctx, name).await
   }
 
+  async fn search_cms_content(
+    &self,
+    ctx: &Context<'_>,
+    query: String,
+    limit: Option<i32>,
+  ) -> Result<Vec<CmsContentSearchResultType>, Error> {
+    CmsParentImplementation::search_cms_content(self, ctx, query, limit).await
+  }
+
   #[graphql(name = "pre_schedule_content_html")]
   async fn pre_schedule_content_html(&self, ctx: &Context<'_>) -> Result<Option<String>, Error> {
     let query_data = ctx.data::<QueryData>()?;
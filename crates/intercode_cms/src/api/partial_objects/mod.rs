@@ -1,11 +1,13 @@
 mod ability_cms_fields;
 mod cms_content_group_cms_fields;
 mod convention_cms_fields;
+mod mutation_root_cms_fields;
 mod query_root_cms_fields;
 mod root_site_cms_fields;
 
 pub use ability_cms_fields::*;
 pub use cms_content_group_cms_fields::*;
 pub use convention_cms_fields::*;
+pub use mutation_root_cms_fields::*;
 pub use query_root_cms_fields::*;
 pub use root_site_cms_fields::*;
@@ -0,0 +1,1372 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+use chrono::Utc;
+use intercode_entities::{
+  active_storage_attachments, active_storage_blobs, cms_content_group_associations,
+  cms_content_revisions, cms_files, cms_files_layouts, cms_files_pages, cms_graphql_queries,
+  cms_layouts, cms_layouts_partials, cms_navigation_items,
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_partials, cms_partials_pages, cms_variables, conventions, pages, root_sites,
+};
+use intercode_full_text_search::search_index::{
+  index_pages, remove_from_search_index, SearchableType,
+};
+use intercode_graphql_core::{
+  after_commit::AfterCommitHooks, lax_id::LaxId, liquid_renderer::LiquidRenderer,
+  query_data::QueryData, schema_data::SchemaData, ModelBackedType,
+};
+use intercode_policies::{ensure_action_permitted, AuthorizationInfo, ReadManageAction};
+use sea_orm::{
+  sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+  ModelTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, TryIntoModel,
+};
+
+use crate::{
+  api::{
+    mutations::*,
+    objects::{
+      CmsContentRevisionType, CmsFileType, CmsGraphqlQueryType, CmsLayoutType,
+      CmsNavigationItemType, CmsPartialType, CmsVariableType, PageType, RevisionedCmsContentType,
+    },
+    policies::{
+      ensure_can_manage_cms_parent, CmsFilePolicy, CmsGraphqlQueryPolicy, CmsLayoutPolicy,
+      CmsPartialPolicy, CmsVariablePolicy, PagePolicy,
+    },
+  },
+  cms_content_sets::{export_cms_content_set, import_cms_content_set, parse_content_set_json},
+  cms_output_cache::{invalidate_cms_output, CmsOutputInvalidation},
+  cms_references::{
+    delete_layout_references, delete_page_references, refresh_references_to_names,
+    update_layout_references, update_page_references,
+  },
+  cms_revisions::{
+    apply_revision, record_baseline_revision, record_revision, CmsRevisionedContentKind,
+  },
+};
+
+fn current_user_id(query_data: &QueryData) -> Option<i64> {
+  query_data.current_user().map(|user| user.id)
+}
+
+fn current_value<V: Clone + Into<sea_orm::Value>>(value: &ActiveValue<V>) -> Option<V> {
+  match value {
+    ActiveValue::Set(value) | ActiveValue::Unchanged(value) => Some(value.clone()),
+    ActiveValue::NotSet => None,
+  }
+}
+
+async fn invalidate_output(
+  ctx: &Context<'_>,
+  cms_parent: &CmsParent,
+  invalidation: CmsOutputInvalidation<'_>,
+) -> Result<()> {
+  let query_data = ctx.data::<QueryData>()?;
+  let schema_data = ctx.data::<SchemaData>()?;
+  let after_commit_hooks = ctx.data::<AfterCommitHooks>()?;
+  invalidate_cms_output(
+    query_data.db(),
+    &schema_data.cms_output_cache,
+    after_commit_hooks,
+    cms_parent,
+    invalidation,
+  )
+  .await?;
+  Ok(())
+}
+
+async fn validate_liquid(ctx: &Context<'_>, content: Option<&str>) -> Result<()> {
+  if let Some(content) = content {
+    let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
+    liquid_renderer
+      .parse_liquid(content)
+      .await
+      .map_err(|err| Error::new(format!("Liquid syntax error: {}", err.message)))?;
+  }
+
+  Ok(())
+}
+
+async fn delete_content_group_associations<C: ConnectionTrait>(
+  db: &C,
+  content_type: &str,
+  content_id: i64,
+) -> Result<(), DbErr> {
+  cms_content_group_associations::Entity::delete_many()
+    .filter(cms_content_group_associations::Column::ContentType.eq(content_type))
+    .filter(cms_content_group_associations::Column::ContentId.eq(content_id))
+    .exec(db)
+    .await?;
+  Ok(())
+}
+
+async fn find_page(ctx: &Context<'_>, id: ID) -> Result<pages::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let page = query_data
+    .cms_parent()
+    .pages()
+    .filter(pages::Column::Id.eq(LaxId::parse(id)?))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("Page not found"))?;
+
+  ensure_action_permitted::<PagePolicy, _>(ctx, &ReadManageAction::Manage, &page).await?;
+  Ok(page)
+}
+
+async fn save_page(
+  ctx: &Context<'_>,
+  mut active_model: pages::ActiveModel,
+) -> Result<pages::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_parent = query_data.cms_parent();
+  let db = query_data.db();
+
+  let slug = current_value(&active_model.slug)
+    .flatten()
+    .filter(|slug| !slug.trim().is_empty())
+    .or_else(|| {
+      current_value(&active_model.name)
+        .flatten()
+        .map(|name| parameterize(&name))
+    })
+    .ok_or_else(|| Error::new("Pages must have a name or a slug"))?;
+  if current_value(&active_model.slug).flatten().as_ref() != Some(&slug) {
+    active_model.slug = ActiveValue::Set(Some(slug.clone()));
+  }
+
+  let mut slug_scope = cms_parent
+    .pages()
+    .filter(pages::Column::Slug.eq(slug.as_str()));
+  if let Some(id) = current_value(&active_model.id) {
+    slug_scope = slug_scope.filter(pages::Column::Id.ne(id));
+  }
+  if slug_scope.count(db).await? > 0 {
+    return Err(Error::new(format!(
+      "There is already a page with the slug {}",
+      slug
+    )));
+  }
+
+  if let Some(cms_layout_id) = current_value(&active_model.cms_layout_id).flatten() {
+    let layout_count = cms_parent
+      .cms_layouts()
+      .filter(cms_layouts::Column::Id.eq(cms_layout_id))
+      .count(db)
+      .await?;
+    if layout_count == 0 {
+      return Err(Error::new("Layout not found"));
+    }
+  }
+
+  validate_liquid(
+    ctx,
+    current_value(&active_model.content).flatten().as_deref(),
+  )
+  .await?;
+
+  if let Some(id) = current_value(&active_model.id) {
+    if let Some(existing) = pages::Entity::find_by_id(id).one(db).await? {
+      record_baseline_revision(db, &existing).await?;
+    }
+  }
+
+  active_model.updated_at = ActiveValue::Set(Some(Utc::now().naive_utc()));
+  let page = active_model.save(db).await?.try_into_model()?;
+  update_page_references(db, cms_parent, &page).await?;
+  record_revision(db, &page, current_user_id(query_data)).await?;
+  index_pages(db, std::slice::from_ref(&page)).await?;
+  invalidate_output(ctx, cms_parent, CmsOutputInvalidation::Page(page.id)).await?;
+
+  Ok(page)
+}
+
+async fn find_cms_partial(ctx: &Context<'_>, id: ID) -> Result<cms_partials::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_partial = query_data
+    .cms_parent()
+    .cms_partials()
+    .filter(cms_partials::Column::Id.eq(LaxId::parse(id)?))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("Partial not found"))?;
+
+  ensure_action_permitted::<CmsPartialPolicy, _>(ctx, &ReadManageAction::Manage, &cms_partial)
+    .await?;
+  Ok(cms_partial)
+}
+
+async fn save_cms_partial(
+  ctx: &Context<'_>,
+  mut active_model: cms_partials::ActiveModel,
+  previous_name: Option<&str>,
+) -> Result<cms_partials::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_parent = query_data.cms_parent();
+  let db = query_data.db();
+
+  let name = current_value(&active_model.name)
+    .filter(|name| !name.trim().is_empty())
+    .ok_or_else(|| Error::new("Partials must have a name"))?;
+
+  let mut name_scope = cms_parent
+    .cms_partials()
+    .filter(cms_partials::Column::Name.eq(name.as_str()));
+  if let Some(id) = current_value(&active_model.id) {
+    name_scope = name_scope.filter(cms_partials::Column::Id.ne(id));
+  }
+  if name_scope.count(db).await? > 0 {
+    return Err(Error::new(format!(
+      "There is already a partial named {}",
+      name
+    )));
+  }
+
+  if let Some(id) = current_value(&active_model.id) {
+    if let Some(existing) = cms_partials::Entity::find_by_id(id).one(db).await? {
+      record_baseline_revision(db, &existing).await?;
+    }
+  }
+
+  active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+  let cms_partial = active_model.save(db).await?.try_into_model()?;
+
+  let mut names = vec![cms_partial.name.as_str()];
+  if let Some(previous_name) = previous_name.filter(|previous_name| *previous_name != name) {
+    names.push(previous_name);
+  }
+  refresh_references_to_names(db, cms_parent, &names).await?;
+  record_revision(db, &cms_partial, current_user_id(query_data)).await?;
+  invalidate_output(ctx, cms_parent, CmsOutputInvalidation::ReferencesTo(&names)).await?;
+
+  Ok(cms_partial)
+}
+
+async fn find_cms_layout(ctx: &Context<'_>, id: ID) -> Result<cms_layouts::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_layout = query_data
+    .cms_parent()
+    .cms_layouts()
+    .filter(cms_layouts::Column::Id.eq(LaxId::parse(id)?))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("Layout not found"))?;
+
+  ensure_action_permitted::<CmsLayoutPolicy, _>(ctx, &ReadManageAction::Manage, &cms_layout)
+    .await?;
+  Ok(cms_layout)
+}
+
+async fn save_cms_layout(
+  ctx: &Context<'_>,
+  mut active_model: cms_layouts::ActiveModel,
+) -> Result<cms_layouts::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let db = query_data.db();
+
+  validate_liquid(
+    ctx,
+    current_value(&active_model.content).flatten().as_deref(),
+  )
+  .await?;
+
+  if let Some(id) = current_value(&active_model.id) {
+    if let Some(existing) = cms_layouts::Entity::find_by_id(id).one(db).await? {
+      record_baseline_revision(db, &existing).await?;
+    }
+  }
+
+  active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+  let cms_layout = active_model.save(db).await?.try_into_model()?;
+  update_layout_references(db, query_data.cms_parent(), &cms_layout).await?;
+  record_revision(db, &cms_layout, current_user_id(query_data)).await?;
+  invalidate_output(
+    ctx,
+    query_data.cms_parent(),
+    CmsOutputInvalidation::Layout(cms_layout.id),
+  )
+  .await?;
+
+  Ok(cms_layout)
+}
+
+async fn find_cms_file(
+  ctx: &Context<'_>,
+  id: ID,
+) -> Result<(cms_files::Model, Option<active_storage_attachments::Model>)> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_file = query_data
+    .cms_parent()
+    .cms_files()
+    .filter(cms_files::Column::Id.eq(LaxId::parse(id)?))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("File not found"))?;
+
+  ensure_action_permitted::<CmsFilePolicy, _>(ctx, &ReadManageAction::Manage, &cms_file).await?;
+
+  let attachment = active_storage_attachments::Entity::find()
+    .filter(active_storage_attachments::Column::RecordType.eq("CmsFile"))
+    .filter(active_storage_attachments::Column::Name.eq("file"))
+    .filter(active_storage_attachments::Column::RecordId.eq(cms_file.id))
+    .one(query_data.db())
+    .await?;
+
+  Ok((cms_file, attachment))
+}
+
+async fn ensure_filename_available(
+  ctx: &Context<'_>,
+  filename: &str,
+  except_cms_file_id: Option<i64>,
+) -> Result<()> {
+  let query_data = ctx.data::<QueryData>()?;
+  let mut cms_files_scope = query_data
+    .cms_parent()
+    .cms_files()
+    .select_only()
+    .column(cms_files::Column::Id);
+  if let Some(id) = except_cms_file_id {
+    cms_files_scope = cms_files_scope.filter(cms_files::Column::Id.ne(id));
+  }
+
+  let conflicting_count = active_storage_attachments::Entity::find()
+    .filter(active_storage_attachments::Column::RecordType.eq("CmsFile"))
+    .filter(active_storage_attachments::Column::Name.eq("file"))
+    .filter(active_storage_attachments::Column::RecordId.in_subquery(cms_files_scope.into_query()))
+    .inner_join(active_storage_blobs::Entity)
+    .filter(active_storage_blobs::Column::Filename.eq(filename))
+    .count(query_data.db())
+    .await?;
+
+  if conflicting_count > 0 {
+    Err(Error::new(format!(
+      "There is already a file named {}",
+      filename
+    )))
+  } else {
+    Ok(())
+  }
+}
+
+async fn find_cms_graphql_query(ctx: &Context<'_>, id: ID) -> Result<cms_graphql_queries::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let query = query_data
+    .cms_parent()
+    .cms_graphql_queries()
+    .filter(cms_graphql_queries::Column::Id.eq(LaxId::parse(id)?))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("GraphQL query not found"))?;
+
+  ensure_action_permitted::<CmsGraphqlQueryPolicy, _>(ctx, &ReadManageAction::Manage, &query)
+    .await?;
+  Ok(query)
+}
+
+async fn save_cms_graphql_query(
+  ctx: &Context<'_>,
+  mut active_model: cms_graphql_queries::ActiveModel,
+  previous_identifier: Option<&str>,
+) -> Result<cms_graphql_queries::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let db = query_data.db();
+
+  let identifier = current_value(&active_model.identifier)
+    .flatten()
+    .filter(|identifier| !identifier.trim().is_empty())
+    .ok_or_else(|| Error::new("GraphQL queries must have an identifier"))?;
+
+  let mut identifier_scope = query_data
+    .cms_parent()
+    .cms_graphql_queries()
+    .filter(cms_graphql_queries::Column::Identifier.eq(identifier.as_str()));
+  if let Some(id) = current_value(&active_model.id) {
+    identifier_scope = identifier_scope.filter(cms_graphql_queries::Column::Id.ne(id));
+  }
+  if identifier_scope.count(db).await? > 0 {
+    return Err(Error::new(format!(
+      "There is already a GraphQL query with the identifier {}",
+      identifier
+    )));
+  }
+
+  if let Some(query) = current_value(&active_model.query).flatten() {
+    parser::parse_query(query)
+      .map_err(|err| Error::new(format!("GraphQL syntax error: {}", err)))?;
+  }
+
+  active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+  let query = active_model.save(db).await?.try_into_model()?;
+
+  let mut identifiers = vec![identifier.as_str()];
+  if let Some(previous_identifier) =
+    previous_identifier.filter(|previous_identifier| *previous_identifier != identifier)
+  {
+    identifiers.push(previous_identifier);
+  }
+  invalidate_output(
+    ctx,
+    query_data.cms_parent(),
+    CmsOutputInvalidation::ReferencesTo(&identifiers),
+  )
+  .await?;
+
+  Ok(query)
+}
+
+async fn find_cms_navigation_item(
+  ctx: &Context<'_>,
+  id: i64,
+) -> Result<cms_navigation_items::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  query_data
+    .cms_parent()
+    .cms_navigation_items()
+    .filter(cms_navigation_items::Column::Id.eq(id))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| Error::new("Navigation item not found"))
+}
+
+/// Makes sure a navigation item's section and page (if it has them) belong to the same parent,
+/// and that the section is really a section
+async fn validate_navigation_item_references(
+  ctx: &Context<'_>,
+  navigation_section_id: Option<i64>,
+  page_id: Option<i64>,
+) -> Result<()> {
+  let query_data = ctx.data::<QueryData>()?;
+  let cms_parent = query_data.cms_parent();
+
+  if let Some(navigation_section_id) = navigation_section_id {
+    let section = find_cms_navigation_item(ctx, navigation_section_id).await?;
+    if section.page_id.is_some() || section.navigation_section_id.is_some() {
+      return Err(Error::new(
+        "Navigation items can only be placed in top-level sections",
+      ));
+    }
+  }
+
+  if let Some(page_id) = page_id {
+    let page_count = cms_parent
+      .pages()
+      .filter(pages::Column::Id.eq(page_id))
+      .count(query_data.db())
+      .await?;
+    if page_count == 0 {
+      return Err(Error::new("Page not found"));
+    }
+  }
+
+  Ok(())
+}
+
+async fn save_cms_navigation_item(
+  ctx: &Context<'_>,
+  mut active_model: cms_navigation_items::ActiveModel,
+) -> Result<cms_navigation_items::Model> {
+  let query_data = ctx.data::<QueryData>()?;
+  let db = query_data.db();
+  let navigation_section_id = current_value(&active_model.navigation_section_id).flatten();
+
+  validate_navigation_item_references(
+    ctx,
+    navigation_section_id,
+    current_value(&active_model.page_id).flatten(),
+  )
+  .await?;
+
+  if current_value(&active_model.position).flatten().is_none() {
+    // new items go at the end of their section
+    let siblings =
+      query_data
+        .cms_parent()
+        .cms_navigation_items()
+        .filter(match navigation_section_id {
+          Some(navigation_section_id) => {
+            cms_navigation_items::Column::NavigationSectionId.eq(navigation_section_id)
+          }
+          None => cms_navigation_items::Column::NavigationSectionId.is_null(),
+        });
+    let max_position: Option<Option<i32>> = siblings
+      .select_only()
+      .column_as(
+        Expr::col(cms_navigation_items::Column::Position).max(),
+        "max_position",
+      )
+      .into_tuple()
+      .one(db)
+      .await?;
+    active_model.position =
+      ActiveValue::Set(Some(max_position.flatten().map(|max| max + 1).unwrap_or(1)));
+  }
+
+  active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+  Ok(active_model.save(db).await?.try_into_model()?)
+}
+
+/// Content sets can be moved between any conventions, so only site admins can use them
+fn ensure_site_admin(ctx: &Context<'_>) -> Result<()> {
+  if ctx.data::<AuthorizationInfo>()?.site_admin_manage() {
+    Ok(())
+  } else {
+    Err(Error::new(
+      "Only site admins can export and import content sets",
+    ))
+  }
+}
+
+/// A convention, or the root site if no convention id is given
+async fn find_cms_parent<C: ConnectionTrait>(
+  db: &C,
+  convention_id: Option<ID>,
+) -> Result<CmsParent> {
+  match convention_id {
+    Some(convention_id) => Ok(
+      conventions::Entity::find_by_id(LaxId::parse(convention_id)?)
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::new("Convention not found"))?
+        .into(),
+    ),
+    None => Ok(
+      root_sites::Entity::find()
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::new("Root site not found"))?
+        .into(),
+    ),
+  }
+}
+
+#[derive(Default)]
+pub struct MutationRootCmsFields;
+
+#[Object]
+impl MutationRootCmsFields {
+  /// Creates a page in the current convention (or the root site, outside of a convention).  The
+  /// content must be valid Liquid.  If no slug is given, one is made from the name.
+  async fn create_page(
+    &self,
+    ctx: &Context<'_>,
+    input: CreatePageInput,
+  ) -> Result<CreatePagePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let mut active_model = pages::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      invariant: ActiveValue::Set(false),
+      skip_clickwrap_agreement: ActiveValue::Set(false),
+      hidden_from_search: ActiveValue::Set(false),
+      created_at: ActiveValue::Set(Some(Utc::now().naive_utc())),
+      ..Default::default()
+    };
+    input.page.apply_to(&mut active_model)?;
+    let page = save_page(ctx, active_model).await?;
+
+    Ok(CreatePagePayload {
+      client_mutation_id: input.client_mutation_id,
+      page: PageType::new(page),
+    })
+  }
+
+  async fn update_page(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdatePageInput,
+  ) -> Result<UpdatePagePayload> {
+    let page = find_page(ctx, input.id).await?;
+    let mut active_model: pages::ActiveModel = page.into();
+    input.page.apply_to(&mut active_model)?;
+    let page = save_page(ctx, active_model).await?;
+
+    Ok(UpdatePagePayload {
+      client_mutation_id: input.client_mutation_id,
+      page: PageType::new(page),
+    })
+  }
+
+  /// Deletes a page, along with any navigation links to it.  The root page and invariant pages
+  /// can't be deleted.
+  async fn delete_page(
+    &self,
+    ctx: &Context<'_>,
+    input: DeletePageInput,
+  ) -> Result<DeletePagePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let page = find_page(ctx, input.id).await?;
+
+    if page.invariant {
+      return Err(Error::new("This page can't be deleted"));
+    }
+    let root_page = query_data.cms_parent().root_page().one(db).await?;
+    if root_page.map(|root_page| root_page.id) == Some(page.id) {
+      return Err(Error::new("The root page can't be deleted"));
+    }
+
+    cms_navigation_items::Entity::delete_many()
+      .filter(cms_navigation_items::Column::PageId.eq(page.id))
+      .exec(db)
+      .await?;
+    delete_page_references(db, page.id).await?;
+    delete_content_group_associations(db, "Page", page.id).await?;
+    remove_from_search_index(db, SearchableType::Page, [page.id]).await?;
+    page.clone().delete(db).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::Page(page.id),
+    )
+    .await?;
+
+    Ok(DeletePagePayload {
+      client_mutation_id: input.client_mutation_id,
+      page: PageType::new(page),
+    })
+  }
+
+  /// Creates a partial.  Pages and layouts that already include a partial by this name start
+  /// preloading it.
+  async fn create_cms_partial(
+    &self,
+    ctx: &Context<'_>,
+    input: CreateCmsPartialInput,
+  ) -> Result<CreateCmsPartialPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let mut active_model = cms_partials::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      invariant: ActiveValue::Set(false),
+      created_at: ActiveValue::Set(Utc::now().naive_utc()),
+      ..Default::default()
+    };
+    input.cms_partial.apply_to(&mut active_model);
+    let cms_partial = save_cms_partial(ctx, active_model, None).await?;
+
+    Ok(CreateCmsPartialPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_partial: CmsPartialType::new(cms_partial),
+    })
+  }
+
+  async fn update_cms_partial(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateCmsPartialInput,
+  ) -> Result<UpdateCmsPartialPayload> {
+    let cms_partial = find_cms_partial(ctx, input.id).await?;
+    let previous_name = cms_partial.name.clone();
+    let mut active_model: cms_partials::ActiveModel = cms_partial.into();
+    input.cms_partial.apply_to(&mut active_model);
+    let cms_partial = save_cms_partial(ctx, active_model, Some(&previous_name)).await?;
+
+    Ok(UpdateCmsPartialPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_partial: CmsPartialType::new(cms_partial),
+    })
+  }
+
+  async fn delete_cms_partial(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsPartialInput,
+  ) -> Result<DeleteCmsPartialPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let cms_partial = find_cms_partial(ctx, input.id).await?;
+
+    if cms_partial.invariant {
+      return Err(Error::new("This partial can't be deleted"));
+    }
+
+    cms_partials_pages::Entity::delete_many()
+      .filter(cms_partials_pages::Column::CmsPartialId.eq(cms_partial.id))
+      .exec(db)
+      .await?;
+    cms_layouts_partials::Entity::delete_many()
+      .filter(cms_layouts_partials::Column::CmsPartialId.eq(cms_partial.id))
+      .exec(db)
+      .await?;
+    delete_content_group_associations(db, "CmsPartial", cms_partial.id).await?;
+    cms_partial.clone().delete(db).await?;
+    refresh_references_to_names(db, query_data.cms_parent(), &[cms_partial.name.as_str()]).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::ReferencesTo(&[cms_partial.name.as_str()]),
+    )
+    .await?;
+
+    Ok(DeleteCmsPartialPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_partial: CmsPartialType::new(cms_partial),
+    })
+  }
+
+  /// Creates a layout.  The content must be valid Liquid.
+  async fn create_cms_layout(
+    &self,
+    ctx: &Context<'_>,
+    input: CreateCmsLayoutInput,
+  ) -> Result<CreateCmsLayoutPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let mut active_model = cms_layouts::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      created_at: ActiveValue::Set(Utc::now().naive_utc()),
+      ..Default::default()
+    };
+    input.cms_layout.apply_to(&mut active_model);
+    let cms_layout = save_cms_layout(ctx, active_model).await?;
+
+    Ok(CreateCmsLayoutPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_layout: CmsLayoutType::new(cms_layout),
+    })
+  }
+
+  async fn update_cms_layout(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateCmsLayoutInput,
+  ) -> Result<UpdateCmsLayoutPayload> {
+    let cms_layout = find_cms_layout(ctx, input.id).await?;
+    let mut active_model: cms_layouts::ActiveModel = cms_layout.into();
+    input.cms_layout.apply_to(&mut active_model);
+    let cms_layout = save_cms_layout(ctx, active_model).await?;
+
+    Ok(UpdateCmsLayoutPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_layout: CmsLayoutType::new(cms_layout),
+    })
+  }
+
+  /// Deletes a layout.  Pages that used it switch to the default layout, which itself can't be
+  /// deleted.
+  async fn delete_cms_layout(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsLayoutInput,
+  ) -> Result<DeleteCmsLayoutPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let cms_layout = find_cms_layout(ctx, input.id).await?;
+
+    let default_layout = query_data.cms_parent().default_layout().one(db).await?;
+    if default_layout.map(|default_layout| default_layout.id) == Some(cms_layout.id) {
+      return Err(Error::new("The default layout can't be deleted"));
+    }
+
+    pages::Entity::update_many()
+      .col_expr(pages::Column::CmsLayoutId, Expr::value(Option::<i64>::None))
+      .filter(pages::Column::CmsLayoutId.eq(cms_layout.id))
+      .exec(db)
+      .await?;
+    delete_layout_references(db, cms_layout.id).await?;
+    delete_content_group_associations(db, "CmsLayout", cms_layout.id).await?;
+    cms_layout.clone().delete(db).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::Layout(cms_layout.id),
+    )
+    .await?;
+
+    Ok(DeleteCmsLayoutPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_layout: CmsLayoutType::new(cms_layout),
+    })
+  }
+
+  /// Attaches an uploaded blob to a new CMS file.  The blob must not be attached to anything
+  /// else, and its filename must not already be in use.
+  async fn create_cms_file(
+    &self,
+    ctx: &Context<'_>,
+    input: CreateCmsFileInput,
+  ) -> Result<CreateCmsFilePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let blob = active_storage_blobs::Entity::find_by_id(LaxId::parse(input.blob_id)?)
+      .one(db)
+      .await?
+      .ok_or_else(|| Error::new("Upload not found"))?;
+    let existing_attachment_count = active_storage_attachments::Entity::find()
+      .filter(active_storage_attachments::Column::BlobId.eq(blob.id))
+      .count(db)
+      .await?;
+    if existing_attachment_count > 0 {
+      return Err(Error::new("That upload is already in use"));
+    }
+    ensure_filename_available(ctx, &blob.filename, None).await?;
+
+    let now = Utc::now().naive_utc();
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let cms_file = cms_files::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      uploader_id: ActiveValue::Set(current_user_id(query_data)),
+      created_at: ActiveValue::Set(now),
+      updated_at: ActiveValue::Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    active_storage_attachments::ActiveModel {
+      name: ActiveValue::Set("file".to_string()),
+      record_type: ActiveValue::Set("CmsFile".to_string()),
+      record_id: ActiveValue::Set(cms_file.id),
+      blob_id: ActiveValue::Set(blob.id),
+      created_at: ActiveValue::Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    refresh_references_to_names(db, query_data.cms_parent(), &[blob.filename.as_str()]).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::ReferencesTo(&[blob.filename.as_str()]),
+    )
+    .await?;
+
+    Ok(CreateCmsFilePayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_file: CmsFileType::new(cms_file),
+    })
+  }
+
+  /// Renames a CMS file.  Content that refers to it by its old name will need to be updated.
+  async fn rename_cms_file(
+    &self,
+    ctx: &Context<'_>,
+    input: RenameCmsFileInput,
+  ) -> Result<RenameCmsFilePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let (cms_file, attachment) = find_cms_file(ctx, input.id).await?;
+    let attachment = attachment.ok_or_else(|| Error::new("File has no attachment"))?;
+    let blob = active_storage_blobs::Entity::find_by_id(attachment.blob_id)
+      .one(db)
+      .await?
+      .ok_or_else(|| Error::new("File has no attachment"))?;
+
+    let filename = input.filename.trim().to_string();
+    if filename.is_empty() {
+      return Err(Error::new("Files must have a name"));
+    }
+    ensure_filename_available(ctx, &filename, Some(cms_file.id)).await?;
+
+    let previous_filename = blob.filename.clone();
+    let mut blob: active_storage_blobs::ActiveModel = blob.into();
+    blob.filename = ActiveValue::Set(filename.clone());
+    blob.update(db).await?;
+
+    let mut active_model: cms_files::ActiveModel = cms_file.into();
+    active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+    let cms_file = active_model.update(db).await?;
+
+    let filenames = [previous_filename.as_str(), filename.as_str()];
+    refresh_references_to_names(db, query_data.cms_parent(), &filenames).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::ReferencesTo(&filenames),
+    )
+    .await?;
+
+    Ok(RenameCmsFilePayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_file: CmsFileType::new(cms_file),
+    })
+  }
+
+  /// Deletes a CMS file.  The uploaded blob is detached but left in storage.
+  async fn delete_cms_file(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsFileInput,
+  ) -> Result<DeleteCmsFilePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let (cms_file, attachment) = find_cms_file(ctx, input.id).await?;
+
+    cms_files_pages::Entity::delete_many()
+      .filter(cms_files_pages::Column::CmsFileId.eq(cms_file.id))
+      .exec(db)
+      .await?;
+    cms_files_layouts::Entity::delete_many()
+      .filter(cms_files_layouts::Column::CmsFileId.eq(cms_file.id))
+      .exec(db)
+      .await?;
+    delete_content_group_associations(db, "CmsFile", cms_file.id).await?;
+
+    let mut filename = None;
+    if let Some(attachment) = attachment {
+      filename = active_storage_blobs::Entity::find_by_id(attachment.blob_id)
+        .one(db)
+        .await?
+        .map(|blob| blob.filename);
+      attachment.delete(db).await?;
+    }
+    cms_file.clone().delete(db).await?;
+
+    if let Some(filename) = filename {
+      refresh_references_to_names(db, query_data.cms_parent(), &[filename.as_str()]).await?;
+      invalidate_output(
+        ctx,
+        query_data.cms_parent(),
+        CmsOutputInvalidation::ReferencesTo(&[filename.as_str()]),
+      )
+      .await?;
+    }
+
+    Ok(DeleteCmsFilePayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_file: CmsFileType::new(cms_file),
+    })
+  }
+
+  /// Creates or updates the variable with the given key
+  async fn set_cms_variable(
+    &self,
+    ctx: &Context<'_>,
+    input: SetCmsVariableInput,
+  ) -> Result<SetCmsVariablePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let cms_parent = query_data.cms_parent();
+    let value: serde_json::Value = serde_json::from_str(&input.cms_variable.value_json)
+      .map_err(|err| Error::new(format!("value_json is not valid JSON: {}", err)))?;
+    let now = Utc::now().naive_utc();
+
+    let existing = cms_parent
+      .cms_variables()
+      .filter(cms_variables::Column::Key.eq(input.cms_variable.key.as_str()))
+      .one(db)
+      .await?;
+
+    let cms_variable = if let Some(existing) = existing {
+      ensure_action_permitted::<CmsVariablePolicy, _>(ctx, &ReadManageAction::Manage, &existing)
+        .await?;
+      record_baseline_revision(db, &existing).await?;
+      let mut active_model: cms_variables::ActiveModel = existing.into();
+      active_model.value = ActiveValue::Set(Some(value));
+      active_model.updated_at = ActiveValue::Set(now);
+      active_model.update(db).await?
+    } else {
+      ensure_can_manage_cms_parent(ctx, cms_parent).await?;
+      let (parent_type, parent_id) = cms_parent.parent_columns();
+      cms_variables::ActiveModel {
+        parent_type: ActiveValue::Set(parent_type),
+        parent_id: ActiveValue::Set(parent_id),
+        key: ActiveValue::Set(input.cms_variable.key),
+        value: ActiveValue::Set(Some(value)),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+        ..Default::default()
+      }
+      .insert(db)
+      .await?
+    };
+    record_revision(db, &cms_variable, current_user_id(query_data)).await?;
+    invalidate_output(ctx, cms_parent, CmsOutputInvalidation::Everything).await?;
+
+    Ok(SetCmsVariablePayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_variable: CmsVariableType::new(cms_variable),
+    })
+  }
+
+  async fn delete_cms_variable(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsVariableInput,
+  ) -> Result<DeleteCmsVariablePayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let cms_variable = query_data
+      .cms_parent()
+      .cms_variables()
+      .filter(cms_variables::Column::Key.eq(input.key.as_str()))
+      .one(db)
+      .await?
+      .ok_or_else(|| Error::new("Variable not found"))?;
+
+    ensure_action_permitted::<CmsVariablePolicy, _>(ctx, &ReadManageAction::Manage, &cms_variable)
+      .await?;
+    delete_content_group_associations(db, "CmsVariable", cms_variable.id).await?;
+    cms_variable.clone().delete(db).await?;
+    invalidate_output(
+      ctx,
+      query_data.cms_parent(),
+      CmsOutputInvalidation::Everything,
+    )
+    .await?;
+
+    Ok(DeleteCmsVariablePayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_variable: CmsVariableType::new(cms_variable),
+    })
+  }
+
+  /// Restores a page, partial, layout or variable to the way it was in the given revision.  The
+  /// revert goes through the same validation as any other save, and is itself recorded as a new
+  /// revision.
+  async fn revert_cms_content(
+    &self,
+    ctx: &Context<'_>,
+    input: RevertCmsContentInput,
+  ) -> Result<RevertCmsContentPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let revision = query_data
+      .cms_parent()
+      .cms_content_revisions()
+      .filter(cms_content_revisions::Column::Id.eq(LaxId::parse(input.revision_id)?))
+      .one(db)
+      .await?
+      .ok_or_else(|| Error::new("Revision not found"))?;
+    let content_type = CmsRevisionedContentKind::from_content_type(&revision.content_type)
+      .ok_or_else(|| Error::new(format!("Can't revert {} content", revision.content_type)))?;
+    let content_id = ID::from(revision.content_id);
+    let invalid_snapshot =
+      |err: serde_json::Error| Error::new(format!("Revision could not be read: {}", err));
+
+    let content = match content_type {
+      CmsRevisionedContentKind::Page => {
+        let page = find_page(ctx, content_id).await?;
+        let mut active_model: pages::ActiveModel = page.into();
+        apply_revision::<pages::Model>(&revision, &mut active_model).map_err(invalid_snapshot)?;
+        RevisionedCmsContentType::Page(PageType::new(save_page(ctx, active_model).await?))
+      }
+      CmsRevisionedContentKind::CmsPartial => {
+        let cms_partial = find_cms_partial(ctx, content_id).await?;
+        let previous_name = cms_partial.name.clone();
+        let mut active_model: cms_partials::ActiveModel = cms_partial.into();
+        apply_revision::<cms_partials::Model>(&revision, &mut active_model)
+          .map_err(invalid_snapshot)?;
+        RevisionedCmsContentType::Partial(CmsPartialType::new(
+          save_cms_partial(ctx, active_model, Some(&previous_name)).await?,
+        ))
+      }
+      CmsRevisionedContentKind::CmsLayout => {
+        let cms_layout = find_cms_layout(ctx, content_id).await?;
+        let mut active_model: cms_layouts::ActiveModel = cms_layout.into();
+        apply_revision::<cms_layouts::Model>(&revision, &mut active_model)
+          .map_err(invalid_snapshot)?;
+        RevisionedCmsContentType::Layout(CmsLayoutType::new(
+          save_cms_layout(ctx, active_model).await?,
+        ))
+      }
+      CmsRevisionedContentKind::CmsVariable => {
+        let cms_variable = query_data
+          .cms_parent()
+          .cms_variables()
+          .filter(cms_variables::Column::Id.eq(revision.content_id))
+          .one(db)
+          .await?
+          .ok_or_else(|| Error::new("Variable not found"))?;
+        ensure_action_permitted::<CmsVariablePolicy, _>(
+          ctx,
+          &ReadManageAction::Manage,
+          &cms_variable,
+        )
+        .await?;
+        record_baseline_revision(db, &cms_variable).await?;
+        let mut active_model: cms_variables::ActiveModel = cms_variable.into();
+        apply_revision::<cms_variables::Model>(&revision, &mut active_model)
+          .map_err(invalid_snapshot)?;
+        active_model.updated_at = ActiveValue::Set(Utc::now().naive_utc());
+        let cms_variable = active_model.update(db).await?;
+        record_revision(db, &cms_variable, current_user_id(query_data)).await?;
+        invalidate_output(
+          ctx,
+          query_data.cms_parent(),
+          CmsOutputInvalidation::Everything,
+        )
+        .await?;
+        RevisionedCmsContentType::Variable(CmsVariableType::new(cms_variable))
+      }
+    };
+
+    let latest_revision = query_data
+      .cms_parent()
+      .cms_content_revisions()
+      .filter(cms_content_revisions::Column::ContentType.eq(content_type.as_str()))
+      .filter(cms_content_revisions::Column::ContentId.eq(revision.content_id))
+      .order_by_desc(cms_content_revisions::Column::CreatedAt)
+      .order_by_desc(cms_content_revisions::Column::Id)
+      .one(db)
+      .await?
+      .ok_or_else(|| Error::new("Revision not found"))?;
+
+    Ok(RevertCmsContentPayload {
+      client_mutation_id: input.client_mutation_id,
+      content,
+      revision: CmsContentRevisionType::new(latest_revision),
+    })
+  }
+
+  /// Creates a navigation item: a link to a page if page_id is given, or a section otherwise.
+  /// Without a position, it goes at the end of its section.
+  async fn create_cms_navigation_item(
+    &self,
+    ctx: &Context<'_>,
+    input: CreateCmsNavigationItemInput,
+  ) -> Result<CreateCmsNavigationItemPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let mut active_model = cms_navigation_items::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      created_at: ActiveValue::Set(Utc::now().naive_utc()),
+      ..Default::default()
+    };
+    input.cms_navigation_item.apply_to(&mut active_model)?;
+    let cms_navigation_item = save_cms_navigation_item(ctx, active_model).await?;
+
+    Ok(CreateCmsNavigationItemPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_navigation_item: CmsNavigationItemType::new(cms_navigation_item),
+    })
+  }
+
+  async fn update_cms_navigation_item(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateCmsNavigationItemInput,
+  ) -> Result<UpdateCmsNavigationItemPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let cms_navigation_item = find_cms_navigation_item(ctx, LaxId::parse(input.id)?).await?;
+    let mut active_model: cms_navigation_items::ActiveModel = cms_navigation_item.into();
+    input.cms_navigation_item.apply_to(&mut active_model)?;
+    let cms_navigation_item = save_cms_navigation_item(ctx, active_model).await?;
+
+    Ok(UpdateCmsNavigationItemPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_navigation_item: CmsNavigationItemType::new(cms_navigation_item),
+    })
+  }
+
+  /// Deletes a navigation item.  Deleting a section deletes everything in it.
+  async fn delete_cms_navigation_item(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsNavigationItemInput,
+  ) -> Result<DeleteCmsNavigationItemPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let cms_navigation_item = find_cms_navigation_item(ctx, LaxId::parse(input.id)?).await?;
+    cms_navigation_items::Entity::delete_many()
+      .filter(cms_navigation_items::Column::NavigationSectionId.eq(cms_navigation_item.id))
+      .exec(db)
+      .await?;
+    cms_navigation_item.clone().delete(db).await?;
+
+    Ok(DeleteCmsNavigationItemPayload {
+      client_mutation_id: input.client_mutation_id,
+      cms_navigation_item: CmsNavigationItemType::new(cms_navigation_item),
+    })
+  }
+
+  /// Moves navigation items to new positions, and optionally into different sections, all at once
+  async fn sort_cms_navigation_items(
+    &self,
+    ctx: &Context<'_>,
+    input: SortCmsNavigationItemsInput,
+  ) -> Result<SortCmsNavigationItemsPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let now = Utc::now().naive_utc();
+    for sort_item in input.sort_items {
+      let cms_navigation_item = find_cms_navigation_item(ctx, LaxId::parse(sort_item.id)?).await?;
+      let navigation_section_id = sort_item
+        .navigation_section_id
+        .map(LaxId::parse)
+        .transpose()?;
+      if navigation_section_id == Some(cms_navigation_item.id) {
+        return Err(Error::new(
+          "Navigation items can't be placed inside themselves",
+        ));
+      }
+      validate_navigation_item_references(ctx, navigation_section_id, None).await?;
+
+      let mut active_model: cms_navigation_items::ActiveModel = cms_navigation_item.into();
+      active_model.position = ActiveValue::Set(Some(sort_item.position));
+      active_model.navigation_section_id = ActiveValue::Set(navigation_section_id);
+      active_model.updated_at = ActiveValue::Set(now);
+      active_model.update(db).await?;
+    }
+
+    Ok(SortCmsNavigationItemsPayload {
+      client_mutation_id: input.client_mutation_id,
+    })
+  }
+
+  /// Creates a stored GraphQL query for use with the assign_graphql_result tag.  The query must
+  /// parse.
+  async fn create_cms_graphql_query(
+    &self,
+    ctx: &Context<'_>,
+    input: CreateCmsGraphqlQueryInput,
+  ) -> Result<CreateCmsGraphqlQueryPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    ensure_can_manage_cms_parent(ctx, query_data.cms_parent()).await?;
+
+    let (parent_type, parent_id) = query_data.cms_parent().parent_columns();
+    let mut active_model = cms_graphql_queries::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      created_at: ActiveValue::Set(Utc::now().naive_utc()),
+      ..Default::default()
+    };
+    input.query.apply_to(&mut active_model);
+    let query = save_cms_graphql_query(ctx, active_model, None).await?;
+
+    Ok(CreateCmsGraphqlQueryPayload {
+      client_mutation_id: input.client_mutation_id,
+      query: CmsGraphqlQueryType::new(query),
+    })
+  }
+
+  async fn update_cms_graphql_query(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateCmsGraphqlQueryInput,
+  ) -> Result<UpdateCmsGraphqlQueryPayload> {
+    let query = find_cms_graphql_query(ctx, input.id).await?;
+    let previous_identifier = query.identifier.clone();
+    let mut active_model: cms_graphql_queries::ActiveModel = query.into();
+    input.query.apply_to(&mut active_model);
+    let query = save_cms_graphql_query(ctx, active_model, previous_identifier.as_deref()).await?;
+
+    Ok(UpdateCmsGraphqlQueryPayload {
+      client_mutation_id: input.client_mutation_id,
+      query: CmsGraphqlQueryType::new(query),
+    })
+  }
+
+  async fn delete_cms_graphql_query(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteCmsGraphqlQueryInput,
+  ) -> Result<DeleteCmsGraphqlQueryPayload> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let query = find_cms_graphql_query(ctx, input.id).await?;
+
+    delete_content_group_associations(db, "CmsGraphqlQuery", query.id).await?;
+    query.clone().delete(db).await?;
+    if let Some(identifier) = &query.identifier {
+      invalidate_output(
+        ctx,
+        query_data.cms_parent(),
+        CmsOutputInvalidation::ReferencesTo(&[identifier.as_str()]),
+      )
+      .await?;
+    }
+
+    Ok(DeleteCmsGraphqlQueryPayload {
+      client_mutation_id: input.client_mutation_id,
+      query: CmsGraphqlQueryType::new(query),
+    })
+  }
+
+  /// Exports a convention's (or the root site's) CMS content as a content set.  Site admins
+  /// only.
+  async fn export_cms_content_set(
+    &self,
+    ctx: &Context<'_>,
+    input: ExportCmsContentSetInput,
+  ) -> Result<ExportCmsContentSetPayload> {
+    ensure_site_admin(ctx)?;
+    let query_data = ctx.data::<QueryData>()?;
+    let cms_parent = find_cms_parent(query_data.db(), input.convention_id).await?;
+    let content_set = export_cms_content_set(query_data.db(), &cms_parent).await?;
+
+    Ok(ExportCmsContentSetPayload {
+      client_mutation_id: input.client_mutation_id,
+      content_set_json: serde_json::to_string(&content_set)?,
+    })
+  }
+
+  /// Imports a content set into a convention (or the root site).  Runs as a dry run unless
+  /// dry_run is set to false.  Site admins only.
+  async fn import_cms_content_set(
+    &self,
+    ctx: &Context<'_>,
+    input: ImportCmsContentSetInput,
+  ) -> Result<ImportCmsContentSetPayload> {
+    ensure_site_admin(ctx)?;
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let cms_parent = find_cms_parent(db, input.convention_id).await?;
+
+    let content_set = match (input.content_set_json, input.source_convention_id) {
+      (Some(content_set_json), None) => parse_content_set_json(&content_set_json)?,
+      (None, Some(source_convention_id)) => {
+        let source = find_cms_parent(db, Some(source_convention_id)).await?;
+        export_cms_content_set(db, &source).await?
+      }
+      _ => {
+        return Err(Error::new(
+          "Exactly one of content_set_json and source_convention_id must be given",
+        ))
+      }
+    };
+
+    // imported pages and layouts get the same syntax check as ones saved through the editor
+    for layout in &content_set.layouts {
+      validate_liquid(ctx, layout.content.as_deref())
+        .await
+        .map_err(|err| Error::new(format!("Layout {}: {}", layout.name, err.message)))?;
+    }
+    for page in &content_set.pages {
+      validate_liquid(ctx, page.content.as_deref())
+        .await
+        .map_err(|err| Error::new(format!("Page {}: {}", page.slug, err.message)))?;
+    }
+
+    let report = import_cms_content_set(
+      db,
+      &cms_parent,
+      &content_set,
+      input.conflict_strategy,
+      current_user_id(query_data),
+      input.dry_run,
+    )
+    .await?;
+    if !input.dry_run {
+      invalidate_output(ctx, &cms_parent, CmsOutputInvalidation::Everything).await?;
+    }
+
+    Ok(ImportCmsContentSetPayload {
+      client_mutation_id: input.client_mutation_id,
+      report,
+    })
+  }
+}
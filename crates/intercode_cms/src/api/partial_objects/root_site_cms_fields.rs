@@ -7,10 +7,12 @@ use url::Url;
 
 use crate::{
   api::objects::{
-    CmsContentType, CmsFileType, CmsGraphqlQueryType, CmsLayoutType, CmsNavigationItemType,
-    CmsPartialType, CmsVariableType, LiquidAssignType, PageType,
+    CmsContentRevisionDiffType, CmsContentRevisionType, CmsContentSearchResultType, CmsContentType,
+    CmsFileType, CmsGraphqlQueryType, CmsLayoutType, CmsNavigationItemType, CmsPartialType,
+    CmsVariableType, LiquidAssignType, PageType,
   },
   cms_parent_implementation::CmsParentImplementation,
+  cms_revisions::CmsRevisionedContentKind,
 };
 
 model_backed_type!(RootSiteCmsFields, root_sites::Model);
@@ -28,6 +30,26 @@ impl RootSiteCmsFields {
     self.model.site_name.as_deref().unwrap_or_default()
   }
 
+  async fn cms_content_revisions(
+    &self,
+    ctx: &Context<'_>,
+    content_type: CmsRevisionedContentKind,
+    content_id: ID,
+  ) -> Result<Vec<CmsContentRevisionType>, Error> {
+    CmsParentImplementation::cms_content_revisions(self, ctx, content_type, content_id).await
+  }
+
+  /// A line-by-line comparison of two revisions of the same content
+  async fn cms_content_revision_diff(
+    &self,
+    ctx: &Context<'_>,
+    from_revision_id: ID,
+    to_revision_id: ID,
+  ) -> Result<CmsContentRevisionDiffType, Error> {
+    CmsParentImplementation::cms_content_revision_diff(self, ctx, from_revision_id, to_revision_id)
+      .await
+  }
+
   async fn cms_files(&self, ctx: &Context<'_>) -> Result<Vec<CmsFileType>, Error> {
     CmsParentImplementation::cms_files(self, ctx).await
   }
@@ -135,6 +157,15 @@ impl RootSiteCmsFields {
     CmsParentImplementation::typeahead_search_cms_content(self, ctx, name).await
   }
 
+  async fn search_cms_content(
+    &self,
+    ctx: &Context<'_>,
+    query: String,
+    limit: Option<i32>,
+  ) -> Result<Vec<CmsContentSearchResultType>, Error> {
+    CmsParentImplementation::search_cms_content(self, ctx, query, limit).await
+  }
+
   async fn url(&self) -> Result<String> {
     let host = env::var("INTERCODE_HOST")?;
     Ok(Url::parse(format!("https://{}", host).as_str())?.to_string())
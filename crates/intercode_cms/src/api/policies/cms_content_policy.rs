@@ -1,14 +1,15 @@
 use std::marker::PhantomData;
 
+use async_graphql::Context;
 use async_trait::async_trait;
 use intercode_entities::{
   cms_content_group_associations, cms_content_groups, cms_content_model::CmsContentModel,
-  cms_files, cms_graphql_queries, cms_layouts, cms_partials, cms_variables, conventions, pages,
-  permissions, root_sites,
+  cms_files, cms_graphql_queries, cms_layouts, cms_parent::CmsParent, cms_partials, cms_variables,
+  conventions, pages, permissions, root_sites,
 };
 use intercode_policies::{
-  user_permission_scope, AuthorizationInfo, EntityPolicy, Policy, ReadManageAction,
-  SimpleGuardablePolicy,
+  ensure_action_permitted, user_permission_scope, AuthorizationInfo, EntityPolicy, Policy,
+  ReadManageAction, SimpleGuardablePolicy,
 };
 use sea_orm::{
   sea_query::{Expr, UnionType},
@@ -216,6 +217,32 @@ where
 
 impl<'a, M: CmsContentAuthorizable + 'static> SimpleGuardablePolicy<'a, M> for CmsContentPolicy<M> {}
 
+/// Creating content (and touching navigation items, which aren't individually authorizable)
+/// requires permission to manage all the CMS content in the parent
+pub async fn ensure_can_manage_cms_parent(
+  ctx: &Context<'_>,
+  cms_parent: &CmsParent,
+) -> async_graphql::Result<()> {
+  match cms_parent {
+    CmsParent::Convention(convention) => {
+      ensure_action_permitted::<CmsContentPolicy<conventions::Model>, _>(
+        ctx,
+        &ReadManageAction::Manage,
+        convention.as_ref(),
+      )
+      .await
+    }
+    CmsParent::RootSite(root_site) => {
+      ensure_action_permitted::<CmsContentPolicy<root_sites::Model>, _>(
+        ctx,
+        &ReadManageAction::Manage,
+        root_site.as_ref(),
+      )
+      .await
+    }
+  }
+}
+
 pub type CmsContentGroupPolicy = CmsContentPolicy<cms_content_groups::Model>;
 pub type CmsFilePolicy = CmsContentPolicy<cms_files::Model>;
 pub type CmsGraphqlQueryPolicy = CmsContentPolicy<cms_graphql_queries::Model>;
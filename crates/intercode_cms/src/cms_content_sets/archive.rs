@@ -0,0 +1,412 @@
+//! Reading and writing content sets on disk.  A set can be a single JSON file, or a directory
+//! (or a ZIP of one) laid out like this, so that it can be kept in version control and edited by
+//! hand:
+//!
+//! ```text
+//! manifest.json           format version, source, root page and default layout
+//! navigation.json         the navigation tree
+//! variables.json          all the CMS variables
+//! pages/*.json            one file per page
+//! partials/*.json         one file per partial
+//! layouts/*.json          one file per layout
+//! graphql_queries/*.json  one file per GraphQL query
+//! files/*.json            one file per CMS file
+//! ```
+//!
+//! Only the contents of the files matter; their names are just there to make the directory easy
+//! to browse.
+
+use std::{
+  collections::HashSet,
+  fs::File,
+  io::{self, Read, Write},
+  path::Path,
+};
+
+use intercode_entities::{CmsContentSetExport, CMS_CONTENT_SET_FORMAT_VERSION};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+  format_version: i32,
+  source: Option<String>,
+  root_page_slug: Option<String>,
+  default_layout_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmsContentSetArchiveFormat {
+  Json,
+  Directory,
+  Zip,
+}
+
+impl CmsContentSetArchiveFormat {
+  /// Goes by the file extension: .json and .zip files are those formats, and anything else is
+  /// treated as a directory
+  pub fn for_path(path: &Path) -> Self {
+    match path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(|extension| extension.to_lowercase())
+      .as_deref()
+    {
+      Some("json") => CmsContentSetArchiveFormat::Json,
+      Some("zip") => CmsContentSetArchiveFormat::Zip,
+      _ => CmsContentSetArchiveFormat::Directory,
+    }
+  }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn check_format_version(format_version: i32) -> io::Result<()> {
+  if format_version > CMS_CONTENT_SET_FORMAT_VERSION {
+    return Err(invalid_data(format!(
+      "This content set uses format version {}, but only versions up to {} can be read",
+      format_version, CMS_CONTENT_SET_FORMAT_VERSION
+    )));
+  }
+
+  Ok(())
+}
+
+/// Turns a name into something safe to use as a file name, made unique among the names already
+/// used in the same directory
+fn file_stem(name: &str, used: &mut HashSet<String>) -> String {
+  let base = name
+    .chars()
+    .map(|c| {
+      if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect::<String>();
+  let base = base.trim_matches('.');
+  let base = if base.is_empty() { "untitled" } else { base };
+
+  let mut stem = base.to_string();
+  let mut counter = 2;
+  while !used.insert(stem.to_lowercase()) {
+    stem = format!("{}-{}", base, counter);
+    counter += 1;
+  }
+  stem
+}
+
+fn json_entry<T: Serialize>(path: String, value: &T) -> io::Result<(String, Vec<u8>)> {
+  let mut bytes = serde_json::to_vec_pretty(value)?;
+  bytes.push(b'\n');
+  Ok((path, bytes))
+}
+
+fn directory_entries<'a, T: Serialize + 'a>(
+  directory: &str,
+  items: impl IntoIterator<Item = (&'a str, &'a T)>,
+) -> io::Result<Vec<(String, Vec<u8>)>> {
+  let mut used = HashSet::new();
+  items
+    .into_iter()
+    .map(|(name, item)| {
+      json_entry(
+        format!("{}/{}.json", directory, file_stem(name, &mut used)),
+        item,
+      )
+    })
+    .collect()
+}
+
+/// The files making up a content set, as relative paths and their contents
+pub fn archive_entries(content_set: &CmsContentSetExport) -> io::Result<Vec<(String, Vec<u8>)>> {
+  let mut entries = vec![
+    json_entry(
+      "manifest.json".to_string(),
+      &Manifest {
+        format_version: content_set.format_version,
+        source: content_set.source.clone(),
+        root_page_slug: content_set.root_page_slug.clone(),
+        default_layout_name: content_set.default_layout_name.clone(),
+      },
+    )?,
+    json_entry("navigation.json".to_string(), &content_set.navigation_items)?,
+    json_entry("variables.json".to_string(), &content_set.variables)?,
+  ];
+
+  entries.extend(directory_entries(
+    "pages",
+    content_set
+      .pages
+      .iter()
+      .map(|page| (page.slug.as_str(), page)),
+  )?);
+  entries.extend(directory_entries(
+    "partials",
+    content_set
+      .partials
+      .iter()
+      .map(|partial| (partial.name.as_str(), partial)),
+  )?);
+  entries.extend(directory_entries(
+    "layouts",
+    content_set
+      .layouts
+      .iter()
+      .map(|layout| (layout.name.as_str(), layout)),
+  )?);
+  entries.extend(directory_entries(
+    "graphql_queries",
+    content_set
+      .graphql_queries
+      .iter()
+      .map(|query| (query.identifier.as_str(), query)),
+  )?);
+  entries.extend(directory_entries(
+    "files",
+    content_set
+      .files
+      .iter()
+      .map(|file| (file.filename.as_str(), file)),
+  )?);
+
+  Ok(entries)
+}
+
+fn parse_entry<T: DeserializeOwned>(path: &str, bytes: &[u8]) -> io::Result<T> {
+  serde_json::from_slice(bytes).map_err(|err| invalid_data(format!("{}: {}", path, err)))
+}
+
+/// Puts a content set back together from its files.  If the files are all inside a single
+/// top-level directory (as happens when a directory is zipped up), that directory is ignored.
+pub fn content_set_from_entries(
+  entries: Vec<(String, Vec<u8>)>,
+) -> io::Result<CmsContentSetExport> {
+  let manifest_path = entries
+    .iter()
+    .map(|(path, _)| path.as_str())
+    .filter(|path| *path == "manifest.json" || path.ends_with("/manifest.json"))
+    .min_by_key(|path| path.len())
+    .ok_or_else(|| invalid_data("This content set has no manifest.json"))?;
+  let prefix = manifest_path
+    .strip_suffix("manifest.json")
+    .unwrap_or_default()
+    .to_string();
+
+  let mut manifest: Option<Manifest> = None;
+  let mut content_set = CmsContentSetExport {
+    format_version: CMS_CONTENT_SET_FORMAT_VERSION,
+    source: None,
+    root_page_slug: None,
+    default_layout_name: None,
+    pages: vec![],
+    partials: vec![],
+    layouts: vec![],
+    navigation_items: vec![],
+    variables: vec![],
+    graphql_queries: vec![],
+    files: vec![],
+  };
+
+  for (path, bytes) in entries {
+    let Some(relative_path) = path.strip_prefix(&prefix) else {
+      continue;
+    };
+    if !relative_path.ends_with(".json") {
+      continue;
+    }
+
+    match relative_path.split_once('/') {
+      None => match relative_path {
+        "manifest.json" => manifest = Some(parse_entry(&path, &bytes)?),
+        "navigation.json" => content_set.navigation_items = parse_entry(&path, &bytes)?,
+        "variables.json" => content_set.variables = parse_entry(&path, &bytes)?,
+        _ => {}
+      },
+      Some(("pages", _)) => content_set.pages.push(parse_entry(&path, &bytes)?),
+      Some(("partials", _)) => content_set.partials.push(parse_entry(&path, &bytes)?),
+      Some(("layouts", _)) => content_set.layouts.push(parse_entry(&path, &bytes)?),
+      Some(("graphql_queries", _)) => content_set
+        .graphql_queries
+        .push(parse_entry(&path, &bytes)?),
+      Some(("files", _)) => content_set.files.push(parse_entry(&path, &bytes)?),
+      Some(_) => {}
+    }
+  }
+
+  let manifest = manifest.ok_or_else(|| invalid_data("This content set has no manifest.json"))?;
+  check_format_version(manifest.format_version)?;
+  content_set.format_version = manifest.format_version;
+  content_set.source = manifest.source;
+  content_set.root_page_slug = manifest.root_page_slug;
+  content_set.default_layout_name = manifest.default_layout_name;
+
+  Ok(content_set)
+}
+
+pub fn parse_content_set_json(json: &str) -> io::Result<CmsContentSetExport> {
+  let content_set: CmsContentSetExport = serde_json::from_str(json).map_err(invalid_data)?;
+  check_format_version(content_set.format_version)?;
+  Ok(content_set)
+}
+
+fn read_directory_entries(
+  root: &Path,
+  directory: &Path,
+  entries: &mut Vec<(String, Vec<u8>)>,
+) -> io::Result<()> {
+  for dir_entry in std::fs::read_dir(directory)? {
+    let path = dir_entry?.path();
+    if path.is_dir() {
+      read_directory_entries(root, &path, entries)?;
+    } else {
+      let relative_path = path
+        .strip_prefix(root)
+        .map_err(invalid_data)?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+      entries.push((relative_path, std::fs::read(&path)?));
+    }
+  }
+
+  Ok(())
+}
+
+pub fn read_content_set(path: &Path) -> io::Result<CmsContentSetExport> {
+  match CmsContentSetArchiveFormat::for_path(path) {
+    CmsContentSetArchiveFormat::Json => parse_content_set_json(&std::fs::read_to_string(path)?),
+    CmsContentSetArchiveFormat::Directory => {
+      let mut entries = vec![];
+      read_directory_entries(path, path, &mut entries)?;
+      content_set_from_entries(entries)
+    }
+    CmsContentSetArchiveFormat::Zip => {
+      let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+      let mut entries = vec![];
+      for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if file.is_dir() {
+          continue;
+        }
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        entries.push((file.name().to_string(), bytes));
+      }
+      content_set_from_entries(entries)
+    }
+  }
+}
+
+/// Writes a content set out in the format its path calls for.  Directories must either not
+/// exist yet or be empty, so that stale files from an earlier export can't sneak into this one.
+pub fn write_content_set(path: &Path, content_set: &CmsContentSetExport) -> io::Result<()> {
+  match CmsContentSetArchiveFormat::for_path(path) {
+    CmsContentSetArchiveFormat::Json => {
+      let mut bytes = serde_json::to_vec_pretty(content_set)?;
+      bytes.push(b'\n');
+      std::fs::write(path, bytes)
+    }
+    CmsContentSetArchiveFormat::Directory => {
+      if path.exists() && std::fs::read_dir(path)?.next().is_some() {
+        return Err(io::Error::new(
+          io::ErrorKind::AlreadyExists,
+          format!("{} already exists and isn't empty", path.display()),
+        ));
+      }
+
+      for (relative_path, bytes) in archive_entries(content_set)? {
+        let entry_path = path.join(relative_path);
+        if let Some(parent) = entry_path.parent() {
+          std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(entry_path, bytes)?;
+      }
+      Ok(())
+    }
+    CmsContentSetArchiveFormat::Zip => {
+      let mut zip = zip::ZipWriter::new(File::create(path)?);
+      let options = zip::write::FileOptions::default();
+      for (relative_path, bytes) in archive_entries(content_set)? {
+        zip.start_file(relative_path, options)?;
+        zip.write_all(&bytes)?;
+      }
+      zip.finish()?;
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use intercode_entities::{CmsPageExport, CmsPartialExport};
+
+  use super::*;
+
+  fn example_content_set() -> CmsContentSetExport {
+    CmsContentSetExport {
+      format_version: CMS_CONTENT_SET_FORMAT_VERSION,
+      source: Some("Example Con 2023".to_string()),
+      root_page_slug: Some("home".to_string()),
+      default_layout_name: None,
+      pages: vec![CmsPageExport {
+        name: Some("Home".to_string()),
+        slug: "home".to_string(),
+        content: Some("{% include 'header' %}".to_string()),
+        admin_notes: None,
+        cms_layout_name: None,
+        skip_clickwrap_agreement: false,
+        hidden_from_search: false,
+      }],
+      partials: vec![
+        CmsPartialExport {
+          name: "header".to_string(),
+          content: Some("Welcome".to_string()),
+          admin_notes: None,
+        },
+        CmsPartialExport {
+          name: "Header".to_string(),
+          content: Some("WELCOME".to_string()),
+          admin_notes: None,
+        },
+      ],
+      layouts: vec![],
+      navigation_items: vec![],
+      variables: vec![],
+      graphql_queries: vec![],
+      files: vec![],
+    }
+  }
+
+  #[test]
+  fn round_trips_through_entries() {
+    let entries = archive_entries(&example_content_set()).unwrap();
+    let paths = entries
+      .iter()
+      .map(|(path, _)| path.as_str())
+      .collect::<Vec<_>>();
+    assert!(paths.contains(&"partials/header.json"));
+    assert!(paths.contains(&"partials/Header-2.json"));
+
+    let nested_entries = entries
+      .into_iter()
+      .map(|(path, bytes)| (format!("example-con/{}", path), bytes))
+      .collect();
+    let content_set = content_set_from_entries(nested_entries).unwrap();
+    assert_eq!(content_set.root_page_slug.as_deref(), Some("home"));
+    assert_eq!(content_set.pages.len(), 1);
+    assert_eq!(content_set.partials.len(), 2);
+  }
+
+  #[test]
+  fn refuses_newer_formats() {
+    let mut content_set = example_content_set();
+    content_set.format_version = CMS_CONTENT_SET_FORMAT_VERSION + 1;
+    let json = serde_json::to_string(&content_set).unwrap();
+
+    assert!(parse_content_set_json(&json).is_err());
+  }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use intercode_entities::{
+  active_storage_attachments, active_storage_blobs, cms_files, cms_graphql_queries, cms_layouts,
+  cms_navigation_items,
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_partials, cms_variables, pages, CmsContentSetExport, CmsFileExport, CmsGraphqlQueryExport,
+  CmsLayoutExport, CmsNavigationItemExport, CmsPageExport, CmsPartialExport, CmsVariableExport,
+  CMS_CONTENT_SET_FORMAT_VERSION,
+};
+use sea_orm::{
+  ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+  QueryTrait,
+};
+
+fn cms_parent_description(cms_parent: &CmsParent) -> Option<String> {
+  match cms_parent {
+    CmsParent::Convention(convention) => convention
+      .name
+      .clone()
+      .or_else(|| Some(convention.domain.clone())),
+    CmsParent::RootSite(root_site) => root_site.site_name.clone(),
+  }
+}
+
+fn export_navigation_items(
+  section_id: Option<i64>,
+  items_by_section_id: &HashMap<Option<i64>, Vec<&cms_navigation_items::Model>>,
+  page_slugs: &HashMap<i64, String>,
+) -> Vec<CmsNavigationItemExport> {
+  items_by_section_id
+    .get(&section_id)
+    .map(|items| {
+      items
+        .iter()
+        .map(|item| CmsNavigationItemExport {
+          title: item.title.clone(),
+          page_slug: item
+            .page_id
+            .and_then(|page_id| page_slugs.get(&page_id).cloned()),
+          items: if item.page_id.is_none() {
+            export_navigation_items(Some(item.id), items_by_section_id, page_slugs)
+          } else {
+            vec![]
+          },
+        })
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+async fn export_files<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+) -> Result<Vec<CmsFileExport>, DbErr> {
+  let attachments = active_storage_attachments::Entity::find()
+    .filter(active_storage_attachments::Column::RecordType.eq("CmsFile"))
+    .filter(active_storage_attachments::Column::Name.eq("file"))
+    .filter(
+      active_storage_attachments::Column::RecordId.in_subquery(
+        cms_parent
+          .cms_files()
+          .select_only()
+          .column(cms_files::Column::Id)
+          .into_query(),
+      ),
+    )
+    .find_also_related(active_storage_blobs::Entity)
+    .order_by_asc(active_storage_attachments::Column::RecordId)
+    .all(db)
+    .await?;
+
+  Ok(
+    attachments
+      .into_iter()
+      .filter_map(|(_, blob)| blob)
+      .map(|blob| CmsFileExport {
+        filename: blob.filename,
+        content_type: blob.content_type,
+        byte_size: blob.byte_size,
+        checksum: blob.checksum,
+        blob_key: blob.key,
+        service_name: blob.service_name,
+      })
+      .collect(),
+  )
+}
+
+/// Gathers up all the CMS content belonging to a parent into a portable content set
+pub async fn export_cms_content_set<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+) -> Result<CmsContentSetExport, DbErr> {
+  let layouts = cms_parent
+    .cms_layouts()
+    .order_by_asc(cms_layouts::Column::Id)
+    .all(db)
+    .await?;
+  let layout_names = layouts
+    .iter()
+    .map(|layout| (layout.id, CmsLayoutExport::export_name(layout)))
+    .collect::<HashMap<_, _>>();
+
+  let pages = cms_parent
+    .pages()
+    .filter(pages::Column::Slug.is_not_null())
+    .order_by_asc(pages::Column::Slug)
+    .all(db)
+    .await?;
+  let page_slugs = pages
+    .iter()
+    .filter_map(|page| page.slug.clone().map(|slug| (page.id, slug)))
+    .collect::<HashMap<_, _>>();
+
+  let navigation_items = cms_parent
+    .cms_navigation_items()
+    .order_by_asc(cms_navigation_items::Column::Position)
+    .order_by_asc(cms_navigation_items::Column::Id)
+    .all(db)
+    .await?;
+  let mut items_by_section_id: HashMap<Option<i64>, Vec<&cms_navigation_items::Model>> =
+    HashMap::new();
+  for item in &navigation_items {
+    items_by_section_id
+      .entry(item.navigation_section_id)
+      .or_default()
+      .push(item);
+  }
+
+  let root_page_slug = cms_parent
+    .root_page()
+    .one(db)
+    .await?
+    .and_then(|page| page.slug);
+  let default_layout_name = cms_parent
+    .default_layout()
+    .one(db)
+    .await?
+    .map(|layout| CmsLayoutExport::export_name(&layout));
+
+  Ok(CmsContentSetExport {
+    format_version: CMS_CONTENT_SET_FORMAT_VERSION,
+    source: cms_parent_description(cms_parent),
+    root_page_slug,
+    default_layout_name,
+    pages: pages
+      .into_iter()
+      .map(|page| CmsPageExport {
+        cms_layout_name: page
+          .cms_layout_id
+          .and_then(|layout_id| layout_names.get(&layout_id).cloned()),
+        name: page.name,
+        slug: page.slug.unwrap_or_default(),
+        content: page.content,
+        admin_notes: page.admin_notes,
+        skip_clickwrap_agreement: page.skip_clickwrap_agreement,
+        hidden_from_search: page.hidden_from_search,
+      })
+      .collect(),
+    partials: cms_parent
+      .cms_partials()
+      .order_by_asc(cms_partials::Column::Name)
+      .all(db)
+      .await?
+      .into_iter()
+      .map(CmsPartialExport::from)
+      .collect(),
+    layouts: layouts.into_iter().map(CmsLayoutExport::from).collect(),
+    navigation_items: export_navigation_items(None, &items_by_section_id, &page_slugs),
+    variables: cms_parent
+      .cms_variables()
+      .order_by_asc(cms_variables::Column::Key)
+      .all(db)
+      .await?
+      .into_iter()
+      .map(CmsVariableExport::from)
+      .collect(),
+    graphql_queries: cms_parent
+      .cms_graphql_queries()
+      .filter(cms_graphql_queries::Column::Identifier.is_not_null())
+      .order_by_asc(cms_graphql_queries::Column::Identifier)
+      .all(db)
+      .await?
+      .into_iter()
+      .map(CmsGraphqlQueryExport::from)
+      .collect(),
+    files: export_files(db, cms_parent).await?,
+  })
+}
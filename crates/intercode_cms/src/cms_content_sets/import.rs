@@ -0,0 +1,939 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::Display,
+  str::FromStr,
+};
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{NaiveDateTime, Utc};
+use intercode_entities::{
+  active_storage_attachments, active_storage_blobs, cms_files, cms_graphql_queries, cms_layouts,
+  cms_navigation_items,
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_partials, cms_variables, conventions, pages, root_sites, CmsContentSetExport, CmsFileExport,
+  CmsGraphqlQueryExport, CmsLayoutExport, CmsNavigationItemExport, CmsPageExport, CmsPartialExport,
+  CmsVariableExport, CMS_CONTENT_SET_FORMAT_VERSION,
+};
+use intercode_full_text_search::search_index::index_pages;
+use sea_orm::{
+  sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait,
+  DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter,
+  QuerySelect, QueryTrait, TransactionTrait, TryIntoModel,
+};
+
+use crate::{
+  active_storage::{can_copy_blobs_in, copy_blob},
+  cms_references::{refresh_references_to_names, update_layout_references, update_page_references},
+  cms_revisions::{record_baseline_revision, record_revision},
+};
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "CmsContentSetConflictStrategy")]
+pub enum CmsContentSetConflictStrategy {
+  /// Keep the existing content and leave the imported version out
+  #[graphql(name = "skip")]
+  Skip,
+  /// Replace the existing content with the imported version
+  #[graphql(name = "overwrite")]
+  Overwrite,
+  /// Import the content alongside the existing content, under a new name
+  #[graphql(name = "rename")]
+  Rename,
+}
+
+impl CmsContentSetConflictStrategy {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CmsContentSetConflictStrategy::Skip => "skip",
+      CmsContentSetConflictStrategy::Overwrite => "overwrite",
+      CmsContentSetConflictStrategy::Rename => "rename",
+    }
+  }
+}
+
+impl FromStr for CmsContentSetConflictStrategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "skip" => Ok(CmsContentSetConflictStrategy::Skip),
+      "overwrite" => Ok(CmsContentSetConflictStrategy::Overwrite),
+      "rename" => Ok(CmsContentSetConflictStrategy::Rename),
+      _ => Err(format!(
+        "Unknown conflict strategy {}; expected skip, overwrite or rename",
+        s
+      )),
+    }
+  }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "CmsContentSetImportAction")]
+pub enum CmsContentSetImportAction {
+  #[graphql(name = "create")]
+  Create,
+  #[graphql(name = "overwrite")]
+  Overwrite,
+  #[graphql(name = "rename")]
+  Rename,
+  #[graphql(name = "skip")]
+  Skip,
+}
+
+impl CmsContentSetImportAction {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CmsContentSetImportAction::Create => "create",
+      CmsContentSetImportAction::Overwrite => "overwrite",
+      CmsContentSetImportAction::Rename => "rename",
+      CmsContentSetImportAction::Skip => "skip",
+    }
+  }
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(name = "CmsContentSetImportItem")]
+pub struct CmsContentSetImportItem {
+  #[graphql(name = "content_type")]
+  pub content_type: String,
+  /// The name, slug, key or filename the content has in the content set
+  pub identifier: String,
+  pub action: CmsContentSetImportAction,
+  /// For renamed content, the name it was imported under
+  #[graphql(name = "imported_as")]
+  pub imported_as: Option<String>,
+}
+
+/// What importing a content set did, or would do in a dry run
+#[derive(SimpleObject, Debug)]
+#[graphql(name = "CmsContentSetImportReport")]
+pub struct CmsContentSetImportReport {
+  #[graphql(name = "dry_run")]
+  pub dry_run: bool,
+  /// True if the import has actually been saved
+  pub imported: bool,
+  pub items: Vec<CmsContentSetImportItem>,
+  /// Things that couldn't be imported as-is and may need fixing up by hand
+  pub warnings: Vec<String>,
+}
+
+impl Display for CmsContentSetImportReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let heading = if self.imported {
+      "Imported content set"
+    } else {
+      "Content set import plan"
+    };
+    writeln!(f, "{}:", heading)?;
+
+    for item in &self.items {
+      match &item.imported_as {
+        Some(imported_as) => writeln!(
+          f,
+          "  {} {} {} as {}",
+          item.action.as_str(),
+          item.content_type,
+          item.identifier,
+          imported_as
+        )?,
+        None => writeln!(
+          f,
+          "  {} {} {}",
+          item.action.as_str(),
+          item.content_type,
+          item.identifier
+        )?,
+      }
+    }
+
+    if !self.warnings.is_empty() {
+      writeln!(f, "\nWarnings:")?;
+      for warning in &self.warnings {
+        writeln!(f, "  - {}", warning)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Finds a variation on a name that isn't already taken, by adding a number to the end
+fn unique_name(name: &str, separator: &str, taken: &HashSet<String>) -> String {
+  (2..)
+    .map(|counter| format!("{}{}{}", name, separator, counter))
+    .find(|candidate| !taken.contains(candidate))
+    .unwrap()
+}
+
+struct ContentSetImporter<'a> {
+  txn: &'a DatabaseTransaction,
+  cms_parent: &'a CmsParent,
+  strategy: CmsContentSetConflictStrategy,
+  author_id: Option<i64>,
+  dry_run: bool,
+  now: NaiveDateTime,
+  report: CmsContentSetImportReport,
+  /// Layout ids in the target, by their name in the content set
+  layout_ids: HashMap<String, i64>,
+  /// Page ids in the target, by their slug in the content set
+  page_ids: HashMap<String, i64>,
+  changed_page_ids: Vec<i64>,
+  changed_layout_ids: Vec<i64>,
+  changed_names: Vec<String>,
+}
+
+impl<'a> ContentSetImporter<'a> {
+  /// Decides what to do with an item whose identifier might already be in use, and records the
+  /// decision in the report.  Returns the action and the identifier to save the item under.
+  fn resolve(
+    &mut self,
+    content_type: &str,
+    identifier: &str,
+    exists: bool,
+    separator: &str,
+    taken: &mut HashSet<String>,
+  ) -> (CmsContentSetImportAction, String) {
+    let (action, imported_as) = match (exists, self.strategy) {
+      (false, _) => (CmsContentSetImportAction::Create, None),
+      (true, CmsContentSetConflictStrategy::Skip) => (CmsContentSetImportAction::Skip, None),
+      (true, CmsContentSetConflictStrategy::Overwrite) => {
+        (CmsContentSetImportAction::Overwrite, None)
+      }
+      (true, CmsContentSetConflictStrategy::Rename) => (
+        CmsContentSetImportAction::Rename,
+        Some(unique_name(identifier, separator, taken)),
+      ),
+    };
+
+    let saved_as = imported_as
+      .clone()
+      .unwrap_or_else(|| identifier.to_string());
+    taken.insert(saved_as.clone());
+    self.report.items.push(CmsContentSetImportItem {
+      content_type: content_type.to_string(),
+      identifier: identifier.to_string(),
+      action,
+      imported_as,
+    });
+
+    (action, saved_as)
+  }
+
+  fn warn(&mut self, warning: String) {
+    self.report.warnings.push(warning);
+  }
+
+  async fn import_layouts(&mut self, layouts: &[CmsLayoutExport]) -> Result<(), DbErr> {
+    let mut existing_layouts = HashMap::new();
+    for layout in self.cms_parent.cms_layouts().all(self.txn).await? {
+      existing_layouts
+        .entry(CmsLayoutExport::export_name(&layout))
+        .or_insert(layout);
+    }
+    let mut taken = existing_layouts.keys().cloned().collect::<HashSet<_>>();
+    for (name, layout) in &existing_layouts {
+      self.layout_ids.insert(name.clone(), layout.id);
+    }
+
+    for layout in layouts {
+      let existing = existing_layouts.get(&layout.name).cloned();
+      let (action, name) = self.resolve(
+        "CmsLayout",
+        &layout.name,
+        existing.is_some(),
+        " ",
+        &mut taken,
+      );
+      let mut active_model = match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => {
+          record_baseline_revision(self.txn, &existing).await?;
+          existing.into_active_model()
+        }
+        _ => {
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          cms_layouts::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            created_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+        }
+      };
+
+      active_model.name = ActiveValue::Set(Some(name));
+      active_model.content = ActiveValue::Set(layout.content.clone());
+      active_model.navbar_classes = ActiveValue::Set(layout.navbar_classes.clone());
+      active_model.admin_notes = ActiveValue::Set(layout.admin_notes.clone());
+      active_model.updated_at = ActiveValue::Set(self.now);
+      let saved = active_model.save(self.txn).await?.try_into_model()?;
+      record_revision(self.txn, &saved, self.author_id).await?;
+
+      self.layout_ids.insert(layout.name.clone(), saved.id);
+      self.changed_layout_ids.push(saved.id);
+    }
+
+    Ok(())
+  }
+
+  async fn import_partials(&mut self, partials: &[CmsPartialExport]) -> Result<(), DbErr> {
+    let existing_partials = self
+      .cms_parent
+      .cms_partials()
+      .all(self.txn)
+      .await?
+      .into_iter()
+      .map(|partial| (partial.name.clone(), partial))
+      .collect::<HashMap<_, _>>();
+    let mut taken = existing_partials.keys().cloned().collect::<HashSet<_>>();
+    let mut renamed = false;
+
+    for partial in partials {
+      let existing = existing_partials.get(&partial.name).cloned();
+      let (action, name) = self.resolve(
+        "CmsPartial",
+        &partial.name,
+        existing.is_some(),
+        "_",
+        &mut taken,
+      );
+      let mut active_model = match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => {
+          record_baseline_revision(self.txn, &existing).await?;
+          existing.into_active_model()
+        }
+        _ => {
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          cms_partials::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            created_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+        }
+      };
+      renamed = renamed || action == CmsContentSetImportAction::Rename;
+
+      active_model.name = ActiveValue::Set(name.clone());
+      active_model.content = ActiveValue::Set(partial.content.clone());
+      active_model.admin_notes = ActiveValue::Set(partial.admin_notes.clone());
+      active_model.updated_at = ActiveValue::Set(self.now);
+      let saved = active_model.save(self.txn).await?.try_into_model()?;
+      record_revision(self.txn, &saved, self.author_id).await?;
+      self.changed_names.push(name);
+    }
+
+    if renamed {
+      self.warn(
+        "Renamed partials are imported under their new names, but imported content that \
+         includes them still refers to the existing partials"
+          .to_string(),
+      );
+    }
+
+    Ok(())
+  }
+
+  async fn import_graphql_queries(
+    &mut self,
+    graphql_queries: &[CmsGraphqlQueryExport],
+  ) -> Result<(), DbErr> {
+    let existing_queries = self
+      .cms_parent
+      .cms_graphql_queries()
+      .all(self.txn)
+      .await?
+      .into_iter()
+      .filter_map(|query| {
+        query
+          .identifier
+          .clone()
+          .map(|identifier| (identifier, query))
+      })
+      .collect::<HashMap<_, _>>();
+    let mut taken = existing_queries.keys().cloned().collect::<HashSet<_>>();
+
+    for graphql_query in graphql_queries {
+      let existing = existing_queries.get(&graphql_query.identifier).cloned();
+      let (action, identifier) = self.resolve(
+        "CmsGraphqlQuery",
+        &graphql_query.identifier,
+        existing.is_some(),
+        "_",
+        &mut taken,
+      );
+      let mut active_model = match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => existing.into_active_model(),
+        _ => {
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          cms_graphql_queries::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            created_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+        }
+      };
+
+      active_model.identifier = ActiveValue::Set(Some(identifier));
+      active_model.query = ActiveValue::Set(graphql_query.query.clone());
+      active_model.admin_notes = ActiveValue::Set(graphql_query.admin_notes.clone());
+      active_model.updated_at = ActiveValue::Set(self.now);
+      active_model.save(self.txn).await?;
+    }
+
+    Ok(())
+  }
+
+  async fn import_variables(&mut self, variables: &[CmsVariableExport]) -> Result<(), DbErr> {
+    let existing_variables = self
+      .cms_parent
+      .cms_variables()
+      .all(self.txn)
+      .await?
+      .into_iter()
+      .map(|variable| (variable.key.clone(), variable))
+      .collect::<HashMap<_, _>>();
+    let mut taken = existing_variables.keys().cloned().collect::<HashSet<_>>();
+
+    for variable in variables {
+      let existing = existing_variables.get(&variable.key).cloned();
+      let (action, key) = self.resolve(
+        "CmsVariable",
+        &variable.key,
+        existing.is_some(),
+        "_",
+        &mut taken,
+      );
+      let mut active_model = match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => {
+          record_baseline_revision(self.txn, &existing).await?;
+          existing.into_active_model()
+        }
+        _ => {
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          cms_variables::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            created_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+        }
+      };
+
+      active_model.key = ActiveValue::Set(key);
+      active_model.value = ActiveValue::Set(variable.value.clone());
+      active_model.updated_at = ActiveValue::Set(self.now);
+      let saved = active_model.save(self.txn).await?.try_into_model()?;
+      record_revision(self.txn, &saved, self.author_id).await?;
+    }
+
+    Ok(())
+  }
+
+  async fn import_files(&mut self, files: &[CmsFileExport]) -> Result<(), DbErr> {
+    let existing_attachments = active_storage_attachments::Entity::find()
+      .filter(active_storage_attachments::Column::RecordType.eq("CmsFile"))
+      .filter(active_storage_attachments::Column::Name.eq("file"))
+      .filter(
+        active_storage_attachments::Column::RecordId.in_subquery(
+          self
+            .cms_parent
+            .cms_files()
+            .select_only()
+            .column(cms_files::Column::Id)
+            .into_query(),
+        ),
+      )
+      .find_also_related(active_storage_blobs::Entity)
+      .all(self.txn)
+      .await?
+      .into_iter()
+      .filter_map(|(attachment, blob)| blob.map(|blob| (blob.filename, attachment)))
+      .collect::<HashMap<_, _>>();
+    let mut taken = existing_attachments.keys().cloned().collect::<HashSet<_>>();
+
+    for file in files {
+      let blob = active_storage_blobs::Entity::find()
+        .filter(active_storage_blobs::Column::Key.eq(file.blob_key.as_str()))
+        .filter(active_storage_blobs::Column::ServiceName.eq(file.service_name.as_str()))
+        .one(self.txn)
+        .await?;
+      let Some(blob) = blob else {
+        self.report.items.push(CmsContentSetImportItem {
+          content_type: "CmsFile".to_string(),
+          identifier: file.filename.clone(),
+          action: CmsContentSetImportAction::Skip,
+          imported_as: None,
+        });
+        self.warn(format!(
+          "{} isn't stored on this server, so it will have to be uploaded by hand",
+          file.filename
+        ));
+        continue;
+      };
+      if !can_copy_blobs_in(&blob.service_name) {
+        self.report.items.push(CmsContentSetImportItem {
+          content_type: "CmsFile".to_string(),
+          identifier: file.filename.clone(),
+          action: CmsContentSetImportAction::Skip,
+          imported_as: None,
+        });
+        self.warn(format!(
+          "{} is stored somewhere it can't be copied from, so it will have to be uploaded by hand",
+          file.filename
+        ));
+        continue;
+      }
+
+      let existing = existing_attachments.get(&file.filename).cloned();
+      if existing.is_some() && self.strategy == CmsContentSetConflictStrategy::Rename {
+        // a file's name belongs to its upload, which is shared with the content set's source
+        self.report.items.push(CmsContentSetImportItem {
+          content_type: "CmsFile".to_string(),
+          identifier: file.filename.clone(),
+          action: CmsContentSetImportAction::Skip,
+          imported_as: None,
+        });
+        self.warn(format!(
+          "{} already exists, and files can't be renamed on import",
+          file.filename
+        ));
+        continue;
+      }
+
+      let (action, _) = self.resolve(
+        "CmsFile",
+        &file.filename,
+        existing.is_some(),
+        "-",
+        &mut taken,
+      );
+      // each site gets its own copy of the upload, so that renaming or deleting the file on one
+      // site can't touch the other
+      match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => {
+          if existing.blob_id != blob.id {
+            let copy = copy_blob(self.txn, &blob, self.now, !self.dry_run).await?;
+            let mut active_model = existing.into_active_model();
+            active_model.blob_id = ActiveValue::Set(copy.id);
+            active_model.update(self.txn).await?;
+          }
+        }
+        _ => {
+          let copy = copy_blob(self.txn, &blob, self.now, !self.dry_run).await?;
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          let cms_file = cms_files::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            uploader_id: ActiveValue::Set(self.author_id),
+            created_at: ActiveValue::Set(self.now),
+            updated_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+          .insert(self.txn)
+          .await?;
+          active_storage_attachments::ActiveModel {
+            name: ActiveValue::Set("file".to_string()),
+            record_type: ActiveValue::Set("CmsFile".to_string()),
+            record_id: ActiveValue::Set(cms_file.id),
+            blob_id: ActiveValue::Set(copy.id),
+            created_at: ActiveValue::Set(self.now),
+            ..Default::default()
+          }
+          .insert(self.txn)
+          .await?;
+        }
+      }
+      self.changed_names.push(file.filename.clone());
+    }
+
+    Ok(())
+  }
+
+  async fn import_pages(&mut self, imported_pages: &[CmsPageExport]) -> Result<(), DbErr> {
+    let existing_pages = self
+      .cms_parent
+      .pages()
+      .all(self.txn)
+      .await?
+      .into_iter()
+      .filter_map(|page| page.slug.clone().map(|slug| (slug, page)))
+      .collect::<HashMap<_, _>>();
+    let mut taken = existing_pages.keys().cloned().collect::<HashSet<_>>();
+    for (slug, page) in &existing_pages {
+      self.page_ids.insert(slug.clone(), page.id);
+    }
+
+    for page in imported_pages {
+      let existing = existing_pages.get(&page.slug).cloned();
+      let (action, slug) = self.resolve("Page", &page.slug, existing.is_some(), "-", &mut taken);
+      let mut active_model = match (action, existing) {
+        (CmsContentSetImportAction::Skip, _) => continue,
+        (CmsContentSetImportAction::Overwrite, Some(existing)) => {
+          record_baseline_revision(self.txn, &existing).await?;
+          existing.into_active_model()
+        }
+        _ => {
+          let (parent_type, parent_id) = self.cms_parent.parent_columns();
+          pages::ActiveModel {
+            parent_type: ActiveValue::Set(parent_type),
+            parent_id: ActiveValue::Set(parent_id),
+            created_at: ActiveValue::Set(Some(self.now)),
+            ..Default::default()
+          }
+        }
+      };
+
+      let cms_layout_id = match &page.cms_layout_name {
+        Some(layout_name) => {
+          let layout_id = self.layout_ids.get(layout_name).copied();
+          if layout_id.is_none() {
+            self.warn(format!(
+              "Page {} uses the layout {}, which isn't in the content set or on this site",
+              page.slug, layout_name
+            ));
+          }
+          layout_id
+        }
+        None => None,
+      };
+
+      active_model.name = ActiveValue::Set(page.name.clone());
+      active_model.slug = ActiveValue::Set(Some(slug));
+      active_model.content = ActiveValue::Set(page.content.clone());
+      active_model.admin_notes = ActiveValue::Set(page.admin_notes.clone());
+      active_model.cms_layout_id = ActiveValue::Set(cms_layout_id);
+      active_model.skip_clickwrap_agreement = ActiveValue::Set(page.skip_clickwrap_agreement);
+      active_model.hidden_from_search = ActiveValue::Set(page.hidden_from_search);
+      active_model.updated_at = ActiveValue::Set(Some(self.now));
+      let saved = active_model.save(self.txn).await?.try_into_model()?;
+      record_revision(self.txn, &saved, self.author_id).await?;
+      index_pages(self.txn, std::slice::from_ref(&saved)).await?;
+
+      self.page_ids.insert(page.slug.clone(), saved.id);
+      self.changed_page_ids.push(saved.id);
+    }
+
+    Ok(())
+  }
+
+  /// Creates a navigation item, unless it links to a page that can't be found.  Returns the new
+  /// item's id.
+  async fn create_navigation_item(
+    &mut self,
+    item: &CmsNavigationItemExport,
+    navigation_section_id: Option<i64>,
+    position: i32,
+  ) -> Result<Option<i64>, DbErr> {
+    let page_id = match &item.page_slug {
+      Some(page_slug) => match self.page_ids.get(page_slug) {
+        Some(page_id) => Some(*page_id),
+        None => {
+          self.warn(format!(
+            "The navigation link {} goes to the page {}, which isn't in the content set or on \
+             this site",
+            item.title.as_deref().unwrap_or_default(),
+            page_slug
+          ));
+          return Ok(None);
+        }
+      },
+      None => None,
+    };
+
+    let (parent_type, parent_id) = self.cms_parent.parent_columns();
+    let created = cms_navigation_items::ActiveModel {
+      parent_type: ActiveValue::Set(parent_type),
+      parent_id: ActiveValue::Set(parent_id),
+      title: ActiveValue::Set(item.title.clone()),
+      navigation_section_id: ActiveValue::Set(navigation_section_id),
+      page_id: ActiveValue::Set(page_id),
+      position: ActiveValue::Set(Some(position)),
+      created_at: ActiveValue::Set(self.now),
+      updated_at: ActiveValue::Set(self.now),
+      ..Default::default()
+    }
+    .insert(self.txn)
+    .await?;
+
+    Ok(Some(created.id))
+  }
+
+  /// Navigation is imported as a whole: when the target already has navigation, skip leaves it
+  /// alone, overwrite replaces it, and rename adds the imported items after the existing ones.
+  async fn import_navigation(&mut self, items: &[CmsNavigationItemExport]) -> Result<(), DbErr> {
+    if items.is_empty() {
+      return Ok(());
+    }
+
+    let existing_items = self.cms_parent.cms_navigation_items();
+    let existing_count = existing_items.clone().count(self.txn).await?;
+    let (action, _) = self.resolve(
+      "CmsNavigationItem",
+      "navigation",
+      existing_count > 0,
+      "",
+      &mut HashSet::new(),
+    );
+
+    let first_position = match action {
+      CmsContentSetImportAction::Skip => return Ok(()),
+      CmsContentSetImportAction::Overwrite => {
+        // links have to go before the sections they're in
+        cms_navigation_items::Entity::delete_many()
+          .filter(cms_navigation_items::Column::NavigationSectionId.is_not_null())
+          .filter(
+            cms_navigation_items::Column::Id.in_subquery(
+              existing_items
+                .clone()
+                .select_only()
+                .column(cms_navigation_items::Column::Id)
+                .into_query(),
+            ),
+          )
+          .exec(self.txn)
+          .await?;
+        cms_navigation_items::Entity::delete_many()
+          .filter(
+            cms_navigation_items::Column::Id.in_subquery(
+              existing_items
+                .select_only()
+                .column(cms_navigation_items::Column::Id)
+                .into_query(),
+            ),
+          )
+          .exec(self.txn)
+          .await?;
+        1
+      }
+      CmsContentSetImportAction::Rename => {
+        let max_position: Option<i32> = existing_items
+          .filter(cms_navigation_items::Column::NavigationSectionId.is_null())
+          .select_only()
+          .column_as(
+            Expr::col(cms_navigation_items::Column::Position).max(),
+            "max_position",
+          )
+          .into_tuple()
+          .one(self.txn)
+          .await?
+          .flatten();
+        max_position.unwrap_or(0) + 1
+      }
+      CmsContentSetImportAction::Create => 1,
+    };
+
+    // sections can only contain links, so there are never more than two levels to create
+    let mut position = first_position;
+    for item in items {
+      let Some(item_id) = self.create_navigation_item(item, None, position).await? else {
+        continue;
+      };
+      position += 1;
+
+      if item.page_slug.is_none() {
+        let mut link_position = 1;
+        for link in &item.items {
+          if self
+            .create_navigation_item(link, Some(item_id), link_position)
+            .await?
+            .is_some()
+          {
+            link_position += 1;
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Points the parent's root page and default layout at the imported ones, if the parent
+  /// doesn't have its own or the strategy is to overwrite
+  async fn import_parent_settings(
+    &mut self,
+    content_set: &CmsContentSetExport,
+  ) -> Result<(), DbErr> {
+    let overwrite = self.strategy == CmsContentSetConflictStrategy::Overwrite;
+    let (current_root_page_id, current_default_layout_id) = match self.cms_parent {
+      CmsParent::Convention(convention) => (convention.root_page_id, convention.default_layout_id),
+      CmsParent::RootSite(root_site) => (root_site.root_page_id, root_site.default_layout_id),
+    };
+
+    let mut root_page_id = current_root_page_id;
+    if let Some(slug) = &content_set.root_page_slug {
+      match self.page_ids.get(slug) {
+        Some(page_id) if current_root_page_id.is_none() || overwrite => {
+          root_page_id = Some(*page_id)
+        }
+        Some(_) => {}
+        None => self.warn(format!("The root page, {}, isn't in the content set", slug)),
+      }
+    }
+
+    let mut default_layout_id = current_default_layout_id;
+    if let Some(name) = &content_set.default_layout_name {
+      match self.layout_ids.get(name) {
+        Some(layout_id) if current_default_layout_id.is_none() || overwrite => {
+          default_layout_id = Some(*layout_id)
+        }
+        Some(_) => {}
+        None => self.warn(format!(
+          "The default layout, {}, isn't in the content set",
+          name
+        )),
+      }
+    }
+
+    if root_page_id == current_root_page_id && default_layout_id == current_default_layout_id {
+      return Ok(());
+    }
+
+    match self.cms_parent {
+      CmsParent::Convention(convention) => {
+        conventions::ActiveModel {
+          id: ActiveValue::Unchanged(convention.id),
+          root_page_id: ActiveValue::Set(root_page_id),
+          default_layout_id: ActiveValue::Set(default_layout_id),
+          ..Default::default()
+        }
+        .update(self.txn)
+        .await?;
+      }
+      CmsParent::RootSite(root_site) => {
+        root_sites::ActiveModel {
+          id: ActiveValue::Unchanged(root_site.id),
+          root_page_id: ActiveValue::Set(root_page_id),
+          default_layout_id: ActiveValue::Set(default_layout_id),
+          ..Default::default()
+        }
+        .update(self.txn)
+        .await?;
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn update_references(&mut self) -> Result<(), DbErr> {
+    if !self.changed_page_ids.is_empty() {
+      let changed_pages = self
+        .cms_parent
+        .pages()
+        .filter(pages::Column::Id.is_in(self.changed_page_ids.clone()))
+        .all(self.txn)
+        .await?;
+      for page in changed_pages {
+        update_page_references(self.txn, self.cms_parent, &page).await?;
+      }
+    }
+
+    if !self.changed_layout_ids.is_empty() {
+      let changed_layouts = self
+        .cms_parent
+        .cms_layouts()
+        .filter(cms_layouts::Column::Id.is_in(self.changed_layout_ids.clone()))
+        .all(self.txn)
+        .await?;
+      for layout in changed_layouts {
+        update_layout_references(self.txn, self.cms_parent, &layout).await?;
+      }
+    }
+
+    let changed_names = self
+      .changed_names
+      .iter()
+      .map(|name| name.as_str())
+      .collect::<Vec<_>>();
+    refresh_references_to_names(self.txn, self.cms_parent, &changed_names).await
+  }
+}
+
+/// Imports a content set into a CMS parent.  Content is matched up with what's already there by
+/// name (or slug, key, identifier or filename), and the strategy decides what happens when
+/// something with the same name exists.
+///
+/// Like user merges, imports run in their own transaction, and dry runs do all the same work
+/// before rolling it back, so that their report is exactly what a real import would do.
+pub async fn import_cms_content_set<C: ConnectionTrait + TransactionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  content_set: &CmsContentSetExport,
+  strategy: CmsContentSetConflictStrategy,
+  author_id: Option<i64>,
+  dry_run: bool,
+) -> Result<CmsContentSetImportReport, DbErr> {
+  if content_set.format_version > CMS_CONTENT_SET_FORMAT_VERSION {
+    return Err(DbErr::Custom(format!(
+      "This content set uses format version {}, but only versions up to {} can be imported",
+      content_set.format_version, CMS_CONTENT_SET_FORMAT_VERSION
+    )));
+  }
+
+  let txn = db.begin().await?;
+  let mut importer = ContentSetImporter {
+    txn: &txn,
+    cms_parent,
+    strategy,
+    author_id,
+    dry_run,
+    now: Utc::now().naive_utc(),
+    report: CmsContentSetImportReport {
+      dry_run,
+      imported: false,
+      items: vec![],
+      warnings: vec![],
+    },
+    layout_ids: HashMap::new(),
+    page_ids: HashMap::new(),
+    changed_page_ids: vec![],
+    changed_layout_ids: vec![],
+    changed_names: vec![],
+  };
+
+  // layouts and partials come first so that pages can refer to them
+  importer.import_layouts(&content_set.layouts).await?;
+  importer.import_partials(&content_set.partials).await?;
+  importer
+    .import_graphql_queries(&content_set.graphql_queries)
+    .await?;
+  importer.import_variables(&content_set.variables).await?;
+  importer.import_files(&content_set.files).await?;
+  importer.import_pages(&content_set.pages).await?;
+  importer
+    .import_navigation(&content_set.navigation_items)
+    .await?;
+  importer.import_parent_settings(content_set).await?;
+  importer.update_references().await?;
+
+  let mut report = importer.report;
+  if dry_run {
+    txn.rollback().await?;
+  } else {
+    txn.commit().await?;
+    report.imported = true;
+  }
+
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_unused_names() {
+    let taken = ["about", "about-2"]
+      .into_iter()
+      .map(String::from)
+      .collect::<HashSet<_>>();
+
+    assert_eq!(unique_name("about", "-", &taken), "about-3");
+    assert_eq!(unique_name("header", "_", &taken), "header_2");
+  }
+}
@@ -0,0 +1,11 @@
+//! Content sets are portable bundles of a CMS parent's content, used to start a convention off
+//! with a copy of the previous year's site or from a template.  Since ids don't carry over from
+//! one parent to another, everything in a set refers to everything else by name.
+
+mod archive;
+mod export;
+mod import;
+
+pub use archive::*;
+pub use export::*;
+pub use import::*;
@@ -0,0 +1,403 @@
+//! Caching for rendered pages and layouts.  A render is cached when it comes out the same for
+//! everyone who sees it: always for anonymous visitors, and for signed-in users only when the
+//! content (including the partials it uses) never looks at the current profile or runs GraphQL
+//! queries.  Cache keys change whenever the page or layout is saved, and whenever any of the CMS
+//! parent's partials, files, GraphQL queries or variables are added, changed or removed, so a
+//! render that raced with an edit can only ever fill an entry nobody will look up again.  Edits
+//! also evict entries by following the reference tables, to free the space sooner.
+
+use std::future::Future;
+
+use chrono::NaiveDateTime;
+use intercode_entities::{
+  cms_files, cms_graphql_queries, cms_layouts, cms_layouts_partials,
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_partials, cms_partials_pages, cms_variables, pages,
+};
+use intercode_graphql_core::{
+  after_commit::AfterCommitHooks,
+  cms_output_cache::{CmsOutputCache, CmsOutputCacheEntry},
+  query_data::QueryData,
+};
+use intercode_liquid::content_references::find_content_references;
+use sea_orm::{
+  sea_query::Query, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QuerySelect,
+  Select,
+};
+use tracing::warn;
+
+use crate::cms_references::find_content_referencing_names;
+
+const PAGE_CONTENT_TYPE: &str = "Page";
+const LAYOUT_CONTENT_TYPE: &str = "CmsLayout";
+
+#[derive(Debug, Clone, Copy)]
+pub enum CachedCmsContent<'a> {
+  Page(&'a pages::Model),
+  Layout(&'a cms_layouts::Model),
+}
+
+impl<'a> CachedCmsContent<'a> {
+  fn content_type(&self) -> &'static str {
+    match self {
+      CachedCmsContent::Page(_) => PAGE_CONTENT_TYPE,
+      CachedCmsContent::Layout(_) => LAYOUT_CONTENT_TYPE,
+    }
+  }
+
+  fn id(&self) -> i64 {
+    match self {
+      CachedCmsContent::Page(page) => page.id,
+      CachedCmsContent::Layout(layout) => layout.id,
+    }
+  }
+
+  fn content(&self) -> &'a str {
+    match self {
+      CachedCmsContent::Page(page) => page.content.as_deref().unwrap_or_default(),
+      CachedCmsContent::Layout(layout) => layout.content.as_deref().unwrap_or_default(),
+    }
+  }
+
+  fn updated_at(&self) -> Option<NaiveDateTime> {
+    match self {
+      CachedCmsContent::Page(page) => page.updated_at,
+      CachedCmsContent::Layout(layout) => Some(layout.updated_at),
+    }
+  }
+
+  /// The layout a page renders inside of
+  fn layout_id(&self, cms_parent: &CmsParent) -> Option<i64> {
+    match self {
+      CachedCmsContent::Page(page) => page.cms_layout_id.or(match cms_parent {
+        CmsParent::Convention(convention) => convention.default_layout_id,
+        CmsParent::RootSite(root_site) => root_site.default_layout_id,
+      }),
+      CachedCmsContent::Layout(layout) => Some(layout.id),
+    }
+  }
+}
+
+/// Everything a cache key is made of.  The content's updated_at and the parent's content version
+/// are part of the key, so saving anything a render could depend on makes its old entries
+/// unreachable even before they're evicted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CmsOutputCacheKey {
+  parent_type: Option<String>,
+  parent_id: Option<i64>,
+  content_type: &'static str,
+  content_id: i64,
+  content_updated_at: Option<NaiveDateTime>,
+  layout_id: Option<i64>,
+  content_version: String,
+  user_signed_in: bool,
+}
+
+impl CmsOutputCacheKey {
+  fn to_key_string(&self) -> String {
+    format!(
+      "{}:{}/{}:{}@{}/layout:{}/content:{}/{}",
+      self.parent_type.as_deref().unwrap_or("RootSite"),
+      self.parent_id.map(|id| id.to_string()).unwrap_or_default(),
+      self.content_type,
+      self.content_id,
+      self
+        .content_updated_at
+        .map(|updated_at| updated_at.timestamp_micros())
+        .unwrap_or_default(),
+      self
+        .layout_id
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| "none".to_string()),
+      self.content_version,
+      if self.user_signed_in {
+        "signed_in"
+      } else {
+        "anonymous"
+      }
+    )
+  }
+}
+
+/// A version string for one kind of CMS content that changes whenever a row is added, removed or
+/// updated
+async fn table_version<E: EntityTrait, C: ConnectionTrait>(
+  db: &C,
+  select: Select<E>,
+  id_column: E::Column,
+  updated_at_column: E::Column,
+) -> Result<String, DbErr> {
+  let (count, last_updated_at) = select
+    .select_only()
+    .column_as(id_column.count(), "count")
+    .column_as(updated_at_column.max(), "last_updated_at")
+    .into_tuple::<(i64, Option<NaiveDateTime>)>()
+    .one(db)
+    .await?
+    .unwrap_or((0, None));
+
+  Ok(format!(
+    "{}-{}",
+    count,
+    last_updated_at
+      .map(|updated_at| updated_at.timestamp_micros())
+      .unwrap_or_default()
+  ))
+}
+
+/// A version string covering everything in a CMS parent that a page or layout can pull in by
+/// name: partials, files, GraphQL queries and variables
+async fn content_version<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+) -> Result<String, DbErr> {
+  Ok(
+    [
+      table_version(
+        db,
+        cms_parent.cms_partials(),
+        cms_partials::Column::Id,
+        cms_partials::Column::UpdatedAt,
+      )
+      .await?,
+      table_version(
+        db,
+        cms_parent.cms_files(),
+        cms_files::Column::Id,
+        cms_files::Column::UpdatedAt,
+      )
+      .await?,
+      table_version(
+        db,
+        cms_parent.cms_graphql_queries(),
+        cms_graphql_queries::Column::Id,
+        cms_graphql_queries::Column::UpdatedAt,
+      )
+      .await?,
+      table_version(
+        db,
+        cms_parent.cms_variables(),
+        cms_variables::Column::Id,
+        cms_variables::Column::UpdatedAt,
+      )
+      .await?,
+    ]
+    .join(","),
+  )
+}
+
+async fn referenced_partials<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  content: CachedCmsContent<'_>,
+) -> Result<Vec<cms_partials::Model>, DbErr> {
+  let partial_ids = match content {
+    CachedCmsContent::Page(page) => Query::select()
+      .column(cms_partials_pages::Column::CmsPartialId)
+      .from(cms_partials_pages::Entity)
+      .and_where(cms_partials_pages::Column::PageId.eq(page.id))
+      .to_owned(),
+    CachedCmsContent::Layout(layout) => Query::select()
+      .column(cms_layouts_partials::Column::CmsPartialId)
+      .from(cms_layouts_partials::Entity)
+      .and_where(cms_layouts_partials::Column::CmsLayoutId.eq(layout.id))
+      .to_owned(),
+  };
+
+  cms_parent
+    .cms_partials()
+    .filter(cms_partials::Column::Id.in_subquery(partial_ids))
+    .all(db)
+    .await
+}
+
+/// Whether a template renders differently depending on who's signed in, beyond whether anyone is
+fn varies_by_user(content: &str) -> bool {
+  content.contains("user_con_profile")
+    || !find_content_references(content)
+      .graphql_query_identifiers
+      .is_empty()
+}
+
+async fn cache_key_for_render<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  content: CachedCmsContent<'_>,
+  user_signed_in: bool,
+) -> Result<Option<CmsOutputCacheKey>, DbErr> {
+  if user_signed_in {
+    if varies_by_user(content.content()) {
+      return Ok(None);
+    }
+
+    let partials = referenced_partials(db, cms_parent, content).await?;
+    if partials
+      .iter()
+      .any(|partial| varies_by_user(partial.content.as_deref().unwrap_or_default()))
+    {
+      return Ok(None);
+    }
+  }
+
+  let (parent_type, parent_id) = cms_parent.parent_columns();
+  Ok(Some(CmsOutputCacheKey {
+    parent_type,
+    parent_id,
+    content_type: content.content_type(),
+    content_id: content.id(),
+    content_updated_at: content.updated_at(),
+    layout_id: content.layout_id(cms_parent),
+    content_version: content_version(db, cms_parent).await?,
+    user_signed_in,
+  }))
+}
+
+/// Renders a page or layout through the output cache, calling `render` only on a miss (or when
+/// the render can't be cached).  Cache failures are logged and fall back to rendering, so a
+/// broken cache can slow pages down but never break them.
+pub async fn render_with_output_cache<F>(
+  cache: &CmsOutputCache,
+  query_data: &QueryData,
+  content: CachedCmsContent<'_>,
+  render: F,
+) -> Result<String, async_graphql::Error>
+where
+  F: Future<Output = Result<String, async_graphql::Error>>,
+{
+  if !cache.is_enabled() {
+    return render.await;
+  }
+
+  let db = query_data.db();
+  let cms_parent = query_data.cms_parent();
+  let user_signed_in = query_data.current_user().is_some();
+
+  let key = match cache_key_for_render(db, cms_parent, content, user_signed_in).await {
+    Ok(Some(key)) => key,
+    Ok(None) => return render.await,
+    Err(err) => {
+      warn!("Error building CMS output cache key: {}", err);
+      return render.await;
+    }
+  };
+  let cache_key = key.to_key_string();
+
+  match cache.get(db, &cache_key).await {
+    Ok(Some(output)) => return Ok(output),
+    Ok(None) => {}
+    Err(err) => warn!("Error reading from CMS output cache: {}", err),
+  }
+
+  let output = render.await?;
+  let entry = CmsOutputCacheEntry {
+    cache_key,
+    parent_type: key.parent_type,
+    parent_id: key.parent_id,
+    content_type: key.content_type.to_string(),
+    content_id: key.content_id,
+    output: output.clone(),
+  };
+  if let Err(err) = cache.put(db, entry).await {
+    warn!("Error writing to CMS output cache: {}", err);
+  }
+
+  Ok(output)
+}
+
+/// Something that changed in the CMS, and so might make cached output stale
+#[derive(Debug, Clone, Copy)]
+pub enum CmsOutputInvalidation<'a> {
+  Page(i64),
+  Layout(i64),
+  /// A partial, file or GraphQL query with one of these names was created, renamed, changed or
+  /// deleted.  Call this after the reference tables are refreshed.
+  ReferencesTo(&'a [&'a str]),
+  /// Something every render depends on changed, such as a CMS variable
+  Everything,
+}
+
+pub async fn invalidate_cms_output<C: ConnectionTrait>(
+  db: &C,
+  cache: &CmsOutputCache,
+  after_commit: &AfterCommitHooks,
+  cms_parent: &CmsParent,
+  invalidation: CmsOutputInvalidation<'_>,
+) -> Result<(), DbErr> {
+  if !cache.is_enabled() {
+    return Ok(());
+  }
+
+  match invalidation {
+    CmsOutputInvalidation::Page(page_id) => {
+      cache
+        .invalidate_contents(db, after_commit, PAGE_CONTENT_TYPE, &[page_id])
+        .await
+    }
+    CmsOutputInvalidation::Layout(layout_id) => {
+      cache
+        .invalidate_contents(db, after_commit, LAYOUT_CONTENT_TYPE, &[layout_id])
+        .await
+    }
+    CmsOutputInvalidation::ReferencesTo(names) => {
+      let (pages, layouts) = find_content_referencing_names(db, cms_parent, names).await?;
+      let page_ids = pages.iter().map(|page| page.id).collect::<Vec<_>>();
+      let layout_ids = layouts.iter().map(|layout| layout.id).collect::<Vec<_>>();
+
+      cache
+        .invalidate_contents(db, after_commit, PAGE_CONTENT_TYPE, &page_ids)
+        .await?;
+      cache
+        .invalidate_contents(db, after_commit, LAYOUT_CONTENT_TYPE, &layout_ids)
+        .await
+    }
+    CmsOutputInvalidation::Everything => {
+      let (parent_type, parent_id) = cms_parent.parent_columns();
+      cache
+        .invalidate_parent(db, after_commit, parent_type.as_deref(), parent_id)
+        .await
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cache_keys_vary_by_signed_in_status() {
+    let key = CmsOutputCacheKey {
+      parent_type: Some("Convention".to_string()),
+      parent_id: Some(12),
+      content_type: PAGE_CONTENT_TYPE,
+      content_id: 34,
+      content_updated_at: NaiveDateTime::from_timestamp_opt(1_700_000_000, 0),
+      layout_id: Some(5),
+      content_version: "2-1700000000000000,0-0,0-0,3-1700000000000000".to_string(),
+      user_signed_in: false,
+    };
+
+    assert_eq!(
+      key.to_key_string(),
+      "Convention:12/Page:34@1700000000000000/layout:5/content:2-1700000000000000,0-0,0-0,3-1700000000000000/anonymous"
+    );
+    assert_ne!(
+      key.to_key_string(),
+      CmsOutputCacheKey {
+        user_signed_in: true,
+        ..key.clone()
+      }
+      .to_key_string()
+    );
+  }
+
+  #[test]
+  fn detects_per_user_content() {
+    assert!(varies_by_user("Hi {{ user_con_profile.first_name }}"));
+    assert!(varies_by_user(
+      "{% assign_graphql_result events = upcomingEvents %}{{ events.size }}"
+    ));
+    assert!(!varies_by_user(
+      "{% include 'header' %}Welcome to {{ convention.name }}"
+    ));
+  }
+}
@@ -1,23 +1,95 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use async_graphql::{Context, Error, ID};
 use async_trait::async_trait;
 use futures::{try_join, TryFutureExt};
 use intercode_entities::{
-  cms_content_groups, cms_files, cms_graphql_queries, cms_layouts, cms_parent::CmsParentTrait,
+  active_storage_blobs, cms_content_groups, cms_content_revisions, cms_files, cms_graphql_queries,
+  cms_layouts, cms_parent::CmsParentTrait, cms_partials, cms_variables, event_proposals, events,
   pages,
 };
+use intercode_full_text_search::text_search::{
+  contains_ignoring_case, ranked_text_search, starts_with_ignoring_case, TextSearchQuery,
+};
 use intercode_graphql_core::{
-  liquid_renderer::LiquidRenderer, query_data::QueryData, ModelBackedType,
+  lax_id::LaxId, liquid_renderer::LiquidRenderer, query_data::QueryData, ModelBackedType,
 };
-use intercode_liquid::render_markdown;
-use sea_orm::{ColumnTrait, QueryFilter};
+use intercode_graphql_loaders::LoaderManager;
+use intercode_liquid::{render_markdown, url_builder::UrlBuilder};
+use intercode_policies::{ensure_action_permitted, ReadManageAction};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect};
 
 use crate::{
-  api::objects::{CmsContentType, CmsLayoutType, LiquidAssignType, PageType},
+  api::{
+    objects::{
+      CmsContentRevisionDiffType, CmsContentRevisionType, CmsContentSearchResultType,
+      CmsContentType, CmsLayoutType, CmsPartialType, LiquidAssignType, PageType,
+    },
+    policies::{
+      CmsContentAuthorizable, CmsContentPolicy, CmsLayoutPolicy, CmsPartialPolicy,
+      CmsVariablePolicy, PagePolicy,
+    },
+  },
+  cms_revisions::{changed_fields, diff_lines, snapshot_body, CmsRevisionedContentKind},
   CmsRenderingContext,
 };
 
+const TYPEAHEAD_LIMIT: u64 = 10;
+const SEARCH_SNIPPET_LENGTH: usize = 160;
+
+/// The images a markdown preview can refer to by filename: those attached to the event or event
+/// proposal being edited, as long as it belongs to the current convention
+async fn preview_image_attachments(
+  ctx: &Context<'_>,
+  event_id: Option<ID>,
+  event_proposal_id: Option<ID>,
+) -> Result<HashMap<String, active_storage_blobs::Model>, Error> {
+  let query_data = ctx.data::<QueryData>()?;
+  let loaders = ctx.data::<Arc<LoaderManager>>()?;
+  let Some(convention) = query_data.convention() else {
+    return Ok(HashMap::new());
+  };
+
+  let blobs = if let Some(event_id) = event_id {
+    let event_id = LaxId::parse(event_id)?;
+    let event_count = events::Entity::find()
+      .filter(events::Column::Id.eq(event_id))
+      .filter(events::Column::ConventionId.eq(convention.id))
+      .count(query_data.db())
+      .await?;
+    if event_count == 0 {
+      return Err(Error::new("Event not found"));
+    }
+
+    loaders.event_attached_images.load_one(event_id).await?
+  } else if let Some(event_proposal_id) = event_proposal_id {
+    let event_proposal_id = LaxId::parse(event_proposal_id)?;
+    let event_proposal_count = event_proposals::Entity::find()
+      .filter(event_proposals::Column::Id.eq(event_proposal_id))
+      .filter(event_proposals::Column::ConventionId.eq(convention.id))
+      .count(query_data.db())
+      .await?;
+    if event_proposal_count == 0 {
+      return Err(Error::new("Event proposal not found"));
+    }
+
+    loaders
+      .event_proposal_attached_images
+      .load_one(event_proposal_id)
+      .await?
+  } else {
+    None
+  };
+
+  Ok(
+    blobs
+      .unwrap_or_default()
+      .into_iter()
+      .map(|blob| (blob.filename.clone(), blob))
+      .collect(),
+  )
+}
+
 macro_rules! assoc_getter {
   ($name: ident) => {
     fn $name<'life0, 'async_trait, T: ModelBackedType<Model = ::intercode_entities::$name::Model>>(
@@ -81,11 +153,132 @@ macro_rules! id_getter {
 pub trait CmsParentImplementation<M>
 where
   Self: ModelBackedType<Model = M>,
-  M: CmsParentTrait + sea_orm::ModelTrait + Sync,
+  M: CmsParentTrait + CmsContentAuthorizable + sea_orm::ModelTrait + Sync,
 {
   assoc_getter!(cms_content_groups);
   id_getter!(cms_content_group, cms_content_groups);
 
+  /// Revisions are visible to people who can manage the content they belong to, or, once that
+  /// content has been deleted, to people who can manage all the content in the parent
+  async fn ensure_can_manage_revisions(
+    &self,
+    ctx: &Context<'_>,
+    content_type: CmsRevisionedContentKind,
+    content_id: i64,
+  ) -> Result<(), Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let model = self.get_model();
+    let manage = ReadManageAction::Manage;
+
+    match content_type {
+      CmsRevisionedContentKind::Page => {
+        let page = model.pages().filter(pages::Column::Id.eq(content_id));
+        if let Some(page) = page.one(db).await? {
+          return ensure_action_permitted::<PagePolicy, _>(ctx, &manage, &page).await;
+        }
+      }
+      CmsRevisionedContentKind::CmsPartial => {
+        let partial = model
+          .cms_partials()
+          .filter(cms_partials::Column::Id.eq(content_id));
+        if let Some(partial) = partial.one(db).await? {
+          return ensure_action_permitted::<CmsPartialPolicy, _>(ctx, &manage, &partial).await;
+        }
+      }
+      CmsRevisionedContentKind::CmsLayout => {
+        let layout = model
+          .cms_layouts()
+          .filter(cms_layouts::Column::Id.eq(content_id));
+        if let Some(layout) = layout.one(db).await? {
+          return ensure_action_permitted::<CmsLayoutPolicy, _>(ctx, &manage, &layout).await;
+        }
+      }
+      CmsRevisionedContentKind::CmsVariable => {
+        let variable = model
+          .cms_variables()
+          .filter(cms_variables::Column::Id.eq(content_id));
+        if let Some(variable) = variable.one(db).await? {
+          return ensure_action_permitted::<CmsVariablePolicy, _>(ctx, &manage, &variable).await;
+        }
+      }
+    }
+
+    ensure_action_permitted::<CmsContentPolicy<M>, _>(ctx, &manage, model).await
+  }
+
+  async fn cms_content_revisions(
+    &self,
+    ctx: &Context<'_>,
+    content_type: CmsRevisionedContentKind,
+    content_id: ID,
+  ) -> Result<Vec<CmsContentRevisionType>, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let content_id = LaxId::parse(content_id)?;
+    self
+      .ensure_can_manage_revisions(ctx, content_type, content_id)
+      .await?;
+
+    Ok(
+      self
+        .get_model()
+        .cms_content_revisions()
+        .filter(cms_content_revisions::Column::ContentType.eq(content_type.as_str()))
+        .filter(cms_content_revisions::Column::ContentId.eq(content_id))
+        .order_by_desc(cms_content_revisions::Column::CreatedAt)
+        .order_by_desc(cms_content_revisions::Column::Id)
+        .all(query_data.db())
+        .await?
+        .into_iter()
+        .map(CmsContentRevisionType::new)
+        .collect(),
+    )
+  }
+
+  async fn cms_content_revision_diff(
+    &self,
+    ctx: &Context<'_>,
+    from_revision_id: ID,
+    to_revision_id: ID,
+  ) -> Result<CmsContentRevisionDiffType, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let revisions = self.get_model().cms_content_revisions();
+    let from_revision = revisions
+      .clone()
+      .filter(cms_content_revisions::Column::Id.eq(LaxId::parse(from_revision_id)?))
+      .one(query_data.db())
+      .await?
+      .ok_or_else(|| Error::new("Revision not found"))?;
+    let to_revision = revisions
+      .filter(cms_content_revisions::Column::Id.eq(LaxId::parse(to_revision_id)?))
+      .one(query_data.db())
+      .await?
+      .ok_or_else(|| Error::new("Revision not found"))?;
+
+    if from_revision.content_type != to_revision.content_type
+      || from_revision.content_id != to_revision.content_id
+    {
+      return Err(Error::new(
+        "Only revisions of the same content can be compared",
+      ));
+    }
+    let content_type = CmsRevisionedContentKind::from_content_type(&from_revision.content_type)
+      .ok_or_else(|| Error::new("Unknown content type"))?;
+    self
+      .ensure_can_manage_revisions(ctx, content_type, from_revision.content_id)
+      .await?;
+
+    Ok(CmsContentRevisionDiffType {
+      changed_fields: changed_fields(&from_revision.snapshot, &to_revision.snapshot),
+      lines: diff_lines(
+        &snapshot_body(&from_revision.snapshot),
+        &snapshot_body(&to_revision.snapshot),
+      ),
+      from_revision: CmsContentRevisionType::new(from_revision),
+      to_revision: CmsContentRevisionType::new(to_revision),
+    })
+  }
+
   assoc_getter!(cms_files);
   id_getter!(cms_file, cms_files);
 
@@ -211,13 +404,18 @@ where
 
   async fn preview_markdown(
     &self,
-    _ctx: &Context<'_>,
+    ctx: &Context<'_>,
     markdown: String,
-    _event_id: Option<ID>,
-    _event_proposal_id: Option<ID>,
+    event_id: Option<ID>,
+    event_proposal_id: Option<ID>,
   ) -> Result<String, Error> {
-    // TODO find images for event or event proposal
-    Ok(render_markdown(&markdown, &Default::default()))
+    let query_data = ctx.data::<QueryData>()?;
+    let image_attachments = preview_image_attachments(ctx, event_id, event_proposal_id).await?;
+    Ok(render_markdown(
+      &markdown,
+      &image_attachments,
+      &UrlBuilder::new(query_data.cms_parent()),
+    ))
   }
 
   async fn root_page(&self, ctx: &Context<'_>) -> Result<PageType, Error> {
@@ -231,12 +429,168 @@ where
       .map(PageType::new)
   }
 
+  /// Pages, partials and layouts whose names contain the given text, with the ones whose names
+  /// start with it first
   async fn typeahead_search_cms_content(
     &self,
-    _ctx: &Context<'_>,
-    _name: Option<String>,
+    ctx: &Context<'_>,
+    name: Option<String>,
   ) -> Result<Vec<CmsContentType>, Error> {
-    // TODO
-    Ok(vec![])
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let model = self.get_model();
+    let name = name
+      .map(|name| name.trim().to_string())
+      .filter(|name| !name.is_empty());
+
+    let mut page_scope = model.pages();
+    let mut partial_scope = model.cms_partials();
+    let mut layout_scope = model.cms_layouts();
+    if let Some(name) = &name {
+      // names that start with what was typed come first; limiting before ordering that way would
+      // drop them whenever there are lots of matches elsewhere in the name
+      page_scope = page_scope
+        .filter(contains_ignoring_case("pages.name", name))
+        .order_by_desc(starts_with_ignoring_case("pages.name", name));
+      partial_scope = partial_scope
+        .filter(contains_ignoring_case("cms_partials.name", name))
+        .order_by_desc(starts_with_ignoring_case("cms_partials.name", name));
+      layout_scope = layout_scope
+        .filter(contains_ignoring_case("cms_layouts.name", name))
+        .order_by_desc(starts_with_ignoring_case("cms_layouts.name", name));
+    }
+
+    let (pages, partials, layouts) = try_join!(
+      page_scope
+        .order_by_asc(pages::Column::Name)
+        .limit(TYPEAHEAD_LIMIT)
+        .all(db),
+      partial_scope
+        .order_by_asc(cms_partials::Column::Name)
+        .limit(TYPEAHEAD_LIMIT)
+        .all(db),
+      layout_scope
+        .order_by_asc(cms_layouts::Column::Name)
+        .limit(TYPEAHEAD_LIMIT)
+        .all(db)
+    )?;
+
+    let mut results = pages
+      .into_iter()
+      .map(|page| {
+        (
+          page.name.clone().unwrap_or_default(),
+          CmsContentType::Page(PageType::new(page)),
+        )
+      })
+      .chain(partials.into_iter().map(|partial| {
+        (
+          partial.name.clone(),
+          CmsContentType::Partial(CmsPartialType::new(partial)),
+        )
+      }))
+      .chain(layouts.into_iter().map(|layout| {
+        (
+          layout.name.clone().unwrap_or_default(),
+          CmsContentType::Layout(CmsLayoutType::new(layout)),
+        )
+      }))
+      .collect::<Vec<_>>();
+
+    let prefix = name.unwrap_or_default().to_lowercase();
+    results.sort_by_cached_key(|(name, _)| {
+      let name = name.to_lowercase();
+      (!name.starts_with(&prefix), name)
+    });
+
+    Ok(
+      results
+        .into_iter()
+        .take(TYPEAHEAD_LIMIT as usize)
+        .map(|(_, content)| content)
+        .collect(),
+    )
+  }
+
+  /// Full text search over the names and content of pages, partials and layouts, for finding
+  /// where a piece of text on the site comes from
+  async fn search_cms_content(
+    &self,
+    ctx: &Context<'_>,
+    query: String,
+    limit: Option<i32>,
+  ) -> Result<Vec<CmsContentSearchResultType>, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let db = query_data.db();
+    let model = self.get_model();
+    ensure_action_permitted::<CmsContentPolicy<M>, _>(ctx, &ReadManageAction::Manage, model)
+      .await?;
+
+    let Some(search) = TextSearchQuery::parse(&query) else {
+      return Ok(vec![]);
+    };
+    let limit = limit.unwrap_or(20).clamp(1, 100) as u64;
+    let columns = ["name", "content"];
+
+    let (pages, partials, layouts) = try_join!(
+      ranked_text_search(
+        db,
+        model.pages(),
+        pages::Column::Id,
+        &columns,
+        &search,
+        limit
+      ),
+      ranked_text_search(
+        db,
+        model.cms_partials(),
+        cms_partials::Column::Id,
+        &columns,
+        &search,
+        limit
+      ),
+      ranked_text_search(
+        db,
+        model.cms_layouts(),
+        cms_layouts::Column::Id,
+        &columns,
+        &search,
+        limit
+      )
+    )?;
+
+    let snippet = |content: Option<&str>| {
+      content.and_then(|content| search.highlight(content, SEARCH_SNIPPET_LENGTH))
+    };
+    let mut results = pages
+      .into_iter()
+      .map(|(page, rank)| CmsContentSearchResultType {
+        snippet: snippet(page.content.as_deref()),
+        rank,
+        content: CmsContentType::Page(PageType::new(page)),
+      })
+      .chain(
+        partials
+          .into_iter()
+          .map(|(partial, rank)| CmsContentSearchResultType {
+            snippet: snippet(partial.content.as_deref()),
+            rank,
+            content: CmsContentType::Partial(CmsPartialType::new(partial)),
+          }),
+      )
+      .chain(
+        layouts
+          .into_iter()
+          .map(|(layout, rank)| CmsContentSearchResultType {
+            snippet: snippet(layout.content.as_deref()),
+            rank,
+            content: CmsContentType::Layout(CmsLayoutType::new(layout)),
+          }),
+      )
+      .collect::<Vec<_>>();
+
+    results.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+    results.truncate(limit as usize);
+    Ok(results)
   }
 }
@@ -0,0 +1,282 @@
+//! The reference tables (cms_partials_pages, cms_files_pages, cms_layouts_partials and
+//! cms_files_layouts) record which partials and files each page and layout uses, including the
+//! ones used indirectly through other partials.  Partial preloading relies on them, so they have
+//! to be rewritten whenever content that could change them is saved.
+
+use std::collections::HashSet;
+
+use intercode_entities::{
+  active_storage_attachments, active_storage_blobs, cms_files, cms_files_layouts, cms_files_pages,
+  cms_layouts, cms_layouts_partials,
+  cms_parent::{CmsParent, CmsParentTrait},
+  cms_partials, cms_partials_pages, pages,
+};
+use intercode_liquid::content_references::find_content_references;
+use sea_orm::{
+  sea_query::Query, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+  QueryFilter, QuerySelect, QueryTrait,
+};
+
+#[derive(Debug, Default)]
+pub struct ResolvedReferences {
+  pub partial_ids: Vec<i64>,
+  pub file_ids: Vec<i64>,
+}
+
+/// Finds the partials and files a piece of content uses in the given CMS parent, following
+/// partials into the partials they include
+pub async fn resolve_references<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  content: &str,
+) -> Result<ResolvedReferences, DbErr> {
+  let references = find_content_references(content);
+  let mut file_names = references.file_names;
+  let mut pending_partial_names = references.partial_names;
+  let mut seen_partial_names = HashSet::new();
+  let mut partial_ids = vec![];
+
+  loop {
+    pending_partial_names.retain(|name| seen_partial_names.insert(name.clone()));
+    if pending_partial_names.is_empty() {
+      break;
+    }
+
+    let partials = cms_parent
+      .cms_partials()
+      .filter(cms_partials::Column::Name.is_in(std::mem::take(&mut pending_partial_names)))
+      .all(db)
+      .await?;
+
+    for partial in partials {
+      partial_ids.push(partial.id);
+      if let Some(content) = &partial.content {
+        let references = find_content_references(content);
+        pending_partial_names.extend(references.partial_names);
+        file_names.extend(references.file_names);
+      }
+    }
+  }
+
+  let file_ids = if file_names.is_empty() {
+    vec![]
+  } else {
+    active_storage_attachments::Entity::find()
+      .filter(active_storage_attachments::Column::RecordType.eq("CmsFile"))
+      .filter(active_storage_attachments::Column::Name.eq("file"))
+      .filter(
+        active_storage_attachments::Column::RecordId.in_subquery(
+          cms_parent
+            .cms_files()
+            .select_only()
+            .column(cms_files::Column::Id)
+            .into_query(),
+        ),
+      )
+      .inner_join(active_storage_blobs::Entity)
+      .filter(active_storage_blobs::Column::Filename.is_in(file_names))
+      .select_only()
+      .column(active_storage_attachments::Column::RecordId)
+      .distinct()
+      .into_tuple::<i64>()
+      .all(db)
+      .await?
+  };
+
+  Ok(ResolvedReferences {
+    partial_ids,
+    file_ids,
+  })
+}
+
+pub async fn update_page_references<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  page: &pages::Model,
+) -> Result<(), DbErr> {
+  let references =
+    resolve_references(db, cms_parent, page.content.as_deref().unwrap_or_default()).await?;
+  delete_page_references(db, page.id).await?;
+
+  if !references.partial_ids.is_empty() {
+    cms_partials_pages::Entity::insert_many(references.partial_ids.into_iter().map(
+      |cms_partial_id| cms_partials_pages::ActiveModel {
+        cms_partial_id: ActiveValue::Set(cms_partial_id),
+        page_id: ActiveValue::Set(page.id),
+      },
+    ))
+    .exec(db)
+    .await?;
+  }
+
+  if !references.file_ids.is_empty() {
+    cms_files_pages::Entity::insert_many(references.file_ids.into_iter().map(|cms_file_id| {
+      cms_files_pages::ActiveModel {
+        cms_file_id: ActiveValue::Set(cms_file_id),
+        page_id: ActiveValue::Set(page.id),
+      }
+    }))
+    .exec(db)
+    .await?;
+  }
+
+  Ok(())
+}
+
+pub async fn update_layout_references<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  layout: &cms_layouts::Model,
+) -> Result<(), DbErr> {
+  let references = resolve_references(
+    db,
+    cms_parent,
+    layout.content.as_deref().unwrap_or_default(),
+  )
+  .await?;
+  delete_layout_references(db, layout.id).await?;
+
+  if !references.partial_ids.is_empty() {
+    cms_layouts_partials::Entity::insert_many(references.partial_ids.into_iter().map(
+      |cms_partial_id| cms_layouts_partials::ActiveModel {
+        cms_partial_id: ActiveValue::Set(cms_partial_id),
+        cms_layout_id: ActiveValue::Set(layout.id),
+      },
+    ))
+    .exec(db)
+    .await?;
+  }
+
+  if !references.file_ids.is_empty() {
+    cms_files_layouts::Entity::insert_many(references.file_ids.into_iter().map(|cms_file_id| {
+      cms_files_layouts::ActiveModel {
+        cms_file_id: ActiveValue::Set(cms_file_id),
+        cms_layout_id: ActiveValue::Set(layout.id),
+      }
+    }))
+    .exec(db)
+    .await?;
+  }
+
+  Ok(())
+}
+
+pub async fn delete_page_references<C: ConnectionTrait>(db: &C, page_id: i64) -> Result<(), DbErr> {
+  cms_partials_pages::Entity::delete_many()
+    .filter(cms_partials_pages::Column::PageId.eq(page_id))
+    .exec(db)
+    .await?;
+  cms_files_pages::Entity::delete_many()
+    .filter(cms_files_pages::Column::PageId.eq(page_id))
+    .exec(db)
+    .await?;
+  Ok(())
+}
+
+pub async fn delete_layout_references<C: ConnectionTrait>(
+  db: &C,
+  layout_id: i64,
+) -> Result<(), DbErr> {
+  cms_layouts_partials::Entity::delete_many()
+    .filter(cms_layouts_partials::Column::CmsLayoutId.eq(layout_id))
+    .exec(db)
+    .await?;
+  cms_files_layouts::Entity::delete_many()
+    .filter(cms_files_layouts::Column::CmsLayoutId.eq(layout_id))
+    .exec(db)
+    .await?;
+  Ok(())
+}
+
+/// Finds every page and layout that might use a partial, file or GraphQL query with one of the
+/// given names: the ones that mention a name themselves, and the ones that use a partial that
+/// does.
+pub async fn find_content_referencing_names<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  names: &[&str],
+) -> Result<(Vec<pages::Model>, Vec<cms_layouts::Model>), DbErr> {
+  if names.is_empty() {
+    return Ok((vec![], vec![]));
+  }
+
+  let mentioning_partial_ids: Vec<i64> = cms_parent
+    .cms_partials()
+    .filter(names.iter().fold(Condition::any(), |condition, name| {
+      condition
+        .add(cms_partials::Column::Name.eq(*name))
+        .add(cms_partials::Column::Content.contains(*name))
+    }))
+    .select_only()
+    .column(cms_partials::Column::Id)
+    .into_tuple()
+    .all(db)
+    .await?;
+
+  let pages = cms_parent
+    .pages()
+    .filter(
+      names
+        .iter()
+        .fold(Condition::any(), |condition, name| {
+          condition.add(pages::Column::Content.contains(*name))
+        })
+        .add(
+          pages::Column::Id.in_subquery(
+            Query::select()
+              .column(cms_partials_pages::Column::PageId)
+              .from(cms_partials_pages::Entity)
+              .and_where(
+                cms_partials_pages::Column::CmsPartialId.is_in(mentioning_partial_ids.clone()),
+              )
+              .to_owned(),
+          ),
+        ),
+    )
+    .all(db)
+    .await?;
+
+  let layouts = cms_parent
+    .cms_layouts()
+    .filter(
+      names
+        .iter()
+        .fold(Condition::any(), |condition, name| {
+          condition.add(cms_layouts::Column::Content.contains(*name))
+        })
+        .add(
+          cms_layouts::Column::Id.in_subquery(
+            Query::select()
+              .column(cms_layouts_partials::Column::CmsLayoutId)
+              .from(cms_layouts_partials::Entity)
+              .and_where(cms_layouts_partials::Column::CmsPartialId.is_in(mentioning_partial_ids))
+              .to_owned(),
+          ),
+        ),
+    )
+    .all(db)
+    .await?;
+
+  Ok((pages, layouts))
+}
+
+/// Re-resolves the references of every page and layout that might use a partial or file with
+/// one of the given names.  Call this after a partial or file is created, renamed, edited or
+/// deleted, with its old and new names.
+pub async fn refresh_references_to_names<C: ConnectionTrait>(
+  db: &C,
+  cms_parent: &CmsParent,
+  names: &[&str],
+) -> Result<(), DbErr> {
+  let (pages, layouts) = find_content_referencing_names(db, cms_parent, names).await?;
+
+  for page in pages {
+    update_page_references(db, cms_parent, &page).await?;
+  }
+
+  for layout in layouts {
+    update_layout_references(db, cms_parent, &layout).await?;
+  }
+
+  Ok(())
+}
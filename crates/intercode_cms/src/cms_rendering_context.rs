@@ -3,17 +3,22 @@ use std::env;
 use html_escape::encode_double_quoted_attribute;
 use http::Uri;
 use intercode_entities::{
-  active_storage_attachments, active_storage_blobs, cms_parent::CmsParentTrait, cms_variables,
-  conventions, events, pages,
+  active_storage_attachments, active_storage_blobs, cms_layouts, cms_parent::CmsParentTrait,
+  cms_variables, conventions, events, pages,
 };
 use intercode_graphql_core::{
-  liquid_renderer::LiquidRenderer, query_data::QueryData, rendering_utils::url_with_possible_host,
+  cms_output_cache::CmsOutputCache, liquid_renderer::LiquidRenderer, query_data::QueryData,
+  rendering_utils::url_with_possible_host,
+};
+use intercode_liquid::{
+  cms_parent_partial_source::PreloadPartialsStrategy, react_component_tag, url_builder::UrlBuilder,
 };
-use intercode_liquid::{cms_parent_partial_source::PreloadPartialsStrategy, react_component_tag};
 use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
 use seawater::ConnectionWrapper;
 use serde_json::json;
 
+use crate::cms_output_cache::{render_with_output_cache, CachedCmsContent};
+
 const NOSCRIPT_WARNING: &str = r#"
 <noscript id="no-javascript-warning">
   <div class="container">
@@ -64,9 +69,11 @@ fn assets_host_script() -> String {
   }
 }
 
-fn active_storage_blob_url(blob: &active_storage_blobs::Model) -> &str {
-  // TODO figure out how we're going to handle generating URLs for AS blobs
-  blob.key.as_str()
+fn convention_blob_url(
+  convention: &conventions::Model,
+  blob: &active_storage_blobs::Model,
+) -> Option<String> {
+  UrlBuilder::for_host(Some(convention.domain.clone())).blob_url(blob)
 }
 
 async fn find_blob_by_attached_model(
@@ -100,11 +107,8 @@ async fn open_graph_image_tag(
       .await
       .unwrap_or(None);
 
-    if let Some(blob) = blob {
-      format!(
-        r#"<meta property="og:image" content="{}">"#,
-        active_storage_blob_url(&blob)
-      )
+    if let Some(url) = blob.and_then(|blob| convention_blob_url(convention, &blob)) {
+      format!(r#"<meta property="og:image" content="{}">"#, url)
     } else {
       "".to_string()
     }
@@ -155,10 +159,7 @@ async fn open_graph_meta_tags(
       encode_double_quoted_attribute(cms_page_title(convention, page)),
       encode_double_quoted_attribute(truncate(
         rendering_context
-          .render_liquid(
-            page.content.as_deref().unwrap_or(""),
-            Some(PreloadPartialsStrategy::ByPage(page))
-          )
+          .render_page_content(page)
           .await
           .unwrap_or_else(|_| "".to_string())
           .trim(),
@@ -194,11 +195,13 @@ async fn convention_favicon_tag(
       .await
       .unwrap_or(None);
 
-    if let Some(blob) = blob {
+    if let Some((blob, url)) =
+      blob.and_then(|blob| convention_blob_url(convention, &blob).map(|url| (blob, url)))
+    {
       format!(
         r#"<link rel="icon" type="{}" href="{}">"#,
         encode_double_quoted_attribute(blob.content_type.as_deref().unwrap_or("")),
-        active_storage_blob_url(&blob)
+        url
       )
     } else {
       "".to_string()
@@ -227,11 +230,17 @@ async fn content_for_head(
 <meta property="og:url" content="{}"/>
 {}
 {}
+{}
 "#,
     page_title,
     assets_host_script(),
     url_with_possible_host("/packs/application.js", env::var("ASSETS_HOST").ok()),
     request_url,
+    if convention.is_some_and(|convention| convention.hidden) {
+      r#"<meta name="robots" content="noindex">"#
+    } else {
+      ""
+    },
     open_graph_meta_tags(
       db.clone(),
       convention,
@@ -249,6 +258,7 @@ pub struct CmsRenderingContext<'a> {
   globals: liquid::Object,
   query_data: &'a QueryData,
   liquid_renderer: &'a dyn LiquidRenderer,
+  output_cache: Option<&'a CmsOutputCache>,
 }
 
 impl<'a> CmsRenderingContext<'a> {
@@ -261,9 +271,18 @@ impl<'a> CmsRenderingContext<'a> {
       globals,
       query_data,
       liquid_renderer,
+      output_cache: None,
     }
   }
 
+  /// Makes render_page_content and render_layout_content go through the output cache.  The
+  /// cache doesn't know about globals, so only use this where every render of a given page or
+  /// layout gets the same ones.
+  pub fn with_output_cache(mut self, output_cache: &'a CmsOutputCache) -> Self {
+    self.output_cache = Some(output_cache);
+    self
+  }
+
   pub async fn cms_variables(&self) -> Result<Vec<cms_variables::Model>, DbErr> {
     self
       .query_data
@@ -306,6 +325,48 @@ impl<'a> CmsRenderingContext<'a> {
       .await
   }
 
+  async fn render_cms_content(
+    &self,
+    content: CachedCmsContent<'_>,
+    text: &str,
+    preload_partials_strategy: PreloadPartialsStrategy<'_>,
+  ) -> Result<String, async_graphql::Error> {
+    let render = self.render_liquid(text, Some(preload_partials_strategy));
+
+    match self.output_cache {
+      Some(output_cache) => {
+        render_with_output_cache(output_cache, self.query_data, content, render).await
+      }
+      None => render.await,
+    }
+  }
+
+  pub async fn render_page_content(
+    &self,
+    page: &pages::Model,
+  ) -> Result<String, async_graphql::Error> {
+    self
+      .render_cms_content(
+        CachedCmsContent::Page(page),
+        page.content.as_deref().unwrap_or(""),
+        PreloadPartialsStrategy::ByPage(page),
+      )
+      .await
+  }
+
+  pub async fn render_layout_content(
+    &self,
+    layout: &cms_layouts::Model,
+  ) -> Result<String, async_graphql::Error> {
+    self
+      .render_cms_content(
+        CachedCmsContent::Layout(layout),
+        layout.content.as_deref().unwrap_or(""),
+        PreloadPartialsStrategy::ByLayout(layout),
+      )
+      .await
+  }
+
   pub async fn render_app_root_content(
     &self,
     request_url: &Uri,
@@ -0,0 +1,578 @@
+//! Revision history for pages, partials, layouts and variables.  Every save stores a snapshot of
+//! the editable fields, which can be diffed against another snapshot of the same content or
+//! written back over it.
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::Utc;
+use intercode_entities::{cms_content_revisions, cms_layouts, cms_partials, cms_variables, pages};
+use sea_orm::{
+  ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+  QueryOrder, Select,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "CmsRevisionedContentKind")]
+pub enum CmsRevisionedContentKind {
+  #[graphql(name = "page")]
+  Page,
+  #[graphql(name = "cms_partial")]
+  CmsPartial,
+  #[graphql(name = "cms_layout")]
+  CmsLayout,
+  #[graphql(name = "cms_variable")]
+  CmsVariable,
+}
+
+impl CmsRevisionedContentKind {
+  /// The value stored in cms_content_revisions.content_type, which matches the Rails class name
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CmsRevisionedContentKind::Page => "Page",
+      CmsRevisionedContentKind::CmsPartial => "CmsPartial",
+      CmsRevisionedContentKind::CmsLayout => "CmsLayout",
+      CmsRevisionedContentKind::CmsVariable => "CmsVariable",
+    }
+  }
+
+  pub fn from_content_type(content_type: &str) -> Option<Self> {
+    match content_type {
+      "Page" => Some(CmsRevisionedContentKind::Page),
+      "CmsPartial" => Some(CmsRevisionedContentKind::CmsPartial),
+      "CmsLayout" => Some(CmsRevisionedContentKind::CmsLayout),
+      "CmsVariable" => Some(CmsRevisionedContentKind::CmsVariable),
+      _ => None,
+    }
+  }
+}
+
+pub trait RevisionedCmsContent {
+  type ActiveModel: ActiveModelTrait;
+  type Snapshot: Serialize + DeserializeOwned;
+  const KIND: CmsRevisionedContentKind;
+
+  fn content_id(&self) -> i64;
+  fn parent_columns(&self) -> (Option<String>, Option<i64>);
+  fn snapshot(&self) -> Self::Snapshot;
+  fn apply_snapshot(snapshot: Self::Snapshot, active_model: &mut Self::ActiveModel);
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PageSnapshot {
+  name: Option<String>,
+  slug: Option<String>,
+  content: Option<String>,
+  admin_notes: Option<String>,
+  cms_layout_id: Option<i64>,
+  skip_clickwrap_agreement: bool,
+  hidden_from_search: bool,
+}
+
+impl RevisionedCmsContent for pages::Model {
+  type ActiveModel = pages::ActiveModel;
+  type Snapshot = PageSnapshot;
+  const KIND: CmsRevisionedContentKind = CmsRevisionedContentKind::Page;
+
+  fn content_id(&self) -> i64 {
+    self.id
+  }
+
+  fn parent_columns(&self) -> (Option<String>, Option<i64>) {
+    (self.parent_type.clone(), self.parent_id)
+  }
+
+  fn snapshot(&self) -> PageSnapshot {
+    PageSnapshot {
+      name: self.name.clone(),
+      slug: self.slug.clone(),
+      content: self.content.clone(),
+      admin_notes: self.admin_notes.clone(),
+      cms_layout_id: self.cms_layout_id,
+      skip_clickwrap_agreement: self.skip_clickwrap_agreement,
+      hidden_from_search: self.hidden_from_search,
+    }
+  }
+
+  fn apply_snapshot(snapshot: PageSnapshot, active_model: &mut pages::ActiveModel) {
+    active_model.name = ActiveValue::Set(snapshot.name);
+    active_model.slug = ActiveValue::Set(snapshot.slug);
+    active_model.content = ActiveValue::Set(snapshot.content);
+    active_model.admin_notes = ActiveValue::Set(snapshot.admin_notes);
+    active_model.cms_layout_id = ActiveValue::Set(snapshot.cms_layout_id);
+    active_model.skip_clickwrap_agreement = ActiveValue::Set(snapshot.skip_clickwrap_agreement);
+    active_model.hidden_from_search = ActiveValue::Set(snapshot.hidden_from_search);
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CmsPartialSnapshot {
+  name: String,
+  content: Option<String>,
+  admin_notes: Option<String>,
+}
+
+impl RevisionedCmsContent for cms_partials::Model {
+  type ActiveModel = cms_partials::ActiveModel;
+  type Snapshot = CmsPartialSnapshot;
+  const KIND: CmsRevisionedContentKind = CmsRevisionedContentKind::CmsPartial;
+
+  fn content_id(&self) -> i64 {
+    self.id
+  }
+
+  fn parent_columns(&self) -> (Option<String>, Option<i64>) {
+    (self.parent_type.clone(), self.parent_id)
+  }
+
+  fn snapshot(&self) -> CmsPartialSnapshot {
+    CmsPartialSnapshot {
+      name: self.name.clone(),
+      content: self.content.clone(),
+      admin_notes: self.admin_notes.clone(),
+    }
+  }
+
+  fn apply_snapshot(snapshot: CmsPartialSnapshot, active_model: &mut cms_partials::ActiveModel) {
+    active_model.name = ActiveValue::Set(snapshot.name);
+    active_model.content = ActiveValue::Set(snapshot.content);
+    active_model.admin_notes = ActiveValue::Set(snapshot.admin_notes);
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CmsLayoutSnapshot {
+  name: Option<String>,
+  content: Option<String>,
+  navbar_classes: Option<String>,
+  admin_notes: Option<String>,
+}
+
+impl RevisionedCmsContent for cms_layouts::Model {
+  type ActiveModel = cms_layouts::ActiveModel;
+  type Snapshot = CmsLayoutSnapshot;
+  const KIND: CmsRevisionedContentKind = CmsRevisionedContentKind::CmsLayout;
+
+  fn content_id(&self) -> i64 {
+    self.id
+  }
+
+  fn parent_columns(&self) -> (Option<String>, Option<i64>) {
+    (self.parent_type.clone(), self.parent_id)
+  }
+
+  fn snapshot(&self) -> CmsLayoutSnapshot {
+    CmsLayoutSnapshot {
+      name: self.name.clone(),
+      content: self.content.clone(),
+      navbar_classes: self.navbar_classes.clone(),
+      admin_notes: self.admin_notes.clone(),
+    }
+  }
+
+  fn apply_snapshot(snapshot: CmsLayoutSnapshot, active_model: &mut cms_layouts::ActiveModel) {
+    active_model.name = ActiveValue::Set(snapshot.name);
+    active_model.content = ActiveValue::Set(snapshot.content);
+    active_model.navbar_classes = ActiveValue::Set(snapshot.navbar_classes);
+    active_model.admin_notes = ActiveValue::Set(snapshot.admin_notes);
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CmsVariableSnapshot {
+  key: String,
+  value: Option<Value>,
+}
+
+impl RevisionedCmsContent for cms_variables::Model {
+  type ActiveModel = cms_variables::ActiveModel;
+  type Snapshot = CmsVariableSnapshot;
+  const KIND: CmsRevisionedContentKind = CmsRevisionedContentKind::CmsVariable;
+
+  fn content_id(&self) -> i64 {
+    self.id
+  }
+
+  fn parent_columns(&self) -> (Option<String>, Option<i64>) {
+    (self.parent_type.clone(), self.parent_id)
+  }
+
+  fn snapshot(&self) -> CmsVariableSnapshot {
+    CmsVariableSnapshot {
+      key: self.key.clone(),
+      value: self.value.clone(),
+    }
+  }
+
+  // the key identifies the variable, so reverting only restores the value
+  fn apply_snapshot(snapshot: CmsVariableSnapshot, active_model: &mut cms_variables::ActiveModel) {
+    active_model.value = ActiveValue::Set(snapshot.value);
+  }
+}
+
+fn revisions_of<M: RevisionedCmsContent>(content: &M) -> Select<cms_content_revisions::Entity> {
+  cms_content_revisions::Entity::find()
+    .filter(cms_content_revisions::Column::ContentType.eq(M::KIND.as_str()))
+    .filter(cms_content_revisions::Column::ContentId.eq(content.content_id()))
+}
+
+async fn insert_revision<C: ConnectionTrait, M: RevisionedCmsContent>(
+  db: &C,
+  content: &M,
+  snapshot: Value,
+  author_id: Option<i64>,
+) -> Result<cms_content_revisions::Model, DbErr> {
+  let now = Utc::now().naive_utc();
+  let (parent_type, parent_id) = content.parent_columns();
+  cms_content_revisions::ActiveModel {
+    parent_type: ActiveValue::Set(parent_type),
+    parent_id: ActiveValue::Set(parent_id),
+    content_type: ActiveValue::Set(M::KIND.as_str().to_string()),
+    content_id: ActiveValue::Set(content.content_id()),
+    author_id: ActiveValue::Set(author_id),
+    snapshot: ActiveValue::Set(snapshot),
+    created_at: ActiveValue::Set(now),
+    updated_at: ActiveValue::Set(now),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+}
+
+fn snapshot_value<M: RevisionedCmsContent>(content: &M) -> Result<Value, DbErr> {
+  serde_json::to_value(content.snapshot()).map_err(|err| DbErr::Custom(err.to_string()))
+}
+
+/// Content written before revision history existed has no revisions, so the first save after
+/// that would leave nothing to revert to.  Call this with the stored content before changing it:
+/// if it has no revisions yet, its current state becomes the first one, with no author.
+pub async fn record_baseline_revision<C: ConnectionTrait, M: RevisionedCmsContent>(
+  db: &C,
+  content: &M,
+) -> Result<Option<cms_content_revisions::Model>, DbErr> {
+  if revisions_of(content).one(db).await?.is_some() {
+    return Ok(None);
+  }
+
+  insert_revision(db, content, snapshot_value(content)?, None)
+    .await
+    .map(Some)
+}
+
+/// Stores a revision of the content as it is now, unless it's identical to the latest one
+pub async fn record_revision<C: ConnectionTrait, M: RevisionedCmsContent>(
+  db: &C,
+  content: &M,
+  author_id: Option<i64>,
+) -> Result<Option<cms_content_revisions::Model>, DbErr> {
+  let snapshot = snapshot_value(content)?;
+
+  let latest = revisions_of(content)
+    .order_by_desc(cms_content_revisions::Column::CreatedAt)
+    .order_by_desc(cms_content_revisions::Column::Id)
+    .one(db)
+    .await?;
+  if latest
+    .map(|latest| latest.snapshot == snapshot)
+    .unwrap_or(false)
+  {
+    return Ok(None);
+  }
+
+  insert_revision(db, content, snapshot, author_id)
+    .await
+    .map(Some)
+}
+
+/// Writes a stored snapshot over the given active model
+pub fn apply_revision<M: RevisionedCmsContent>(
+  revision: &cms_content_revisions::Model,
+  active_model: &mut M::ActiveModel,
+) -> Result<(), serde_json::Error> {
+  let snapshot: M::Snapshot = serde_json::from_value(revision.snapshot.clone())?;
+  M::apply_snapshot(snapshot, active_model);
+  Ok(())
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "CmsContentDiffLineKind")]
+pub enum DiffLineKind {
+  #[graphql(name = "unchanged")]
+  Unchanged,
+  #[graphql(name = "added")]
+  Added,
+  #[graphql(name = "removed")]
+  Removed,
+}
+
+#[derive(SimpleObject, Clone, Debug, PartialEq, Eq)]
+#[graphql(name = "CmsContentDiffLine")]
+pub struct DiffLine {
+  pub kind: DiffLineKind,
+  pub text: String,
+  /// The line's number in the older text, if it appears there
+  #[graphql(name = "old_line_number")]
+  pub old_line_number: Option<i32>,
+  /// The line's number in the newer text, if it appears there
+  #[graphql(name = "new_line_number")]
+  pub new_line_number: Option<i32>,
+}
+
+impl DiffLine {
+  fn new(
+    kind: DiffLineKind,
+    text: &str,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+  ) -> Self {
+    DiffLine {
+      kind,
+      text: text.to_string(),
+      old_line_number: old_index.map(|index| index as i32 + 1),
+      new_line_number: new_index.map(|index| index as i32 + 1),
+    }
+  }
+}
+
+/// The most cells diff_lines' LCS table may have (64MB of u32s).  When the changed parts of the
+/// two texts are bigger than this, they're shown as replaced outright instead.
+const MAX_LCS_CELLS: usize = 16 * 1024 * 1024;
+
+/// A line-by-line diff using the longest common subsequence.  Lines that are the same at the
+/// start and end are matched up front, so the quadratic part only covers what changed.  If even
+/// that is too big to diff (see MAX_LCS_CELLS), the changed lines are all removed then re-added.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+  let old_lines: Vec<&str> = old.lines().collect();
+  let new_lines: Vec<&str> = new.lines().collect();
+
+  let prefix_len = old_lines
+    .iter()
+    .zip(new_lines.iter())
+    .take_while(|(old_line, new_line)| old_line == new_line)
+    .count();
+  let suffix_len = old_lines[prefix_len..]
+    .iter()
+    .rev()
+    .zip(new_lines[prefix_len..].iter().rev())
+    .take_while(|(old_line, new_line)| old_line == new_line)
+    .count();
+
+  let old_middle = &old_lines[prefix_len..old_lines.len() - suffix_len];
+  let new_middle = &new_lines[prefix_len..new_lines.len() - suffix_len];
+
+  let lcs_cells = (old_middle.len() + 1).saturating_mul(new_middle.len() + 1);
+  let diff_middle = lcs_cells <= MAX_LCS_CELLS;
+
+  // lcs[i][j] is the length of the longest common subsequence of old_middle[i..] and
+  // new_middle[j..]
+  let mut lcs = if diff_middle {
+    vec![vec![0u32; new_middle.len() + 1]; old_middle.len() + 1]
+  } else {
+    Vec::new()
+  };
+  for i in (0..lcs.len().saturating_sub(1)).rev() {
+    for j in (0..new_middle.len()).rev() {
+      lcs[i][j] = if old_middle[i] == new_middle[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+
+  let mut diff = Vec::with_capacity(old_lines.len().max(new_lines.len()));
+  for (index, line) in old_lines[..prefix_len].iter().enumerate() {
+    diff.push(DiffLine::new(
+      DiffLineKind::Unchanged,
+      line,
+      Some(index),
+      Some(index),
+    ));
+  }
+
+  let (mut i, mut j) = (0, 0);
+  while i < old_middle.len() || j < new_middle.len() {
+    if !diff_middle {
+      if i < old_middle.len() {
+        diff.push(DiffLine::new(
+          DiffLineKind::Removed,
+          old_middle[i],
+          Some(prefix_len + i),
+          None,
+        ));
+        i += 1;
+      } else {
+        diff.push(DiffLine::new(
+          DiffLineKind::Added,
+          new_middle[j],
+          None,
+          Some(prefix_len + j),
+        ));
+        j += 1;
+      }
+    } else if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+      diff.push(DiffLine::new(
+        DiffLineKind::Unchanged,
+        old_middle[i],
+        Some(prefix_len + i),
+        Some(prefix_len + j),
+      ));
+      i += 1;
+      j += 1;
+    } else if j < new_middle.len() && (i == old_middle.len() || lcs[i][j + 1] > lcs[i + 1][j]) {
+      diff.push(DiffLine::new(
+        DiffLineKind::Added,
+        new_middle[j],
+        None,
+        Some(prefix_len + j),
+      ));
+      j += 1;
+    } else {
+      diff.push(DiffLine::new(
+        DiffLineKind::Removed,
+        old_middle[i],
+        Some(prefix_len + i),
+        None,
+      ));
+      i += 1;
+    }
+  }
+
+  let old_suffix_start = old_lines.len() - suffix_len;
+  let new_suffix_start = new_lines.len() - suffix_len;
+  for offset in 0..suffix_len {
+    diff.push(DiffLine::new(
+      DiffLineKind::Unchanged,
+      old_lines[old_suffix_start + offset],
+      Some(old_suffix_start + offset),
+      Some(new_suffix_start + offset),
+    ));
+  }
+
+  diff
+}
+
+/// The text of a snapshot that gets diffed line by line: the content of pages, partials and
+/// layouts, or the value of a variable as pretty-printed JSON
+pub fn snapshot_body(snapshot: &Value) -> String {
+  match (snapshot.get("content"), snapshot.get("value")) {
+    (Some(Value::String(content)), _) => content.clone(),
+    (_, Some(value)) if !value.is_null() => serde_json::to_string_pretty(value).unwrap_or_default(),
+    _ => String::new(),
+  }
+}
+
+/// The names of the fields other than the body that differ between two snapshots
+pub fn changed_fields(old: &Value, new: &Value) -> Vec<String> {
+  let empty = serde_json::Map::new();
+  let old = old.as_object().unwrap_or(&empty);
+  let new = new.as_object().unwrap_or(&empty);
+
+  let mut fields: Vec<String> = old
+    .keys()
+    .chain(new.keys().filter(|key| !old.contains_key(*key)))
+    .filter(|key| key.as_str() != "content" && key.as_str() != "value")
+    .filter(|key| old.get(*key) != new.get(*key))
+    .cloned()
+    .collect();
+  fields.sort();
+  fields
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn summarize(diff: &[DiffLine]) -> Vec<String> {
+    diff
+      .iter()
+      .map(|line| {
+        let marker = match line.kind {
+          DiffLineKind::Unchanged => ' ',
+          DiffLineKind::Added => '+',
+          DiffLineKind::Removed => '-',
+        };
+        format!("{}{}", marker, line.text)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn diffs_changed_lines() {
+    let diff = diff_lines(
+      "<h1>Welcome</h1>\n<p>Hi</p>\n{% include 'footer' %}",
+      "<h1>Welcome</h1>\n<p>Hello</p>\n<p>New</p>\n{% include 'footer' %}",
+    );
+
+    assert_eq!(
+      summarize(&diff),
+      vec![
+        " <h1>Welcome</h1>",
+        "-<p>Hi</p>",
+        "+<p>Hello</p>",
+        "+<p>New</p>",
+        " {% include 'footer' %}",
+      ]
+    );
+    assert_eq!(diff[2].old_line_number, None);
+    assert_eq!(diff[2].new_line_number, Some(2));
+    assert_eq!(diff[4].old_line_number, Some(3));
+    assert_eq!(diff[4].new_line_number, Some(4));
+  }
+
+  #[test]
+  fn replaces_changes_too_big_to_diff() {
+    let line_count = 5000;
+    let old = (0..line_count)
+      .map(|index| format!("old {}", index))
+      .chain(["same".to_string()])
+      .collect::<Vec<_>>()
+      .join("\n");
+    let new = (0..line_count)
+      .map(|index| {
+        if index == 10 {
+          "old 10".to_string()
+        } else {
+          format!("new {}", index)
+        }
+      })
+      .chain(["same".to_string()])
+      .collect::<Vec<_>>()
+      .join("\n");
+    assert!(line_count * line_count > MAX_LCS_CELLS);
+
+    let diff = diff_lines(&old, &new);
+    let summary = summarize(&diff);
+
+    assert_eq!(diff.len(), line_count * 2 + 1);
+    assert!(summary[..line_count]
+      .iter()
+      .all(|line| line.starts_with('-')));
+    assert!(summary[line_count..line_count * 2]
+      .iter()
+      .all(|line| line.starts_with('+')));
+    assert_eq!(summary[line_count * 2], " same");
+    assert_eq!(diff[line_count * 2].old_line_number, Some(5001));
+    assert_eq!(diff[line_count * 2].new_line_number, Some(5001));
+  }
+
+  #[test]
+  fn diffs_against_empty_text() {
+    assert_eq!(summarize(&diff_lines("", "a\nb")), vec!["+a", "+b"]);
+    assert_eq!(summarize(&diff_lines("a\nb", "")), vec!["-a", "-b"]);
+    assert_eq!(summarize(&diff_lines("a\nb", "a\nb")), vec![" a", " b"]);
+  }
+
+  #[test]
+  fn finds_changed_fields_outside_the_body() {
+    let old = serde_json::json!({ "name": "Home", "slug": "home", "content": "a" });
+    let new = serde_json::json!({ "name": "Home", "slug": "index", "content": "b" });
+
+    assert_eq!(changed_fields(&old, &new), vec!["slug"]);
+    assert_eq!(snapshot_body(&new), "b");
+    assert_eq!(
+      snapshot_body(&serde_json::json!({ "key": "x", "value": [1] })),
+      "[\n  1\n]"
+    );
+  }
+}
@@ -1,6 +1,12 @@
+pub mod actions;
+mod active_storage;
 pub mod api;
+pub mod cms_content_sets;
+pub mod cms_output_cache;
 mod cms_parent_implementation;
+pub mod cms_references;
 mod cms_rendering_context;
+pub mod cms_revisions;
 
 pub use cms_parent_implementation::CmsParentImplementation;
 pub use cms_rendering_context::*;
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, env};
+
+use axum::{body::Bytes, debug_handler, extract::Query};
+use http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+use intercode_graphql_core::query_data::QueryData;
+use intercode_server::QueryDataFromRequest;
+use tracing::log::*;
+
+use crate::inbound::{
+  receive_email, verify_sns_signature, RecipientOutcome, SesReceivedNotification, SnsEnvelope,
+};
+
+/// Inbound mail endpoints are authenticated with a shared secret, passed either as a bearer token
+/// or (for SNS subscriptions, which can't send custom headers) a `token` query parameter.  If
+/// INBOUND_EMAIL_TOKEN isn't set, inbound mail is disabled entirely.
+fn inbound_email_token_valid(headers: &HeaderMap, params: &HashMap<String, String>) -> bool {
+  let Ok(expected) = env::var("INBOUND_EMAIL_TOKEN") else {
+    return false;
+  };
+
+  let provided = headers
+    .get(AUTHORIZATION)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Bearer "))
+    .or_else(|| params.get("token").map(String::as_str));
+
+  match provided {
+    Some(provided) => {
+      provided.len() == expected.len()
+        && provided
+          .bytes()
+          .zip(expected.bytes())
+          .fold(0, |acc, (a, b)| acc | (a ^ b))
+          == 0
+    }
+    None => false,
+  }
+}
+
+async fn process_raw_email(
+  raw: &[u8],
+  recipients: &[String],
+  query_data: &QueryData,
+) -> Result<(), StatusCode> {
+  let outcomes = receive_email(raw, recipients, query_data.db())
+    .await
+    .map_err(|err| {
+      error!("Error processing inbound email: {:?}", err);
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  debug!("Inbound email outcomes: {:?}", outcomes);
+
+  // ask the sender to redeliver; recipients we've already forwarded to will be skipped then
+  if outcomes
+    .iter()
+    .any(|outcome| matches!(outcome, RecipientOutcome::Failed { .. }))
+  {
+    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+  }
+
+  Ok(())
+}
+
+/// Accepts a raw RFC 822 message, e.g. from a local MTA's pipe or LMTP-to-HTTP bridge.  Envelope
+/// recipients can be passed as a comma-separated `recipients` query parameter; otherwise they're
+/// taken from the message's To and Cc headers.
+#[debug_handler]
+pub async fn inbound_email(
+  headers: HeaderMap,
+  Query(params): Query<HashMap<String, String>>,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+  if !inbound_email_token_valid(&headers, &params) {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  let recipients = params
+    .get("recipients")
+    .map(|recipients| {
+      recipients
+        .split(',')
+        .map(|recipient| recipient.trim().to_string())
+        .filter(|recipient| !recipient.is_empty())
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+
+  process_raw_email(&body, &recipients, &query_data).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// Receives SES receipt notifications delivered over SNS.  Subscription confirmations are logged
+/// rather than followed automatically, so that an operator has to approve new topics.  Every
+/// message has to carry a valid SNS signature, and if INBOUND_EMAIL_SNS_TOPIC_ARN is set, it has
+/// to come from that topic.
+#[debug_handler]
+pub async fn inbound_email_sns(
+  headers: HeaderMap,
+  Query(params): Query<HashMap<String, String>>,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  body: String,
+) -> Result<StatusCode, StatusCode> {
+  if !inbound_email_token_valid(&headers, &params) {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  let envelope: SnsEnvelope = serde_json::from_str(&body).map_err(|err| {
+    warn!("Invalid SNS message: {}", err);
+    StatusCode::BAD_REQUEST
+  })?;
+
+  if let Err(err) = verify_sns_signature(&envelope).await {
+    warn!("Rejecting SNS message: {}", err.message);
+    return Err(StatusCode::FORBIDDEN);
+  }
+  if let Ok(topic_arn) = env::var("INBOUND_EMAIL_SNS_TOPIC_ARN") {
+    if envelope.topic_arn != topic_arn {
+      warn!(
+        "Rejecting SNS message from unexpected topic {}",
+        envelope.topic_arn
+      );
+      return Err(StatusCode::FORBIDDEN);
+    }
+  }
+
+  match envelope.message_type.as_str() {
+    "SubscriptionConfirmation" => {
+      info!(
+        "Received SNS subscription confirmation for {}; visit {} to confirm it",
+        envelope.topic_arn,
+        envelope.subscribe_url.as_deref().unwrap_or_default()
+      );
+      Ok(StatusCode::NO_CONTENT)
+    }
+    "Notification" => {
+      let notification: SesReceivedNotification =
+        serde_json::from_str(&envelope.message).map_err(|err| {
+          warn!("Invalid SES notification in SNS message: {}", err);
+          StatusCode::BAD_REQUEST
+        })?;
+
+      if notification.notification_type != "Received" {
+        return Ok(StatusCode::NO_CONTENT);
+      }
+
+      let raw = notification.load_raw_message().await.map_err(|err| {
+        error!("Error loading inbound email content: {:?}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+      })?;
+
+      process_raw_email(&raw, &notification.receipt.recipients, &query_data).await?;
+      Ok(StatusCode::NO_CONTENT)
+    }
+    _ => Ok(StatusCode::NO_CONTENT),
+  }
+}
@@ -0,0 +1,3 @@
+mod inbound_email;
+
+pub use inbound_email::*;
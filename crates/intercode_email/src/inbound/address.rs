@@ -0,0 +1,22 @@
+/// Normalizes an email address for route matching: lowercases it and strips any "+tag" suffix
+/// from the local part, so that e.g. "Con+Registration@Example.com" matches "con@example.com".
+pub fn normalize_address(address: &str) -> String {
+  let address = address.trim().to_lowercase();
+  match address.rsplit_once('@') {
+    Some((local_part, domain)) => {
+      let local_part = local_part
+        .split_once('+')
+        .map(|(base, _tag)| base)
+        .unwrap_or(local_part);
+      format!("{}@{}", local_part, domain)
+    }
+    None => address,
+  }
+}
+
+/// Splits a normalized address into its local part and domain.
+pub fn split_address(address: &str) -> Option<(&str, &str)> {
+  address
+    .rsplit_once('@')
+    .filter(|(local_part, domain)| !local_part.is_empty() && !domain.is_empty())
+}
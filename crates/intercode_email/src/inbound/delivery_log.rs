@@ -0,0 +1,108 @@
+use async_graphql::Error;
+use chrono::{Duration, Utc};
+use intercode_entities::inbound_email_deliveries;
+use sea_orm::{
+  sea_query::{Expr, OnConflict},
+  ColumnTrait, EntityTrait, QueryFilter, Set,
+};
+use seawater::ConnectionWrapper;
+use sha2::{Digest, Sha256};
+
+/// If a delivery has been claimed but not finished for this long, we assume whoever claimed it
+/// died partway through and let a retry have another go
+const STALE_CLAIM_MINUTES: i64 = 10;
+
+/// Identifies a message across redeliveries.  SNS and MTAs both retry with exactly the same bytes,
+/// so a hash of the raw message is enough.
+pub fn inbound_message_key(raw: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(raw))
+}
+
+/// Claims the job of forwarding a message to one recipient.  Returns false if it's already been
+/// forwarded, or if another delivery of the same message is working on it right now.
+pub async fn claim_inbound_delivery(
+  message_key: &str,
+  recipient: &str,
+  db: &ConnectionWrapper,
+) -> Result<bool, Error> {
+  let now = Utc::now().naive_utc();
+
+  let inserted = inbound_email_deliveries::Entity::insert(inbound_email_deliveries::ActiveModel {
+    message_key: Set(message_key.to_string()),
+    recipient: Set(recipient.to_string()),
+    forwarded_at: Set(None),
+    created_at: Set(now),
+    updated_at: Set(now),
+    ..Default::default()
+  })
+  .on_conflict(
+    OnConflict::columns([
+      inbound_email_deliveries::Column::MessageKey,
+      inbound_email_deliveries::Column::Recipient,
+    ])
+    .do_nothing()
+    .to_owned(),
+  )
+  .exec_without_returning(db)
+  .await?;
+
+  if inserted == 1 {
+    return Ok(true);
+  }
+
+  let reclaimed = inbound_email_deliveries::Entity::update_many()
+    .col_expr(
+      inbound_email_deliveries::Column::UpdatedAt,
+      Expr::value(now),
+    )
+    .filter(inbound_email_deliveries::Column::MessageKey.eq(message_key))
+    .filter(inbound_email_deliveries::Column::Recipient.eq(recipient))
+    .filter(inbound_email_deliveries::Column::ForwardedAt.is_null())
+    .filter(
+      inbound_email_deliveries::Column::UpdatedAt.lt(now - Duration::minutes(STALE_CLAIM_MINUTES)),
+    )
+    .exec(db)
+    .await?;
+
+  Ok(reclaimed.rows_affected == 1)
+}
+
+pub async fn finish_inbound_delivery(
+  message_key: &str,
+  recipient: &str,
+  db: &ConnectionWrapper,
+) -> Result<(), Error> {
+  let now = Utc::now().naive_utc();
+
+  inbound_email_deliveries::Entity::update_many()
+    .col_expr(
+      inbound_email_deliveries::Column::ForwardedAt,
+      Expr::value(now),
+    )
+    .col_expr(
+      inbound_email_deliveries::Column::UpdatedAt,
+      Expr::value(now),
+    )
+    .filter(inbound_email_deliveries::Column::MessageKey.eq(message_key))
+    .filter(inbound_email_deliveries::Column::Recipient.eq(recipient))
+    .exec(db)
+    .await?;
+
+  Ok(())
+}
+
+/// Gives up a claim after forwarding failed, so that the next redelivery will try again
+pub async fn release_inbound_delivery(
+  message_key: &str,
+  recipient: &str,
+  db: &ConnectionWrapper,
+) -> Result<(), Error> {
+  inbound_email_deliveries::Entity::delete_many()
+    .filter(inbound_email_deliveries::Column::MessageKey.eq(message_key))
+    .filter(inbound_email_deliveries::Column::Recipient.eq(recipient))
+    .filter(inbound_email_deliveries::Column::ForwardedAt.is_null())
+    .exec(db)
+    .await?;
+
+  Ok(())
+}
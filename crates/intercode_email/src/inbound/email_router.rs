@@ -0,0 +1,196 @@
+use std::fmt::Display;
+
+use intercode_entities::{
+  conventions, email_routes, events, links::StaffPositionToUserConProfiles, staff_positions,
+  team_members, user_con_profiles, users,
+};
+use sea_orm::{
+  sea_query::Expr, ColumnTrait, DbErr, EntityTrait, ModelTrait, QueryFilter, QuerySelect,
+};
+use seawater::ConnectionWrapper;
+
+use super::{normalize_address, split_address};
+
+/// What an inbound address resolved to
+#[derive(Debug)]
+pub enum InboundEmailTarget {
+  EmailRoute(email_routes::Model),
+  StaffPosition(staff_positions::Model),
+  EventTeam(events::Model),
+  CatchAll(staff_positions::Model),
+}
+
+impl Display for InboundEmailTarget {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      InboundEmailTarget::EmailRoute(route) => write!(f, "email route {}", route.id),
+      InboundEmailTarget::StaffPosition(staff_position) => {
+        write!(f, "staff position {}", staff_position.id)
+      }
+      InboundEmailTarget::EventTeam(event) => write!(f, "team mailing list for event {}", event.id),
+      InboundEmailTarget::CatchAll(staff_position) => {
+        write!(f, "catch-all staff position {}", staff_position.id)
+      }
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct InboundEmailRoute {
+  pub target: InboundEmailTarget,
+  pub forward_addresses: Vec<String>,
+}
+
+async fn staff_position_forward_addresses(
+  staff_position: &staff_positions::Model,
+  db: &ConnectionWrapper,
+) -> Result<Vec<String>, DbErr> {
+  let mut addresses = users::Entity::find()
+    .filter(
+      users::Column::Id.in_subquery(
+        QuerySelect::query(
+          &mut staff_position
+            .find_linked(StaffPositionToUserConProfiles)
+            .select_only()
+            .column(user_con_profiles::Column::UserId),
+        )
+        .take(),
+      ),
+    )
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|user| user.email)
+    .collect::<Vec<_>>();
+
+  addresses.extend(staff_position.cc_addresses.iter().cloned());
+  Ok(addresses)
+}
+
+async fn event_team_forward_addresses(
+  event: &events::Model,
+  db: &ConnectionWrapper,
+) -> Result<Vec<String>, DbErr> {
+  Ok(
+    users::Entity::find()
+      .filter(
+        users::Column::Id.in_subquery(
+          QuerySelect::query(
+            &mut event
+              .find_related(team_members::Entity)
+              .inner_join(user_con_profiles::Entity)
+              .select_only()
+              .column(user_con_profiles::Column::UserId),
+          )
+          .take(),
+        ),
+      )
+      .all(db)
+      .await?
+      .into_iter()
+      .map(|user| user.email)
+      .collect(),
+  )
+}
+
+/// Figures out where mail sent to one of our addresses should go.  In priority order, we look
+/// for a site-wide email route, a staff position address or alias, an event team mailing list,
+/// and finally the catch-all staff position for the convention's domain.
+pub async fn resolve_inbound_route(
+  recipient: &str,
+  db: &ConnectionWrapper,
+) -> Result<Option<InboundEmailRoute>, DbErr> {
+  let address = normalize_address(recipient);
+  let Some((local_part, domain)) = split_address(&address) else {
+    return Ok(None);
+  };
+
+  if let Some(route) = email_routes::Entity::find()
+    .filter(Expr::cust_with_values(
+      "lower(email_routes.receiver_address) = $1",
+      vec![address.clone()],
+    ))
+    .one(db)
+    .await?
+  {
+    return Ok(Some(InboundEmailRoute {
+      forward_addresses: route.forward_addresses.clone(),
+      target: InboundEmailTarget::EmailRoute(route),
+    }));
+  }
+
+  if let Some(staff_position) = staff_positions::Entity::find()
+    .filter(Expr::cust_with_values(
+      "lower(staff_positions.email) = $1 OR (
+        staff_positions.convention_id IN (SELECT id FROM conventions WHERE lower(domain) = $2)
+        AND EXISTS (
+          SELECT 1 FROM unnest(staff_positions.email_aliases) email_alias
+          WHERE lower(email_alias) IN ($3, $1)
+        )
+      )",
+      vec![address.clone(), domain.to_string(), local_part.to_string()],
+    ))
+    .one(db)
+    .await?
+  {
+    return Ok(Some(InboundEmailRoute {
+      forward_addresses: staff_position_forward_addresses(&staff_position, db).await?,
+      target: InboundEmailTarget::StaffPosition(staff_position),
+    }));
+  }
+
+  if let Some(event) = events::Entity::find()
+    .filter(events::Column::Status.eq("active"))
+    .filter(Expr::cust_with_values(
+      "lower(events.team_mailing_list_name) = $1",
+      vec![local_part.to_string()],
+    ))
+    .filter(
+      events::Column::ConventionId.in_subquery(
+        QuerySelect::query(
+          &mut conventions::Entity::find()
+            .filter(Expr::cust_with_values(
+              "lower(conventions.event_mailing_list_domain) = $1",
+              vec![domain.to_string()],
+            ))
+            .select_only()
+            .column(conventions::Column::Id),
+        )
+        .take(),
+      ),
+    )
+    .one(db)
+    .await?
+  {
+    return Ok(Some(InboundEmailRoute {
+      forward_addresses: event_team_forward_addresses(&event, db).await?,
+      target: InboundEmailTarget::EventTeam(event),
+    }));
+  }
+
+  if let Some(staff_position) = staff_positions::Entity::find()
+    .filter(
+      staff_positions::Column::Id.in_subquery(
+        QuerySelect::query(
+          &mut conventions::Entity::find()
+            .filter(Expr::cust_with_values(
+              "lower(conventions.domain) = $1",
+              vec![domain.to_string()],
+            ))
+            .select_only()
+            .column(conventions::Column::CatchAllStaffPositionId),
+        )
+        .take(),
+      ),
+    )
+    .one(db)
+    .await?
+  {
+    return Ok(Some(InboundEmailRoute {
+      forward_addresses: staff_position_forward_addresses(&staff_position, db).await?,
+      target: InboundEmailTarget::CatchAll(staff_position),
+    }));
+  }
+
+  Ok(None)
+}
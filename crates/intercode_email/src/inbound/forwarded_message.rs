@@ -0,0 +1,214 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Header we add to every message we forward, naming the address it was forwarded for.  Seeing
+/// our own header come back is how we detect forwarding loops.
+pub const FORWARDED_FOR_HEADER: &str = "X-Intercode-Forwarded-For";
+pub const ORIGINAL_FROM_HEADER: &str = "X-Intercode-Original-From";
+
+// Headers that either stop being true once we re-send the message from our own domain, or that
+// we replace with our own values
+const STRIPPED_HEADERS: &[&str] = &[
+  "bcc",
+  "dkim-signature",
+  "from",
+  "reply-to",
+  "return-path",
+  "sender",
+];
+
+/// Who originally sent a message, as parsed out of its headers.
+#[derive(Debug, Clone)]
+pub struct OriginalSender {
+  pub display_name: Option<String>,
+  pub address: String,
+  /// The raw (still encoded) value of the original From header
+  pub from_header: String,
+  /// The raw (still encoded) value of the original Reply-To header, if there was one
+  pub reply_to_header: Option<String>,
+}
+
+/// Splits a raw RFC 822 message into its header fields (each including any folded continuation
+/// lines, without the trailing line ending) and its body.
+pub fn split_header_fields(raw: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+  let mut fields: Vec<&[u8]> = Vec::new();
+  let mut position = 0;
+  let mut field_start: Option<usize> = None;
+  let mut field_end = 0;
+
+  while position < raw.len() {
+    let line_end = raw[position..]
+      .iter()
+      .position(|&byte| byte == b'\n')
+      .map(|offset| position + offset)
+      .unwrap_or(raw.len());
+    let content_end = if line_end > position && raw[line_end - 1] == b'\r' {
+      line_end - 1
+    } else {
+      line_end
+    };
+    let next_position = (line_end + 1).min(raw.len());
+
+    if content_end == position {
+      // blank line: end of headers
+      if let Some(start) = field_start {
+        fields.push(&raw[start..field_end]);
+      }
+      return (fields, &raw[next_position..]);
+    }
+
+    let is_continuation = raw[position] == b' ' || raw[position] == b'\t';
+    if !is_continuation {
+      if let Some(start) = field_start {
+        fields.push(&raw[start..field_end]);
+      }
+      field_start = Some(position);
+    }
+    field_end = content_end;
+    position = next_position;
+  }
+
+  if let Some(start) = field_start {
+    fields.push(&raw[start..field_end]);
+  }
+  (fields, &[])
+}
+
+fn header_field_name(field: &[u8]) -> String {
+  let name_end = field
+    .iter()
+    .position(|&byte| byte == b':')
+    .unwrap_or(field.len());
+  String::from_utf8_lossy(&field[..name_end])
+    .trim()
+    .to_lowercase()
+}
+
+fn encode_display_name(display_name: &str) -> String {
+  if display_name.is_ascii() {
+    format!(
+      "\"{}\"",
+      display_name.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+  } else {
+    format!("=?UTF-8?B?{}?=", STANDARD.encode(display_name))
+  }
+}
+
+/// Rewrites a raw message so we can re-send it from `recipient` (an address at one of our own
+/// domains) without failing the original sender's SPF/DMARC checks.  The original sender stays
+/// reachable through Reply-To, and the message is tagged with the recipient for loop detection.
+pub fn build_forwarded_message(raw: &[u8], sender: &OriginalSender, recipient: &str) -> Vec<u8> {
+  let (fields, body) = split_header_fields(raw);
+  let sender_name = sender.display_name.as_deref().unwrap_or(&sender.address);
+  let reply_to = sender
+    .reply_to_header
+    .as_deref()
+    .unwrap_or(&sender.from_header);
+
+  let mut message: Vec<u8> = Vec::with_capacity(raw.len() + 512);
+  let mut push_header = |name: &str, value: &str| {
+    message.extend_from_slice(name.as_bytes());
+    message.extend_from_slice(b": ");
+    message.extend_from_slice(value.trim().as_bytes());
+    message.extend_from_slice(b"\r\n");
+  };
+
+  push_header(
+    "From",
+    &format!(
+      "{} <{}>",
+      encode_display_name(&format!("{} via {}", sender_name, recipient)),
+      recipient
+    ),
+  );
+  push_header("Reply-To", reply_to);
+  push_header(ORIGINAL_FROM_HEADER, &sender.from_header);
+  push_header(FORWARDED_FOR_HEADER, recipient);
+
+  for field in fields {
+    if STRIPPED_HEADERS.contains(&header_field_name(field).as_str()) {
+      continue;
+    }
+    message.extend_from_slice(field);
+    message.extend_from_slice(b"\r\n");
+  }
+
+  message.extend_from_slice(b"\r\n");
+  message.extend_from_slice(body);
+  message
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RAW_MESSAGE: &[u8] = b"Return-Path: <bounce@sender.example>\r\n\
+DKIM-Signature: v=1; a=rsa-sha256;\r\n\
+\tb=abc123\r\n\
+From: Jane Doe <jane@sender.example>\r\n\
+To: con@convention.example\r\n\
+Subject: A question\r\n\
+\r\n\
+Hello there.\r\n";
+
+  fn sender() -> OriginalSender {
+    OriginalSender {
+      display_name: Some("Jane Doe".to_string()),
+      address: "jane@sender.example".to_string(),
+      from_header: "Jane Doe <jane@sender.example>".to_string(),
+      reply_to_header: None,
+    }
+  }
+
+  #[test]
+  fn splits_folded_headers_and_body() {
+    let (fields, body) = split_header_fields(RAW_MESSAGE);
+    assert_eq!(fields.len(), 5);
+    assert_eq!(
+      fields[1],
+      b"DKIM-Signature: v=1; a=rsa-sha256;\r\n\tb=abc123"
+    );
+    assert_eq!(body, b"Hello there.\r\n");
+  }
+
+  #[test]
+  fn splits_messages_with_bare_newlines() {
+    let (fields, body) = split_header_fields(b"Subject: hi\nTo: a@b.example\n\nbody\n");
+    assert_eq!(fields, vec![&b"Subject: hi"[..], &b"To: a@b.example"[..]]);
+    assert_eq!(body, b"body\n");
+  }
+
+  #[test]
+  fn rewrites_sender_headers() {
+    let forwarded = build_forwarded_message(RAW_MESSAGE, &sender(), "con@convention.example");
+    let forwarded = String::from_utf8(forwarded).unwrap();
+
+    assert_eq!(
+      forwarded,
+      "From: \"Jane Doe via con@convention.example\" <con@convention.example>\r\n\
+Reply-To: Jane Doe <jane@sender.example>\r\n\
+X-Intercode-Original-From: Jane Doe <jane@sender.example>\r\n\
+X-Intercode-Forwarded-For: con@convention.example\r\n\
+To: con@convention.example\r\n\
+Subject: A question\r\n\
+\r\n\
+Hello there.\r\n"
+    );
+  }
+
+  #[test]
+  fn keeps_existing_reply_to_and_encodes_non_ascii_names() {
+    let sender = OriginalSender {
+      display_name: Some("Zoë".to_string()),
+      reply_to_header: Some("list@sender.example".to_string()),
+      ..sender()
+    };
+    let forwarded = build_forwarded_message(RAW_MESSAGE, &sender, "con@convention.example");
+    let forwarded = String::from_utf8(forwarded).unwrap();
+
+    assert!(forwarded.starts_with(&format!(
+      "From: =?UTF-8?B?{}?= <con@convention.example>\r\nReply-To: list@sender.example\r\n",
+      STANDARD.encode("Zoë via con@convention.example")
+    )));
+  }
+}
@@ -0,0 +1,15 @@
+mod address;
+mod delivery_log;
+mod email_router;
+mod forwarded_message;
+mod receive_email;
+mod ses_notification;
+mod sns_signature;
+
+pub use address::*;
+pub use delivery_log::*;
+pub use email_router::*;
+pub use forwarded_message::*;
+pub use receive_email::*;
+pub use ses_notification::*;
+pub use sns_signature::*;
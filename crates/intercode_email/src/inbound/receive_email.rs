@@ -0,0 +1,185 @@
+use async_graphql::Error;
+use mailparse::{addrparse_header, parse_headers, MailAddr, MailHeaderMap};
+use seawater::ConnectionWrapper;
+use tracing::log::*;
+
+use super::{
+  build_forwarded_message, claim_inbound_delivery, finish_inbound_delivery, inbound_message_key,
+  normalize_address, release_inbound_delivery, resolve_inbound_route, OriginalSender,
+  FORWARDED_FOR_HEADER,
+};
+use crate::send_raw_email;
+
+/// Any message that has already passed through this many of our forwarders is assumed to be
+/// stuck in a loop, even if it hasn't hit the same address twice
+pub const MAX_FORWARDING_HOPS: usize = 5;
+
+#[derive(Debug)]
+pub enum RecipientOutcome {
+  Forwarded {
+    recipient: String,
+    forward_addresses: Vec<String>,
+  },
+  NoRoute {
+    recipient: String,
+  },
+  LoopDetected {
+    recipient: String,
+  },
+  /// An earlier delivery of this same message already forwarded it to this recipient
+  AlreadyForwarded {
+    recipient: String,
+  },
+  Failed {
+    recipient: String,
+    error: String,
+  },
+}
+
+fn header_addresses(headers: &[mailparse::MailHeader], name: &str) -> Vec<String> {
+  headers
+    .get_all_headers(name)
+    .into_iter()
+    .filter_map(|header| addrparse_header(header).ok())
+    .flat_map(|list| {
+      list
+        .iter()
+        .flat_map(|addr| match addr {
+          MailAddr::Single(info) => vec![info.addr.clone()],
+          MailAddr::Group(group) => group.addrs.iter().map(|info| info.addr.clone()).collect(),
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+fn original_sender(headers: &[mailparse::MailHeader]) -> Result<OriginalSender, Error> {
+  let from_header = headers
+    .get_first_header("From")
+    .ok_or_else(|| Error::new("Message has no From header"))?;
+  let from_info = addrparse_header(from_header)?
+    .extract_single_info()
+    .ok_or_else(|| Error::new("Message From header does not contain a single address"))?;
+
+  Ok(OriginalSender {
+    display_name: from_info.display_name,
+    address: from_info.addr,
+    from_header: String::from_utf8_lossy(from_header.get_value_raw()).into_owned(),
+    reply_to_header: headers
+      .get_first_header("Reply-To")
+      .map(|header| String::from_utf8_lossy(header.get_value_raw()).into_owned()),
+  })
+}
+
+/// Processes a raw RFC 822 message delivered to one or more of our addresses, forwarding a
+/// rewritten copy to wherever each recipient routes.  If the delivery mechanism doesn't tell us
+/// the envelope recipients, we fall back to the To and Cc headers.
+///
+/// Forwarding to one recipient failing doesn't stop us from trying the others; it shows up as a
+/// Failed outcome.  Each recipient that was forwarded to is recorded, so when the message gets
+/// redelivered after a failure, only the recipients that didn't get it yet are tried again.
+pub async fn receive_email(
+  raw: &[u8],
+  envelope_recipients: &[String],
+  db: &ConnectionWrapper,
+) -> Result<Vec<RecipientOutcome>, Error> {
+  let (headers, _body_offset) = parse_headers(raw)?;
+  let sender = original_sender(&headers)?;
+  let message_key = inbound_message_key(raw);
+
+  let previously_forwarded_for = headers
+    .get_all_values(FORWARDED_FOR_HEADER)
+    .iter()
+    .map(|value| normalize_address(value))
+    .collect::<Vec<_>>();
+
+  let recipients = if envelope_recipients.is_empty() {
+    let mut recipients = header_addresses(&headers, "To");
+    recipients.extend(header_addresses(&headers, "Cc"));
+    recipients
+  } else {
+    envelope_recipients.to_vec()
+  };
+
+  let mut outcomes: Vec<RecipientOutcome> = Vec::with_capacity(recipients.len());
+  let mut seen_recipients: Vec<String> = Vec::with_capacity(recipients.len());
+
+  for recipient in recipients {
+    let recipient = normalize_address(&recipient);
+    if seen_recipients.contains(&recipient) {
+      continue;
+    }
+    seen_recipients.push(recipient.clone());
+
+    if previously_forwarded_for.len() >= MAX_FORWARDING_HOPS
+      || previously_forwarded_for.contains(&recipient)
+    {
+      warn!(
+        "Dropping message to {} from {}: forwarding loop detected",
+        recipient, sender.address
+      );
+      outcomes.push(RecipientOutcome::LoopDetected { recipient });
+      continue;
+    }
+
+    let Some(route) = resolve_inbound_route(&recipient, db).await? else {
+      info!("No inbound email route for {}", recipient);
+      outcomes.push(RecipientOutcome::NoRoute { recipient });
+      continue;
+    };
+
+    let mut forward_addresses: Vec<String> = Vec::with_capacity(route.forward_addresses.len());
+    for address in route.forward_addresses {
+      let normalized = normalize_address(&address);
+      if normalized != recipient
+        && !forward_addresses
+          .iter()
+          .any(|existing| normalize_address(existing) == normalized)
+      {
+        forward_addresses.push(address);
+      }
+    }
+
+    if forward_addresses.is_empty() {
+      info!(
+        "{} resolved to {} but it has no addresses",
+        recipient, route.target
+      );
+      outcomes.push(RecipientOutcome::NoRoute { recipient });
+      continue;
+    }
+
+    if !claim_inbound_delivery(&message_key, &recipient, db).await? {
+      info!("Message for {} was already forwarded", recipient);
+      outcomes.push(RecipientOutcome::AlreadyForwarded { recipient });
+      continue;
+    }
+
+    info!(
+      "Forwarding message for {} ({}) to {}",
+      recipient,
+      route.target,
+      forward_addresses.join(", ")
+    );
+    let forwarded = build_forwarded_message(raw, &sender, &recipient);
+    match send_raw_email(forward_addresses.clone(), forwarded).await {
+      Ok(_) => {
+        finish_inbound_delivery(&message_key, &recipient, db).await?;
+        outcomes.push(RecipientOutcome::Forwarded {
+          recipient,
+          forward_addresses,
+        });
+      }
+      Err(err) => {
+        error!("Error forwarding message for {}: {:?}", recipient, err);
+        release_inbound_delivery(&message_key, &recipient, db).await?;
+        outcomes.push(RecipientOutcome::Failed {
+          recipient,
+          error: err.to_string(),
+        });
+      }
+    }
+  }
+
+  Ok(outcomes)
+}
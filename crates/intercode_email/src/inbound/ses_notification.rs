@@ -0,0 +1,110 @@
+use std::env;
+
+use async_graphql::Error;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+
+use crate::get_aws_config;
+
+/// The outer envelope of an SNS HTTP(S) delivery
+#[derive(Deserialize, Debug)]
+pub struct SnsEnvelope {
+  #[serde(rename = "Type")]
+  pub message_type: String,
+  #[serde(rename = "MessageId")]
+  pub message_id: String,
+  #[serde(rename = "TopicArn")]
+  pub topic_arn: String,
+  #[serde(rename = "Subject")]
+  pub subject: Option<String>,
+  #[serde(rename = "Message")]
+  pub message: String,
+  #[serde(rename = "Timestamp")]
+  pub timestamp: String,
+  #[serde(rename = "Token")]
+  pub token: Option<String>,
+  #[serde(rename = "SubscribeURL")]
+  pub subscribe_url: Option<String>,
+  #[serde(rename = "SignatureVersion")]
+  pub signature_version: String,
+  #[serde(rename = "Signature")]
+  pub signature: String,
+  #[serde(rename = "SigningCertURL")]
+  pub signing_cert_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum SesReceiptAction {
+  S3 {
+    #[serde(rename = "bucketName")]
+    bucket_name: String,
+    #[serde(rename = "objectKey")]
+    object_key: String,
+  },
+  #[serde(rename = "SNS")]
+  Sns { encoding: Option<String> },
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SesReceipt {
+  pub recipients: Vec<String>,
+  pub action: SesReceiptAction,
+}
+
+/// An SES receipt rule notification, as found in the Message of an SNS envelope
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SesReceivedNotification {
+  pub notification_type: String,
+  pub receipt: SesReceipt,
+  pub content: Option<String>,
+}
+
+impl SesReceivedNotification {
+  /// Gets the raw message, either inline from the notification (for SNS actions) or from the
+  /// bucket SES stored it in (for S3 actions).  We'll only read from the bucket named in
+  /// INBOUND_EMAIL_S3_BUCKET, so that a notification can't get us to fetch (and forward) an
+  /// arbitrary object.
+  pub async fn load_raw_message(&self) -> Result<Vec<u8>, Error> {
+    match &self.receipt.action {
+      SesReceiptAction::S3 {
+        bucket_name,
+        object_key,
+      } => {
+        if env::var("INBOUND_EMAIL_S3_BUCKET").ok().as_deref() != Some(bucket_name.as_str()) {
+          return Err(Error::new(format!(
+            "SES notification refers to bucket {}, which is not INBOUND_EMAIL_S3_BUCKET",
+            bucket_name
+          )));
+        }
+
+        let client = aws_sdk_s3::Client::new(get_aws_config().await);
+        let object = client
+          .get_object()
+          .bucket(bucket_name)
+          .key(object_key)
+          .send()
+          .await?;
+        Ok(object.body.collect().await?.into_bytes().to_vec())
+      }
+      SesReceiptAction::Sns { encoding } => {
+        let content = self
+          .content
+          .as_deref()
+          .ok_or_else(|| Error::new("SES notification has no message content"))?;
+
+        if encoding.as_deref() == Some("BASE64") {
+          Ok(STANDARD.decode(content)?)
+        } else {
+          Ok(content.as_bytes().to_vec())
+        }
+      }
+      SesReceiptAction::Other => Err(Error::new(
+        "SES notification uses an unsupported receipt action",
+      )),
+    }
+  }
+}
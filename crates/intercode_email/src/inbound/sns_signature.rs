@@ -0,0 +1,327 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::Error;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use ring::signature::{self, RsaParameters, UnparsedPublicKey};
+use url::Url;
+
+use super::SnsEnvelope;
+
+/// DER encoding of the rsaEncryption algorithm OID (1.2.840.113549.1.1.1)
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// SNS signs every message with one of a small number of long-lived certificates, so we keep the
+/// ones we've seen around rather than fetching them again for each message
+static SIGNING_CERTIFICATES: Lazy<Mutex<HashMap<String, Arc<Vec<u8>>>>> =
+  Lazy::new(Default::default);
+
+/// Builds the string SNS signs for a message, as described in
+/// https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+pub fn sns_string_to_sign(envelope: &SnsEnvelope) -> String {
+  let mut fields: Vec<(&str, Option<&str>)> = vec![
+    ("Message", Some(&envelope.message)),
+    ("MessageId", Some(&envelope.message_id)),
+  ];
+
+  if envelope.message_type == "Notification" {
+    fields.push(("Subject", envelope.subject.as_deref()));
+    fields.push(("Timestamp", Some(&envelope.timestamp)));
+  } else {
+    fields.push(("SubscribeURL", envelope.subscribe_url.as_deref()));
+    fields.push(("Timestamp", Some(&envelope.timestamp)));
+    fields.push(("Token", envelope.token.as_deref()));
+  }
+  fields.push(("TopicArn", Some(&envelope.topic_arn)));
+  fields.push(("Type", Some(&envelope.message_type)));
+
+  fields
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|value| format!("{}\n{}\n", name, value)))
+    .collect()
+}
+
+/// Only certificates served over HTTPS from an SNS endpoint can sign messages; otherwise anyone
+/// could sign a message with their own key and point us at it
+pub fn signing_cert_url_trusted(url: &str) -> bool {
+  let Ok(url) = Url::parse(url) else {
+    return false;
+  };
+
+  let region = url.host_str().and_then(|host| {
+    host.strip_prefix("sns.").and_then(|host| {
+      host
+        .strip_suffix(".amazonaws.com")
+        .or_else(|| host.strip_suffix(".amazonaws.com.cn"))
+    })
+  });
+
+  url.scheme() == "https"
+    && url.port().is_none()
+    && url.path().ends_with(".pem")
+    && region.is_some_and(|region| {
+      !region.is_empty()
+        && region
+          .chars()
+          .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    })
+}
+
+/// Splits the first DER element off the front of `input`, returning its tag, its contents and
+/// whatever follows it
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+  let (&tag, input) = input.split_first()?;
+  let (&first_length_byte, input) = input.split_first()?;
+
+  let (length, input) = if first_length_byte < 0x80 {
+    (first_length_byte as usize, input)
+  } else {
+    let length_bytes = (first_length_byte & 0x7f) as usize;
+    if length_bytes == 0 || length_bytes > 4 || input.len() < length_bytes {
+      return None;
+    }
+    let (length_bytes, input) = input.split_at(length_bytes);
+    (
+      length_bytes
+        .iter()
+        .fold(0_usize, |length, byte| (length << 8) | *byte as usize),
+      input,
+    )
+  };
+
+  if input.len() < length {
+    return None;
+  }
+  let (contents, rest) = input.split_at(length);
+  Some((tag, contents, rest))
+}
+
+/// Digs the RSAPublicKey out of an X.509 certificate's SubjectPublicKeyInfo, which is the form
+/// ring wants RSA public keys in
+fn rsa_public_key_from_certificate(certificate: &[u8]) -> Option<&[u8]> {
+  const SEQUENCE: u8 = 0x30;
+  const BIT_STRING: u8 = 0x03;
+  const OBJECT_IDENTIFIER: u8 = 0x06;
+  const EXPLICIT_VERSION: u8 = 0xa0;
+
+  let (SEQUENCE, certificate, _) = der_element(certificate)? else {
+    return None;
+  };
+  let (SEQUENCE, tbs_certificate, _) = der_element(certificate)? else {
+    return None;
+  };
+
+  let mut fields = tbs_certificate;
+  let (tag, _, rest) = der_element(fields)?;
+  if tag == EXPLICIT_VERSION {
+    fields = rest;
+  }
+  // serialNumber, signature, issuer, validity and subject come before subjectPublicKeyInfo
+  for _ in 0..5 {
+    let (_, _, rest) = der_element(fields)?;
+    fields = rest;
+  }
+
+  let (SEQUENCE, public_key_info, _) = der_element(fields)? else {
+    return None;
+  };
+  let (SEQUENCE, algorithm, public_key) = der_element(public_key_info)? else {
+    return None;
+  };
+  let (OBJECT_IDENTIFIER, algorithm_oid, _) = der_element(algorithm)? else {
+    return None;
+  };
+  if algorithm_oid != RSA_ENCRYPTION_OID {
+    return None;
+  }
+  let (BIT_STRING, public_key, _) = der_element(public_key)? else {
+    return None;
+  };
+
+  // the first byte of a BIT STRING is the number of unused bits at the end, which is always 0
+  // for a key
+  match public_key.split_first()? {
+    (0, public_key) => Some(public_key),
+    _ => None,
+  }
+}
+
+fn pem_certificate_to_der(pem: &str) -> Result<Vec<u8>, Error> {
+  let base64 = pem
+    .lines()
+    .skip_while(|line| line.trim() != "-----BEGIN CERTIFICATE-----")
+    .skip(1)
+    .take_while(|line| line.trim() != "-----END CERTIFICATE-----")
+    .map(str::trim)
+    .collect::<String>();
+
+  if base64.is_empty() {
+    return Err(Error::new(
+      "SNS signing certificate is not a PEM certificate",
+    ));
+  }
+
+  Ok(STANDARD.decode(base64)?)
+}
+
+async fn signing_certificate(url: &str) -> Result<Arc<Vec<u8>>, Error> {
+  if let Some(certificate) = SIGNING_CERTIFICATES.lock().get(url) {
+    return Ok(certificate.clone());
+  }
+
+  let pem = reqwest::get(url).await?.error_for_status()?.text().await?;
+  let certificate = Arc::new(pem_certificate_to_der(&pem)?);
+  SIGNING_CERTIFICATES
+    .lock()
+    .insert(url.to_string(), certificate.clone());
+
+  Ok(certificate)
+}
+
+fn signature_algorithm(signature_version: &str) -> Result<&'static RsaParameters, Error> {
+  match signature_version {
+    "1" => Ok(&signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY),
+    "2" => Ok(&signature::RSA_PKCS1_2048_8192_SHA256),
+    _ => Err(Error::new(format!(
+      "Unsupported SNS signature version {:?}",
+      signature_version
+    ))),
+  }
+}
+
+fn verify_signature_with_certificate(
+  certificate: &[u8],
+  algorithm: &'static RsaParameters,
+  string_to_sign: &str,
+  signature: &str,
+) -> Result<(), Error> {
+  let public_key = rsa_public_key_from_certificate(certificate)
+    .ok_or_else(|| Error::new("SNS signing certificate does not contain an RSA public key"))?;
+  let signature = STANDARD.decode(signature)?;
+
+  UnparsedPublicKey::new(algorithm, public_key)
+    .verify(string_to_sign.as_bytes(), &signature)
+    .map_err(|_| Error::new("SNS message signature is invalid"))
+}
+
+/// Checks that an SNS message really came from SNS: it has to be signed by a certificate served
+/// from an SNS endpoint.
+pub async fn verify_sns_signature(envelope: &SnsEnvelope) -> Result<(), Error> {
+  let algorithm = signature_algorithm(&envelope.signature_version)?;
+  if !signing_cert_url_trusted(&envelope.signing_cert_url) {
+    return Err(Error::new(format!(
+      "SNS signing certificate URL {} is not an SNS endpoint",
+      envelope.signing_cert_url
+    )));
+  }
+
+  let certificate = signing_certificate(&envelope.signing_cert_url).await?;
+  verify_signature_with_certificate(
+    &certificate,
+    algorithm,
+    &sns_string_to_sign(envelope),
+    &envelope.signature,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // a self-signed certificate, and a SignatureVersion 2 signature of the notification below made
+  // with its key using openssl
+  const TEST_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIDCTCCAfGgAwIBAgIUbhXF/FrW9NxB+b2Hrz/1YY04eSQwDQYJKoZIhvcNAQEL
+BQAwEzERMA8GA1UEAwwIc25zLnRlc3QwIBcNMjYxMDE5MDMzMjM2WhgPMjEyNjA5
+MjUwMzMyMzZaMBMxETAPBgNVBAMMCHNucy50ZXN0MIIBIjANBgkqhkiG9w0BAQEF
+AAOCAQ8AMIIBCgKCAQEAvUrV6BoToZuoJNBRjgoP7/TJn5GMEwWsSIQCOmHIZ/Y1
+2Wfe/qBeVuBMRsIqOXipvRMm7MWSva1jMmvEjyEpwbq8HoJ9iDIgqHHW5XVnaGPi
+mYDuhiUtbkkrH05HMnBhL6WmxWcNnT/Kr/71/IzXVHZ7RtuZO0fCeiMm7Z2aWMfP
+AIP0M/BbgkA59dxUdYLUPKQNPl2c/olZXZ/icmqFeHEf7JxP0DEo4Id/wWeY5tQ7
+//90S3c67DzGBa+1JKBjxRD7qV6xA4jMxbQEdpHu0PYKQg/80Sqo9tFqA7a9G3ho
+5S/THMwoAc/6q4wiLiV79QRzA/SuESZOSCGVGIwB4QIDAQABo1MwUTAdBgNVHQ4E
+FgQUBwXQXgo3iTEg4FM1C3s0J62iQdswHwYDVR0jBBgwFoAUBwXQXgo3iTEg4FM1
+C3s0J62iQdswDwYDVR0TAQH/BAUwAwEB/zANBgkqhkiG9w0BAQsFAAOCAQEAGZaZ
+GHuobKN2vBTnUBmxyqP4t8itjfn+JgcYwojfeEIL8We/BdJfAz1c4gidRjbxuC+D
+KGaBhl4KqKICc5VT+WVsahu9FYn9Dr2klWNii6XekcCiF9FfOc0nfItRF1doCQyo
+elp4Q8JOf9X7ApptEHxQbnujDgo3RWBoDOrJthIZ7wdzzcPhJoAB+lEnhNE5kLFD
+2GOEBQuIipGmKu6lopatnu7oMlqnFVlrd9vabROUXjs1nKqZ3FATcZ/nMZkHYZBu
+jVKXAtU4ZR4fZtynxJGEqzHmRwcAlfHi9pBaRNdWQjpT6gDWg5Bv/LCsD1tC+l5p
+x7IOXkQWdukg0bQn+w==
+-----END CERTIFICATE-----";
+  const TEST_SIGNATURE: &str = "ZGQTIVAxGk73D2zvpOFFamZo3t66y1jBBh7DswjV0kaJqkNcV/WQNHyKHoA0Vgpr55qc2mX17z5+SIDQTyll/uOO/Oij+sBBH71iWeFJnytkwrexgVPk8bWCaxbdTWZ9SpMndCPiJU5xzShfbhm+Ge7oWJaZL+iuVHjAIa+o+H7vKlv4VHCPnJrn5Hqcigwzedz/PQyD1btdS3nVifpBtgHFeAR9AdVJZB/8EE1jd8ZFnQn4iMee0Pt9wcOIWr0wOiRcixbkCWzFs95RKD2jU0grEco59o35wW69vFgLvdj4rJQGbz5/Ot0EL7JrY/JsedbB/OMS+y3Yq2bE0arvZQ==";
+
+  fn notification() -> SnsEnvelope {
+    SnsEnvelope {
+      message_type: "Notification".to_string(),
+      message_id: "22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324".to_string(),
+      topic_arn: "arn:aws:sns:us-west-2:123456789012:inbound-email".to_string(),
+      subject: None,
+      message: "{\"notificationType\":\"Received\"}".to_string(),
+      timestamp: "2026-10-19T12:00:00.000Z".to_string(),
+      token: None,
+      subscribe_url: None,
+      signature_version: "2".to_string(),
+      signature: TEST_SIGNATURE.to_string(),
+      signing_cert_url: "https://sns.us-west-2.amazonaws.com/SimpleNotificationService-1234.pem"
+        .to_string(),
+    }
+  }
+
+  #[test]
+  fn builds_notification_string_to_sign() {
+    assert_eq!(
+      sns_string_to_sign(&notification()),
+      "Message\n{\"notificationType\":\"Received\"}\n\
+       MessageId\n22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324\n\
+       Timestamp\n2026-10-19T12:00:00.000Z\n\
+       TopicArn\narn:aws:sns:us-west-2:123456789012:inbound-email\n\
+       Type\nNotification\n"
+    );
+  }
+
+  #[test]
+  fn only_trusts_sns_certificate_urls() {
+    assert!(signing_cert_url_trusted(
+      "https://sns.us-west-2.amazonaws.com/SimpleNotificationService-1234.pem"
+    ));
+    assert!(signing_cert_url_trusted(
+      "https://sns.cn-north-1.amazonaws.com.cn/SimpleNotificationService-1234.pem"
+    ));
+    assert!(!signing_cert_url_trusted(
+      "http://sns.us-west-2.amazonaws.com/SimpleNotificationService-1234.pem"
+    ));
+    assert!(!signing_cert_url_trusted(
+      "https://sns.us-west-2.amazonaws.com.evil.example/cert.pem"
+    ));
+    assert!(!signing_cert_url_trusted(
+      "https://evil.example/sns.us-west-2.amazonaws.com.pem"
+    ));
+  }
+
+  #[test]
+  fn verifies_signatures_with_the_certificate_key() {
+    let certificate = pem_certificate_to_der(TEST_CERTIFICATE).unwrap();
+    let envelope = notification();
+
+    assert!(verify_signature_with_certificate(
+      &certificate,
+      signature_algorithm("2").unwrap(),
+      &sns_string_to_sign(&envelope),
+      &envelope.signature,
+    )
+    .is_ok());
+
+    let mut tampered = notification();
+    tampered.message = "{\"notificationType\":\"Bounce\"}".to_string();
+    assert!(verify_signature_with_certificate(
+      &certificate,
+      signature_algorithm("2").unwrap(),
+      &sns_string_to_sign(&tampered),
+      &tampered.signature,
+    )
+    .is_err());
+  }
+}
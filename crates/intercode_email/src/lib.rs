@@ -1,7 +1,11 @@
+pub mod actions;
+pub mod inbound;
 pub mod objects;
+pub mod outbox;
 pub mod partial_objects;
 pub mod policies;
 pub mod query_builders;
 mod send_email;
 
-pub use send_email::send_email;
+pub(crate) use send_email::get_aws_config;
+pub use send_email::{send_email, send_raw_email};
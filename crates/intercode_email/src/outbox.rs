@@ -0,0 +1,322 @@
+use std::{env, sync::Arc, time::Duration};
+
+use aws_sdk_sesv2::types::Destination;
+use chrono::Utc;
+use intercode_entities::outbox_emails;
+use sea_orm::{
+  sea_query::{Cond, LockBehavior, LockType},
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+  QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::log::*;
+
+use crate::send_email;
+
+pub const OUTBOX_STATUS_PENDING: &str = "pending";
+pub const OUTBOX_STATUS_SENT: &str = "sent";
+pub const OUTBOX_STATUS_FAILED: &str = "failed";
+
+/// How fast the outbox worker drains the queue.  The defaults match SES's default sending rate of
+/// 14 messages per second.
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+  pub batch_size: u64,
+  pub batch_interval: Duration,
+  pub max_attempts: i32,
+}
+
+impl Default for OutboxConfig {
+  fn default() -> Self {
+    Self {
+      batch_size: 14,
+      batch_interval: Duration::from_secs(1),
+      max_attempts: 5,
+    }
+  }
+}
+
+impl OutboxConfig {
+  pub fn from_env() -> Self {
+    let default = Self::default();
+
+    Self {
+      batch_size: env::var("OUTBOX_BATCH_SIZE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default.batch_size),
+      batch_interval: env::var("OUTBOX_BATCH_INTERVAL_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(default.batch_interval),
+      max_attempts: env::var("OUTBOX_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default.max_attempts),
+    }
+  }
+}
+
+/// Builds a pending outbox row.  Callers can set the optional association columns (e.g.
+/// bulk_email_campaign_id) before inserting it.
+pub fn new_outbox_email(
+  from_address: &str,
+  to_address: &str,
+  subject: &str,
+  body_html: Option<String>,
+  body_text: Option<String>,
+) -> outbox_emails::ActiveModel {
+  let now = Utc::now().naive_utc();
+
+  outbox_emails::ActiveModel {
+    from_address: Set(from_address.to_string()),
+    to_address: Set(to_address.to_string()),
+    subject: Set(subject.to_string()),
+    body_html: Set(body_html),
+    body_text: Set(body_text),
+    status: Set(OUTBOX_STATUS_PENDING.to_string()),
+    attempts: Set(0),
+    created_at: Set(now),
+    updated_at: Set(now),
+    ..Default::default()
+  }
+}
+
+/// How long a worker has to send the messages it claimed before another worker may pick them up
+/// again.  This only comes into play if a worker dies mid-batch.
+const CLAIM_LEASE_MINUTES: i64 = 10;
+
+/// Claims up to `config.batch_size` due messages.  The claim is a short transaction that counts
+/// the attempt and pushes send_after out by CLAIM_LEASE_MINUTES, so the rows stay invisible to other
+/// workers while we send them without holding a transaction (and its row locks) open across the
+/// SES calls.
+async fn claim_outbox_batch<C: ConnectionTrait + TransactionTrait>(
+  db: &C,
+  config: &OutboxConfig,
+) -> Result<Vec<outbox_emails::Model>, DbErr> {
+  let now = Utc::now().naive_utc();
+  let txn = db.begin().await?;
+
+  let due_emails = outbox_emails::Entity::find()
+    .filter(outbox_emails::Column::Status.eq(OUTBOX_STATUS_PENDING))
+    .filter(
+      Cond::any()
+        .add(outbox_emails::Column::SendAfter.is_null())
+        .add(outbox_emails::Column::SendAfter.lte(now)),
+    )
+    .order_by_asc(outbox_emails::Column::Id)
+    .limit(config.batch_size)
+    .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+    .all(&txn)
+    .await?;
+
+  let mut claimed = Vec::with_capacity(due_emails.len());
+  for outbox_email in due_emails {
+    let attempts = outbox_email.attempts + 1;
+    let mut active_model: outbox_emails::ActiveModel = outbox_email.into();
+    active_model.attempts = Set(attempts);
+    active_model.send_after = Set(Some(now + chrono::Duration::minutes(CLAIM_LEASE_MINUTES)));
+    active_model.updated_at = Set(now);
+    claimed.push(active_model.update(&txn).await?);
+  }
+
+  txn.commit().await?;
+  Ok(claimed)
+}
+
+/// Sends up to `config.batch_size` due messages from the outbox.  Rows are claimed before we work
+/// on them, so multiple servers can run workers against the same database.  Returns the number of
+/// messages attempted.
+pub async fn deliver_outbox_batch(
+  db: &DatabaseConnection,
+  config: &OutboxConfig,
+) -> Result<usize, DbErr> {
+  let claimed_emails = claim_outbox_batch(db, config).await?;
+  let count = claimed_emails.len();
+
+  for outbox_email in claimed_emails {
+    let result = send_email(
+      &outbox_email.from_address,
+      Destination::builder()
+        .to_addresses(&outbox_email.to_address)
+        .build(),
+      &outbox_email.subject,
+      outbox_email.body_html.as_deref(),
+      outbox_email.body_text.as_deref(),
+    )
+    .await;
+
+    let attempts = outbox_email.attempts;
+    let mut active_model: outbox_emails::ActiveModel = outbox_email.into();
+    active_model.updated_at = Set(Utc::now().naive_utc());
+
+    match result {
+      Ok(_) => {
+        active_model.status = Set(OUTBOX_STATUS_SENT.to_string());
+        active_model.sent_at = Set(Some(Utc::now().naive_utc()));
+        active_model.send_after = Set(None);
+        active_model.last_error = Set(None);
+      }
+      Err(err) => {
+        warn!("Error sending outbox email: {}", err);
+        active_model.last_error = Set(Some(err.to_string()));
+        if attempts >= config.max_attempts {
+          active_model.status = Set(OUTBOX_STATUS_FAILED.to_string());
+        } else {
+          // exponential backoff: 2, 4, 8... minutes
+          active_model.send_after = Set(Some(
+            Utc::now().naive_utc() + chrono::Duration::minutes(2_i64.pow(attempts as u32)),
+          ));
+        }
+      }
+    }
+
+    active_model.update(db).await?;
+  }
+
+  Ok(count)
+}
+
+/// Drains the outbox forever, one rate-limited batch at a time.
+pub async fn run_outbox_worker(db: Arc<DatabaseConnection>, config: OutboxConfig) {
+  let mut interval = tokio::time::interval(config.batch_interval);
+
+  loop {
+    interval.tick().await;
+
+    if let Err(err) = deliver_outbox_batch(&db, &config).await {
+      error!("Error delivering outbox batch: {}", err);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use intercode_policies::test_helpers::with_test_db;
+
+  use super::*;
+
+  async fn insert_pending_emails<C: ConnectionTrait>(db: &C, count: usize) -> Vec<i64> {
+    let mut ids = Vec::with_capacity(count);
+    for index in 0..count {
+      let outbox_email = new_outbox_email(
+        "noreply@intercode.test",
+        &format!("recipient{}@intercode.test", index),
+        "Hello",
+        None,
+        Some("Hello".to_string()),
+      )
+      .insert(db)
+      .await
+      .unwrap();
+      ids.push(outbox_email.id);
+    }
+    ids
+  }
+
+  fn config_with_batch_size(batch_size: u64) -> OutboxConfig {
+    OutboxConfig {
+      batch_size,
+      ..Default::default()
+    }
+  }
+
+  #[tokio::test]
+  async fn claims_at_most_one_batch_in_queue_order() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let ids = insert_pending_emails(&db, 5).await;
+        let config = config_with_batch_size(3);
+
+        let first_batch = claim_outbox_batch(&db, &config).await.unwrap();
+        assert_eq!(
+          first_batch.iter().map(|email| email.id).collect::<Vec<_>>(),
+          ids[0..3]
+        );
+        assert!(first_batch.iter().all(|email| email.attempts == 1));
+
+        let second_batch = claim_outbox_batch(&db, &config).await.unwrap();
+        assert_eq!(
+          second_batch
+            .iter()
+            .map(|email| email.id)
+            .collect::<Vec<_>>(),
+          ids[3..5]
+        );
+      })
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn claimed_emails_are_leased_until_the_lease_runs_out() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let ids = insert_pending_emails(&db, 2).await;
+        let config = config_with_batch_size(10);
+
+        let claimed = claim_outbox_batch(&db, &config).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+        let now = Utc::now().naive_utc();
+        assert!(claimed
+          .iter()
+          .all(|email| email.send_after.is_some_and(|send_after| send_after > now)));
+        assert!(claim_outbox_batch(&db, &config).await.unwrap().is_empty());
+
+        // once the lease expires (e.g. the worker died mid-batch), the email is claimable again
+        let mut expired: outbox_emails::ActiveModel = claimed[0].clone().into();
+        expired.send_after = Set(Some(now - chrono::Duration::seconds(1)));
+        expired.update(&db).await.unwrap();
+
+        let reclaimed = claim_outbox_batch(&db, &config).await.unwrap();
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].id, ids[0]);
+        assert_eq!(reclaimed[0].attempts, 2);
+      })
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn skips_emails_that_are_not_due_or_not_pending() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let ids = insert_pending_emails(&db, 3).await;
+        let now = Utc::now().naive_utc();
+
+        outbox_emails::ActiveModel {
+          id: sea_orm::ActiveValue::Unchanged(ids[0]),
+          send_after: Set(Some(now + chrono::Duration::minutes(5))),
+          ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        outbox_emails::ActiveModel {
+          id: sea_orm::ActiveValue::Unchanged(ids[1]),
+          status: Set(OUTBOX_STATUS_SENT.to_string()),
+          ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+
+        let claimed = claim_outbox_batch(&db, &config_with_batch_size(10))
+          .await
+          .unwrap();
+        assert_eq!(
+          claimed.iter().map(|email| email.id).collect::<Vec<_>>(),
+          vec![ids[2]]
+        );
+      })
+    })
+    .await;
+  }
+
+  #[test]
+  fn default_config_matches_the_ses_sending_rate() {
+    let config = OutboxConfig::default();
+    assert_eq!(config.batch_size, 14);
+    assert_eq!(config.batch_interval, Duration::from_secs(1));
+  }
+}
@@ -2,14 +2,15 @@ use aws_config::{BehaviorVersion, SdkConfig};
 use aws_sdk_sesv2::{
   error::SdkError,
   operation::send_email::{SendEmailError, SendEmailOutput},
-  types::{Body, Content, Destination, EmailContent, Message},
+  types::{Body, Content, Destination, EmailContent, Message, RawMessage},
 };
+use aws_smithy_types::Blob;
 use tokio::sync::OnceCell;
 
 static AWS_CONFIG: OnceCell<SdkConfig> = OnceCell::const_new();
 static SES_CLIENT: OnceCell<aws_sdk_sesv2::Client> = OnceCell::const_new();
 
-async fn get_aws_config() -> &'static SdkConfig {
+pub(crate) async fn get_aws_config() -> &'static SdkConfig {
   AWS_CONFIG
     .get_or_init(|| aws_config::load_defaults(BehaviorVersion::latest()))
     .await
@@ -55,3 +56,26 @@ pub async fn send_email(
     .send()
     .await
 }
+
+/// Sends an already-formatted RFC 822 message as-is.  The From header in the message is used as
+/// the sender, and `to_addresses` are the envelope recipients (regardless of what the message's
+/// own To header says).
+pub async fn send_raw_email(
+  to_addresses: Vec<String>,
+  data: Vec<u8>,
+) -> Result<SendEmailOutput, SdkError<SendEmailError>> {
+  let client = get_ses_client().await;
+  let content =
+    EmailContent::builder().raw(RawMessage::builder().data(Blob::new(data)).build().unwrap());
+
+  client
+    .send_email()
+    .destination(
+      Destination::builder()
+        .set_to_addresses(Some(to_addresses))
+        .build(),
+    )
+    .content(content.build())
+    .send()
+    .await
+}
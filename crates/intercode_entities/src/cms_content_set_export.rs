@@ -0,0 +1,143 @@
+use sea_orm::JsonValue;
+use serde::{Deserialize, Serialize};
+
+use crate::{cms_graphql_queries, cms_layouts, cms_partials, cms_variables};
+
+/// Bumped whenever a change to these structs would stop an older importer from reading a set
+pub const CMS_CONTENT_SET_FORMAT_VERSION: i32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsPageExport {
+  pub name: Option<String>,
+  pub slug: String,
+  pub content: Option<String>,
+  pub admin_notes: Option<String>,
+  /// Layouts are referred to by name, since ids don't survive the trip to another parent
+  pub cms_layout_name: Option<String>,
+  pub skip_clickwrap_agreement: bool,
+  pub hidden_from_search: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsPartialExport {
+  pub name: String,
+  pub content: Option<String>,
+  pub admin_notes: Option<String>,
+}
+
+impl From<cms_partials::Model> for CmsPartialExport {
+  fn from(value: cms_partials::Model) -> Self {
+    CmsPartialExport {
+      name: value.name,
+      content: value.content,
+      admin_notes: value.admin_notes,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsLayoutExport {
+  pub name: String,
+  pub content: Option<String>,
+  pub navbar_classes: Option<String>,
+  pub admin_notes: Option<String>,
+}
+
+impl CmsLayoutExport {
+  /// Layouts don't have to have a name, but they need one to be referred to in an export
+  pub fn export_name(layout: &cms_layouts::Model) -> String {
+    layout
+      .name
+      .clone()
+      .filter(|name| !name.trim().is_empty())
+      .unwrap_or_else(|| format!("Layout {}", layout.id))
+  }
+}
+
+impl From<cms_layouts::Model> for CmsLayoutExport {
+  fn from(value: cms_layouts::Model) -> Self {
+    CmsLayoutExport {
+      name: CmsLayoutExport::export_name(&value),
+      content: value.content,
+      navbar_classes: value.navbar_classes,
+      admin_notes: value.admin_notes,
+    }
+  }
+}
+
+/// A navigation link (if page_slug is set) or section (if not).  Items are listed in position
+/// order, and a section's links are nested inside it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsNavigationItemExport {
+  pub title: Option<String>,
+  pub page_slug: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub items: Vec<CmsNavigationItemExport>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsVariableExport {
+  pub key: String,
+  pub value: Option<JsonValue>,
+}
+
+impl From<cms_variables::Model> for CmsVariableExport {
+  fn from(value: cms_variables::Model) -> Self {
+    CmsVariableExport {
+      key: value.key,
+      value: value.value,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsGraphqlQueryExport {
+  pub identifier: String,
+  pub query: Option<String>,
+  pub admin_notes: Option<String>,
+}
+
+impl From<cms_graphql_queries::Model> for CmsGraphqlQueryExport {
+  fn from(value: cms_graphql_queries::Model) -> Self {
+    CmsGraphqlQueryExport {
+      identifier: value.identifier.unwrap_or_default(),
+      query: value.query,
+      admin_notes: value.admin_notes,
+    }
+  }
+}
+
+/// Files are exported as a description of their upload rather than its bytes.  An import can
+/// only bring a file along if the upload it points at is stored in the same place.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsFileExport {
+  pub filename: String,
+  pub content_type: Option<String>,
+  pub byte_size: i64,
+  pub checksum: Option<String>,
+  pub blob_key: String,
+  pub service_name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmsContentSetExport {
+  pub format_version: i32,
+  /// A human-readable description of where the content came from
+  pub source: Option<String>,
+  pub root_page_slug: Option<String>,
+  pub default_layout_name: Option<String>,
+  #[serde(default)]
+  pub pages: Vec<CmsPageExport>,
+  #[serde(default)]
+  pub partials: Vec<CmsPartialExport>,
+  #[serde(default)]
+  pub layouts: Vec<CmsLayoutExport>,
+  #[serde(default)]
+  pub navigation_items: Vec<CmsNavigationItemExport>,
+  #[serde(default)]
+  pub variables: Vec<CmsVariableExport>,
+  #[serde(default)]
+  pub graphql_queries: Vec<CmsGraphqlQueryExport>,
+  #[serde(default)]
+  pub files: Vec<CmsFileExport>,
+}
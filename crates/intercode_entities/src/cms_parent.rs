@@ -1,17 +1,41 @@
 use crate::{
-  cms_content_groups, cms_files, cms_graphql_queries, cms_layouts, cms_navigation_items,
-  cms_partials, cms_variables, conventions, pages, root_sites,
+  cms_content_groups, cms_content_revisions, cms_files, cms_graphql_queries, cms_layouts,
+  cms_navigation_items, cms_partials, cms_variables, conventions, pages, root_sites,
 };
 use async_trait::async_trait;
 use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Select};
 
+/// CMS pages other than the root page are served under this prefix, by slug
+const CMS_PAGE_PATH_PREFIX: &str = "/pages/";
+
+/// The path a page is served at: / for the site's root page, /pages/{slug} for any other.  This
+/// is the inverse of CmsParentTrait::cms_page_for_path, so links built from it always resolve.
+/// Pages can't be routed at custom paths, because the frontend's router only serves CMS pages
+/// at these two.
+pub fn cms_page_path(slug: &str, is_root_page: bool) -> String {
+  if is_root_page {
+    "/".to_string()
+  } else {
+    format!("{}{}", CMS_PAGE_PATH_PREFIX, slug)
+  }
+}
+
 #[derive(Clone, Debug)]
 pub enum CmsParent {
   Convention(Box<conventions::Model>),
   RootSite(Box<root_sites::Model>),
 }
 
-impl CmsParent {}
+impl CmsParent {
+  /// The parent_type and parent_id that content belonging to this parent gets.  Root site
+  /// content has neither.
+  pub fn parent_columns(&self) -> (Option<String>, Option<i64>) {
+    match self {
+      CmsParent::Convention(convention) => (Some("Convention".to_string()), Some(convention.id)),
+      CmsParent::RootSite(_) => (None, None),
+    }
+  }
+}
 
 impl From<conventions::Model> for CmsParent {
   fn from(convention: conventions::Model) -> Self {
@@ -28,6 +52,7 @@ impl From<root_sites::Model> for CmsParent {
 #[async_trait]
 pub trait CmsParentTrait {
   fn cms_content_groups(&self) -> Select<cms_content_groups::Entity>;
+  fn cms_content_revisions(&self) -> Select<cms_content_revisions::Entity>;
   fn cms_files(&self) -> Select<cms_files::Entity>;
   fn cms_graphql_queries(&self) -> Select<cms_graphql_queries::Entity>;
   fn cms_layouts(&self) -> Select<cms_layouts::Entity>;
@@ -40,8 +65,7 @@ pub trait CmsParentTrait {
   fn root_page(&self) -> Select<pages::Entity>;
 
   fn cms_page_for_path(&self, path: &str) -> Option<Select<pages::Entity>> {
-    if path.starts_with("/pages/") {
-      let (_, slug) = path.split_at(7);
+    if let Some(slug) = path.strip_prefix(CMS_PAGE_PATH_PREFIX) {
       Some(self.pages().filter(pages::Column::Slug.eq(slug)))
     } else if path == "/" {
       Some(self.root_page())
@@ -90,6 +114,7 @@ macro_rules! enum_assoc {
 
 impl CmsParentTrait for CmsParent {
   enum_assoc!(cms_content_groups);
+  enum_assoc!(cms_content_revisions);
   enum_assoc!(cms_files);
   enum_assoc!(cms_graphql_queries);
   enum_assoc!(cms_layouts);
@@ -125,6 +150,7 @@ macro_rules! convention_assoc {
 
 impl CmsParentTrait for conventions::Model {
   convention_assoc!(cms_content_groups);
+  convention_assoc!(cms_content_revisions);
   convention_assoc!(cms_files);
   convention_assoc!(cms_graphql_queries);
   convention_assoc!(cms_layouts);
@@ -154,6 +180,7 @@ macro_rules! root_site_assoc {
 
 impl CmsParentTrait for root_sites::Model {
   root_site_assoc!(cms_content_groups);
+  root_site_assoc!(cms_content_revisions);
   root_site_assoc!(cms_files);
   root_site_assoc!(cms_graphql_queries);
   root_site_assoc!(cms_layouts);
@@ -49,6 +49,7 @@ pub struct Model {
   pub open_graph_image: Option<String>,
   #[sea_orm(column_type = "Text", nullable)]
   pub favicon: Option<String>,
+  pub two_factor_required_permissions: Json,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -125,6 +126,8 @@ pub enum Relation {
   UserConProfiles,
   #[sea_orm(has_many = "super::user_activity_alerts::Entity")]
   UserActivityAlerts,
+  #[sea_orm(has_many = "super::bulk_email_campaigns::Entity")]
+  BulkEmailCampaigns,
 }
 
 impl Related<super::users::Entity> for Entity {
@@ -175,6 +178,12 @@ impl Related<super::departments::Entity> for Entity {
   }
 }
 
+impl Related<super::bulk_email_campaigns::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::BulkEmailCampaigns.def()
+  }
+}
+
 impl Related<super::coupons::Entity> for Entity {
   fn to() -> RelationDef {
     Relation::Coupons.def()
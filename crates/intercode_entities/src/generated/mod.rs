@@ -10,8 +10,10 @@ pub mod ahoy_visits;
 pub mod ar_internal_metadata;
 pub mod assumed_identity_request_logs;
 pub mod assumed_identity_sessions;
+pub mod bulk_email_campaigns;
 pub mod cms_content_group_associations;
 pub mod cms_content_groups;
+pub mod cms_content_revisions;
 pub mod cms_files;
 pub mod cms_files_layouts;
 pub mod cms_files_pages;
@@ -21,6 +23,7 @@ pub mod cms_layouts_partials;
 pub mod cms_navigation_items;
 pub mod cms_partials;
 pub mod cms_partials_pages;
+pub mod cms_rendered_outputs;
 pub mod cms_variables;
 pub mod conventions;
 pub mod coupon_applications;
@@ -36,8 +39,11 @@ pub mod form_items;
 pub mod form_response_changes;
 pub mod form_sections;
 pub mod forms;
+pub mod inbound_email_deliveries;
 pub mod maximum_event_provided_tickets_overrides;
 pub mod notification_destinations;
+pub mod notification_digest_items;
+pub mod notification_preferences;
 pub mod notification_templates;
 pub mod oauth_access_grants;
 pub mod oauth_access_tokens;
@@ -48,8 +54,12 @@ pub mod orders;
 pub mod organization_roles;
 pub mod organization_roles_users;
 pub mod organizations;
+pub mod otp_recovery_codes;
+pub mod outbox_emails;
 pub mod pages;
 pub mod permissions;
+pub mod personal_access_tokens;
+pub mod personal_data_requests;
 pub mod pg_search_documents;
 pub mod product_variants;
 pub mod products;
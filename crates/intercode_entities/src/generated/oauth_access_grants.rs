@@ -16,6 +16,8 @@ pub struct Model {
   pub created_at: DateTime,
   pub revoked_at: Option<DateTime>,
   pub scopes: Option<String>,
+  pub code_challenge: Option<String>,
+  pub code_challenge_method: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -8,8 +8,10 @@ pub use super::ahoy_visits::Entity as AhoyVisits;
 pub use super::ar_internal_metadata::Entity as ArInternalMetadata;
 pub use super::assumed_identity_request_logs::Entity as AssumedIdentityRequestLogs;
 pub use super::assumed_identity_sessions::Entity as AssumedIdentitySessions;
+pub use super::bulk_email_campaigns::Entity as BulkEmailCampaigns;
 pub use super::cms_content_group_associations::Entity as CmsContentGroupAssociations;
 pub use super::cms_content_groups::Entity as CmsContentGroups;
+pub use super::cms_content_revisions::Entity as CmsContentRevisions;
 pub use super::cms_files::Entity as CmsFiles;
 pub use super::cms_files_layouts::Entity as CmsFilesLayouts;
 pub use super::cms_files_pages::Entity as CmsFilesPages;
@@ -19,6 +21,7 @@ pub use super::cms_layouts_partials::Entity as CmsLayoutsPartials;
 pub use super::cms_navigation_items::Entity as CmsNavigationItems;
 pub use super::cms_partials::Entity as CmsPartials;
 pub use super::cms_partials_pages::Entity as CmsPartialsPages;
+pub use super::cms_rendered_outputs::Entity as CmsRenderedOutputs;
 pub use super::cms_variables::Entity as CmsVariables;
 pub use super::conventions::Entity as Conventions;
 pub use super::coupon_applications::Entity as CouponApplications;
@@ -34,8 +37,11 @@ pub use super::form_items::Entity as FormItems;
 pub use super::form_response_changes::Entity as FormResponseChanges;
 pub use super::form_sections::Entity as FormSections;
 pub use super::forms::Entity as Forms;
+pub use super::inbound_email_deliveries::Entity as InboundEmailDeliveries;
 pub use super::maximum_event_provided_tickets_overrides::Entity as MaximumEventProvidedTicketsOverrides;
 pub use super::notification_destinations::Entity as NotificationDestinations;
+pub use super::notification_digest_items::Entity as NotificationDigestItems;
+pub use super::notification_preferences::Entity as NotificationPreferences;
 pub use super::notification_templates::Entity as NotificationTemplates;
 pub use super::oauth_access_grants::Entity as OauthAccessGrants;
 pub use super::oauth_access_tokens::Entity as OauthAccessTokens;
@@ -46,8 +52,12 @@ pub use super::orders::Entity as Orders;
 pub use super::organization_roles::Entity as OrganizationRoles;
 pub use super::organization_roles_users::Entity as OrganizationRolesUsers;
 pub use super::organizations::Entity as Organizations;
+pub use super::otp_recovery_codes::Entity as OtpRecoveryCodes;
+pub use super::outbox_emails::Entity as OutboxEmails;
 pub use super::pages::Entity as Pages;
 pub use super::permissions::Entity as Permissions;
+pub use super::personal_access_tokens::Entity as PersonalAccessTokens;
+pub use super::personal_data_requests::Entity as PersonalDataRequests;
 pub use super::pg_search_documents::Entity as PgSearchDocuments;
 pub use super::product_variants::Entity as ProductVariants;
 pub use super::products::Entity as Products;
@@ -11,6 +11,8 @@ pub struct Model {
   pub site_name: Option<String>,
   pub root_page_id: Option<i64>,
   pub default_layout_id: Option<i64>,
+  pub two_factor_required_for_site_admins: bool,
+  pub two_factor_required_permissions: Json,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -12,6 +12,7 @@ pub struct Model {
   pub data: Option<String>,
   pub created_at: Option<DateTime>,
   pub updated_at: Option<DateTime>,
+  pub user_id: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
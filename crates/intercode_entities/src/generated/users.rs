@@ -28,6 +28,15 @@ pub struct Model {
   pub legacy_password_sha1: Option<String>,
   #[sea_orm(column_type = "Text", nullable)]
   pub legacy_password_sha1_salt: Option<String>,
+  pub confirmation_token: Option<String>,
+  pub confirmed_at: Option<DateTime>,
+  pub confirmation_sent_at: Option<DateTime>,
+  pub failed_attempts: i32,
+  pub locked_at: Option<DateTime>,
+  #[sea_orm(column_type = "Text", nullable)]
+  pub otp_secret_ciphertext: Option<String>,
+  pub otp_enabled_at: Option<DateTime>,
+  pub otp_consumed_timestep: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -48,6 +57,14 @@ pub enum Relation {
   UserActivityAlerts,
   #[sea_orm(has_many = "super::signup_requests::Entity")]
   SignupRequests,
+  #[sea_orm(has_many = "super::otp_recovery_codes::Entity")]
+  OtpRecoveryCodes,
+  #[sea_orm(has_many = "super::personal_access_tokens::Entity")]
+  PersonalAccessTokens,
+  #[sea_orm(has_many = "super::personal_data_requests::Entity")]
+  PersonalDataRequests,
+  #[sea_orm(has_many = "super::cms_content_revisions::Entity")]
+  CmsContentRevisions,
 }
 
 impl Related<super::cms_files::Entity> for Entity {
@@ -56,6 +73,12 @@ impl Related<super::cms_files::Entity> for Entity {
   }
 }
 
+impl Related<super::cms_content_revisions::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::CmsContentRevisions.def()
+  }
+}
+
 impl Related<super::conventions::Entity> for Entity {
   fn to() -> RelationDef {
     Relation::Conventions.def()
@@ -68,6 +91,24 @@ impl Related<super::signup_changes::Entity> for Entity {
   }
 }
 
+impl Related<super::otp_recovery_codes::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::OtpRecoveryCodes.def()
+  }
+}
+
+impl Related<super::personal_access_tokens::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::PersonalAccessTokens.def()
+  }
+}
+
+impl Related<super::personal_data_requests::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::PersonalDataRequests.def()
+  }
+}
+
 impl Related<super::runs::Entity> for Entity {
   fn to() -> RelationDef {
     Relation::Runs.def()
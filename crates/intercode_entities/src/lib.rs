@@ -1,6 +1,7 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.7.0
 #![allow(clippy::derive_partial_eq_without_eq)]
 
+mod cms_content_set_export;
 mod form_export;
 mod generated;
 pub mod links;
@@ -13,6 +14,7 @@ pub mod cms_content_model;
 pub mod cms_parent;
 pub mod model_ext;
 
+pub use cms_content_set_export::*;
 pub use form_export::*;
 pub use maximum_event_signups_value::MaximumEventSignupsValue;
 pub use model_ext::UserNames;
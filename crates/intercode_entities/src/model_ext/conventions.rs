@@ -43,6 +43,16 @@ impl conventions::Model {
     }
   }
 
+  /// Whether anonymous visitors can see the event list.  Single-event sites always show their
+  /// event.
+  pub fn event_list_is_public(&self) -> bool {
+    self.site_mode == "single_event" || self.show_event_list == "yes"
+  }
+
+  pub fn schedule_is_public(&self) -> bool {
+    self.show_schedule == "yes"
+  }
+
   pub fn tickets_available_for_purchase(&self) -> bool {
     if self.ended() {
       return false;
@@ -0,0 +1,18 @@
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Select};
+
+use crate::events;
+
+impl events::Entity {
+  /// Events in a convention created at or after a given time (or all of them, if there's no time
+  /// given).  This doesn't filter by status; callers showing events publicly should.
+  pub fn find_created_since(convention_id: i64, since: Option<NaiveDateTime>) -> Select<Self> {
+    let scope = Self::find().filter(events::Column::ConventionId.eq(convention_id));
+
+    if let Some(since) = since {
+      scope.filter(events::Column::CreatedAt.gte(since))
+    } else {
+      scope
+    }
+  }
+}
@@ -1,11 +1,14 @@
 pub mod conventions;
 mod coupons;
 pub mod event_proposals;
+pub mod events;
 pub mod form_item_permissions;
 pub mod form_responses;
+pub mod oauth;
 pub mod order_by_title;
 pub mod orders;
 pub mod permissions;
+pub mod personal_access_tokens;
 pub mod time_bounds;
 pub mod user_con_profiles;
 pub mod user_names;
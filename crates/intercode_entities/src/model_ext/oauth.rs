@@ -0,0 +1,89 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{oauth_access_grants, oauth_access_tokens, oauth_applications};
+
+/// Doorkeeper's default_scopes setting: what tokens get when nobody asks for anything specific
+pub const OAUTH_DEFAULT_SCOPES: &str = "public";
+
+/// Every scope third-party applications can ask for
+pub const OAUTH_SCOPES: &[&str] = &[
+  "public",
+  "openid",
+  "email",
+  "profile",
+  "read_profile",
+  "read_signups",
+  "read_events",
+  "read_conventions",
+  "read_organizations",
+  "manage_profile",
+  "manage_signups",
+  "manage_events",
+  "manage_conventions",
+  "manage_organizations",
+];
+
+/// The OpenID Connect scopes, which only affect what goes in id_tokens and userinfo responses
+pub const OPENID_CONNECT_SCOPES: &[&str] = &["openid", "email", "profile"];
+
+fn scopes_or_default(scopes: Option<&str>) -> &str {
+  match scopes.map(str::trim) {
+    Some(scopes) if !scopes.is_empty() => scopes,
+    _ => OAUTH_DEFAULT_SCOPES,
+  }
+}
+
+impl oauth_access_tokens::Model {
+  pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+    self
+      .expires_in
+      .map(|expires_in| self.created_at + Duration::seconds(expires_in.into()) <= now)
+      .unwrap_or(false)
+  }
+
+  /// Whether this token can still be used to make requests
+  pub fn is_accessible(&self, now: NaiveDateTime) -> bool {
+    self.revoked_at.is_none() && !self.is_expired(now)
+  }
+
+  /// The space-separated scopes this token was granted
+  pub fn scope_string(&self) -> &str {
+    scopes_or_default(self.scopes.as_deref())
+  }
+}
+
+impl oauth_access_grants::Model {
+  pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+    self.created_at + Duration::seconds(self.expires_in.into()) <= now
+  }
+
+  pub fn is_accessible(&self, now: NaiveDateTime) -> bool {
+    self.revoked_at.is_none() && !self.is_expired(now)
+  }
+
+  pub fn scope_string(&self) -> &str {
+    scopes_or_default(self.scopes.as_deref())
+  }
+}
+
+impl oauth_applications::Model {
+  /// Applications can register several redirect URIs, one per line
+  pub fn redirect_uris(&self) -> impl Iterator<Item = &str> {
+    self
+      .redirect_uri
+      .lines()
+      .map(str::trim)
+      .filter(|uri| !uri.is_empty())
+  }
+
+  /// The scopes this application may ask for.  Applications that haven't been restricted to
+  /// particular scopes can ask for any of them.
+  pub fn allowed_scopes(&self) -> Vec<&str> {
+    let scopes = self.scopes.split_whitespace().collect::<Vec<_>>();
+    if scopes.is_empty() {
+      OAUTH_SCOPES.to_vec()
+    } else {
+      scopes
+    }
+  }
+}
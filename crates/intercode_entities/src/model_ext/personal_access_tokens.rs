@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+
+use super::oauth::{OAUTH_SCOPES, OPENID_CONNECT_SCOPES};
+use crate::personal_access_tokens;
+
+/// Personal access tokens start with this, so that they can't be mistaken for OAuth access tokens
+/// and secret scanners can spot them
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "icpat_";
+
+/// The scopes a personal access token can be given: everything an OAuth application can ask for,
+/// except the OpenID Connect scopes, which only mean something to OAuth clients
+pub fn personal_access_token_scopes() -> impl Iterator<Item = &'static str> {
+  OAUTH_SCOPES
+    .iter()
+    .copied()
+    .filter(|scope| !OPENID_CONNECT_SCOPES.contains(scope))
+}
+
+impl personal_access_tokens::Model {
+  pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+    self
+      .expires_at
+      .map(|expires_at| expires_at <= now)
+      .unwrap_or(false)
+  }
+
+  /// Whether this token can still be used to make requests
+  pub fn is_accessible(&self, now: NaiveDateTime) -> bool {
+    self.revoked_at.is_none() && !self.is_expired(now)
+  }
+
+  pub fn scope_list(&self) -> Vec<&str> {
+    self.scopes.split_whitespace().collect()
+  }
+}
@@ -1,3 +1,5 @@
+use chrono::{Duration, NaiveDateTime};
+
 use super::UserNames;
 use crate::users;
 
@@ -9,6 +11,21 @@ impl users::Model {
       vec![]
     }
   }
+
+  /// Accounts that predate email confirmation never got a confirmation token, so only users who
+  /// were sent one and haven't used it yet count as unconfirmed
+  pub fn is_confirmed(&self) -> bool {
+    self.confirmation_token.is_none() || self.confirmed_at.is_some()
+  }
+
+  /// Whether the account is still locked out after too many failed sign-in attempts.  Locks
+  /// expire on their own after `unlock_in`.
+  pub fn is_access_locked(&self, now: NaiveDateTime, unlock_in: Duration) -> bool {
+    self
+      .locked_at
+      .map(|locked_at| locked_at + unlock_in > now)
+      .unwrap_or(false)
+  }
 }
 
 impl UserNames for users::Model {
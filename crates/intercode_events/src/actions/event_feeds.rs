@@ -0,0 +1,272 @@
+use axum::{debug_handler, extract::Query, response::IntoResponse};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use http::{header::CONTENT_TYPE, StatusCode};
+use intercode_entities::events;
+use intercode_graphql_core::query_data::QueryData;
+use intercode_liquid::url_builder::UrlBuilder;
+use intercode_server::QueryDataFromRequest;
+use sea_orm::{ColumnTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use tracing::log::error;
+
+const FEED_EVENT_LIMIT: u64 = 50;
+
+#[derive(Deserialize, Debug)]
+pub struct EventFeedParams {
+  /// Only include events created at or after this time, given as an RFC 3339 timestamp or a
+  /// YYYY-MM-DD date
+  since: Option<String>,
+}
+
+struct FeedInfo {
+  title: String,
+  feed_url: String,
+  site_url: String,
+}
+
+struct FeedEvent {
+  title: String,
+  url: String,
+  created_at: NaiveDateTime,
+  updated_at: NaiveDateTime,
+  summary: Option<String>,
+}
+
+fn parse_since(since: &str) -> Option<NaiveDateTime> {
+  DateTime::parse_from_rfc3339(since)
+    .map(|since| since.naive_utc())
+    .ok()
+    .or_else(|| {
+      NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+}
+
+fn rfc3339(datetime: NaiveDateTime) -> String {
+  DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc).to_rfc3339()
+}
+
+fn atom_feed(info: &FeedInfo, feed_events: &[FeedEvent]) -> String {
+  let updated = feed_events
+    .iter()
+    .map(|event| event.updated_at)
+    .max()
+    .unwrap_or_else(|| Utc::now().naive_utc());
+
+  let mut xml = format!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+     <title>{title}</title>\n  \
+     <id>{feed_url}</id>\n  \
+     <link rel=\"self\" href=\"{feed_url_attr}\"/>\n  \
+     <link href=\"{site_url_attr}\"/>\n  \
+     <updated>{updated}</updated>\n  \
+     <author><name>{title}</name></author>\n",
+    title = encode_text(&info.title),
+    feed_url = encode_text(&info.feed_url),
+    feed_url_attr = encode_double_quoted_attribute(&info.feed_url),
+    site_url_attr = encode_double_quoted_attribute(&info.site_url),
+    updated = rfc3339(updated),
+  );
+
+  for event in feed_events {
+    xml.push_str(&format!(
+      "  <entry>\n    \
+       <title>{}</title>\n    \
+       <id>{}</id>\n    \
+       <link href=\"{}\"/>\n    \
+       <published>{}</published>\n    \
+       <updated>{}</updated>\n",
+      encode_text(&event.title),
+      encode_text(&event.url),
+      encode_double_quoted_attribute(&event.url),
+      rfc3339(event.created_at),
+      rfc3339(event.updated_at),
+    ));
+    if let Some(summary) = &event.summary {
+      xml.push_str(&format!(
+        "    <summary>{}</summary>\n",
+        encode_text(summary)
+      ));
+    }
+    xml.push_str("  </entry>\n");
+  }
+
+  xml.push_str("</feed>\n");
+  xml
+}
+
+fn rss_feed(info: &FeedInfo, feed_events: &[FeedEvent]) -> String {
+  let mut xml = format!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <rss version=\"2.0\">\n  \
+     <channel>\n    \
+     <title>{title}</title>\n    \
+     <link>{site_url}</link>\n    \
+     <description>{title}</description>\n",
+    title = encode_text(&info.title),
+    site_url = encode_text(&info.site_url),
+  );
+
+  for event in feed_events {
+    xml.push_str(&format!(
+      "    <item>\n      \
+       <title>{}</title>\n      \
+       <link>{url}</link>\n      \
+       <guid isPermaLink=\"true\">{url}</guid>\n      \
+       <pubDate>{}</pubDate>\n",
+      encode_text(&event.title),
+      DateTime::<Utc>::from_naive_utc_and_offset(event.created_at, Utc).to_rfc2822(),
+      url = encode_text(&event.url),
+    ));
+    if let Some(summary) = &event.summary {
+      xml.push_str(&format!(
+        "      <description>{}</description>\n",
+        encode_text(summary)
+      ));
+    }
+    xml.push_str("    </item>\n");
+  }
+
+  xml.push_str("  </channel>\n</rss>\n");
+  xml
+}
+
+/// Loads the newest active events for a feed, using the same query as the events_created_since
+/// Liquid drop.  Returns None if the convention doesn't show its event list publicly.
+async fn load_feed(
+  query_data: &QueryData,
+  params: &EventFeedParams,
+  feed_path: &str,
+) -> Result<(FeedInfo, Vec<FeedEvent>), StatusCode> {
+  let convention = query_data.convention().ok_or(StatusCode::NOT_FOUND)?;
+  if !convention.event_list_is_public() {
+    return Err(StatusCode::NOT_FOUND);
+  }
+
+  let since = match params.since.as_deref() {
+    Some(since) => Some(parse_since(since).ok_or(StatusCode::BAD_REQUEST)?),
+    None => None,
+  };
+
+  let url_builder = UrlBuilder::new(query_data.cms_parent());
+  let absolute_url = |path: &str| {
+    url_builder
+      .absolute_url(path)
+      .unwrap_or_else(|_| path.to_string())
+  };
+
+  let feed_events = events::Entity::find_created_since(convention.id, since)
+    .filter(events::Column::Status.eq("active"))
+    .order_by_desc(events::Column::CreatedAt)
+    .limit(FEED_EVENT_LIMIT)
+    .all(query_data.db())
+    .await
+    .map_err(|err| {
+      error!("{}", err);
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .filter_map(|event| {
+      let created_at = event.created_at?;
+      Some(FeedEvent {
+        url: absolute_url(&format!("/events/{}", event.id)),
+        title: event.title,
+        created_at,
+        updated_at: event.updated_at.unwrap_or(created_at),
+        summary: event.short_blurb,
+      })
+    })
+    .collect();
+
+  let info = FeedInfo {
+    title: format!(
+      "New events at {}",
+      convention.name.as_deref().unwrap_or_default()
+    ),
+    feed_url: absolute_url(feed_path),
+    site_url: absolute_url("/events"),
+  };
+
+  Ok((info, feed_events))
+}
+
+/// An Atom feed of newly added events
+#[debug_handler]
+pub async fn events_atom_feed(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Query(params): Query<EventFeedParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+  let (info, feed_events) = load_feed(&query_data, &params, "/feeds/events.atom").await?;
+
+  Ok((
+    [(CONTENT_TYPE, "application/atom+xml")],
+    atom_feed(&info, &feed_events),
+  ))
+}
+
+/// An RSS 2.0 feed of newly added events
+#[debug_handler]
+pub async fn events_rss_feed(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Query(params): Query<EventFeedParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+  let (info, feed_events) = load_feed(&query_data, &params, "/feeds/events.rss").await?;
+
+  Ok((
+    [(CONTENT_TYPE, "application/rss+xml")],
+    rss_feed(&info, &feed_events),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn feed_fixture() -> (FeedInfo, Vec<FeedEvent>) {
+    let created_at = NaiveDateTime::from_timestamp_opt(1_700_000_000, 0).unwrap();
+    (
+      FeedInfo {
+        title: "New events at Con & Co".to_string(),
+        feed_url: "https://con.example.com/feeds/events.atom".to_string(),
+        site_url: "https://con.example.com/events".to_string(),
+      },
+      vec![FeedEvent {
+        title: "Murder <at> the Manor".to_string(),
+        url: "https://con.example.com/events/12".to_string(),
+        created_at,
+        updated_at: created_at,
+        summary: Some("A whodunit".to_string()),
+      }],
+    )
+  }
+
+  #[test]
+  fn parses_since_params() {
+    assert_eq!(
+      parse_since("2023-11-14"),
+      NaiveDate::from_ymd_opt(2023, 11, 14).and_then(|date| date.and_hms_opt(0, 0, 0))
+    );
+    assert_eq!(
+      parse_since("2023-11-14T22:13:20-05:00"),
+      NaiveDateTime::from_timestamp_opt(1_700_018_000, 0)
+    );
+    assert_eq!(parse_since("last tuesday"), None);
+  }
+
+  #[test]
+  fn builds_escaped_feeds() {
+    let (info, feed_events) = feed_fixture();
+
+    let atom = atom_feed(&info, &feed_events);
+    assert!(atom.contains("<title>New events at Con &amp; Co</title>"));
+    assert!(atom.contains("<title>Murder &lt;at&gt; the Manor</title>"));
+    assert!(atom.contains("<published>2023-11-14T22:13:20+00:00</published>"));
+
+    let rss = rss_feed(&info, &feed_events);
+    assert!(rss.contains("<guid isPermaLink=\"true\">https://con.example.com/events/12</guid>"));
+    assert!(rss.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+  }
+}
@@ -0,0 +1,3 @@
+mod event_feeds;
+
+pub use event_feeds::*;
@@ -1,3 +1,4 @@
+pub mod actions;
 pub mod objects;
 pub mod partial_objects;
 pub mod policies;
@@ -13,7 +13,7 @@ use intercode_graphql_loaders::{
   attached_images_by_filename::attached_images_by_filename,
   filtered_event_runs_loader::EventRunsLoaderFilter, LoaderManager,
 };
-use intercode_liquid::render_markdown;
+use intercode_liquid::{render_markdown, url_builder::UrlBuilder};
 use intercode_policies::{
   policies::{EventAction, EventPolicy},
   ModelBackedTypeGuardablePolicy,
@@ -141,9 +141,11 @@ impl EventEventsFields {
   #[graphql(name = "description_html")]
   async fn description_html(&self, ctx: &Context<'_>) -> Result<String, Error> {
     let loaders = ctx.data::<Arc<LoaderManager>>()?;
+    let query_data = ctx.data::<QueryData>()?;
     Ok(render_markdown(
       self.model.description.as_deref().unwrap_or_default(),
       &attached_images_by_filename(self.get_model(), loaders).await?,
+      &UrlBuilder::new(query_data.cms_parent()),
     ))
   }
 
@@ -227,9 +229,11 @@ impl EventEventsFields {
   #[graphql(name = "short_blurb_html")]
   async fn short_blurb_html(&self, ctx: &Context<'_>) -> Result<String, Error> {
     let loaders = ctx.data::<Arc<LoaderManager>>()?;
+    let query_data = ctx.data::<QueryData>()?;
     Ok(render_markdown(
       self.model.short_blurb.as_deref().unwrap_or_default(),
       &attached_images_by_filename(self.get_model(), loaders).await?,
+      &UrlBuilder::new(query_data.cms_parent()),
     ))
   }
 
@@ -11,12 +11,14 @@ use intercode_entities::{
   active_storage_blobs, form_items, forms,
   model_ext::{form_item_permissions::FormItemRole, FormResponse},
 };
-use intercode_graphql_core::{scalars::JsonScalar, schema_data::SchemaData, ModelBackedType};
+use intercode_graphql_core::{
+  query_data::QueryData, scalars::JsonScalar, schema_data::SchemaData, ModelBackedType,
+};
 use intercode_graphql_loaders::{
   attached_images_by_filename::attached_images_by_filename, LoaderManager,
 };
 use intercode_inflector::IntercodeInflector;
-use intercode_liquid::render_markdown;
+use intercode_liquid::{render_markdown, url_builder::UrlBuilder};
 use sea_orm::EntityTrait;
 use seawater::loaders::ExpectModels;
 use serde_json::Value;
@@ -59,6 +61,7 @@ pub fn form_response_as_json<'a, E: EntityTrait>(
   form_response: &dyn FormResponse<Entity = E>,
   form_items: impl Iterator<Item = &'a form_items::Model>,
   attached_images: &HashMap<String, active_storage_blobs::Model>,
+  url_builder: &UrlBuilder,
   viewer_role: FormItemRole,
   format: FormResponsePresentationFormat,
   language_loader: &FluentLanguageLoader,
@@ -77,6 +80,7 @@ pub fn form_response_as_json<'a, E: EntityTrait>(
                 form_item,
                 &value,
                 attached_images,
+                url_builder,
                 viewer_role,
                 format,
                 language_loader,
@@ -108,6 +112,7 @@ pub fn render_form_item_response(
   form_item: &form_items::Model,
   value: &Value,
   attached_images: &HashMap<String, active_storage_blobs::Model>,
+  url_builder: &UrlBuilder,
   viewer_role: FormItemRole,
   format: FormResponsePresentationFormat,
   language_loader: &FluentLanguageLoader,
@@ -126,7 +131,7 @@ pub fn render_form_item_response(
 
   if let Value::String(value) = value {
     if form_item_takes_markdown_input(form_item) {
-      return Value::String(render_markdown(value, attached_images));
+      return Value::String(render_markdown(value, attached_images, url_builder));
     }
 
     Value::String(value.to_string())
@@ -219,6 +224,7 @@ where
       model,
       form_items.iter(),
       &attached_images,
+      &UrlBuilder::new(ctx.data::<QueryData>()?.cms_parent()),
       viewer_role,
       FormResponsePresentationFormat::Plain,
       &schema_data.language_loader,
@@ -246,6 +252,7 @@ where
       model,
       form_items.iter(),
       &attached_images,
+      &UrlBuilder::new(ctx.data::<QueryData>()?.cms_parent()),
       viewer_role,
       FormResponsePresentationFormat::Html,
       &schema_data.language_loader,
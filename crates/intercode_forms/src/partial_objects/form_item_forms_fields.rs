@@ -2,9 +2,9 @@ use async_graphql::*;
 use intercode_entities::{form_items, model_ext::form_item_permissions::FormItemRole};
 use intercode_graphql_core::{
   enums::FormItemExposeIn, load_one_by_model_id, loader_result_to_required_single,
-  model_backed_type, scalars::JsonScalar,
+  model_backed_type, query_data::QueryData, scalars::JsonScalar,
 };
-use intercode_liquid::render_markdown;
+use intercode_liquid::{render_markdown, url_builder::UrlBuilder};
 use serde_json::json;
 
 use super::FormSectionFormsFields;
@@ -75,7 +75,8 @@ impl FormItemFormsFields {
   }
 
   #[graphql(name = "rendered_properties")]
-  async fn rendered_properties(&self) -> Result<JsonScalar, Error> {
+  async fn rendered_properties(&self, ctx: &Context<'_>) -> Result<JsonScalar, Error> {
+    let url_builder = UrlBuilder::new(ctx.data::<QueryData>()?.cms_parent());
     if let Some(properties) = &self.model.properties {
       if let Some(properties) = properties.as_object() {
         let is_static_text = if let Some(item_type) = &self.model.item_type {
@@ -90,7 +91,11 @@ impl FormItemFormsFields {
             .map(|(key, value)| {
               let value = if let Some(value) = value.as_str() {
                 if (is_static_text && key == "content") || key == "caption" {
-                  serde_json::Value::String(render_markdown(value, &Default::default()))
+                  serde_json::Value::String(render_markdown(
+                    value,
+                    &Default::default(),
+                    &url_builder,
+                  ))
                 } else {
                   serde_json::Value::String(value.to_string())
                 }
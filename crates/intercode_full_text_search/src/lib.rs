@@ -1,16 +1,4 @@
-pub mod objects;
-
-pub fn add(left: usize, right: usize) -> usize {
-  left + right
-}
-
-#[cfg(test)]
-mod tests {
-  use super::*;
-
-  #[test]
-  fn it_works() {
-    let result = add(2, 2);
-    assert_eq!(result, 4);
-  }
-}
+pub mod partial_objects;
+pub mod search_index;
+pub mod site_search;
+pub mod text_search;
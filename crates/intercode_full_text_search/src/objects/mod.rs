@@ -1,3 +0,0 @@
-mod search_result_type;
-
-pub use search_result_type::*;
@@ -0,0 +1,7 @@
+mod query_root_full_text_search_fields;
+mod search_result_entry_full_text_search_fields;
+mod search_result_full_text_search_fields;
+
+pub use query_root_full_text_search_fields::*;
+pub use search_result_entry_full_text_search_fields::*;
+pub use search_result_full_text_search_fields::*;
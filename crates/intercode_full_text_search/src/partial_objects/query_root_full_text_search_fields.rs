@@ -0,0 +1,40 @@
+use async_graphql::*;
+use intercode_graphql_core::query_data::QueryData;
+use intercode_policies::AuthorizationInfo;
+
+use crate::{
+  site_search::{site_search, SiteSearchResults},
+  text_search::TextSearchQuery,
+};
+
+const DEFAULT_SITE_SEARCH_LIMIT: u64 = 10;
+const MAX_SITE_SEARCH_LIMIT: u64 = 50;
+
+pub struct QueryRootFullTextSearchFields;
+
+impl QueryRootFullTextSearchFields {
+  pub async fn site_search(
+    ctx: &Context<'_>,
+    query: String,
+    limit: Option<u64>,
+  ) -> Result<SiteSearchResults> {
+    let Some(search) = TextSearchQuery::parse(&query) else {
+      return Ok(SiteSearchResults::default());
+    };
+    let query_data = ctx.data::<QueryData>()?;
+    let authorization_info = ctx.data::<AuthorizationInfo>()?;
+
+    Ok(
+      site_search(
+        query_data.db(),
+        authorization_info,
+        query_data.convention().map(|convention| convention.id),
+        &search,
+        limit
+          .unwrap_or(DEFAULT_SITE_SEARCH_LIMIT)
+          .min(MAX_SITE_SEARCH_LIMIT),
+      )
+      .await?,
+    )
+  }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+
+use crate::site_search::SiteSearchEntry;
+
+pub struct SearchResultEntryFullTextSearchFields {
+  entry: Arc<SiteSearchEntry>,
+}
+
+impl SearchResultEntryFullTextSearchFields {
+  pub fn new(entry: Arc<SiteSearchEntry>) -> Self {
+    Self { entry }
+  }
+}
+
+#[Object]
+impl SearchResultEntryFullTextSearchFields {
+  /// An HTML excerpt of the matching text, with the matches wrapped in <mark> tags
+  async fn highlight(&self) -> Option<&str> {
+    self.entry.highlight.as_deref()
+  }
+
+  async fn rank(&self) -> f64 {
+    self.entry.rank
+  }
+
+  async fn title(&self) -> Option<String> {
+    self.entry.model.title()
+  }
+}
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+
+use crate::site_search::SiteSearchResults;
+
+pub struct SearchResultFullTextSearchFields {
+  results: Arc<SiteSearchResults>,
+}
+
+impl SearchResultFullTextSearchFields {
+  pub fn new(results: Arc<SiteSearchResults>) -> Self {
+    Self { results }
+  }
+}
+
+#[Object]
+impl SearchResultFullTextSearchFields {
+  /// How many records matched in all, which may be more than the entries returned
+  #[graphql(name = "total_entries")]
+  async fn total_entries(&self) -> u64 {
+    self.results.total_entries
+  }
+}
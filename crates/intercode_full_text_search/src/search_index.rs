@@ -0,0 +1,571 @@
+//! Keeps pg_search_documents in sync with the records that show up in site search.  Each
+//! searchable record gets one document holding the text it can be found by; Postgres fills in
+//! content_vector from the content with a trigger.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use chrono::Utc;
+use intercode_entities::{
+  event_proposals, events, model_ext::event_proposals::EventProposalStatus, pages,
+  pg_search_documents, team_members, user_con_profiles, users, UserNames,
+};
+use sea_orm::{
+  ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait, QueryFilter,
+  QueryOrder, Select,
+};
+
+const REBUILD_BATCH_SIZE: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchableType {
+  Event,
+  EventProposal,
+  Page,
+  UserConProfile,
+}
+
+impl SearchableType {
+  pub const ALL: [SearchableType; 4] = [
+    SearchableType::Event,
+    SearchableType::EventProposal,
+    SearchableType::Page,
+    SearchableType::UserConProfile,
+  ];
+
+  /// The searchable_type column value, which is the model's class name on the Rails side
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SearchableType::Event => "Event",
+      SearchableType::EventProposal => "EventProposal",
+      SearchableType::Page => "Page",
+      SearchableType::UserConProfile => "UserConProfile",
+    }
+  }
+}
+
+impl FromStr for SearchableType {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    SearchableType::ALL
+      .into_iter()
+      .find(|searchable_type| searchable_type.as_str() == s)
+      .ok_or_else(|| format!("Unknown searchable type: {}", s))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchDocument {
+  pub convention_id: Option<i64>,
+  pub content: String,
+  pub hidden_from_search: bool,
+}
+
+impl SearchDocument {
+  fn new<'a>(
+    convention_id: Option<i64>,
+    parts: impl IntoIterator<Item = Option<&'a str>>,
+    hidden_from_search: bool,
+  ) -> Self {
+    SearchDocument {
+      convention_id,
+      content: parts
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" "),
+      hidden_from_search,
+    }
+  }
+}
+
+/// Reduces HTML (and any Liquid in it) to the words a reader would see
+pub fn strip_markup(html: &str) -> String {
+  let mut text = String::with_capacity(html.len());
+  let mut rest = html;
+
+  while let Some(start) = rest.find(['<', '{']) {
+    text.push_str(&rest[..start]);
+    let tail = &rest[start..];
+    let closer = if tail.starts_with('<') {
+      Some(">")
+    } else if tail.starts_with("{%") {
+      Some("%}")
+    } else if tail.starts_with("{{") {
+      Some("}}")
+    } else {
+      None
+    };
+
+    match closer.and_then(|closer| tail.find(closer).map(|end| end + closer.len())) {
+      Some(end) => {
+        text.push(' ');
+        rest = &tail[end..];
+      }
+      None => {
+        text.push_str(&tail[..1]);
+        rest = &tail[1..];
+      }
+    }
+  }
+  text.push_str(rest);
+
+  html_escape::decode_html_entities(&text)
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn strip_optional_markup(html: &Option<String>) -> Option<String> {
+  html.as_deref().map(strip_markup)
+}
+
+async fn event_documents<C: ConnectionTrait>(
+  db: &C,
+  events: &[events::Model],
+) -> Result<Vec<(i64, SearchDocument)>, DbErr> {
+  let team_members = team_members::Entity::find()
+    .filter(team_members::Column::EventId.is_in(events.iter().map(|event| event.id)))
+    .find_also_related(user_con_profiles::Entity)
+    .all(db)
+    .await?;
+  let mut team_member_names: HashMap<i64, Vec<String>> = HashMap::new();
+  for (team_member, profile) in team_members {
+    if let (Some(event_id), Some(profile)) = (team_member.event_id, profile) {
+      team_member_names
+        .entry(event_id)
+        .or_default()
+        .push(profile.name_without_nickname());
+    }
+  }
+
+  Ok(
+    events
+      .iter()
+      .map(|event| {
+        let names = team_member_names
+          .get(&event.id)
+          .map(|names| names.join(" "));
+        let description = strip_optional_markup(&event.description);
+        let short_blurb = strip_optional_markup(&event.short_blurb);
+
+        (
+          event.id,
+          SearchDocument::new(
+            Some(event.convention_id),
+            [
+              Some(event.title.as_str()),
+              event.author.as_deref(),
+              event.organization.as_deref(),
+              names.as_deref(),
+              short_blurb.as_deref(),
+              description.as_deref(),
+            ],
+            event.status != "active",
+          ),
+        )
+      })
+      .collect(),
+  )
+}
+
+async fn event_proposal_documents<C: ConnectionTrait>(
+  db: &C,
+  event_proposals: &[event_proposals::Model],
+) -> Result<Vec<(i64, SearchDocument)>, DbErr> {
+  let owner_names = user_con_profiles::Entity::find()
+    .filter(
+      user_con_profiles::Column::Id.is_in(
+        event_proposals
+          .iter()
+          .filter_map(|event_proposal| event_proposal.owner_id),
+      ),
+    )
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|profile| (profile.id, profile.name_without_nickname()))
+    .collect::<HashMap<_, _>>();
+
+  Ok(
+    event_proposals
+      .iter()
+      .map(|event_proposal| {
+        let description = strip_optional_markup(&event_proposal.description);
+        let short_blurb = strip_optional_markup(&event_proposal.short_blurb);
+
+        (
+          event_proposal.id,
+          SearchDocument::new(
+            event_proposal.convention_id,
+            [
+              event_proposal.title.as_deref(),
+              event_proposal
+                .owner_id
+                .and_then(|owner_id| owner_names.get(&owner_id))
+                .map(String::as_str),
+              short_blurb.as_deref(),
+              description.as_deref(),
+            ],
+            // drafts are only visible to their owners, who have other ways of finding them
+            !matches!(
+              event_proposal.status,
+              Some(ref status) if *status != EventProposalStatus::Draft
+            ),
+          ),
+        )
+      })
+      .collect(),
+  )
+}
+
+fn page_documents(pages: &[pages::Model]) -> Vec<(i64, SearchDocument)> {
+  pages
+    .iter()
+    .map(|page| {
+      let content = strip_optional_markup(&page.content);
+
+      (
+        page.id,
+        SearchDocument::new(
+          if page.parent_type.as_deref() == Some("Convention") {
+            page.parent_id
+          } else {
+            None
+          },
+          [page.name.as_deref(), content.as_deref()],
+          page.hidden_from_search,
+        ),
+      )
+    })
+    .collect()
+}
+
+async fn user_con_profile_documents<C: ConnectionTrait>(
+  db: &C,
+  user_con_profiles: &[user_con_profiles::Model],
+) -> Result<Vec<(i64, SearchDocument)>, DbErr> {
+  let emails = users::Entity::find()
+    .filter(users::Column::Id.is_in(user_con_profiles.iter().map(|profile| profile.user_id)))
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|user| (user.id, user.email))
+    .collect::<HashMap<_, _>>();
+
+  Ok(
+    user_con_profiles
+      .iter()
+      .map(|profile| {
+        (
+          profile.id,
+          SearchDocument::new(
+            Some(profile.convention_id),
+            [
+              Some(profile.name_without_nickname().as_str()),
+              profile.nickname.as_deref(),
+              emails.get(&profile.user_id).map(String::as_str),
+            ],
+            false,
+          ),
+        )
+      })
+      .collect(),
+  )
+}
+
+async fn replace_search_documents<C: ConnectionTrait>(
+  db: &C,
+  searchable_type: SearchableType,
+  documents: Vec<(i64, SearchDocument)>,
+) -> Result<(), DbErr> {
+  if documents.is_empty() {
+    return Ok(());
+  }
+
+  remove_from_search_index(
+    db,
+    searchable_type,
+    documents.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+  )
+  .await?;
+
+  let now = Utc::now().naive_utc();
+  pg_search_documents::Entity::insert_many(documents.into_iter().map(|(id, document)| {
+    pg_search_documents::ActiveModel {
+      content: ActiveValue::Set(Some(document.content)),
+      convention_id: ActiveValue::Set(document.convention_id),
+      searchable_type: ActiveValue::Set(Some(searchable_type.as_str().to_string())),
+      searchable_id: ActiveValue::Set(Some(id)),
+      hidden_from_search: ActiveValue::Set(document.hidden_from_search),
+      created_at: ActiveValue::Set(now),
+      updated_at: ActiveValue::Set(now),
+      ..Default::default()
+    }
+  }))
+  .exec_without_returning(db)
+  .await?;
+
+  Ok(())
+}
+
+/// Drops the search documents for some records, e.g. because they were deleted
+pub async fn remove_from_search_index<C: ConnectionTrait>(
+  db: &C,
+  searchable_type: SearchableType,
+  ids: impl IntoIterator<Item = i64>,
+) -> Result<(), DbErr> {
+  pg_search_documents::Entity::delete_many()
+    .filter(pg_search_documents::Column::SearchableType.eq(searchable_type.as_str()))
+    .filter(pg_search_documents::Column::SearchableId.is_in(ids))
+    .exec(db)
+    .await?;
+
+  Ok(())
+}
+
+pub async fn index_events<C: ConnectionTrait>(
+  db: &C,
+  events: &[events::Model],
+) -> Result<(), DbErr> {
+  let documents = event_documents(db, events).await?;
+  replace_search_documents(db, SearchableType::Event, documents).await
+}
+
+pub async fn index_event_proposals<C: ConnectionTrait>(
+  db: &C,
+  event_proposals: &[event_proposals::Model],
+) -> Result<(), DbErr> {
+  let documents = event_proposal_documents(db, event_proposals).await?;
+  replace_search_documents(db, SearchableType::EventProposal, documents).await
+}
+
+pub async fn index_pages<C: ConnectionTrait>(db: &C, pages: &[pages::Model]) -> Result<(), DbErr> {
+  replace_search_documents(db, SearchableType::Page, page_documents(pages)).await
+}
+
+pub async fn index_user_con_profiles<C: ConnectionTrait>(
+  db: &C,
+  user_con_profiles: &[user_con_profiles::Model],
+) -> Result<(), DbErr> {
+  let documents = user_con_profile_documents(db, user_con_profiles).await?;
+  replace_search_documents(db, SearchableType::UserConProfile, documents).await
+}
+
+/// Re-reads some records of a given type by id and refreshes their search documents, dropping
+/// the documents of any that no longer exist
+pub async fn reindex_records<C: ConnectionTrait>(
+  db: &C,
+  searchable_type: SearchableType,
+  ids: Vec<i64>,
+) -> Result<(), DbErr> {
+  if ids.is_empty() {
+    return Ok(());
+  }
+
+  remove_from_search_index(db, searchable_type, ids.clone()).await?;
+  match searchable_type {
+    SearchableType::Event => {
+      let events = events::Entity::find()
+        .filter(events::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+      index_events(db, &events).await
+    }
+    SearchableType::EventProposal => {
+      let event_proposals = event_proposals::Entity::find()
+        .filter(event_proposals::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+      index_event_proposals(db, &event_proposals).await
+    }
+    SearchableType::Page => {
+      let pages = pages::Entity::find()
+        .filter(pages::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+      index_pages(db, &pages).await
+    }
+    SearchableType::UserConProfile => {
+      let user_con_profiles = user_con_profiles::Entity::find()
+        .filter(user_con_profiles::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+      index_user_con_profiles(db, &user_con_profiles).await
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchIndexRebuildReport {
+  pub convention_id: Option<i64>,
+  pub documents_removed: u64,
+  pub documents_by_type: Vec<(SearchableType, u64)>,
+}
+
+impl Display for SearchIndexRebuildReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.convention_id {
+      Some(convention_id) => writeln!(f, "Rebuilt search index for convention {}", convention_id)?,
+      None => writeln!(
+        f,
+        "Rebuilt search index for all conventions and the root site"
+      )?,
+    }
+    writeln!(f, "  Removed {} old documents", self.documents_removed)?;
+    for (searchable_type, count) in &self.documents_by_type {
+      writeln!(
+        f,
+        "  Indexed {} {} records",
+        count,
+        searchable_type.as_str()
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+async fn rebuild_in_batches<C, E, F, Fut>(
+  db: &C,
+  scope: Select<E>,
+  order_column: E::Column,
+  index: F,
+) -> Result<u64, DbErr>
+where
+  C: ConnectionTrait,
+  E: EntityTrait,
+  E::Model: Sync,
+  F: Fn(Vec<E::Model>) -> Fut,
+  Fut: std::future::Future<Output = Result<(), DbErr>>,
+{
+  let mut batches = scope
+    .order_by_asc(order_column)
+    .paginate(db, REBUILD_BATCH_SIZE);
+  let mut count = 0;
+  while let Some(batch) = batches.fetch_and_next().await? {
+    count += batch.len() as u64;
+    index(batch).await?;
+  }
+
+  Ok(count)
+}
+
+/// Throws away the search documents for a convention (or everything, if convention_id is None)
+/// and indexes its records from scratch
+pub async fn rebuild_search_index<C: ConnectionTrait>(
+  db: &C,
+  convention_id: Option<i64>,
+) -> Result<SearchIndexRebuildReport, DbErr> {
+  let mut existing_documents = pg_search_documents::Entity::delete_many().filter(
+    pg_search_documents::Column::SearchableType.is_in(
+      SearchableType::ALL
+        .iter()
+        .map(|searchable_type| searchable_type.as_str()),
+    ),
+  );
+  if let Some(convention_id) = convention_id {
+    existing_documents =
+      existing_documents.filter(pg_search_documents::Column::ConventionId.eq(convention_id));
+  }
+  let documents_removed = existing_documents.exec(db).await?.rows_affected;
+
+  let mut event_scope = events::Entity::find();
+  let mut event_proposal_scope = event_proposals::Entity::find();
+  let mut page_scope = pages::Entity::find();
+  let mut user_con_profile_scope = user_con_profiles::Entity::find();
+  if let Some(convention_id) = convention_id {
+    event_scope = event_scope.filter(events::Column::ConventionId.eq(convention_id));
+    event_proposal_scope =
+      event_proposal_scope.filter(event_proposals::Column::ConventionId.eq(convention_id));
+    page_scope = page_scope
+      .filter(pages::Column::ParentType.eq("Convention"))
+      .filter(pages::Column::ParentId.eq(convention_id));
+    user_con_profile_scope =
+      user_con_profile_scope.filter(user_con_profiles::Column::ConventionId.eq(convention_id));
+  } else {
+    // pages without a parent don't show up anywhere, so there's no point in indexing them
+    page_scope = page_scope.filter(pages::Column::ParentType.is_not_null());
+  }
+
+  let documents_by_type = vec![
+    (
+      SearchableType::Event,
+      rebuild_in_batches(db, event_scope, events::Column::Id, |batch| async move {
+        index_events(db, &batch).await
+      })
+      .await?,
+    ),
+    (
+      SearchableType::EventProposal,
+      rebuild_in_batches(
+        db,
+        event_proposal_scope,
+        event_proposals::Column::Id,
+        |batch| async move { index_event_proposals(db, &batch).await },
+      )
+      .await?,
+    ),
+    (
+      SearchableType::Page,
+      rebuild_in_batches(db, page_scope, pages::Column::Id, |batch| async move {
+        index_pages(db, &batch).await
+      })
+      .await?,
+    ),
+    (
+      SearchableType::UserConProfile,
+      rebuild_in_batches(
+        db,
+        user_con_profile_scope,
+        user_con_profiles::Column::Id,
+        |batch| async move { index_user_con_profiles(db, &batch).await },
+      )
+      .await?,
+    ),
+  ];
+
+  Ok(SearchIndexRebuildReport {
+    convention_id,
+    documents_removed,
+    documents_by_type,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strips_html_and_liquid() {
+    assert_eq!(
+      strip_markup(
+        "<p>Welcome to {{ convention.name }}!</p>\n{% if user %}<b>Fish &amp; chips</b>{% endif %} 3 < 4"
+      ),
+      "Welcome to ! Fish & chips 3 < 4"
+    );
+  }
+
+  #[test]
+  fn joins_the_nonblank_parts_of_a_document() {
+    let document = SearchDocument::new(
+      Some(1),
+      [Some("Title"), None, Some("  "), Some("Blurb ")],
+      false,
+    );
+
+    assert_eq!(document.content, "Title Blurb");
+  }
+
+  #[test]
+  fn round_trips_searchable_types() {
+    for searchable_type in SearchableType::ALL {
+      assert_eq!(
+        searchable_type.as_str().parse::<SearchableType>(),
+        Ok(searchable_type)
+      );
+    }
+  }
+}
@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+
+use intercode_entities::{event_proposals, events, pages, pg_search_documents, user_con_profiles};
+use intercode_policies::{
+  policies::{
+    EventAction, EventPolicy, EventProposalAction, EventProposalPolicy, UserConProfileAction,
+    UserConProfilePolicy,
+  },
+  AuthorizationInfo, EntityPolicy,
+};
+use sea_orm::{
+  sea_query::{Cond, Expr, SimpleExpr},
+  ColumnTrait, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+  QuerySelect, QueryTrait,
+};
+
+use crate::{search_index::SearchableType, text_search::TextSearchQuery};
+
+const SEARCH_CONFIG: &str = "public.english_unaccent";
+const HIGHLIGHT_LENGTH: usize = 200;
+
+#[derive(Debug, Clone)]
+pub enum SearchableModel {
+  Event(events::Model),
+  EventProposal(event_proposals::Model),
+  Page(pages::Model),
+  UserConProfile(user_con_profiles::Model),
+}
+
+impl SearchableModel {
+  pub fn title(&self) -> Option<String> {
+    match self {
+      SearchableModel::Event(event) => Some(event.title.clone()),
+      SearchableModel::EventProposal(event_proposal) => event_proposal.title.clone(),
+      SearchableModel::Page(page) => page.name.clone(),
+      SearchableModel::UserConProfile(user_con_profile) => Some(user_con_profile.name()),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SiteSearchEntry {
+  pub model: SearchableModel,
+  pub rank: f64,
+  pub highlight: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SiteSearchResults {
+  pub total_entries: u64,
+  pub entries: Vec<SiteSearchEntry>,
+}
+
+fn searchable_ids_in(searchable_type: SearchableType, ids: SimpleExpr) -> SimpleExpr {
+  pg_search_documents::Column::SearchableType
+    .eq(searchable_type.as_str())
+    .and(ids)
+}
+
+/// The documents a principal is allowed to find.  Records are only searchable where the policy
+/// for their model would let the principal read them, and attendee profiles are further limited
+/// to people who can read the convention's whole attendee list.
+async fn visible_documents_condition(
+  principal: &AuthorizationInfo,
+  convention_id: Option<i64>,
+) -> Result<Cond, DbErr> {
+  let mut condition =
+    Cond::any().add(pg_search_documents::Column::SearchableType.eq(SearchableType::Page.as_str()));
+
+  let Some(convention_id) = convention_id else {
+    return Ok(condition);
+  };
+
+  condition = condition
+    .add(searchable_ids_in(
+      SearchableType::Event,
+      pg_search_documents::Column::SearchableId.in_subquery(
+        EventPolicy::accessible_to(principal, &EventAction::Read)
+          .select_only()
+          .column(events::Column::Id)
+          .into_query(),
+      ),
+    ))
+    .add(searchable_ids_in(
+      SearchableType::EventProposal,
+      pg_search_documents::Column::SearchableId.in_subquery(
+        EventProposalPolicy::accessible_to(principal, &EventProposalAction::Read)
+          .select_only()
+          .column(event_proposals::Column::Id)
+          .into_query(),
+      ),
+    ));
+
+  let can_search_profiles = principal.site_admin_read()
+    || principal
+      .has_scope_and_convention_permission(
+        "read_conventions",
+        "read_user_con_profiles",
+        convention_id,
+      )
+      .await?;
+  if can_search_profiles {
+    condition = condition.add(searchable_ids_in(
+      SearchableType::UserConProfile,
+      pg_search_documents::Column::SearchableId.in_subquery(
+        UserConProfilePolicy::accessible_to(principal, &UserConProfileAction::Read)
+          .select_only()
+          .column(user_con_profiles::Column::Id)
+          .into_query(),
+      ),
+    ));
+  }
+
+  Ok(condition)
+}
+
+async fn load_models<C: ConnectionTrait>(
+  db: &C,
+  ids_by_type: HashMap<SearchableType, Vec<i64>>,
+) -> Result<HashMap<(SearchableType, i64), SearchableModel>, DbErr> {
+  let mut models = HashMap::new();
+
+  for (searchable_type, ids) in ids_by_type {
+    match searchable_type {
+      SearchableType::Event => {
+        for event in events::Entity::find()
+          .filter(events::Column::Id.is_in(ids))
+          .all(db)
+          .await?
+        {
+          models.insert((searchable_type, event.id), SearchableModel::Event(event));
+        }
+      }
+      SearchableType::EventProposal => {
+        for event_proposal in event_proposals::Entity::find()
+          .filter(event_proposals::Column::Id.is_in(ids))
+          .all(db)
+          .await?
+        {
+          models.insert(
+            (searchable_type, event_proposal.id),
+            SearchableModel::EventProposal(event_proposal),
+          );
+        }
+      }
+      SearchableType::Page => {
+        for page in pages::Entity::find()
+          .filter(pages::Column::Id.is_in(ids))
+          .all(db)
+          .await?
+        {
+          models.insert((searchable_type, page.id), SearchableModel::Page(page));
+        }
+      }
+      SearchableType::UserConProfile => {
+        for user_con_profile in user_con_profiles::Entity::find()
+          .filter(user_con_profiles::Column::Id.is_in(ids))
+          .all(db)
+          .await?
+        {
+          models.insert(
+            (searchable_type, user_con_profile.id),
+            SearchableModel::UserConProfile(user_con_profile),
+          );
+        }
+      }
+    }
+  }
+
+  Ok(models)
+}
+
+/// Searches the indexed events, event proposals, pages and attendee profiles of a convention (or
+/// the root site's pages, if convention_id is None) for the records a principal can see, best
+/// matches first
+pub async fn site_search<C: ConnectionTrait>(
+  db: &C,
+  principal: &AuthorizationInfo,
+  convention_id: Option<i64>,
+  search: &TextSearchQuery,
+  limit: u64,
+) -> Result<SiteSearchResults, DbErr> {
+  let tsquery_sql = format!("websearch_to_tsquery('{}', ?)", SEARCH_CONFIG);
+  let scope = pg_search_documents::Entity::find()
+    .filter(pg_search_documents::Column::HiddenFromSearch.eq(false))
+    .filter(match convention_id {
+      Some(convention_id) => pg_search_documents::Column::ConventionId.eq(convention_id),
+      None => pg_search_documents::Column::ConventionId.is_null(),
+    })
+    .filter(visible_documents_condition(principal, convention_id).await?)
+    .filter(Expr::cust_with_values(
+      format!("pg_search_documents.content_vector @@ {}", tsquery_sql),
+      [search.query()],
+    ));
+
+  let total_entries = scope.clone().count(db).await?;
+  if total_entries == 0 {
+    return Ok(SiteSearchResults::default());
+  }
+
+  let ranked_ids: Vec<(i64, f64)> = scope
+    .select_only()
+    .column(pg_search_documents::Column::Id)
+    .column_as(
+      Expr::cust_with_values(
+        format!(
+          "ts_rank(pg_search_documents.content_vector, {})::float8",
+          tsquery_sql
+        ),
+        [search.query()],
+      ),
+      "search_rank",
+    )
+    .order_by_desc(Expr::cust("search_rank"))
+    .order_by_asc(pg_search_documents::Column::Id)
+    .limit(limit)
+    .into_tuple()
+    .all(db)
+    .await?;
+
+  let mut documents_by_id = pg_search_documents::Entity::find()
+    .filter(pg_search_documents::Column::Id.is_in(ranked_ids.iter().map(|(id, _)| *id)))
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|document| (document.id, document))
+    .collect::<HashMap<_, _>>();
+  let documents = ranked_ids
+    .into_iter()
+    .filter_map(|(id, rank)| documents_by_id.remove(&id).map(|document| (document, rank)))
+    .collect::<Vec<_>>();
+
+  let mut ids_by_type: HashMap<SearchableType, Vec<i64>> = HashMap::new();
+  for (document, _) in &documents {
+    if let (Some(searchable_type), Some(searchable_id)) = (
+      document
+        .searchable_type
+        .as_deref()
+        .and_then(|searchable_type| searchable_type.parse::<SearchableType>().ok()),
+      document.searchable_id,
+    ) {
+      ids_by_type
+        .entry(searchable_type)
+        .or_default()
+        .push(searchable_id);
+    }
+  }
+  let mut models = load_models(db, ids_by_type).await?;
+
+  let entries = documents
+    .into_iter()
+    .filter_map(|(document, rank)| {
+      let searchable_type = document.searchable_type.as_deref()?.parse().ok()?;
+      let model = models.remove(&(searchable_type, document.searchable_id?))?;
+
+      Some(SiteSearchEntry {
+        model,
+        rank,
+        highlight: document
+          .content
+          .as_deref()
+          .and_then(|content| search.highlight(content, HIGHLIGHT_LENGTH)),
+      })
+    })
+    .collect();
+
+  Ok(SiteSearchResults {
+    total_entries,
+    entries,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Utc;
+  use intercode_entities::{
+    conventions, event_categories, forms, model_ext::event_proposals::EventProposalStatus, users,
+  };
+  use intercode_policies::test_helpers::with_test_db;
+  use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+  use super::*;
+
+  struct Fixture {
+    convention: conventions::Model,
+    event_category: event_categories::Model,
+  }
+
+  async fn insert_fixture<C: ConnectionTrait>(db: &C) -> Fixture {
+    let now = Utc::now().naive_utc();
+    let convention = conventions::ActiveModel {
+      domain: Set("search.intercode.test".to_string()),
+      email_from: Set("noreply@intercode.test".to_string()),
+      language: Set("en".to_string()),
+      timezone_mode: Set("user_local".to_string()),
+      ticket_mode: Set("disabled".to_string()),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let form = forms::ActiveModel {
+      convention_id: Set(Some(convention.id)),
+      form_type: Set("event".to_string()),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let event_category = event_categories::ActiveModel {
+      convention_id: Set(convention.id),
+      name: Set("Larp".to_string()),
+      team_member_name: Set("GM".to_string()),
+      scheduling_ui: Set("regular".to_string()),
+      default_color: Set("#ffffff".to_string()),
+      full_color: Set("#cccccc".to_string()),
+      signed_up_color: Set("#000000".to_string()),
+      event_form_id: Set(form.id),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    Fixture {
+      convention,
+      event_category,
+    }
+  }
+
+  async fn insert_user<C: ConnectionTrait>(db: &C, email: &str, site_admin: bool) -> users::Model {
+    users::ActiveModel {
+      first_name: Set("Test".to_string()),
+      last_name: Set("User".to_string()),
+      email: Set(email.to_string()),
+      site_admin: Set(Some(site_admin)),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+  }
+
+  async fn insert_profile<C: ConnectionTrait>(
+    db: &C,
+    fixture: &Fixture,
+    user: &users::Model,
+  ) -> user_con_profiles::Model {
+    user_con_profiles::ActiveModel {
+      user_id: Set(user.id),
+      convention_id: Set(fixture.convention.id),
+      first_name: Set(user.first_name.clone()),
+      last_name: Set(user.last_name.clone()),
+      ical_secret: Set(format!("secret-{}", user.id)),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+  }
+
+  async fn insert_event<C: ConnectionTrait>(db: &C, fixture: &Fixture, status: &str) -> i64 {
+    events::ActiveModel {
+      convention_id: Set(fixture.convention.id),
+      event_category_id: Set(fixture.event_category.id),
+      title: Set(format!("A {} event", status)),
+      length_seconds: Set(3600),
+      status: Set(status.to_string()),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+    .id
+  }
+
+  async fn insert_event_proposal<C: ConnectionTrait>(
+    db: &C,
+    fixture: &Fixture,
+    owner: &user_con_profiles::Model,
+    status: EventProposalStatus,
+  ) -> i64 {
+    let now = Utc::now().naive_utc();
+    event_proposals::ActiveModel {
+      convention_id: Set(Some(fixture.convention.id)),
+      event_category_id: Set(fixture.event_category.id),
+      owner_id: Set(Some(owner.id)),
+      status: Set(Some(status)),
+      title: Set(Some("A proposal".to_string())),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+    .id
+  }
+
+  async fn index<C: ConnectionTrait>(
+    db: &C,
+    fixture: &Fixture,
+    searchable_type: SearchableType,
+    searchable_id: i64,
+  ) {
+    let now = Utc::now().naive_utc();
+    pg_search_documents::ActiveModel {
+      convention_id: Set(Some(fixture.convention.id)),
+      searchable_type: Set(Some(searchable_type.as_str().to_string())),
+      searchable_id: Set(Some(searchable_id)),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+  }
+
+  async fn visible_documents<C: ConnectionTrait>(
+    db: &C,
+    principal: &AuthorizationInfo,
+    fixture: &Fixture,
+  ) -> Vec<(String, i64)> {
+    let mut documents = pg_search_documents::Entity::find()
+      .filter(pg_search_documents::Column::ConventionId.eq(fixture.convention.id))
+      .filter(
+        visible_documents_condition(principal, Some(fixture.convention.id))
+          .await
+          .unwrap(),
+      )
+      .all(db)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|document| {
+        (
+          document.searchable_type.unwrap_or_default(),
+          document.searchable_id.unwrap_or_default(),
+        )
+      })
+      .collect::<Vec<_>>();
+    documents.sort();
+    documents
+  }
+
+  #[tokio::test]
+  async fn test_hides_events_the_principal_cant_read() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let fixture = insert_fixture(&db).await;
+        let active_event_id = insert_event(&db, &fixture, "active").await;
+        let dropped_event_id = insert_event(&db, &fixture, "dropped").await;
+        index(&db, &fixture, SearchableType::Event, active_event_id).await;
+        index(&db, &fixture, SearchableType::Event, dropped_event_id).await;
+        let admin = insert_user(&db, "admin@intercode.test", true).await;
+
+        let anonymous = AuthorizationInfo::for_test(db.clone(), None, None, None).await;
+        assert_eq!(visible_documents(&db, &anonymous, &fixture).await, vec![]);
+
+        let site_admin = AuthorizationInfo::for_test(db.clone(), Some(admin), None, None).await;
+        assert_eq!(
+          visible_documents(&db, &site_admin, &fixture).await,
+          vec![
+            ("Event".to_string(), active_event_id),
+            ("Event".to_string(), dropped_event_id),
+          ]
+        );
+      })
+    })
+    .await
+  }
+
+  #[tokio::test]
+  async fn test_only_shows_draft_proposals_to_their_owners() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let fixture = insert_fixture(&db).await;
+        let owner = insert_user(&db, "owner@intercode.test", false).await;
+        let other = insert_user(&db, "other@intercode.test", false).await;
+        let admin = insert_user(&db, "admin@intercode.test", true).await;
+        let owner_profile = insert_profile(&db, &fixture, &owner).await;
+        let draft_id =
+          insert_event_proposal(&db, &fixture, &owner_profile, EventProposalStatus::Draft).await;
+        index(&db, &fixture, SearchableType::EventProposal, draft_id).await;
+
+        let as_owner = AuthorizationInfo::for_test(db.clone(), Some(owner), None, None).await;
+        assert_eq!(
+          visible_documents(&db, &as_owner, &fixture).await,
+          vec![("EventProposal".to_string(), draft_id)]
+        );
+
+        let as_other = AuthorizationInfo::for_test(db.clone(), Some(other), None, None).await;
+        assert_eq!(visible_documents(&db, &as_other, &fixture).await, vec![]);
+
+        let as_admin = AuthorizationInfo::for_test(db.clone(), Some(admin), None, None).await;
+        assert_eq!(visible_documents(&db, &as_admin, &fixture).await, vec![]);
+      })
+    })
+    .await
+  }
+
+  #[tokio::test]
+  async fn test_hides_other_attendees_profiles() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let fixture = insert_fixture(&db).await;
+        let attendee = insert_user(&db, "attendee@intercode.test", false).await;
+        let other = insert_user(&db, "other@intercode.test", false).await;
+        let admin = insert_user(&db, "admin@intercode.test", true).await;
+        let attendee_profile = insert_profile(&db, &fixture, &attendee).await;
+        let other_profile = insert_profile(&db, &fixture, &other).await;
+        index(
+          &db,
+          &fixture,
+          SearchableType::UserConProfile,
+          attendee_profile.id,
+        )
+        .await;
+        index(
+          &db,
+          &fixture,
+          SearchableType::UserConProfile,
+          other_profile.id,
+        )
+        .await;
+
+        let as_attendee = AuthorizationInfo::for_test(db.clone(), Some(attendee), None, None).await;
+        assert_eq!(visible_documents(&db, &as_attendee, &fixture).await, vec![]);
+
+        let as_admin = AuthorizationInfo::for_test(db.clone(), Some(admin), None, None).await;
+        assert_eq!(
+          visible_documents(&db, &as_admin, &fixture).await,
+          vec![
+            ("UserConProfile".to_string(), attendee_profile.id),
+            ("UserConProfile".to_string(), other_profile.id),
+          ]
+        );
+      })
+    })
+    .await
+  }
+}
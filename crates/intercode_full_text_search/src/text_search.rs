@@ -0,0 +1,339 @@
+//! Searching text columns with a mix of Postgres full text search and plain substring matching.
+//! Full text search finds prose by its words (including other forms of the same word), and
+//! substring matching finds the things full text search mangles, like bits of markup or Liquid.
+
+use std::ops::Range;
+
+use sea_orm::{
+  sea_query::{Expr, SimpleExpr},
+  ColumnTrait, ConnectionTrait, DbErr, EntityName, EntityTrait, QueryFilter, QueryOrder,
+  QuerySelect, Select,
+};
+
+const TEXT_SEARCH_CONFIG: &str = "english";
+
+/// Escapes the wildcard characters in a string so it can be used in a LIKE pattern
+pub fn escape_like(text: &str) -> String {
+  text
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// A case-insensitive substring match against a column
+pub fn contains_ignoring_case(column_sql: &str, text: &str) -> SimpleExpr {
+  Expr::cust_with_values(
+    format!("{} ILIKE ?", column_sql),
+    [format!("%{}%", escape_like(text))],
+  )
+}
+
+/// A case-insensitive prefix match against a column
+pub fn starts_with_ignoring_case(column_sql: &str, text: &str) -> SimpleExpr {
+  Expr::cust_with_values(
+    format!("{} ILIKE ?", column_sql),
+    [format!("{}%", escape_like(text))],
+  )
+}
+
+fn document_sql(columns: &[&str]) -> String {
+  columns
+    .iter()
+    .map(|column| format!("coalesce({}, '')", column))
+    .collect::<Vec<_>>()
+    .join(" || ' ' || ")
+}
+
+fn fold_case(c: char) -> char {
+  c.to_lowercase().next().unwrap_or(c)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSearchQuery {
+  query: String,
+  phrase: String,
+  terms: Vec<String>,
+}
+
+impl TextSearchQuery {
+  /// Parses a query in the same syntax as Postgres's websearch_to_tsquery.  Returns None for
+  /// queries with nothing to search for.
+  pub fn parse(query: &str) -> Option<Self> {
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    let phrase = query.replace('"', "");
+    let mut terms: Vec<String> = vec![];
+    for word in query.split(' ') {
+      // negated words and the OR operator aren't things to highlight
+      if word.starts_with('-') || word.eq_ignore_ascii_case("or") {
+        continue;
+      }
+      let term = word.trim_matches('"').to_lowercase();
+      if !term.is_empty() && !terms.contains(&term) {
+        terms.push(term);
+      }
+    }
+
+    if terms.is_empty() {
+      return None;
+    }
+
+    Some(TextSearchQuery {
+      query,
+      phrase,
+      terms,
+    })
+  }
+
+  pub fn query(&self) -> &str {
+    &self.query
+  }
+
+  /// A condition matching rows whose columns, taken together, match the query's words, or where
+  /// any one of the columns contains the query verbatim
+  pub fn matches(&self, columns: &[&str]) -> SimpleExpr {
+    let substring_conditions = columns
+      .iter()
+      .map(|column| format!(" OR {} ILIKE ?", column))
+      .collect::<String>();
+    let pattern = format!("%{}%", escape_like(&self.phrase));
+
+    Expr::cust_with_values(
+      format!(
+        "(to_tsvector('{config}', {document}) @@ websearch_to_tsquery('{config}', ?){substrings})",
+        config = TEXT_SEARCH_CONFIG,
+        document = document_sql(columns),
+        substrings = substring_conditions
+      ),
+      std::iter::once(self.query.clone()).chain(columns.iter().map(|_| pattern.clone())),
+    )
+  }
+
+  /// How well a row matches, as a double.  Verbatim matches count for the most, and more so in
+  /// earlier columns, so list the columns in order of importance (e.g. name before content).
+  pub fn rank(&self, columns: &[&str]) -> SimpleExpr {
+    let substring_bonuses = columns
+      .iter()
+      .enumerate()
+      .map(|(index, column)| {
+        format!(
+          " + CASE WHEN {} ILIKE ? THEN {} ELSE 0 END",
+          column,
+          columns.len() - index
+        )
+      })
+      .collect::<String>();
+    let pattern = format!("%{}%", escape_like(&self.phrase));
+
+    Expr::cust_with_values(
+      format!(
+        "(ts_rank(to_tsvector('{config}', {document}), websearch_to_tsquery('{config}', ?)){substrings})::float8",
+        config = TEXT_SEARCH_CONFIG,
+        document = document_sql(columns),
+        substrings = substring_bonuses
+      ),
+      std::iter::once(self.query.clone()).chain(columns.iter().map(|_| pattern.clone())),
+    )
+  }
+
+  /// Where the query's phrase and words appear in some text, as non-overlapping character ranges
+  /// in order.  The whole phrase takes precedence over its words, and longer words over shorter
+  /// ones.
+  fn match_ranges(&self, chars: &[char]) -> Vec<Range<usize>> {
+    let folded = chars.iter().map(|c| fold_case(*c)).collect::<Vec<_>>();
+    let mut needles = self.terms.clone();
+    needles.sort_by_key(|term| std::cmp::Reverse(term.chars().count()));
+    needles.insert(0, self.phrase.to_lowercase());
+
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for needle in needles {
+      let needle = needle.chars().map(fold_case).collect::<Vec<_>>();
+      if needle.is_empty() || needle.len() > folded.len() {
+        continue;
+      }
+
+      let mut start = 0;
+      while start + needle.len() <= folded.len() {
+        let range = start..(start + needle.len());
+        if folded[range.clone()] == needle[..]
+          && !ranges
+            .iter()
+            .any(|existing| existing.start < range.end && range.start < existing.end)
+        {
+          ranges.push(range.clone());
+          start = range.end;
+        } else {
+          start += 1;
+        }
+      }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    ranges
+  }
+
+  /// An HTML excerpt of some text of up to about max_length characters, centered on the first
+  /// place the query appears, with the matches wrapped in <mark> tags.  Falls back to the start
+  /// of the text if the query only matched other forms of its words.
+  pub fn highlight(&self, text: &str, max_length: usize) -> Option<String> {
+    let chars = text
+      .split_whitespace()
+      .collect::<Vec<_>>()
+      .join(" ")
+      .chars()
+      .collect::<Vec<_>>();
+    if chars.is_empty() {
+      return None;
+    }
+
+    let ranges = self.match_ranges(&chars);
+    let start = match ranges.first() {
+      Some(first) => {
+        let context = max_length.saturating_sub(first.len()) / 2;
+        first
+          .start
+          .saturating_sub(context)
+          .min(chars.len().saturating_sub(max_length))
+      }
+      None => 0,
+    };
+    let end = (start + max_length).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+      snippet.push('…');
+    }
+    let mut position = start;
+    for range in ranges
+      .iter()
+      .filter(|range| range.start < end && range.end > start)
+    {
+      let mark_start = range.start.max(start);
+      let mark_end = range.end.min(end);
+      let before = chars[position..mark_start].iter().collect::<String>();
+      let marked = chars[mark_start..mark_end].iter().collect::<String>();
+      snippet.push_str(&html_escape::encode_text(&before));
+      snippet.push_str("<mark>");
+      snippet.push_str(&html_escape::encode_text(&marked));
+      snippet.push_str("</mark>");
+      position = mark_end;
+    }
+    let rest = chars[position..end].iter().collect::<String>();
+    snippet.push_str(&html_escape::encode_text(&rest));
+    if end < chars.len() {
+      snippet.push('…');
+    }
+
+    Some(snippet)
+  }
+}
+
+/// Runs a text search over some columns of a scope, returning up to `limit` of the matching
+/// models along with their ranks, best first
+pub async fn ranked_text_search<E, C>(
+  db: &C,
+  scope: Select<E>,
+  id_column: E::Column,
+  columns: &[&str],
+  search: &TextSearchQuery,
+  limit: u64,
+) -> Result<Vec<(E::Model, f64)>, DbErr>
+where
+  E: EntityTrait,
+  E::Model: Send + Sync,
+  C: ConnectionTrait,
+{
+  let table_name = E::default().table_name().to_string();
+  let qualified_columns = columns
+    .iter()
+    .map(|column| format!("{}.{}", table_name, column))
+    .collect::<Vec<_>>();
+  let qualified_columns = qualified_columns
+    .iter()
+    .map(String::as_str)
+    .collect::<Vec<_>>();
+
+  let ranked_ids: Vec<(i64, f64)> = scope
+    .clone()
+    .filter(search.matches(&qualified_columns))
+    .select_only()
+    .column(id_column)
+    .column_as(search.rank(&qualified_columns), "search_rank")
+    .order_by_desc(Expr::cust("search_rank"))
+    .limit(limit)
+    .into_tuple()
+    .all(db)
+    .await?;
+  if ranked_ids.is_empty() {
+    return Ok(vec![]);
+  }
+
+  let mut models = scope
+    .filter(id_column.is_in(ranked_ids.iter().map(|(id, _)| *id)))
+    .all(db)
+    .await?;
+  let mut results = Vec::with_capacity(models.len());
+  for (id, rank) in ranked_ids {
+    if let Some(index) = models
+      .iter()
+      .position(|model| sea_orm::ModelTrait::get(model, id_column) == id.into())
+    {
+      results.push((models.swap_remove(index), rank));
+    }
+  }
+
+  Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_prefixes() {
+    let sql = sea_orm::sea_query::Query::select()
+      .expr(starts_with_ignoring_case("pages.name", "Con"))
+      .to_string(sea_orm::sea_query::PostgresQueryBuilder);
+
+    assert_eq!(sql, "SELECT pages.name ILIKE 'Con%'");
+  }
+
+  #[test]
+  fn parses_websearch_syntax() {
+    let search = TextSearchQuery::parse("  \"early  registration\" -refund or Badge ").unwrap();
+    assert_eq!(search.query(), "\"early registration\" -refund or Badge");
+    assert_eq!(search.terms, vec!["early", "registration", "badge"]);
+    assert!(TextSearchQuery::parse("   ").is_none());
+  }
+
+  #[test]
+  fn highlights_matches_in_context() {
+    let search = TextSearchQuery::parse("con suite").unwrap();
+    let snippet = search
+      .highlight(
+        "The <b>Con Suite</b> is open\nall weekend, so stop by the con's suite",
+        30,
+      )
+      .unwrap();
+
+    assert_eq!(
+      snippet,
+      "The &lt;b&gt;<mark>Con Suite</mark>&lt;/b&gt; is open a…"
+    );
+  }
+
+  #[test]
+  fn centers_on_the_first_match() {
+    let search = TextSearchQuery::parse("badge").unwrap();
+    let text = format!("{} pick up your badge at the front desk", "x".repeat(100));
+
+    assert_eq!(
+      search.highlight(&text, 21).unwrap(),
+      "…up your <mark>badge</mark> at the …"
+    );
+  }
+
+  #[test]
+  fn escapes_like_wildcards() {
+    assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+  }
+}
@@ -7,7 +7,10 @@ use async_graphql::{
 };
 use async_graphql_axum::{GraphQLBatchRequest, GraphQLResponse};
 use axum::response::{self, IntoResponse};
-use intercode_graphql_core::liquid_renderer::{LiquidRenderer, LiquidRendererFromRequest};
+use intercode_graphql_core::{
+  after_commit::AfterCommitHooks,
+  liquid_renderer::{LiquidRenderer, LiquidRendererFromRequest},
+};
 use intercode_graphql_loaders::LoaderManager;
 use intercode_server::AuthorizationInfoAndQueryDataFromRequest;
 
@@ -17,6 +20,7 @@ pub async fn graphql_handler_inner(
   schema: IntercodeSchema,
   AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
   LiquidRendererFromRequest(liquid_renderer): LiquidRendererFromRequest,
+  after_commit_hooks: AfterCommitHooks,
   req: GraphQLBatchRequest,
 ) -> GraphQLResponse {
   let loader_manager = Arc::new(LoaderManager::new(query_data.db().clone()));
@@ -25,7 +29,8 @@ pub async fn graphql_handler_inner(
     .data(query_data)
     .data(loader_manager)
     .data::<Arc<dyn LiquidRenderer>>(liquid_renderer)
-    .data(authorization_info);
+    .data(authorization_info)
+    .data(after_commit_hooks);
 
   let req = intercode_store::inject_request_data(req);
   let req = intercode_notifiers::inject_request_data(req);
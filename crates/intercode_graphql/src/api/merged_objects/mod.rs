@@ -24,6 +24,8 @@ mod product_variant_type;
 mod room_type;
 mod root_site_type;
 mod run_type;
+mod search_result_entry_type;
+mod search_result_type;
 mod signup_change_type;
 mod signup_request_type;
 mod signup_type;
@@ -57,6 +59,8 @@ pub use organization_type::*;
 pub use permission_type::*;
 pub use root_site_type::*;
 pub use run_type::*;
+pub use search_result_entry_type::*;
+pub use search_result_type::*;
 pub use signup_change_type::*;
 pub use signup_request_type::*;
 pub use signup_type::*;
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+use intercode_full_text_search::{
+  partial_objects::SearchResultEntryFullTextSearchFields, site_search::SiteSearchEntry,
+};
+
+use crate::api::unions::SearchableModelType;
+
+pub struct SearchResultEntryGlueFields {
+  entry: Arc<SiteSearchEntry>,
+}
+
+#[Object]
+impl SearchResultEntryGlueFields {
+  async fn model(&self) -> SearchableModelType {
+    self.entry.model.clone().into()
+  }
+}
+
+#[derive(MergedObject)]
+#[graphql(name = "SearchResultEntry")]
+pub struct SearchResultEntryType(
+  SearchResultEntryFullTextSearchFields,
+  SearchResultEntryGlueFields,
+);
+
+impl SearchResultEntryType {
+  pub fn new(entry: Arc<SiteSearchEntry>) -> Self {
+    Self(
+      SearchResultEntryFullTextSearchFields::new(entry.clone()),
+      SearchResultEntryGlueFields { entry },
+    )
+  }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+use intercode_full_text_search::{
+  partial_objects::SearchResultFullTextSearchFields, site_search::SiteSearchResults,
+};
+
+use super::SearchResultEntryType;
+
+pub struct SearchResultGlueFields {
+  results: Arc<SiteSearchResults>,
+}
+
+#[Object]
+impl SearchResultGlueFields {
+  async fn entries(&self) -> Vec<SearchResultEntryType> {
+    self
+      .results
+      .entries
+      .iter()
+      .map(|entry| SearchResultEntryType::new(Arc::new(entry.clone())))
+      .collect()
+  }
+}
+
+#[derive(MergedObject)]
+#[graphql(name = "SearchResult")]
+pub struct SearchResultType(SearchResultFullTextSearchFields, SearchResultGlueFields);
+
+impl SearchResultType {
+  pub fn new(results: SiteSearchResults) -> Self {
+    let results = Arc::new(results);
+
+    Self(
+      SearchResultFullTextSearchFields::new(results.clone()),
+      SearchResultGlueFields { results },
+    )
+  }
+}
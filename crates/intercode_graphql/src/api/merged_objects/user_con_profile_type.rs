@@ -4,6 +4,7 @@ use async_graphql::*;
 use intercode_entities::user_con_profiles;
 use intercode_forms::partial_objects::UserConProfileFormsFields;
 use intercode_graphql_core::model_backed_type;
+use intercode_notifiers::partial_objects::UserConProfileNotifiersFields;
 use intercode_policies::policies::{UserConProfileAction, UserConProfilePolicy};
 use intercode_policies::ModelBackedTypeGuardablePolicy;
 use intercode_store::partial_objects::{UserConProfileStoreExtensions, UserConProfileStoreFields};
@@ -72,5 +73,6 @@ merged_model_backed_type!(
   UserConProfileGlueFields,
   UserConProfileUsersFields,
   UserConProfileFormsFields,
+  UserConProfileNotifiersFields,
   UserConProfileStoreFields
 );
@@ -1,10 +1,14 @@
-use async_graphql::Object;
+use async_graphql::MergedObject;
+use intercode_cms::api::partial_objects::MutationRootCmsFields;
+use intercode_notifiers::partial_objects::MutationRootNotifiersFields;
+use intercode_reporting::partial_objects::MutationRootReportingFields;
+use intercode_users::partial_objects::MutationRootUsersFields;
 
-pub struct MutationRoot;
-
-#[Object(name = "Mutation")]
-impl MutationRoot {
-  async fn delete_me(&self) -> bool {
-    true
-  }
-}
+#[derive(MergedObject, Default)]
+#[graphql(name = "Mutation")]
+pub struct MutationRoot(
+  MutationRootCmsFields,
+  MutationRootNotifiersFields,
+  MutationRootReportingFields,
+  MutationRootUsersFields,
+);
@@ -1,7 +1,7 @@
 use super::interfaces::CmsParentInterface;
 use super::merged_objects::{
-  AbilityType, ConventionType, EventType, OrganizationType, RootSiteType, UserConProfileType,
-  UserType,
+  AbilityType, ConventionType, EventType, OrganizationType, RootSiteType, SearchResultType,
+  UserConProfileType, UserType,
 };
 use async_graphql::connection::Connection;
 use async_graphql::*;
@@ -10,6 +10,7 @@ use intercode_conventions::partial_objects::QueryRootConventionsFields;
 use intercode_conventions::query_builders::ConventionFiltersInput;
 use intercode_email::partial_objects::QueryRootEmailFields;
 use intercode_events::partial_objects::QueryRootEventsFields;
+use intercode_full_text_search::partial_objects::QueryRootFullTextSearchFields;
 use intercode_graphql_core::entity_relay_connection::type_converting_query;
 use intercode_graphql_core::{ModelBackedType, ModelPaginator};
 use intercode_query_builders::sort_input::SortInput;
@@ -113,6 +114,19 @@ impl QueryRootGlueFields {
     QueryRootCmsFields::root_site(ctx).await
   }
 
+  /// Searches the current convention's events, pages, event proposals and (for staff who can see
+  /// them) attendee profiles, best matches first
+  pub async fn site_search(
+    &self,
+    ctx: &Context<'_>,
+    query: String,
+    limit: Option<u64>,
+  ) -> Result<SearchResultType, Error> {
+    QueryRootFullTextSearchFields::site_search(ctx, query, limit)
+      .await
+      .map(SearchResultType::new)
+  }
+
   pub async fn user(&self, ctx: &Context<'_>, id: Option<ID>) -> Result<UserType, Error> {
     UserType::from_future_result(QueryRootUsersFields::user(ctx, id)).await
   }
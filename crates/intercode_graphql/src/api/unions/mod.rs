@@ -1,7 +1,9 @@
 mod permissioned_model_type;
 mod permissioned_role_type;
+mod searchable_model_type;
 mod ticket_type_parent_type;
 
 pub use permissioned_model_type::*;
 pub use permissioned_role_type::*;
+pub use searchable_model_type::*;
 pub use ticket_type_parent_type::*;
@@ -0,0 +1,30 @@
+use async_graphql::Union;
+use intercode_cms::api::objects::PageType;
+use intercode_full_text_search::site_search::SearchableModel;
+use intercode_graphql_core::ModelBackedType;
+
+use crate::api::merged_objects::{EventProposalType, EventType, UserConProfileType};
+
+#[derive(Union)]
+#[graphql(name = "SearchableModel")]
+pub enum SearchableModelType {
+  Event(EventType),
+  EventProposal(EventProposalType),
+  Page(PageType),
+  UserConProfile(UserConProfileType),
+}
+
+impl From<SearchableModel> for SearchableModelType {
+  fn from(value: SearchableModel) -> Self {
+    match value {
+      SearchableModel::Event(model) => SearchableModelType::Event(EventType::new(model)),
+      SearchableModel::EventProposal(model) => {
+        SearchableModelType::EventProposal(EventProposalType::new(model))
+      }
+      SearchableModel::Page(model) => SearchableModelType::Page(PageType::new(model)),
+      SearchableModel::UserConProfile(model) => {
+        SearchableModelType::UserConProfile(UserConProfileType::new(model))
+      }
+    }
+  }
+}
@@ -12,7 +12,7 @@ pub fn build_intercode_graphql_schema_minimal(
 ) -> SchemaBuilder<QueryRoot, MutationRoot, EmptySubscription> {
   async_graphql::Schema::build(
     api::QueryRoot::default(),
-    api::MutationRoot,
+    api::MutationRoot::default(),
     EmptySubscription,
   )
   .register_output_type::<CmsParentInterface>()
@@ -0,0 +1,58 @@
+use std::{
+  fmt::Debug,
+  sync::{Arc, Mutex},
+};
+
+type AfterCommitHook = Box<dyn FnOnce() + Send>;
+
+/// Work to do once the request's database transaction has committed, for side effects that other
+/// requests would otherwise undo by acting on data we haven't committed yet (such as evicting
+/// cached output that a concurrent render could fill straight back in).  If the transaction rolls
+/// back, the hooks are dropped without running.  Clones share the same list of hooks.
+#[derive(Clone, Default)]
+pub struct AfterCommitHooks {
+  hooks: Arc<Mutex<Vec<AfterCommitHook>>>,
+}
+
+impl AfterCommitHooks {
+  pub fn add(&self, hook: impl FnOnce() + Send + 'static) {
+    self.hooks.lock().unwrap().push(Box::new(hook));
+  }
+
+  pub fn run(&self) {
+    let hooks = std::mem::take(&mut *self.hooks.lock().unwrap());
+    for hook in hooks {
+      hook();
+    }
+  }
+}
+
+impl Debug for AfterCommitHooks {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AfterCommitHooks")
+      .field("len", &self.hooks.lock().unwrap().len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use super::*;
+
+  #[test]
+  fn runs_each_hook_once() {
+    let hooks = AfterCommitHooks::default();
+    let count = Arc::new(AtomicUsize::new(0));
+    let hook_count = count.clone();
+    hooks.clone().add(move || {
+      hook_count.fetch_add(1, Ordering::SeqCst);
+    });
+
+    hooks.run();
+    hooks.run();
+
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+  }
+}
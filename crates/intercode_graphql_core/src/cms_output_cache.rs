@@ -0,0 +1,401 @@
+use std::{
+  collections::HashMap,
+  env,
+  str::FromStr,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use intercode_entities::cms_rendered_outputs;
+use sea_orm::{
+  sea_query::OnConflict, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+  QueryFilter,
+};
+
+use crate::after_commit::AfterCommitHooks;
+
+/// Where rendered CMS output is kept.  Caching is off unless CMS_OUTPUT_CACHE_BACKEND turns it on.
+/// The memory store is private to each server process, so content edits made through one instance
+/// won't evict entries held by the others until they expire.  It's only safe for deployments
+/// running a single instance; anything bigger should use the Postgres store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmsOutputCacheBackend {
+  Disabled,
+  Memory,
+  Postgres,
+}
+
+impl FromStr for CmsOutputCacheBackend {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.trim().to_lowercase().as_str() {
+      "" | "off" | "none" | "disabled" => Ok(Self::Disabled),
+      "memory" => Ok(Self::Memory),
+      "postgres" | "database" => Ok(Self::Postgres),
+      other => Err(format!("Unknown CMS output cache backend: {}", other)),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct CmsOutputCacheConfig {
+  pub backend: CmsOutputCacheBackend,
+  pub ttl: Duration,
+  pub max_memory_entries: usize,
+}
+
+impl Default for CmsOutputCacheConfig {
+  fn default() -> Self {
+    Self {
+      backend: CmsOutputCacheBackend::Disabled,
+      ttl: Duration::from_secs(300),
+      max_memory_entries: 10_000,
+    }
+  }
+}
+
+impl CmsOutputCacheConfig {
+  pub fn from_env() -> Self {
+    let default = Self::default();
+
+    Self {
+      backend: env::var("CMS_OUTPUT_CACHE_BACKEND")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default.backend),
+      ttl: env::var("CMS_OUTPUT_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(default.ttl),
+      max_memory_entries: env::var("CMS_OUTPUT_CACHE_MAX_ENTRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default.max_memory_entries),
+    }
+  }
+}
+
+/// A rendered page or layout, along with what it was rendered from so that it can be found again
+/// when that content changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CmsOutputCacheEntry {
+  pub cache_key: String,
+  pub parent_type: Option<String>,
+  pub parent_id: Option<i64>,
+  pub content_type: String,
+  pub content_id: i64,
+  pub output: String,
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+  entry: CmsOutputCacheEntry,
+  expires_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+struct MemoryStore {
+  entries: Mutex<HashMap<String, MemoryEntry>>,
+  max_entries: usize,
+}
+
+impl MemoryStore {
+  fn new(max_entries: usize) -> Self {
+    MemoryStore {
+      entries: Mutex::new(HashMap::new()),
+      max_entries,
+    }
+  }
+
+  fn get(&self, cache_key: &str, now: NaiveDateTime) -> Option<String> {
+    let entries = self.entries.lock().unwrap();
+    entries
+      .get(cache_key)
+      .filter(|memory_entry| memory_entry.expires_at > now)
+      .map(|memory_entry| memory_entry.entry.output.clone())
+  }
+
+  fn put(&self, entry: CmsOutputCacheEntry, expires_at: NaiveDateTime, now: NaiveDateTime) {
+    let mut entries = self.entries.lock().unwrap();
+
+    if entries.len() >= self.max_entries && !entries.contains_key(&entry.cache_key) {
+      entries.retain(|_, memory_entry| memory_entry.expires_at > now);
+    }
+    if entries.len() >= self.max_entries && !entries.contains_key(&entry.cache_key) {
+      // every entry has the same lifetime, so the one expiring soonest is the oldest
+      let oldest_key = entries
+        .iter()
+        .min_by_key(|(_, memory_entry)| memory_entry.expires_at)
+        .map(|(key, _)| key.clone());
+      if let Some(oldest_key) = oldest_key {
+        entries.remove(&oldest_key);
+      }
+    }
+
+    entries.insert(entry.cache_key.clone(), MemoryEntry { entry, expires_at });
+  }
+
+  fn remove_where(&self, predicate: impl Fn(&CmsOutputCacheEntry) -> bool) {
+    let mut entries = self.entries.lock().unwrap();
+    entries.retain(|_, memory_entry| !predicate(&memory_entry.entry));
+  }
+}
+
+#[derive(Debug, Clone)]
+enum CmsOutputStore {
+  Disabled,
+  Memory(Arc<MemoryStore>),
+  Postgres,
+}
+
+/// A cache of rendered CMS output.  Clones share the same underlying store.  What goes into the
+/// cache key and when entries get invalidated is up to the CMS; this only stores and evicts them.
+#[derive(Debug, Clone)]
+pub struct CmsOutputCache {
+  store: CmsOutputStore,
+  ttl: Duration,
+}
+
+fn parent_condition(parent_type: Option<&str>, parent_id: Option<i64>) -> Condition {
+  Condition::all()
+    .add(match parent_type {
+      Some(parent_type) => cms_rendered_outputs::Column::ParentType.eq(parent_type),
+      None => cms_rendered_outputs::Column::ParentType.is_null(),
+    })
+    .add(match parent_id {
+      Some(parent_id) => cms_rendered_outputs::Column::ParentId.eq(parent_id),
+      None => cms_rendered_outputs::Column::ParentId.is_null(),
+    })
+}
+
+impl CmsOutputCache {
+  pub fn new(config: &CmsOutputCacheConfig) -> Self {
+    let store = match config.backend {
+      CmsOutputCacheBackend::Disabled => CmsOutputStore::Disabled,
+      CmsOutputCacheBackend::Memory => {
+        CmsOutputStore::Memory(Arc::new(MemoryStore::new(config.max_memory_entries)))
+      }
+      CmsOutputCacheBackend::Postgres => CmsOutputStore::Postgres,
+    };
+
+    CmsOutputCache {
+      store,
+      ttl: config.ttl,
+    }
+  }
+
+  pub fn from_env() -> Self {
+    Self::new(&CmsOutputCacheConfig::from_env())
+  }
+
+  pub fn disabled() -> Self {
+    Self::new(&CmsOutputCacheConfig {
+      backend: CmsOutputCacheBackend::Disabled,
+      ..Default::default()
+    })
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    !matches!(self.store, CmsOutputStore::Disabled)
+  }
+
+  fn expires_at(&self, now: NaiveDateTime) -> NaiveDateTime {
+    now + chrono::Duration::from_std(self.ttl).unwrap_or_else(|_| chrono::Duration::zero())
+  }
+
+  pub async fn get<C: ConnectionTrait>(
+    &self,
+    db: &C,
+    cache_key: &str,
+  ) -> Result<Option<String>, DbErr> {
+    let now = Utc::now().naive_utc();
+
+    match &self.store {
+      CmsOutputStore::Disabled => Ok(None),
+      CmsOutputStore::Memory(store) => Ok(store.get(cache_key, now)),
+      CmsOutputStore::Postgres => Ok(
+        cms_rendered_outputs::Entity::find()
+          .filter(cms_rendered_outputs::Column::CacheKey.eq(cache_key))
+          .filter(cms_rendered_outputs::Column::ExpiresAt.gt(now))
+          .one(db)
+          .await?
+          .map(|rendered_output| rendered_output.output),
+      ),
+    }
+  }
+
+  pub async fn put<C: ConnectionTrait>(
+    &self,
+    db: &C,
+    entry: CmsOutputCacheEntry,
+  ) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let expires_at = self.expires_at(now);
+
+    match &self.store {
+      CmsOutputStore::Disabled => Ok(()),
+      CmsOutputStore::Memory(store) => {
+        store.put(entry, expires_at, now);
+        Ok(())
+      }
+      CmsOutputStore::Postgres => {
+        // clear out anything stale that was rendered from the same content while we're here
+        cms_rendered_outputs::Entity::delete_many()
+          .filter(cms_rendered_outputs::Column::ContentType.eq(entry.content_type.as_str()))
+          .filter(cms_rendered_outputs::Column::ContentId.eq(entry.content_id))
+          .filter(cms_rendered_outputs::Column::ExpiresAt.lte(now))
+          .exec(db)
+          .await?;
+
+        cms_rendered_outputs::Entity::insert(cms_rendered_outputs::ActiveModel {
+          id: ActiveValue::NotSet,
+          cache_key: ActiveValue::Set(entry.cache_key),
+          parent_type: ActiveValue::Set(entry.parent_type),
+          parent_id: ActiveValue::Set(entry.parent_id),
+          content_type: ActiveValue::Set(entry.content_type),
+          content_id: ActiveValue::Set(entry.content_id),
+          output: ActiveValue::Set(entry.output),
+          expires_at: ActiveValue::Set(expires_at),
+          created_at: ActiveValue::Set(now),
+          updated_at: ActiveValue::Set(now),
+        })
+        .on_conflict(
+          OnConflict::column(cms_rendered_outputs::Column::CacheKey)
+            .update_columns([
+              cms_rendered_outputs::Column::Output,
+              cms_rendered_outputs::Column::ExpiresAt,
+              cms_rendered_outputs::Column::UpdatedAt,
+            ])
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+        Ok(())
+      }
+    }
+  }
+
+  /// Evicts everything rendered from the given pages or layouts.  Postgres entries are deleted in
+  /// the current transaction; memory entries are evicted once it commits, so that a render racing
+  /// with the edit can't put the old output straight back.
+  pub async fn invalidate_contents<C: ConnectionTrait>(
+    &self,
+    db: &C,
+    after_commit: &AfterCommitHooks,
+    content_type: &str,
+    content_ids: &[i64],
+  ) -> Result<(), DbErr> {
+    if content_ids.is_empty() {
+      return Ok(());
+    }
+
+    match &self.store {
+      CmsOutputStore::Disabled => {}
+      CmsOutputStore::Memory(store) => {
+        let store = store.clone();
+        let content_type = content_type.to_string();
+        let content_ids = content_ids.to_vec();
+        after_commit.add(move || {
+          store.remove_where(|entry| {
+            entry.content_type == content_type && content_ids.contains(&entry.content_id)
+          })
+        });
+      }
+      CmsOutputStore::Postgres => {
+        cms_rendered_outputs::Entity::delete_many()
+          .filter(cms_rendered_outputs::Column::ContentType.eq(content_type))
+          .filter(cms_rendered_outputs::Column::ContentId.is_in(content_ids.iter().copied()))
+          .exec(db)
+          .await?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Evicts everything rendered for a convention (or, with no parent, for the root site), on the
+  /// same schedule as invalidate_contents
+  pub async fn invalidate_parent<C: ConnectionTrait>(
+    &self,
+    db: &C,
+    after_commit: &AfterCommitHooks,
+    parent_type: Option<&str>,
+    parent_id: Option<i64>,
+  ) -> Result<(), DbErr> {
+    match &self.store {
+      CmsOutputStore::Disabled => {}
+      CmsOutputStore::Memory(store) => {
+        let store = store.clone();
+        let parent_type = parent_type.map(str::to_string);
+        after_commit.add(move || {
+          store
+            .remove_where(|entry| entry.parent_type == parent_type && entry.parent_id == parent_id)
+        });
+      }
+      CmsOutputStore::Postgres => {
+        cms_rendered_outputs::Entity::delete_many()
+          .filter(parent_condition(parent_type, parent_id))
+          .exec(db)
+          .await?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(cache_key: &str, content_id: i64) -> CmsOutputCacheEntry {
+    CmsOutputCacheEntry {
+      cache_key: cache_key.to_string(),
+      parent_type: Some("Convention".to_string()),
+      parent_id: Some(1),
+      content_type: "Page".to_string(),
+      content_id,
+      output: format!("<p>{}</p>", cache_key),
+    }
+  }
+
+  fn at(seconds: i64) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+  }
+
+  #[test]
+  fn memory_store_expires_entries() {
+    let store = MemoryStore::new(10);
+    store.put(entry("a", 1), at(60), at(0));
+
+    assert_eq!(store.get("a", at(30)), Some("<p>a</p>".to_string()));
+    assert_eq!(store.get("a", at(60)), None);
+  }
+
+  #[test]
+  fn memory_store_evicts_the_oldest_entry_when_full() {
+    let store = MemoryStore::new(2);
+    store.put(entry("a", 1), at(60), at(0));
+    store.put(entry("b", 2), at(70), at(10));
+    store.put(entry("c", 3), at(80), at(20));
+
+    assert_eq!(store.get("a", at(20)), None);
+    assert!(store.get("b", at(20)).is_some());
+    assert!(store.get("c", at(20)).is_some());
+  }
+
+  #[test]
+  fn memory_store_removes_matching_entries() {
+    let store = MemoryStore::new(10);
+    store.put(entry("a", 1), at(60), at(0));
+    store.put(entry("b", 2), at(60), at(0));
+    store.remove_where(|entry| entry.content_id == 1);
+
+    assert_eq!(store.get("a", at(0)), None);
+    assert!(store.get("b", at(0)).is_some());
+  }
+}
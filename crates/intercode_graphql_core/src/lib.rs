@@ -1,3 +1,5 @@
+pub mod after_commit;
+pub mod cms_output_cache;
 mod embedded_graphql_executor;
 pub mod entity_relay_connection;
 pub mod enums;
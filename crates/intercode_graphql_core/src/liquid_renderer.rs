@@ -11,6 +11,9 @@ pub trait LiquidRenderer: Send + Sync + Debug {
     preload_partials_strategy: Option<PreloadPartialsStrategy<'_>>,
   ) -> Result<String, async_graphql::Error>;
 
+  /// Parses the given template without rendering it, returning any syntax errors
+  async fn parse_liquid(&self, content: &str) -> Result<(), async_graphql::Error>;
+
   async fn builtin_globals(
     &self,
   ) -> Result<Box<dyn liquid::ObjectView + Send>, async_graphql::Error>;
@@ -1,15 +1,22 @@
 use i18n_embed::fluent::FluentLanguageLoader;
+use seawater::render_budget::RenderBudget;
 use std::{fmt::Debug, sync::Arc};
 
+use crate::cms_output_cache::CmsOutputCache;
+
 #[derive(Clone)]
 pub struct SchemaData {
   pub language_loader: Arc<FluentLanguageLoader>,
+  pub cms_output_cache: CmsOutputCache,
+  pub render_budget: RenderBudget,
 }
 
 impl Debug for SchemaData {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("SchemaData")
       .field("language_loader", &self.language_loader)
+      .field("cms_output_cache", &self.cms_output_cache)
+      .field("render_budget", &self.render_budget)
       .finish_non_exhaustive()
   }
 }
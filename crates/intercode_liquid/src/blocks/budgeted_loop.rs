@@ -0,0 +1,127 @@
+use std::{cell::Cell, collections::BTreeSet, io::Write};
+
+use liquid_core::{
+  model::{KString, KStringCow, KStringRef, ScalarCow, Value},
+  runtime::{PartialStore, Registers},
+  BlockReflection, Language, ParseBlock, Renderable, Result, Runtime, TagBlock, TagTokenIter,
+  ValueCow,
+};
+use seawater::render_budget::{current_render_budget, RenderBudgetTracker};
+
+thread_local! {
+  static IN_METERED_LOOP: Cell<bool> = Cell::new(false);
+}
+
+/// Wraps a looping block (for or tablerow) so that its work counts against the render budget.
+/// Everything else about the block is left to the wrapped implementation.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BudgetedLoopBlock<B: ParseBlock + Clone + 'static> {
+  inner: B,
+}
+
+impl<B: ParseBlock + Clone + 'static> BudgetedLoopBlock<B> {
+  pub fn new(inner: B) -> Self {
+    Self { inner }
+  }
+}
+
+impl<B: ParseBlock + Clone + 'static> ParseBlock for BudgetedLoopBlock<B> {
+  fn parse(
+    &self,
+    arguments: TagTokenIter<'_>,
+    tokens: TagBlock<'_, '_>,
+    options: &Language,
+  ) -> Result<Box<dyn Renderable>> {
+    Ok(Box::new(BudgetedLoop {
+      inner: self.inner.parse(arguments, tokens, options)?,
+    }))
+  }
+
+  fn reflection(&self) -> &dyn BlockReflection {
+    self.inner.reflection()
+  }
+}
+
+#[derive(Debug)]
+struct BudgetedLoop {
+  inner: Box<dyn Renderable>,
+}
+
+impl Renderable for BudgetedLoop {
+  fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+    let Some(tracker) = current_render_budget() else {
+      return self.inner.render_to(writer, runtime);
+    };
+    tracker.check()?;
+
+    // A loop inside another loop renders against the outer loop's metered runtime already, so
+    // wrapping it again would count its steps twice
+    if IN_METERED_LOOP.with(Cell::get) {
+      return self.inner.render_to(writer, runtime);
+    }
+
+    IN_METERED_LOOP.with(|in_loop| in_loop.set(true));
+    let result = self.inner.render_to(
+      writer,
+      &MeteredRuntime {
+        inner: runtime,
+        tracker: &tracker,
+      },
+    );
+    IN_METERED_LOOP.with(|in_loop| in_loop.set(false));
+
+    // A loop that goes over budget without writing anything still has to fail
+    tracker.check()?;
+    result
+  }
+}
+
+/// A runtime that charges a loop step whenever its registers are consulted.  Loop blocks check
+/// them for break and continue after every pass through their body, and templates check them
+/// after every element, so this ticks steadily for as long as the loop runs.
+struct MeteredRuntime<'r> {
+  inner: &'r dyn Runtime,
+  tracker: &'r RenderBudgetTracker,
+}
+
+impl<'r> Runtime for MeteredRuntime<'r> {
+  fn partials(&self) -> &dyn PartialStore {
+    self.inner.partials()
+  }
+
+  fn name(&self) -> Option<KStringRef<'_>> {
+    self.inner.name()
+  }
+
+  fn roots(&self) -> BTreeSet<KStringCow<'_>> {
+    self.inner.roots()
+  }
+
+  fn try_get(&self, path: &[ScalarCow<'_>]) -> Option<ValueCow<'_>> {
+    self.inner.try_get(path)
+  }
+
+  fn get(&self, path: &[ScalarCow<'_>]) -> Result<ValueCow<'_>> {
+    self.tracker.check()?;
+    self.inner.get(path)
+  }
+
+  fn set_global(&self, name: KString, val: Value) -> Option<Value> {
+    self.inner.set_global(name, val)
+  }
+
+  fn set_index(&self, name: KString, val: Value) -> Option<Value> {
+    self.inner.set_index(name, val)
+  }
+
+  fn get_index<'a>(&'a self, name: &str) -> Option<ValueCow<'a>> {
+    self.inner.get_index(name)
+  }
+
+  fn registers(&self) -> &Registers {
+    // Registers can't fail, so going over budget here is remembered by the tracker and surfaces
+    // at the next write, variable lookup or the end of the loop
+    let _ = self.tracker.charge_loop_iteration();
+    self.inner.registers()
+  }
+}
@@ -1,3 +1,5 @@
+mod budgeted_loop;
 mod spoiler;
 
+pub use budgeted_loop::*;
 pub use spoiler::*;
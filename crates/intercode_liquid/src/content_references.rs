@@ -0,0 +1,126 @@
+use crate::variable_references::{split_tag_markup, template_segments, Segment};
+
+/// The partials, files and GraphQL queries a template refers to by name.  Only literal names
+/// count: a partial or file whose name is computed at render time can't be found without
+/// rendering.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ContentReferences {
+  pub partial_names: Vec<String>,
+  pub file_names: Vec<String>,
+  pub graphql_query_identifiers: Vec<String>,
+}
+
+fn first_string_literal(args: &str) -> Option<&str> {
+  let args = args.trim_start();
+  let quote = args.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+  let rest = &args[1..];
+  rest.find(quote).map(|end| &rest[..end])
+}
+
+fn push_unique(names: &mut Vec<String>, name: &str) {
+  if !name.is_empty() && !names.iter().any(|existing| existing == name) {
+    names.push(name.to_string());
+  }
+}
+
+impl ContentReferences {
+  fn scan_tag(&mut self, markup: &str, skip_until: &mut Option<&'static str>) {
+    let (tag_name, args) = split_tag_markup(markup);
+
+    if let Some(end_tag) = *skip_until {
+      if tag_name == end_tag {
+        *skip_until = None;
+      }
+      return;
+    }
+
+    match tag_name {
+      "raw" => *skip_until = Some("endraw"),
+      "comment" => *skip_until = Some("endcomment"),
+      "liquid" => {
+        for line in args.lines() {
+          self.scan_tag(line, skip_until);
+        }
+      }
+      "include" | "render" => {
+        if let Some(name) = first_string_literal(args) {
+          push_unique(&mut self.partial_names, name);
+        }
+      }
+      "file_url" => {
+        if let Some(name) = first_string_literal(args) {
+          push_unique(&mut self.file_names, name);
+        }
+      }
+      "assign_graphql_result" => {
+        if let Some((_, query)) = args.split_once('=') {
+          let query = query.trim_start();
+          let identifier_length = query
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(query.len());
+          push_unique(
+            &mut self.graphql_query_identifiers,
+            &query[..identifier_length],
+          );
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Statically finds the partials (via include and render), files (via file_url) and GraphQL
+/// queries (via assign_graphql_result) a template refers to, in order of first use
+pub fn find_content_references(content: &str) -> ContentReferences {
+  let mut references = ContentReferences::default();
+  let mut skip_until = None;
+
+  for segment in template_segments(content) {
+    if let Segment::Tag(markup) = segment {
+      references.scan_tag(markup, &mut skip_until);
+    }
+  }
+
+  references
+}
+
+#[cfg(test)]
+mod tests {
+  use super::find_content_references;
+
+  #[test]
+  fn finds_partials_and_files() {
+    let references = find_content_references(
+      "{% include 'header' %}<img src=\"{% file_url \"logo.png\" %}\">\
+       {%- render \"footer\", year: 2023 -%}{% include 'header' %}",
+    );
+
+    assert_eq!(references.partial_names, vec!["header", "footer"]);
+    assert_eq!(references.file_names, vec!["logo.png"]);
+  }
+
+  #[test]
+  fn finds_graphql_queries() {
+    let references = find_content_references(
+      "{% assign_graphql_result events = upcomingEvents(limit: 5) %}\
+       {% assign_graphql_result me=myProfile %}",
+    );
+
+    assert_eq!(
+      references.graphql_query_identifiers,
+      vec!["upcomingEvents", "myProfile"]
+    );
+  }
+
+  #[test]
+  fn ignores_dynamic_names_and_raw_blocks() {
+    let references = find_content_references(
+      "{% include partial_name %}{% raw %}{% include 'not_really' %}{% endraw %}\
+       {% comment %}{% file_url 'old.png' %}{% endcomment %}\
+       {% liquid\n  include 'inside_liquid'\n  echo 'hi'\n%}",
+    );
+
+    assert_eq!(references.partial_names, vec!["inside_liquid"]);
+    assert!(references.file_names.is_empty());
+  }
+}
@@ -1,8 +1,9 @@
-use crate::invalid_input;
+use std::sync::Arc;
+
+use crate::{invalid_input, url_builder::UrlBuilder};
 use liquid_core::{
   Display_filter, Filter, FilterReflection, ParseFilter, Result, Runtime, Value, ValueView,
 };
-use url::Url;
 
 #[derive(Clone, FilterReflection)]
 #[filter(
@@ -68,11 +69,11 @@ impl Filter for EmailLinkFilter {
 #[derive(Clone, FilterReflection)]
 #[filter(
   name = "absolute_url",
-  description = "Given a relative URL, turns it into an absolute URL for the current convention.  Given an absolute \
-    URL, changes the hostname to the current convention host."
+  description = "Given a relative URL, turns it into an absolute URL for the current site.  Given an absolute \
+    URL, changes the hostname to the current site's host."
 )]
 pub struct AbsoluteUrl {
-  pub convention_domain: Option<String>,
+  pub url_builder: Arc<UrlBuilder>,
 }
 
 impl ParseFilter for AbsoluteUrl {
@@ -81,7 +82,7 @@ impl ParseFilter for AbsoluteUrl {
     _arguments: liquid_core::parser::FilterArguments,
   ) -> Result<Box<dyn liquid_core::Filter>> {
     Ok(Box::new(AbsoluteUrlFilter {
-      convention_domain: self.convention_domain.clone(),
+      url_builder: self.url_builder.clone(),
     }))
   }
 
@@ -93,7 +94,7 @@ impl ParseFilter for AbsoluteUrl {
 #[derive(Debug, Default, Display_filter)]
 #[name = "absolute_url"]
 struct AbsoluteUrlFilter {
-  convention_domain: Option<String>,
+  url_builder: Arc<UrlBuilder>,
 }
 
 impl Filter for AbsoluteUrlFilter {
@@ -102,25 +103,11 @@ impl Filter for AbsoluteUrlFilter {
 
     match input {
       Value::Nil => Ok(Value::scalar("")),
-      Value::Scalar(url) => match &self.convention_domain {
-        None => Ok(Value::Scalar(url)),
-        Some(convention_domain) => {
-          let options = Url::options();
-          let convention_base = Url::parse(&format!("http://{}", convention_domain)).ok();
-          let base_url = options.base_url(convention_base.as_ref());
-          let url = base_url.parse(&url.to_kstr().into_string());
-          match url {
-            Ok(mut parsed_url) => {
-              let set_host_result = parsed_url.set_host(Some(convention_domain));
-              match set_host_result {
-                Ok(_) => Ok(Value::scalar(parsed_url.to_string())),
-                Err(error) => Err(invalid_input(format!("Can't set host on URL: {}", error))),
-              }
-            }
-            Err(error) => Err(invalid_input(format!("Can't parse URL: {}", error))),
-          }
-        }
-      },
+      Value::Scalar(url) => self
+        .url_builder
+        .absolute_url(&url.to_kstr())
+        .map(Value::scalar)
+        .map_err(invalid_input),
       _ => Err(invalid_input("String expected")),
     }
   }
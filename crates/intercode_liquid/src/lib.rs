@@ -1,10 +1,13 @@
 pub mod blocks;
 pub mod cms_parent_partial_source;
+pub mod content_references;
 mod dig;
 pub mod filters;
 mod markdown;
 mod react_component_tag;
 pub mod tags;
+pub mod url_builder;
+pub mod variable_references;
 
 pub use markdown::*;
 pub use react_component_tag::react_component_tag;
@@ -12,10 +15,17 @@ use seawater::ConnectionWrapper;
 
 pub use dig::liquid_datetime_to_chrono_datetime;
 use i18n_embed::fluent::FluentLanguageLoader;
-use intercode_entities::{active_storage_blobs, cms_parent::CmsParent, conventions};
+use intercode_entities::{cms_parent::CmsParent, conventions};
 use liquid::{partials::PartialCompiler, Error, Parser, ParserBuilder};
-use std::{fmt::Debug, future::Future, pin::Pin, sync::Weak};
+use liquid_lib::stdlib;
+use std::{
+  fmt::Debug,
+  future::Future,
+  pin::Pin,
+  sync::{Arc, Weak},
+};
 use tags::GraphQLExecutorBuilder;
+use url_builder::UrlBuilder;
 
 pub trait GraphQLExecutor: Debug + Send + Sync {
   fn execute(
@@ -44,11 +54,13 @@ pub fn build_liquid_parser(
   convention: Option<&conventions::Model>,
   language_loader: Weak<FluentLanguageLoader>,
   cms_parent: &CmsParent,
+  url_builder: UrlBuilder,
   db: ConnectionWrapper,
   user_signed_in: bool,
   graphql_executor_builder: Box<dyn GraphQLExecutorBuilder>,
   partial_compiler: impl PartialCompiler,
 ) -> Result<Parser, liquid_core::Error> {
+  let url_builder = Arc::new(url_builder);
   let builder = ParserBuilder::with_stdlib()
     .filter(filters::Pluralize)
     .filter(filters::EmailLink::new(user_signed_in))
@@ -57,7 +69,7 @@ pub fn build_liquid_parser(
     .filter(filters::Singularize)
     .filter(filters::Titleize)
     .filter(filters::AbsoluteUrl {
-      convention_domain: convention.map(|c| c.domain.clone()),
+      url_builder: url_builder.clone(),
     })
     .filter(filters::CondenseWhitespace)
     .filter(filters::MD5)
@@ -77,24 +89,22 @@ pub fn build_liquid_parser(
     .tag(tags::CookieConsentTag)
     .tag(tags::EventAdminMenuTag)
     .tag(tags::EventRunsSectionTag)
-    .tag(tags::FileUrlTag::new(cms_parent, db))
+    .tag(tags::FileUrlTag::new(cms_parent, db, url_builder.clone()))
     .tag(tags::LongFormEventDetailsTag)
     .tag(tags::MapTag)
     .tag(tags::MaximumEventSignupsPreviewTag {
       convention_timezone: convention.and_then(|c| c.timezone_name.clone()),
     })
     .tag(tags::NewEventProposalButtonTag)
-    .tag(tags::PageUrlTag)
+    .tag(tags::PageUrlTag::new(url_builder))
     .tag(tags::ShortFormEventDetailsTag)
     .tag(tags::WithdrawUserSignupButtonTag)
     .tag(tags::YouTubeTag)
     .block(blocks::SpoilerBlock)
+    .block(blocks::BudgetedLoopBlock::new(stdlib::ForBlock))
+    .block(blocks::BudgetedLoopBlock::new(stdlib::TableRowBlock))
+    .tag(tags::BudgetedIncludeTag::new(stdlib::IncludeTag))
     .partials(partial_compiler);
 
   builder.build()
 }
-
-pub fn build_active_storage_blob_url(blob: &active_storage_blobs::Model) -> String {
-  // TODO do something actually real here
-  format!("https://assets.neilhosting.net/{}", blob.key)
-}
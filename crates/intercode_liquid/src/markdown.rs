@@ -1,8 +1,141 @@
-use crate::build_active_storage_blob_url;
+use crate::url_builder::UrlBuilder;
+use ammonia::Builder;
 use intercode_entities::active_storage_blobs;
 use linkify::{LinkFinder, LinkKind};
-use pulldown_cmark::{html, Event, LinkType, Options, Tag};
-use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Tag};
+use std::collections::{HashMap, HashSet};
+
+/// How user-provided markdown gets turned into HTML.  The defaults match what the Rails app does
+/// for event descriptions, bios and form content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkdownOptions {
+  /// Open links in a new tab, without passing along the referrer
+  pub link_targets: bool,
+  /// Mark images up with Bootstrap's img-fluid class so they shrink to fit their container
+  pub responsive_images: bool,
+  /// If the output is a single paragraph, return its contents without the wrapping <p> tag
+  pub strip_single_p: bool,
+  /// Run the output through the allowlist sanitizer, removing scripts, event handlers and
+  /// anything else not in SANITIZER_TAGS
+  pub sanitize: bool,
+}
+
+impl Default for MarkdownOptions {
+  fn default() -> Self {
+    Self {
+      link_targets: true,
+      responsive_images: true,
+      strip_single_p: true,
+      sanitize: true,
+    }
+  }
+}
+
+// Based on the Rails HTML safe list, plus the table and footnote markup the markdown renderer
+// produces
+const SANITIZER_TAGS: &[&str] = &[
+  "a",
+  "abbr",
+  "acronym",
+  "address",
+  "b",
+  "big",
+  "blockquote",
+  "br",
+  "cite",
+  "code",
+  "dd",
+  "del",
+  "dfn",
+  "div",
+  "dl",
+  "dt",
+  "em",
+  "h1",
+  "h2",
+  "h3",
+  "h4",
+  "h5",
+  "h6",
+  "hr",
+  "i",
+  "img",
+  "ins",
+  "kbd",
+  "li",
+  "ol",
+  "p",
+  "pre",
+  "samp",
+  "small",
+  "span",
+  "strong",
+  "sub",
+  "sup",
+  "table",
+  "tbody",
+  "td",
+  "tfoot",
+  "th",
+  "thead",
+  "tr",
+  "tt",
+  "ul",
+  "var",
+];
+
+const SANITIZER_GENERIC_ATTRIBUTES: &[&str] = &["class", "id", "lang", "title"];
+
+const SANITIZER_TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+  ("a", &["href", "name", "rel", "target"]),
+  ("abbr", &["title"]),
+  ("blockquote", &["cite"]),
+  ("del", &["cite", "datetime"]),
+  ("img", &["alt", "height", "src", "width"]),
+  ("ins", &["cite", "datetime"]),
+  ("ol", &["start"]),
+];
+
+const SANITIZER_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+static SANITIZER: Lazy<Builder<'static>> = Lazy::new(|| {
+  let mut builder = Builder::empty();
+  builder
+    .add_tags(SANITIZER_TAGS)
+    .add_generic_attributes(SANITIZER_GENERIC_ATTRIBUTES)
+    .add_clean_content_tags(&["script", "style"])
+    .url_schemes(
+      SANITIZER_URL_SCHEMES
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>(),
+    )
+    // we set rel ourselves on markdown links, so ammonia mustn't overwrite it
+    .link_rel(None);
+
+  for (tag, attributes) in SANITIZER_TAG_ATTRIBUTES {
+    builder.add_tag_attributes(tag, *attributes);
+  }
+
+  builder
+});
+
+/// Removes everything from a chunk of HTML that isn't on the allowlist
+pub fn sanitize_html(html: &str) -> String {
+  SANITIZER.clean(html).to_string()
+}
+
+/// If a chunk of HTML is a single paragraph, returns just the contents of that paragraph.
+/// Anything else is returned as-is.
+pub fn strip_single_p(html: &str) -> &str {
+  let trimmed = html.trim();
+  trimmed
+    .strip_prefix("<p>")
+    .and_then(|rest| rest.strip_suffix("</p>"))
+    .filter(|inner| !inner.contains("</p>"))
+    .unwrap_or(html)
+}
 
 fn linkify_event<'a>(event: Event<'a>) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
   match event {
@@ -11,22 +144,27 @@ fn linkify_event<'a>(event: Event<'a>) -> Box<dyn Iterator<Item = Event<'a>> + '
       let events: Vec<_> = LinkFinder::new()
         .url_must_have_scheme(false)
         .spans(text)
-        .flat_map(|span| match span.kind() {
-          Some(LinkKind::Email) | Some(LinkKind::Url) => vec![
+        .flat_map(|span| {
+          let link_type = match span.kind() {
+            Some(LinkKind::Email) => LinkType::Email,
+            Some(LinkKind::Url) => LinkType::Inline,
+            _ => return vec![Event::Text(span.as_str().to_owned().into())].into_iter(),
+          };
+
+          vec![
             Event::Start(Tag::Link(
-              LinkType::Inline,
+              link_type,
               span.as_str().to_owned().into(),
               "".to_string().into(),
             )),
             Event::Text(span.as_str().to_owned().into()),
             Event::End(Tag::Link(
-              LinkType::Inline,
+              link_type,
               span.as_str().to_owned().into(),
               "".to_string().into(),
             )),
           ]
-          .into_iter(),
-          _ => vec![event.clone()].into_iter(),
+          .into_iter()
         })
         .collect();
 
@@ -36,9 +174,106 @@ fn linkify_event<'a>(event: Event<'a>) -> Box<dyn Iterator<Item = Event<'a>> + '
   }
 }
 
+fn link_href(link_type: LinkType, destination: &str) -> String {
+  if link_type == LinkType::Email && !destination.starts_with("mailto:") {
+    format!("mailto:{}", destination)
+  } else {
+    destination.to_string()
+  }
+}
+
+fn title_attribute(title: &str) -> String {
+  if title.is_empty() {
+    String::new()
+  } else {
+    format!(
+      r#" title="{}""#,
+      html_escape::encode_double_quoted_attribute(title)
+    )
+  }
+}
+
+fn link_open_tag(link_type: LinkType, destination: &str, title: &str) -> String {
+  format!(
+    r#"<a href="{}"{} target="_blank" rel="noreferrer noopener">"#,
+    html_escape::encode_double_quoted_attribute(&link_href(link_type, destination)),
+    title_attribute(title)
+  )
+}
+
+fn image_tag(destination: &str, title: &str, alt: &str) -> String {
+  format!(
+    r#"<img src="{}" alt="{}"{} class="img-fluid" />"#,
+    html_escape::encode_double_quoted_attribute(destination),
+    html_escape::encode_double_quoted_attribute(alt),
+    title_attribute(title)
+  )
+}
+
+/// pulldown_cmark has no way to add attributes to the tags it generates, so where we want extra
+/// attributes on links and images, we render those tags ourselves
+fn add_link_and_image_attributes<'a>(
+  mut events: impl Iterator<Item = Event<'a>>,
+  options: &MarkdownOptions,
+) -> Vec<Event<'a>> {
+  let mut output = Vec::new();
+
+  while let Some(event) = events.next() {
+    match event {
+      Event::Start(Tag::Link(link_type, destination, title)) if options.link_targets => {
+        output.push(Event::Html(
+          link_open_tag(link_type, &destination, &title).into(),
+        ));
+      }
+      Event::End(Tag::Link(..)) if options.link_targets => {
+        output.push(Event::Html(CowStr::Borrowed("</a>")));
+      }
+      Event::Start(Tag::Image(_, destination, title)) if options.responsive_images => {
+        // everything up to the matching end tag is the image's alt text
+        let mut alt = String::new();
+        let mut depth = 1;
+        for inner_event in events.by_ref() {
+          match inner_event {
+            Event::Start(Tag::Image(..)) => depth += 1,
+            Event::End(Tag::Image(..)) => {
+              depth -= 1;
+              if depth == 0 {
+                break;
+              }
+            }
+            Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => alt.push(' '),
+            _ => {}
+          }
+        }
+
+        output.push(Event::Html(image_tag(&destination, &title, &alt).into()));
+      }
+      _ => output.push(event),
+    }
+  }
+
+  output
+}
+
 pub fn render_markdown(
   markdown: &str,
   image_attachments: &HashMap<String, active_storage_blobs::Model>,
+  url_builder: &UrlBuilder,
+) -> String {
+  render_markdown_with_options(
+    markdown,
+    image_attachments,
+    url_builder,
+    &MarkdownOptions::default(),
+  )
+}
+
+pub fn render_markdown_with_options(
+  markdown: &str,
+  image_attachments: &HashMap<String, active_storage_blobs::Model>,
+  url_builder: &UrlBuilder,
+  markdown_options: &MarkdownOptions,
 ) -> String {
   // no_intra_emphasis is implicitly part of commonmark so we don't need to/can't specify it here
   let mut options = Options::empty();
@@ -52,13 +287,11 @@ pub fn render_markdown(
     .map(|event| -> Event {
       match event {
         Event::Start(Tag::Image(link_type, destination, title)) => {
-          let blob = image_attachments.get(&destination.to_string());
-          if let Some(blob) = blob {
-            Event::Start(Tag::Image(
-              link_type,
-              build_active_storage_blob_url(blob).into(),
-              title,
-            ))
+          let url = image_attachments
+            .get(&destination.to_string())
+            .and_then(|blob| url_builder.blob_url(blob));
+          if let Some(url) = url {
+            Event::Start(Tag::Image(link_type, url.into(), title))
           } else {
             Event::Start(Tag::Image(link_type, destination, title))
           }
@@ -67,10 +300,77 @@ pub fn render_markdown(
       }
     });
 
-  // TODO figure out how to get target="_blank" rel="noreferrer noopener" onto all links
-  // TODO figure out how to get class="img-fluid" onto all images
-  // TODO strip_single_p
   let mut html_output = String::new();
-  html::push_html(&mut html_output, parser);
-  html_output
+  html::push_html(
+    &mut html_output,
+    add_link_and_image_attributes(parser, markdown_options).into_iter(),
+  );
+
+  if markdown_options.sanitize {
+    html_output = sanitize_html(&html_output);
+  }
+
+  if markdown_options.strip_single_p {
+    strip_single_p(&html_output).to_string()
+  } else {
+    html_output
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(markdown: &str, options: MarkdownOptions) -> String {
+    render_markdown_with_options(markdown, &HashMap::new(), &UrlBuilder::default(), &options)
+  }
+
+  #[test]
+  fn adds_targets_to_links_and_classes_to_images() {
+    let options = MarkdownOptions {
+      sanitize: false,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      render("[Games](https://example.com/games)", options),
+      r#"<a href="https://example.com/games" target="_blank" rel="noreferrer noopener">Games</a>"#
+    );
+    assert_eq!(
+      render("Write to gm@example.com", options),
+      r#"Write to <a href="mailto:gm@example.com" target="_blank" rel="noreferrer noopener">gm@example.com</a>"#
+    );
+    assert_eq!(
+      render("![A *big* map](map.png)", options),
+      r#"<img src="map.png" alt="A big map" class="img-fluid" />"#
+    );
+  }
+
+  #[test]
+  fn only_strips_a_single_paragraph() {
+    assert_eq!(strip_single_p("<p>One</p>\n"), "One");
+    assert_eq!(
+      strip_single_p("<p>One</p>\n<p>Two</p>\n"),
+      "<p>One</p>\n<p>Two</p>\n"
+    );
+    assert_eq!(
+      strip_single_p("<ul><li>One</li></ul>"),
+      "<ul><li>One</li></ul>"
+    );
+  }
+
+  #[test]
+  fn sanitizes_user_provided_html() {
+    let html = render(
+      "Hello <script>alert('hi')</script><b onclick=\"steal()\">there</b>\n\n[x](javascript:steal())",
+      MarkdownOptions::default(),
+    );
+
+    assert!(!html.contains("script"));
+    assert!(!html.contains("alert"));
+    assert!(!html.contains("onclick"));
+    assert!(!html.contains("javascript:"));
+    assert!(html.contains("<b>there</b>"));
+    assert!(html.contains(r#"target="_blank""#));
+  }
 }
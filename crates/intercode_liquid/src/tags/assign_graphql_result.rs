@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Debug, io::Write};
+use std::{collections::HashMap, fmt::Debug, future::Future, io::Write};
 
 use async_graphql::{indexmap::IndexMap, Variables};
 use async_graphql_value::ConstValue;
@@ -14,7 +14,10 @@ use liquid_core::{
   ValueCow,
 };
 use sea_orm::{ColumnTrait, QueryFilter, Select};
-use seawater::ConnectionWrapper;
+use seawater::{
+  render_budget::{current_render_budget, RenderBudgetExceeded},
+  ConnectionWrapper,
+};
 use tokio::runtime::Handle;
 
 use crate::GraphQLExecutor;
@@ -31,6 +34,38 @@ fn graphql_const_to_liquid_value(
   serde_json::from_value::<liquid_core::Value>(serde_json::to_value(value)?)
 }
 
+/// Runs a future to completion from inside a (synchronous) render.  If the render has a budget,
+/// this gives up once the budget's time runs out instead of waiting indefinitely, and cancels the
+/// future so it doesn't keep running (and holding a database connection) after the render fails.
+fn block_on_within_budget<T: Send + 'static>(
+  future: impl Future<Output = T> + Send + 'static,
+) -> Result<T> {
+  let mut handle = tokio::spawn(future);
+  let joined = match current_render_budget() {
+    Some(tracker) => {
+      let remaining = tracker.remaining_time();
+      let timed =
+        Handle::current().block_on(async { tokio::time::timeout(remaining, &mut handle).await });
+      match timed {
+        Ok(joined) => joined,
+        Err(_) => {
+          handle.abort();
+          return Err(
+            tracker
+              .exceed(RenderBudgetExceeded::WallTime(
+                tracker.budget().max_wall_time,
+              ))
+              .into(),
+          );
+        }
+      }
+    }
+    None => Handle::current().block_on(handle),
+  };
+
+  joined.map_err(|err| Error::with_msg(err.to_string()))
+}
+
 pub trait GraphQLExecutorBuilder: Send + Sync + DynClone {
   fn build_executor(&self) -> Box<dyn GraphQLExecutor>;
 }
@@ -194,20 +229,24 @@ impl AssignGraphQLResult {
 
 impl Renderable for AssignGraphQLResult {
   fn render_to(&self, _writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+    if let Some(tracker) = current_render_budget() {
+      tracker
+        .charge_graphql_query()
+        .map_err(Error::from)
+        .trace_with(|| self.trace().into())?;
+    }
+
     let db_ref = self.db.clone();
     let cms_graphql_queries_select = self
       .cms_parent_graphql_queries_scope
       .clone()
       .filter(cms_graphql_queries::Column::Identifier.eq(self.query_name.clone()));
-    let graphql_query_handle =
-      tokio::spawn(async move { cms_graphql_queries_select.one(db_ref.as_ref()).await });
-    let graphql_query = Handle::current()
-      .block_on(graphql_query_handle)
-      .unwrap()
-      .map_err(|err| liquid_core::Error::with_msg(err.to_string()))?
-      .ok_or_else(|| {
-        liquid_core::Error::with_msg(format!("GraphQL query not found: {}", self.query_name))
-      })?;
+    let graphql_query =
+      block_on_within_budget(async move { cms_graphql_queries_select.one(db_ref.as_ref()).await })?
+        .map_err(|err| liquid_core::Error::with_msg(err.to_string()))?
+        .ok_or_else(|| {
+          liquid_core::Error::with_msg(format!("GraphQL query not found: {}", self.query_name))
+        })?;
 
     let mut variables_map = IndexMap::with_capacity(self.arg_mapping.len());
     let variable_pairs = self
@@ -231,8 +270,7 @@ impl Renderable for AssignGraphQLResult {
       async_graphql::Request::new(graphql_query.query.unwrap_or_else(|| String::from("")))
         .variables(Variables::from_value(ConstValue::Object(variables_map)));
     let executor = self.graphql_executor_builder.build_executor();
-    let response_handle = tokio::spawn(async move { executor.execute(request).await });
-    let response = Handle::current().block_on(response_handle).unwrap();
+    let response = block_on_within_budget(async move { executor.execute(request).await })?;
 
     runtime.set_global(
       liquid_core::model::KString::from_string(self.destination.clone()),
@@ -254,3 +292,61 @@ impl Renderable for AssignGraphQLResult {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      Arc,
+    },
+    time::Duration,
+  };
+
+  use seawater::render_budget::{with_render_budget, RenderBudget, RenderBudgetTracker};
+
+  use super::*;
+
+  struct SetOnDrop(Arc<AtomicBool>);
+
+  impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+      self.0.store(true, Ordering::SeqCst);
+    }
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn cancels_work_that_runs_out_the_budget() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicBool::new(false));
+    let tracker = Arc::new(RenderBudgetTracker::new(RenderBudget {
+      max_wall_time: Duration::from_millis(50),
+      ..Default::default()
+    }));
+
+    let guard = SetOnDrop(dropped.clone());
+    let task_finished = finished.clone();
+    let result = tokio::task::spawn_blocking(move || {
+      with_render_budget(tracker, || {
+        block_on_within_budget(async move {
+          let _guard = guard;
+          tokio::time::sleep(Duration::from_secs(5)).await;
+          task_finished.store(true, Ordering::SeqCst);
+        })
+      })
+    })
+    .await
+    .unwrap();
+    assert!(result.is_err());
+
+    // aborting drops the task the next time the runtime gets to it
+    for _ in 0..100 {
+      if dropped.load(Ordering::SeqCst) {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(dropped.load(Ordering::SeqCst));
+    assert!(!finished.load(Ordering::SeqCst));
+  }
+}
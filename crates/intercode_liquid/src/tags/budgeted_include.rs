@@ -0,0 +1,46 @@
+use std::io::Write;
+
+use liquid_core::{Language, ParseTag, Renderable, Result, Runtime, TagReflection, TagTokenIter};
+use seawater::render_budget::current_render_budget;
+
+/// Wraps the include tag so that partials including partials can only nest as deep as the
+/// render budget allows
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BudgetedIncludeTag<T: ParseTag + Clone + 'static> {
+  inner: T,
+}
+
+impl<T: ParseTag + Clone + 'static> BudgetedIncludeTag<T> {
+  pub fn new(inner: T) -> Self {
+    Self { inner }
+  }
+}
+
+impl<T: ParseTag + Clone + 'static> ParseTag for BudgetedIncludeTag<T> {
+  fn parse(&self, arguments: TagTokenIter<'_>, options: &Language) -> Result<Box<dyn Renderable>> {
+    Ok(Box::new(BudgetedInclude {
+      inner: self.inner.parse(arguments, options)?,
+    }))
+  }
+
+  fn reflection(&self) -> &dyn TagReflection {
+    self.inner.reflection()
+  }
+}
+
+#[derive(Debug)]
+struct BudgetedInclude {
+  inner: Box<dyn Renderable>,
+}
+
+impl Renderable for BudgetedInclude {
+  fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+    match current_render_budget() {
+      Some(tracker) => {
+        let _depth = tracker.enter_include()?;
+        self.inner.render_to(writer, runtime)
+      }
+      None => self.inner.render_to(writer, runtime),
+    }
+  }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 
 use intercode_entities::cms_parent::{CmsParent, CmsParentTrait};
 use intercode_entities::{active_storage_attachments, active_storage_blobs, cms_files};
@@ -11,19 +11,21 @@ use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect, Select};
 use seawater::ConnectionWrapper;
 use tokio::runtime::Handle;
 
-use crate::build_active_storage_blob_url;
+use crate::url_builder::UrlBuilder;
 
 #[derive(Clone, Debug)]
 pub struct FileUrlTag {
   cms_parent_file_scope: Select<cms_files::Entity>,
   db: ConnectionWrapper,
+  url_builder: Arc<UrlBuilder>,
 }
 
 impl FileUrlTag {
-  pub fn new(cms_parent: &CmsParent, db: ConnectionWrapper) -> Self {
+  pub fn new(cms_parent: &CmsParent, db: ConnectionWrapper, url_builder: Arc<UrlBuilder>) -> Self {
     FileUrlTag {
       cms_parent_file_scope: cms_parent.cms_files(),
       db,
+      url_builder,
     }
   }
 }
@@ -53,6 +55,7 @@ impl ParseTag for FileUrlTag {
       cms_parent_file_scope: self.cms_parent_file_scope.clone(),
       filename,
       db: self.db.clone(),
+      url_builder: self.url_builder.clone(),
     }))
   }
 
@@ -66,6 +69,7 @@ struct FileUrl {
   filename: Expression,
   cms_parent_file_scope: Select<cms_files::Entity>,
   db: ConnectionWrapper,
+  url_builder: Arc<UrlBuilder>,
 }
 
 impl Renderable for FileUrl {
@@ -79,6 +83,7 @@ impl Renderable for FileUrl {
     let filename = filename.to_kstr().into_owned();
     let db = self.db.clone();
     let scope = self.cms_parent_file_scope.clone();
+    let blob_filename = filename.to_string();
 
     let attachment_handle = tokio::spawn(async move {
       active_storage_attachments::Entity::find()
@@ -88,6 +93,7 @@ impl Renderable for FileUrl {
           QuerySelect::query(&mut scope.select_only().column(cms_files::Column::Id)).to_owned(),
         ))
         .find_also_related(active_storage_blobs::Entity)
+        .filter(active_storage_blobs::Column::Filename.eq(blob_filename))
         .one(db.as_ref())
         .await
     });
@@ -114,7 +120,12 @@ impl Renderable for FileUrl {
       ))),
     }?;
 
-    let url = build_active_storage_blob_url(&blob);
+    let url = self.url_builder.blob_url(&blob).ok_or_else(|| {
+      liquid_core::Error::with_msg(format!(
+        "Can't build a URL for {}: no uploads host is configured",
+        filename
+      ))
+    })?;
 
     if let Err(error) = writer.write(url.as_bytes()) {
       Err(Error::with_msg(error.to_string()))
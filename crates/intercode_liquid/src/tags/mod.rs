@@ -1,5 +1,6 @@
 mod add_to_calendar_dropdown;
 mod assign_graphql_result;
+mod budgeted_include;
 mod cookie_consent;
 mod event_admin_menu;
 mod event_runs_section;
@@ -16,6 +17,7 @@ mod youtube;
 use super::react_component_tag::*;
 pub use add_to_calendar_dropdown::*;
 pub use assign_graphql_result::*;
+pub use budgeted_include::*;
 pub use cookie_consent::*;
 pub use event_admin_menu::*;
 pub use event_runs_section::*;
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 
 use liquid::Error;
 use liquid_core::{
@@ -6,8 +6,18 @@ use liquid_core::{
   ValueView,
 };
 
+use crate::url_builder::UrlBuilder;
+
 #[derive(Clone, Debug)]
-pub struct PageUrlTag;
+pub struct PageUrlTag {
+  url_builder: Arc<UrlBuilder>,
+}
+
+impl PageUrlTag {
+  pub fn new(url_builder: Arc<UrlBuilder>) -> Self {
+    PageUrlTag { url_builder }
+  }
+}
 
 impl TagReflection for PageUrlTag {
   fn tag(&self) -> &'static str {
@@ -30,7 +40,10 @@ impl ParseTag for PageUrlTag {
 
     arguments.expect_nothing()?;
 
-    Ok(Box::new(PageUrl { slug }))
+    Ok(Box::new(PageUrl {
+      slug,
+      url_builder: self.url_builder.clone(),
+    }))
   }
 
   fn reflection(&self) -> &dyn TagReflection {
@@ -41,6 +54,7 @@ impl ParseTag for PageUrlTag {
 #[derive(Debug)]
 struct PageUrl {
   slug: Expression,
+  url_builder: Arc<UrlBuilder>,
 }
 
 impl Renderable for PageUrl {
@@ -53,8 +67,7 @@ impl Renderable for PageUrl {
     }
     let slug = slug.to_kstr().into_owned();
 
-    // TODO do something actually real here
-    let url = format!("/pages/{}", slug);
+    let url = self.url_builder.page_url(&slug);
 
     if let Err(error) = writer.write(url.as_bytes()) {
       Err(Error::with_msg(error.to_string()))
@@ -0,0 +1,207 @@
+use std::env;
+
+use intercode_entities::{
+  active_storage_blobs,
+  cms_parent::{cms_page_path, CmsParent, CmsParentTrait},
+};
+use sea_orm::{ConnectionTrait, DbErr};
+use url::Url;
+
+/// Builds the URLs that rendered content links to.  Page paths come from cms_page_path, the same
+/// routing rules the server and frontend serve pages by, so a page moved between sites or
+/// domains still links to wherever it's served.  Uploaded files are served from the uploads host.
+#[derive(Clone, Debug, Default)]
+pub struct UrlBuilder {
+  host: Option<String>,
+  uploads_host: Option<String>,
+  root_page_slug: Option<String>,
+}
+
+fn configured_host(name: &str) -> Option<String> {
+  env::var(name)
+    .ok()
+    .map(|host| host.trim().to_string())
+    .filter(|host| !host.is_empty())
+}
+
+fn cms_parent_host(cms_parent: &CmsParent) -> Option<String> {
+  match cms_parent {
+    CmsParent::Convention(convention) => Some(convention.domain.clone()),
+    CmsParent::RootSite(_) => configured_host("INTERCODE_HOST"),
+  }
+}
+
+/// Where uploaded files are served from: UPLOADS_HOST if it's set, then ASSETS_HOST, then the S3
+/// bucket ActiveStorage keeps them in.  The site itself doesn't serve them.
+fn configured_uploads_host() -> Option<String> {
+  configured_host("UPLOADS_HOST")
+    .or_else(|| configured_host("ASSETS_HOST"))
+    .or_else(|| {
+      configured_host("AWS_S3_BUCKET").map(|bucket| format!("{}.s3.amazonaws.com", bucket))
+    })
+}
+
+impl UrlBuilder {
+  /// A builder for a given site host, serving files from the configured uploads host.  It doesn't
+  /// know about the site's root page.
+  pub fn for_host(host: Option<String>) -> Self {
+    UrlBuilder {
+      host,
+      uploads_host: configured_uploads_host(),
+      ..Default::default()
+    }
+  }
+
+  /// A builder for a CMS parent's domain, without its root page.  This is enough for building
+  /// file URLs and absolute URLs; use for_cms_parent for page URLs.
+  pub fn new(cms_parent: &CmsParent) -> Self {
+    Self::for_host(cms_parent_host(cms_parent))
+  }
+
+  /// A builder that knows everything about how a CMS parent routes its pages
+  pub async fn for_cms_parent<C: ConnectionTrait>(
+    db: &C,
+    cms_parent: &CmsParent,
+  ) -> Result<Self, DbErr> {
+    let root_page_slug = cms_parent
+      .root_page()
+      .one(db)
+      .await?
+      .and_then(|page| page.slug);
+
+    Ok(Self::new(cms_parent).with_root_page_slug(root_page_slug))
+  }
+
+  pub fn with_uploads_host(mut self, uploads_host: Option<String>) -> Self {
+    self.uploads_host = uploads_host;
+    self
+  }
+
+  pub fn with_root_page_slug(mut self, root_page_slug: Option<String>) -> Self {
+    self.root_page_slug = root_page_slug;
+    self
+  }
+
+  fn base_url(host: &str) -> Option<Url> {
+    Url::parse(&format!("https://{}/", host)).ok()
+  }
+
+  fn url_for_path(&self, host: Option<&str>, path: &str) -> String {
+    host
+      .and_then(Self::base_url)
+      .and_then(|base| base.join(path).ok())
+      .map(|url| url.to_string())
+      .unwrap_or_else(|| path.to_string())
+  }
+
+  pub fn page_path(&self, slug: &str) -> String {
+    cms_page_path(slug, self.root_page_slug.as_deref() == Some(slug))
+  }
+
+  /// The full URL of a page, or just its path if the site's host isn't known
+  pub fn page_url(&self, slug: &str) -> String {
+    self.url_for_path(self.host.as_deref(), &self.page_path(slug))
+  }
+
+  /// The URL of an uploaded file, or None if there's no uploads host to serve it from
+  pub fn blob_url(&self, blob: &active_storage_blobs::Model) -> Option<String> {
+    let base = Self::base_url(self.uploads_host.as_deref()?)?;
+    base.join(&blob.key).ok().map(|url| url.to_string())
+  }
+
+  /// Resolves a relative URL against the site, and moves absolute web URLs onto the site's host.
+  /// Other kinds of URL (mailto: and so on) pass through untouched.
+  pub fn absolute_url(&self, url: &str) -> Result<String, String> {
+    let Some(host) = &self.host else {
+      return Ok(url.to_string());
+    };
+    let base = Self::base_url(host).ok_or_else(|| format!("Invalid host: {}", host))?;
+    let mut parsed = base
+      .join(url)
+      .map_err(|error| format!("Can't parse URL: {}", error))?;
+
+    if matches!(parsed.scheme(), "http" | "https") {
+      parsed
+        .set_host(Some(host))
+        .map_err(|error| format!("Can't set host on URL: {}", error))?;
+    }
+
+    Ok(parsed.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn builder() -> UrlBuilder {
+    UrlBuilder::default()
+      .with_uploads_host(Some("uploads.example.com".to_string()))
+      .with_root_page_slug(Some("home".to_string()))
+  }
+
+  #[test]
+  fn routes_pages_like_the_frontend() {
+    let builder = UrlBuilder {
+      host: Some("con.example.com".to_string()),
+      ..builder()
+    };
+
+    assert_eq!(builder.page_url("home"), "https://con.example.com/");
+    assert_eq!(builder.page_url("faq"), "https://con.example.com/pages/faq");
+  }
+
+  #[test]
+  fn serves_files_from_the_uploads_host_only() {
+    let blob = active_storage_blobs::Model {
+      id: 1,
+      key: "abc123".to_string(),
+      filename: "logo.png".to_string(),
+      content_type: Some("image/png".to_string()),
+      metadata: None,
+      service_name: "amazon".to_string(),
+      byte_size: 100,
+      checksum: None,
+      created_at: Default::default(),
+    };
+    let builder = UrlBuilder {
+      host: Some("con.example.com".to_string()),
+      ..builder()
+    };
+
+    assert_eq!(
+      builder.blob_url(&blob).as_deref(),
+      Some("https://uploads.example.com/abc123")
+    );
+    assert_eq!(builder.with_uploads_host(None).blob_url(&blob), None);
+  }
+
+  #[test]
+  fn falls_back_to_paths_without_a_host() {
+    assert_eq!(builder().page_url("faq"), "/pages/faq");
+    assert_eq!(builder().absolute_url("/pages/faq").unwrap(), "/pages/faq");
+  }
+
+  #[test]
+  fn moves_absolute_urls_onto_the_site_host() {
+    let builder = UrlBuilder {
+      host: Some("staging.example.com".to_string()),
+      ..builder()
+    };
+
+    assert_eq!(
+      builder
+        .absolute_url("https://con.example.com/events?page=2")
+        .unwrap(),
+      "https://staging.example.com/events?page=2"
+    );
+    assert_eq!(
+      builder.absolute_url("events").unwrap(),
+      "https://staging.example.com/events"
+    );
+    assert_eq!(
+      builder.absolute_url("mailto:gm@example.com").unwrap(),
+      "mailto:gm@example.com"
+    );
+  }
+}
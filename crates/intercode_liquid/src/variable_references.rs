@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+
+// Identifiers that can appear in expression position but aren't variable lookups
+const EXPRESSION_KEYWORDS: &[&str] = &[
+  "and", "blank", "contains", "continue", "empty", "false", "in", "nil", "null", "or", "reversed",
+  "true",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+  Identifier(&'a str),
+  Dot,
+  Pipe,
+  Colon,
+  Other,
+}
+
+fn tokenize_expression(markup: &str) -> Vec<Token<'_>> {
+  let mut tokens = Vec::new();
+  let bytes = markup.as_bytes();
+  let mut index = 0;
+
+  while index < bytes.len() {
+    let c = bytes[index];
+
+    if c.is_ascii_whitespace() {
+      index += 1;
+    } else if c == b'"' || c == b'\'' {
+      index += 1;
+      while index < bytes.len() && bytes[index] != c {
+        index += 1;
+      }
+      index += 1;
+      tokens.push(Token::Other);
+    } else if c.is_ascii_digit() {
+      while index < bytes.len() && bytes[index].is_ascii_digit() {
+        index += 1;
+      }
+      if index + 1 < bytes.len() && bytes[index] == b'.' && bytes[index + 1].is_ascii_digit() {
+        index += 1;
+        while index < bytes.len() && bytes[index].is_ascii_digit() {
+          index += 1;
+        }
+      }
+      tokens.push(Token::Other);
+    } else if c.is_ascii_alphabetic() || c == b'_' {
+      let start = index;
+      while index < bytes.len()
+        && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_' || bytes[index] == b'-')
+      {
+        index += 1;
+      }
+      if index < bytes.len() && bytes[index] == b'?' {
+        index += 1;
+      }
+      tokens.push(Token::Identifier(&markup[start..index]));
+    } else if c == b'.' {
+      if index + 1 < bytes.len() && bytes[index + 1] == b'.' {
+        // a range like (1..count), so whatever follows is a lookup in its own right
+        index += 2;
+        tokens.push(Token::Other);
+      } else {
+        index += 1;
+        tokens.push(Token::Dot);
+      }
+    } else if c == b'|' {
+      index += 1;
+      tokens.push(Token::Pipe);
+    } else if c == b':' {
+      index += 1;
+      tokens.push(Token::Colon);
+    } else {
+      index += 1;
+      tokens.push(Token::Other);
+    }
+  }
+
+  tokens
+}
+
+/// Finds the names of the top-level variables an expression looks up.  Property accesses
+/// (`foo.bar`), filter names and keyword argument names are not variable lookups.
+fn root_variables_in_expression(markup: &str) -> Vec<&str> {
+  let tokens = tokenize_expression(markup);
+  let mut variables = Vec::new();
+
+  for (index, token) in tokens.iter().enumerate() {
+    let Token::Identifier(name) = token else {
+      continue;
+    };
+
+    let previous = index.checked_sub(1).and_then(|prev| tokens.get(prev));
+    if matches!(previous, Some(Token::Dot | Token::Pipe)) {
+      continue;
+    }
+
+    if matches!(tokens.get(index + 1), Some(Token::Colon)) {
+      continue;
+    }
+
+    if EXPRESSION_KEYWORDS.contains(name) {
+      continue;
+    }
+
+    variables.push(*name);
+  }
+
+  variables
+}
+
+pub(crate) enum Segment<'a> {
+  Output(&'a str),
+  Tag(&'a str),
+}
+
+pub(crate) fn template_segments(content: &str) -> Vec<Segment<'_>> {
+  let mut segments = Vec::new();
+  let mut rest = content;
+
+  while let Some(start) = rest.find('{') {
+    let after_brace = &rest[start + 1..];
+    let (closer, is_output) = if after_brace.starts_with('{') {
+      ("}}", true)
+    } else if after_brace.starts_with('%') {
+      ("%}", false)
+    } else {
+      rest = after_brace;
+      continue;
+    };
+
+    let inner_start = &after_brace[1..];
+    let Some(end) = inner_start.find(closer) else {
+      break;
+    };
+
+    let markup = inner_start[..end]
+      .trim_start_matches('-')
+      .trim_end_matches('-')
+      .trim();
+    segments.push(if is_output {
+      Segment::Output(markup)
+    } else {
+      Segment::Tag(markup)
+    });
+    rest = &inner_start[end + closer.len()..];
+  }
+
+  segments
+}
+
+pub(crate) fn split_tag_markup(markup: &str) -> (&str, &str) {
+  let markup = markup.trim();
+  match markup.find(|c: char| c.is_whitespace()) {
+    Some(index) => (&markup[..index], markup[index..].trim()),
+    None => (markup, ""),
+  }
+}
+
+struct VariableReferenceScanner {
+  defined: HashSet<String>,
+  undefined: Vec<String>,
+  skip_until: Option<&'static str>,
+}
+
+impl VariableReferenceScanner {
+  fn reference_all(&mut self, markup: &str) {
+    for name in root_variables_in_expression(markup) {
+      if !self.defined.contains(name) && !self.undefined.iter().any(|existing| existing == name) {
+        self.undefined.push(name.to_string());
+      }
+    }
+  }
+
+  fn define(&mut self, name: &str) {
+    self.defined.insert(name.to_string());
+  }
+
+  fn scan_tag(&mut self, markup: &str) {
+    let (tag_name, args) = split_tag_markup(markup);
+
+    if let Some(skip_until) = self.skip_until {
+      if tag_name == skip_until {
+        self.skip_until = None;
+      }
+      return;
+    }
+
+    match tag_name {
+      "raw" => self.skip_until = Some("endraw"),
+      "comment" => self.skip_until = Some("endcomment"),
+      "liquid" => {
+        for line in args.lines() {
+          self.scan_tag(line);
+        }
+      }
+      "if" | "elsif" | "unless" | "case" | "when" | "echo" | "cycle" => self.reference_all(args),
+      "assign" => {
+        if let Some((name, expression)) = args.split_once('=') {
+          self.reference_all(expression);
+          self.define(name.trim());
+        }
+      }
+      "for" | "tablerow" => {
+        if let Some((name, collection)) = args.split_once(" in ") {
+          self.reference_all(collection);
+          self.define(name.trim());
+          self.define(if tag_name == "for" {
+            "forloop"
+          } else {
+            "tablerowloop"
+          });
+        }
+      }
+      "capture" | "increment" | "decrement" => self.define(args.trim()),
+      "assign_graphql_result" => {
+        if let Some((name, _)) = args.split_once('=') {
+          self.define(name.trim());
+        }
+      }
+      // everything else either takes no expressions or takes literal arguments
+      _ => {}
+    }
+  }
+}
+
+/// Statically finds variables that a Liquid template reads without them being defined, either
+/// in `defined` or by the template itself (via assign, capture, for, etc).  Custom tags are
+/// assumed to take literal arguments.  Returns names in order of first use.
+pub fn find_undefined_variables(content: &str, defined: &[&str]) -> Vec<String> {
+  let mut scanner = VariableReferenceScanner {
+    defined: defined.iter().map(|name| name.to_string()).collect(),
+    undefined: Vec::new(),
+    skip_until: None,
+  };
+
+  for segment in template_segments(content) {
+    match segment {
+      Segment::Output(markup) => {
+        if scanner.skip_until.is_none() {
+          scanner.reference_all(markup)
+        }
+      }
+      Segment::Tag(markup) => scanner.scan_tag(markup),
+    }
+  }
+
+  scanner.undefined
+}
+
+#[cfg(test)]
+mod tests {
+  use super::find_undefined_variables;
+
+  #[test]
+  fn finds_undefined_root_variables() {
+    assert_eq!(
+      find_undefined_variables(
+        "Hi {{ signup_request.user_con_profile.name }}, see {{ evnt.title | upcase }}",
+        &["signup_request"]
+      ),
+      vec!["evnt"]
+    );
+  }
+
+  #[test]
+  fn ignores_filter_names_and_keyword_arguments() {
+    assert_eq!(
+      find_undefined_variables(
+        "{{ run.starts_at | date: \"%a\" | default: fallback }}",
+        &["run"]
+      ),
+      vec!["fallback"]
+    );
+  }
+
+  #[test]
+  fn respects_variables_defined_by_the_template() {
+    assert_eq!(
+      find_undefined_variables(
+        "{% assign n = event.runs.size %}{% for run in event.runs limit: n %}{{ forloop.index }}: \
+         {{ run.id }}{% endfor %}{% capture x %}hi{% endcapture %}{{ x }}{% if n > 1 and ok %}{% endif %}",
+        &["event"]
+      ),
+      vec!["ok"]
+    );
+  }
+
+  #[test]
+  fn skips_raw_and_comment_blocks() {
+    assert_eq!(
+      find_undefined_variables(
+        "{% raw %}{{ not_a_variable }}{% endraw %}{% comment %}{{ nope }}{% endcomment %}{{- yep -}}",
+        &[]
+      ),
+      vec!["yep"]
+    );
+  }
+
+  #[test]
+  fn handles_ranges_and_literals() {
+    assert_eq!(
+      find_undefined_variables(
+        "{% for i in (1..count) %}{{ 'string.with.dots' }}{{ 2.5 }}{{ empty }}{% endfor %}",
+        &[]
+      ),
+      vec!["count"]
+    );
+  }
+}
@@ -5,7 +5,7 @@ use futures::try_join;
 use intercode_entities::events;
 use intercode_liquid::liquid_datetime_to_chrono_datetime;
 use liquid::{ObjectView, ValueView};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Select};
+use sea_orm::Select;
 use seawater::{Context, DropError, DropResultTrait, ModelBackedDrop};
 use seawater::{DropResult, DropStore};
 
@@ -49,13 +49,10 @@ impl EventsCreatedSince {
     &self,
     start_date: Option<liquid::model::DateTime>,
   ) -> Select<events::Entity> {
-    let scope = events::Entity::find().filter(events::Column::ConventionId.eq(self.convention_id));
-
-    if let Some(start_date) = start_date {
-      scope.filter(events::Column::CreatedAt.gte(liquid_datetime_to_chrono_datetime(&start_date)))
-    } else {
-      scope
-    }
+    events::Entity::find_created_since(
+      self.convention_id,
+      start_date.map(|start_date| liquid_datetime_to_chrono_datetime(&start_date).naive_utc()),
+    )
   }
 
   async fn query_and_store(
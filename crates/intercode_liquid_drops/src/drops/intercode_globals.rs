@@ -104,3 +104,9 @@ impl IntercodeGlobals {
     }
   }
 }
+
+impl IntercodeGlobals {
+  /// The top-level variable names available to every template rendered with these globals
+  pub const VARIABLE_NAMES: &'static [&'static str] =
+    &["convention", "conventions", "event", "user_con_profile"];
+}
@@ -8,7 +8,9 @@ use intercode_liquid::{
   build_liquid_parser,
   cms_parent_partial_source::{LazyCmsPartialSource, PreloadPartialsStrategy},
   tags::GraphQLExecutorBuilder,
+  url_builder::UrlBuilder,
 };
+use liquid::Parser;
 use liquid_core::partials::LazyCompiler;
 use seawater::ConnectionWrapper;
 use std::{fmt::Debug, sync::Arc};
@@ -62,6 +64,38 @@ impl<B: GraphQLExecutorBuilder> IntercodeLiquidRenderer<B> {
   }
 }
 
+impl<B: GraphQLExecutorBuilder + Clone + 'static> IntercodeLiquidRenderer<B> {
+  async fn build_parser(
+    &self,
+    preload_partials_strategy: Option<PreloadPartialsStrategy<'_>>,
+  ) -> Result<Parser, liquid_core::Error> {
+    let partial_compiler = build_partial_compiler(
+      self.query_data.cms_parent().clone(),
+      self.query_data.db().clone(),
+      preload_partials_strategy,
+    )
+    .await?;
+    let user_signed_in = self.query_data.current_user().is_some();
+    let url_builder =
+      UrlBuilder::for_cms_parent(self.query_data.db(), self.query_data.cms_parent())
+        .await
+        .map_err(|db_err| {
+          liquid_core::Error::with_msg(format!("Error loading page routes: {}", db_err))
+        })?;
+
+    build_liquid_parser(
+      self.query_data.convention(),
+      Arc::downgrade(&self.schema_data.language_loader),
+      self.query_data.cms_parent(),
+      url_builder,
+      self.query_data.db().clone(),
+      user_signed_in,
+      Box::new(self.graphql_executor_builder.clone()),
+      partial_compiler,
+    )
+  }
+}
+
 #[async_trait]
 impl<B: GraphQLExecutorBuilder + Clone + 'static> LiquidRenderer for IntercodeLiquidRenderer<B> {
   async fn builtin_globals(
@@ -78,31 +112,20 @@ impl<B: GraphQLExecutorBuilder + Clone + 'static> LiquidRenderer for IntercodeLi
   ) -> Result<String, async_graphql::Error> {
     let schema_data: SchemaData = self.schema_data.clone();
     let query_data: QueryData = self.query_data.clone();
-    let cms_parent = query_data.cms_parent().clone();
-
-    let partial_compiler = build_partial_compiler(
-      cms_parent,
-      query_data.db().clone(),
-      preload_partials_strategy,
-    )
-    .await?;
-    let user_signed_in = query_data.current_user().is_some();
-
-    let parser = build_liquid_parser(
-      query_data.convention(),
-      Arc::downgrade(&schema_data.language_loader),
-      query_data.cms_parent(),
-      query_data.db().clone(),
-      user_signed_in,
-      Box::new(self.graphql_executor_builder.clone()),
-      partial_compiler,
-    )?;
+    let parser = self.build_parser(preload_partials_strategy).await?;
 
     let renderer = seawater::Renderer::new(
       parser,
       move |store| DropContext::new(schema_data.clone(), query_data.clone(), store),
       IntercodeGlobals::new,
-    );
+    )
+    .with_budget(self.schema_data.render_budget.clone());
     renderer.render_liquid(content, globals).await
   }
+
+  async fn parse_liquid(&self, content: &str) -> Result<(), async_graphql::Error> {
+    let parser = self.build_parser(None).await?;
+    parser.parse(content)?;
+    Ok(())
+  }
 }
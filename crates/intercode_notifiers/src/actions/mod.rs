@@ -0,0 +1,3 @@
+mod sms_webhook;
+
+pub use sms_webhook::*;
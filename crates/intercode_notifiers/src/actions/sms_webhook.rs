@@ -0,0 +1,68 @@
+use std::{collections::BTreeMap, env};
+
+use axum::{
+  debug_handler,
+  extract::{Host, OriginalUri},
+  response::IntoResponse,
+  Form,
+};
+use http::{header::CONTENT_TYPE, HeaderMap, StatusCode};
+use intercode_server::QueryDataFromRequest;
+use tracing::log::*;
+
+use crate::{
+  sms::{set_sms_consent_for_number, SmsKeyword},
+  SMS_TRANSPORT,
+};
+
+const EMPTY_TWIML_RESPONSE: &str =
+  "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Response></Response>";
+
+fn webhook_url(host: &str, uri: &OriginalUri) -> String {
+  // behind a proxy, the URL we see may not be the one the provider signed, so allow overriding it
+  env::var("SMS_WEBHOOK_URL").unwrap_or_else(|_| format!("https://{}{}", host, uri.0))
+}
+
+/// Receives inbound messages from the SMS provider.  We only act on opt-out and opt-in keywords,
+/// which update allow_sms on every profile with the sender's number.  Carriers require us to
+/// stop texting anyone who replies STOP, so this must keep working even when nothing else does.
+#[debug_handler]
+pub async fn sms_webhook(
+  Host(host): Host,
+  original_uri: OriginalUri,
+  headers: HeaderMap,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Form(params): Form<BTreeMap<String, String>>,
+) -> Result<impl IntoResponse, StatusCode> {
+  let signature = headers
+    .get("X-Twilio-Signature")
+    .and_then(|value| value.to_str().ok());
+
+  if !SMS_TRANSPORT.verify_webhook_signature(&webhook_url(&host, &original_uri), &params, signature)
+  {
+    warn!("Rejecting inbound SMS webhook request with invalid signature");
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  let (Some(from), Some(body)) = (params.get("From"), params.get("Body")) else {
+    return Err(StatusCode::BAD_REQUEST);
+  };
+
+  if let Some(allow_sms) = SmsKeyword::parse(body).and_then(|keyword| keyword.allow_sms()) {
+    let updated_count = set_sms_consent_for_number(from, allow_sms, query_data.db())
+      .await
+      .map_err(|err| {
+        error!("Error updating SMS consent: {:?}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+      })?;
+
+    info!(
+      "Set allow_sms to {} on {} profile(s) in response to inbound SMS keyword",
+      allow_sms, updated_count
+    );
+  }
+
+  // The provider sends its own confirmation replies for opt-out and opt-in keywords, so we
+  // respond with an empty TwiML document
+  Ok(([(CONTENT_TYPE, "text/xml")], EMPTY_TWIML_RESPONSE))
+}
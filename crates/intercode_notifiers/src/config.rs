@@ -7,6 +7,14 @@ fn default_sends_sms() -> bool {
   false
 }
 
+fn default_allows_opt_out() -> bool {
+  true
+}
+
+fn default_allows_digest() -> bool {
+  false
+}
+
 trait Keyed {
   fn get_key(&self) -> &str;
 
@@ -38,8 +46,16 @@ pub struct NotificationEventConfig {
   pub key: String,
   pub name: String,
   pub destination_description: String,
+  /// The Liquid variables this notification's templates get, besides the global ones
+  pub assigns: Vec<String>,
   #[serde(default = "default_sends_sms")]
   pub sends_sms: bool,
+  /// Whether recipients may turn this notification off entirely
+  #[serde(default = "default_allows_opt_out")]
+  pub allows_opt_out: bool,
+  /// Whether recipients may batch this notification into a daily digest
+  #[serde(default = "default_allows_digest")]
+  pub allows_digest: bool,
 }
 
 impl Keyed for NotificationEventConfig {
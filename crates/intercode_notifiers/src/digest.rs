@@ -0,0 +1,375 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  sync::Arc,
+  time::Duration,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use html_escape::encode_text;
+use intercode_email::outbox::new_outbox_email;
+use intercode_entities::{
+  conventions, events, notification_digest_items, runs, user_con_profiles, users,
+};
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+  QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use seawater::ConnectionWrapper;
+use tracing::log::*;
+
+use crate::RenderedNotification;
+
+/// How long the oldest item in someone's digest waits before the digest is sent
+pub const DIGEST_INTERVAL_HOURS: i64 = 24;
+
+/// Holds a rendered notification for each of the given profiles until their next digest.
+pub async fn enqueue_digest_items(
+  rendered: &RenderedNotification,
+  user_con_profiles: &[user_con_profiles::Model],
+  qualified_event_key: &str,
+  run_id: Option<i64>,
+  db: &ConnectionWrapper,
+) -> Result<(), DbErr> {
+  if user_con_profiles.is_empty() {
+    return Ok(());
+  }
+
+  let now = Utc::now().naive_utc();
+  notification_digest_items::Entity::insert_many(user_con_profiles.iter().map(|ucp| {
+    notification_digest_items::ActiveModel {
+      user_con_profile_id: Set(ucp.id),
+      event_key: Set(qualified_event_key.to_string()),
+      run_id: Set(run_id),
+      subject: Set(rendered.subject.clone()),
+      body_html: Set(rendered.body_html.clone()),
+      body_text: Set(rendered.body_text.clone()),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+  }))
+  .exec(db)
+  .await?;
+
+  Ok(())
+}
+
+/// One notification in a digest, along with the event and run it concerns (if any)
+#[derive(Debug, Clone, Default)]
+pub struct DigestEntry {
+  pub event_id: Option<i64>,
+  pub event_title: Option<String>,
+  pub run_id: Option<i64>,
+  pub run_starts_at: Option<NaiveDateTime>,
+  pub subject: String,
+  pub body_html: Option<String>,
+  pub body_text: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedDigest {
+  pub subject: String,
+  pub body_html: String,
+  pub body_text: String,
+}
+
+type EventGroupKey = (bool, String, i64);
+type RunGroupKey = (Option<NaiveDateTime>, i64);
+
+fn format_run_time(starts_at: NaiveDateTime, timezone: Option<Tz>) -> String {
+  match timezone {
+    Some(timezone) => starts_at
+      .and_utc()
+      .with_timezone(&timezone)
+      .format("%a %-I:%M%P %Z")
+      .to_string(),
+    None => starts_at.format("%a %-I:%M%P UTC").to_string(),
+  }
+}
+
+/// Combines a profile's pending notifications into a single email.  Notifications are grouped by
+/// event (alphabetically, with notifications not tied to an event last), then by run in start
+/// order, keeping the order they happened in within each group.
+pub fn build_digest(
+  convention_name: &str,
+  entries: &[DigestEntry],
+  timezone: Option<Tz>,
+) -> RenderedDigest {
+  let mut groups: BTreeMap<EventGroupKey, BTreeMap<RunGroupKey, Vec<&DigestEntry>>> =
+    BTreeMap::new();
+
+  for entry in entries {
+    let event_key = match (entry.event_id, &entry.event_title) {
+      (Some(event_id), Some(title)) => (false, title.to_lowercase(), event_id),
+      _ => (true, String::new(), 0),
+    };
+    let run_key = (entry.run_starts_at, entry.run_id.unwrap_or(0));
+
+    groups
+      .entry(event_key)
+      .or_default()
+      .entry(run_key)
+      .or_default()
+      .push(entry);
+  }
+
+  let mut body_html = String::new();
+  let mut body_text = String::new();
+
+  for ((no_event, _, _), runs) in &groups {
+    let event_title = if *no_event {
+      "Other notifications"
+    } else {
+      runs
+        .values()
+        .next()
+        .and_then(|entries| entries[0].event_title.as_deref())
+        .unwrap_or_default()
+    };
+    body_html.push_str(&format!("<h2>{}</h2>\n", encode_text(event_title)));
+    body_text.push_str(&format!("== {} ==\n\n", event_title));
+
+    for ((run_starts_at, _), run_entries) in runs {
+      if let Some(run_starts_at) = run_starts_at {
+        let run_time = format_run_time(*run_starts_at, timezone);
+        body_html.push_str(&format!("<h3>{}</h3>\n", encode_text(&run_time)));
+        body_text.push_str(&format!("-- {} --\n\n", run_time));
+      }
+
+      for entry in run_entries {
+        body_html.push_str(&format!("<h4>{}</h4>\n", encode_text(&entry.subject)));
+        match (&entry.body_html, &entry.body_text) {
+          (Some(html), _) => body_html.push_str(html),
+          (None, Some(text)) => body_html.push_str(&format!("<p>{}</p>", encode_text(text))),
+          (None, None) => {}
+        }
+        body_html.push('\n');
+
+        body_text.push_str(&entry.subject);
+        body_text.push('\n');
+        if let Some(text) = entry.body_text.as_ref().or(entry.body_html.as_ref()) {
+          body_text.push_str(text.trim());
+          body_text.push('\n');
+        }
+        body_text.push('\n');
+      }
+    }
+  }
+
+  RenderedDigest {
+    subject: format!(
+      "{}: {} new notification{}",
+      convention_name,
+      entries.len(),
+      if entries.len() == 1 { "" } else { "s" }
+    ),
+    body_html,
+    body_text,
+  }
+}
+
+async fn send_digest<C: ConnectionTrait>(user_con_profile_id: i64, db: &C) -> Result<(), DbErr> {
+  let items = notification_digest_items::Entity::find()
+    .filter(notification_digest_items::Column::UserConProfileId.eq(user_con_profile_id))
+    .filter(notification_digest_items::Column::DeliveredAt.is_null())
+    .order_by_asc(notification_digest_items::Column::Id)
+    .all(db)
+    .await?;
+  if items.is_empty() {
+    return Ok(());
+  }
+
+  let Some((user_con_profile, Some(user))) =
+    user_con_profiles::Entity::find_by_id(user_con_profile_id)
+      .find_also_related(users::Entity)
+      .one(db)
+      .await?
+  else {
+    return Ok(());
+  };
+  let convention = conventions::Entity::find_by_id(user_con_profile.convention_id)
+    .one(db)
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound("Convention not found".to_string()))?;
+
+  let runs_by_id = runs::Entity::find()
+    .filter(runs::Column::Id.is_in(items.iter().filter_map(|item| item.run_id)))
+    .find_also_related(events::Entity)
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|(run, event)| (run.id, (run, event)))
+    .collect::<HashMap<_, _>>();
+
+  let entries = items
+    .iter()
+    .map(|item| {
+      let run_and_event = item.run_id.and_then(|run_id| runs_by_id.get(&run_id));
+      let run = run_and_event.map(|(run, _)| run);
+      let event = run_and_event.and_then(|(_, event)| event.as_ref());
+
+      DigestEntry {
+        event_id: event.map(|event| event.id),
+        event_title: event.map(|event| event.title.clone()),
+        run_id: run.map(|run| run.id),
+        run_starts_at: run.map(|run| run.starts_at),
+        subject: item.subject.clone(),
+        body_html: item.body_html.clone(),
+        body_text: item.body_text.clone(),
+      }
+    })
+    .collect::<Vec<_>>();
+
+  let timezone = convention
+    .timezone_name
+    .as_deref()
+    .and_then(|name| name.parse::<Tz>().ok());
+  let digest = build_digest(
+    convention.name.as_deref().unwrap_or_default(),
+    &entries,
+    timezone,
+  );
+
+  let mut outbox_email = new_outbox_email(
+    &convention.email_from,
+    &user.email,
+    &digest.subject,
+    Some(digest.body_html),
+    Some(digest.body_text),
+  );
+  outbox_email.user_con_profile_id = Set(Some(user_con_profile.id));
+  let outbox_email = outbox_email.insert(db).await?;
+
+  let now = Utc::now().naive_utc();
+  notification_digest_items::Entity::update_many()
+    .col_expr(
+      notification_digest_items::Column::DeliveredAt,
+      Expr::value(now),
+    )
+    .col_expr(
+      notification_digest_items::Column::UpdatedAt,
+      Expr::value(now),
+    )
+    .col_expr(
+      notification_digest_items::Column::OutboxEmailId,
+      Expr::value(outbox_email.id),
+    )
+    .filter(notification_digest_items::Column::Id.is_in(items.iter().map(|item| item.id)))
+    .exec(db)
+    .await?;
+
+  Ok(())
+}
+
+/// Queues a digest email for every profile whose oldest undelivered digest item is at least
+/// DIGEST_INTERVAL_HOURS old.  Returns the number of digests queued.
+pub async fn send_due_digests(db: &DatabaseConnection) -> Result<usize, DbErr> {
+  let cutoff = Utc::now().naive_utc() - chrono::Duration::hours(DIGEST_INTERVAL_HOURS);
+  let user_con_profile_ids: Vec<i64> = notification_digest_items::Entity::find()
+    .select_only()
+    .column(notification_digest_items::Column::UserConProfileId)
+    .filter(notification_digest_items::Column::DeliveredAt.is_null())
+    .group_by(notification_digest_items::Column::UserConProfileId)
+    .having(
+      Expr::expr(Func::min(Expr::col(
+        notification_digest_items::Column::CreatedAt,
+      )))
+      .lte(cutoff),
+    )
+    .into_tuple()
+    .all(db)
+    .await?;
+
+  for user_con_profile_id in &user_con_profile_ids {
+    let txn = db.begin().await?;
+    send_digest(*user_con_profile_id, &txn).await?;
+    txn.commit().await?;
+  }
+
+  Ok(user_con_profile_ids.len())
+}
+
+/// Checks for due digests once an hour, forever.  The digests themselves go out through the
+/// outbox.
+pub async fn run_digest_worker(db: Arc<DatabaseConnection>) {
+  let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+
+  loop {
+    interval.tick().await;
+
+    match send_due_digests(&db).await {
+      Ok(0) => {}
+      Ok(count) => info!("Queued {} notification digest(s)", count),
+      Err(err) => error!("Error sending notification digests: {}", err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+
+  fn starts_at(hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 10, 17)
+      .unwrap()
+      .and_hms_opt(hour, 0, 0)
+      .unwrap()
+  }
+
+  fn entry(event: Option<(i64, &str)>, run: Option<(i64, u32)>, subject: &str) -> DigestEntry {
+    DigestEntry {
+      event_id: event.map(|(id, _)| id),
+      event_title: event.map(|(_, title)| title.to_string()),
+      run_id: run.map(|(id, _)| id),
+      run_starts_at: run.map(|(_, hour)| starts_at(hour)),
+      subject: subject.to_string(),
+      body_text: Some(format!("{} body", subject)),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn groups_by_event_then_run() {
+    let digest = build_digest(
+      "TestCon",
+      &[
+        entry(Some((2, "Zombie Hunt")), Some((20, 14)), "Signup 1"),
+        entry(None, None, "Misc"),
+        entry(Some((1, "Alpha Game")), Some((11, 18)), "Signup 2"),
+        entry(Some((1, "Alpha Game")), Some((10, 9)), "Signup 3"),
+        entry(Some((2, "Zombie Hunt")), Some((20, 14)), "Withdrawal 1"),
+      ],
+      None,
+    );
+
+    assert_eq!(digest.subject, "TestCon: 5 new notifications");
+    assert_eq!(
+      digest.body_text,
+      "== Alpha Game ==\n\n\
+-- Sat 9:00am UTC --\n\nSignup 3\nSignup 3 body\n\n\
+-- Sat 6:00pm UTC --\n\nSignup 2\nSignup 2 body\n\n\
+== Zombie Hunt ==\n\n\
+-- Sat 2:00pm UTC --\n\nSignup 1\nSignup 1 body\n\nWithdrawal 1\nWithdrawal 1 body\n\n\
+== Other notifications ==\n\nMisc\nMisc body\n\n"
+    );
+  }
+
+  #[test]
+  fn escapes_text_in_html() {
+    let digest = build_digest(
+      "TestCon",
+      &[entry(Some((1, "Cats & Dogs")), None, "<Hello>")],
+      Some(chrono_tz::America::New_York),
+    );
+
+    assert_eq!(digest.subject, "TestCon: 1 new notification");
+    assert_eq!(
+      digest.body_html,
+      "<h2>Cats &amp; Dogs</h2>\n<h4>&lt;Hello&gt;</h4>\n<p>&lt;Hello&gt; body</p>\n"
+    );
+  }
+}
@@ -1,36 +1,41 @@
+pub mod actions;
 mod config;
+mod digest;
+pub mod mutations;
 mod notification_destination;
+mod notification_preferences;
+mod notification_template_validation;
 mod notifier;
 mod notifier_preview;
+pub mod objects;
 pub mod partial_objects;
 mod rendered_notification;
 pub mod signup_requests;
+pub mod sms;
 
-use std::{env, sync::Arc};
+use std::sync::Arc;
 
 pub use config::*;
+pub use digest::*;
 pub use notification_destination::*;
+pub use notification_preferences::*;
+pub use notification_template_validation::*;
 pub use notifier::*;
 pub use notifier_preview::*;
 use once_cell::sync::Lazy;
 pub use rendered_notification::*;
-use tracing::log::*;
+use sms::{build_sms_transport_from_env, LocalSmsTransport, SmsTransport};
+use tracing::log::error;
 
-static TWILIO_CLIENT: Lazy<Arc<twilio::Client>> = Lazy::new(|| {
-  let sid = env::var("TWILIO_ACCOUNT_SID");
-  let auth_token = env::var("TWILIO_AUTH_TOKEN");
-  let (sid, auth_token) = match (sid, auth_token) {
-    (Ok(sid), Ok(auth_token)) => (sid, auth_token),
-    _ => {
-      warn!(
-        "Could not get TWILIO_ACCOUNT_SID and/or TWILIO_AUTH_TOKEN from environment, Twilio API operations will fail"
-      );
-      ("".to_string(), "".to_string())
-    }
-  };
-  Arc::new(twilio::Client::new(&sid, &auth_token))
+/// The server checks the SMS configuration with sms::validate_sms_transport_env before starting, so
+/// the fallback here only matters for command-line tools that never send texts
+pub static SMS_TRANSPORT: Lazy<Arc<dyn SmsTransport>> = Lazy::new(|| {
+  build_sms_transport_from_env().unwrap_or_else(|err| {
+    error!("{}; SMS messages will be logged locally", err.message);
+    Arc::new(LocalSmsTransport::new(None))
+  })
 });
 
 pub fn inject_request_data(req: async_graphql::BatchRequest) -> async_graphql::BatchRequest {
-  req.data::<Arc<twilio::Client>>(TWILIO_CLIENT.clone())
+  req.data::<Arc<dyn SmsTransport>>(SMS_TRANSPORT.clone())
 }
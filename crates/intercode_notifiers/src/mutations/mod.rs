@@ -0,0 +1,7 @@
+mod send_test_notification;
+mod update_notification_preferences;
+mod update_notification_template;
+
+pub use send_test_notification::*;
+pub use update_notification_preferences::*;
+pub use update_notification_template::*;
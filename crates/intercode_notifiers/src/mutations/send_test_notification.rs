@@ -0,0 +1,22 @@
+use async_graphql::{InputObject, SimpleObject};
+
+#[derive(InputObject)]
+pub struct SendTestNotificationInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The key of the notification event whose template should be sent.
+  #[graphql(name = "event_key")]
+  pub event_key: String,
+  /// If true, send the email version of the notification to the current user.
+  pub email: bool,
+  /// If true, send the SMS version of the notification to the current user's mobile phone.
+  pub sms: bool,
+}
+
+#[derive(SimpleObject)]
+pub struct SendTestNotificationPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+}
@@ -0,0 +1,33 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::{objects::NotificationPreferenceType, NotificationDeliveryMode};
+
+#[derive(InputObject)]
+pub struct NotificationPreferenceInput {
+  /// The qualified key of the notification event, e.g. signups/new_signup
+  #[graphql(name = "event_key")]
+  pub event_key: String,
+  #[graphql(name = "delivery_mode")]
+  pub delivery_mode: NotificationDeliveryMode,
+}
+
+#[derive(InputObject)]
+pub struct UpdateNotificationPreferencesInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The profile to update.  Defaults to the current user's profile in this convention.
+  #[graphql(name = "user_con_profile_id")]
+  pub user_con_profile_id: Option<ID>,
+  /// The preferences to change.  Events not listed here keep their current preference.
+  pub preferences: Vec<NotificationPreferenceInput>,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateNotificationPreferencesPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "notification_preferences")]
+  pub notification_preferences: Vec<NotificationPreferenceType>,
+}
@@ -0,0 +1,64 @@
+use async_graphql::{InputObject, SimpleObject};
+use intercode_cms::api::objects::NotificationTemplateType;
+use intercode_entities::notification_templates;
+use sea_orm::ActiveValue;
+
+use crate::NotificationTemplateContent;
+
+#[derive(InputObject, Default)]
+pub struct NotificationTemplateInput {
+  pub subject: Option<String>,
+  #[graphql(name = "body_html")]
+  pub body_html: Option<String>,
+  #[graphql(name = "body_text")]
+  pub body_text: Option<String>,
+  #[graphql(name = "body_sms")]
+  pub body_sms: Option<String>,
+}
+
+impl NotificationTemplateInput {
+  pub fn content(&self) -> NotificationTemplateContent<'_> {
+    NotificationTemplateContent {
+      subject: self.subject.as_deref(),
+      body_html: self.body_html.as_deref(),
+      body_text: self.body_text.as_deref(),
+      body_sms: self.body_sms.as_deref(),
+    }
+  }
+
+  /// Only the fields that were passed in get changed; the rest of the template stays as it was
+  pub fn apply_to(self, active_model: &mut notification_templates::ActiveModel) {
+    if let Some(subject) = self.subject {
+      active_model.subject = ActiveValue::Set(Some(subject));
+    }
+    if let Some(body_html) = self.body_html {
+      active_model.body_html = ActiveValue::Set(Some(body_html));
+    }
+    if let Some(body_text) = self.body_text {
+      active_model.body_text = ActiveValue::Set(Some(body_text));
+    }
+    if let Some(body_sms) = self.body_sms {
+      active_model.body_sms = ActiveValue::Set(Some(body_sms));
+    }
+  }
+}
+
+#[derive(InputObject)]
+pub struct UpdateNotificationTemplateInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "event_key")]
+  pub event_key: String,
+  #[graphql(name = "notification_template")]
+  pub notification_template: NotificationTemplateInput,
+}
+
+#[derive(SimpleObject)]
+pub struct UpdateNotificationTemplatePayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "notification_template")]
+  pub notification_template: NotificationTemplateType,
+}
@@ -4,6 +4,8 @@ use intercode_entities::{
 use sea_orm::{sea_query::Cond, ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect};
 use seawater::ConnectionWrapper;
 
+use crate::sms::sms_destination_for_profile;
+
 pub enum NotificationDestination {
   UserConProfile(user_con_profiles::Model),
   StaffPosition(staff_positions::Model),
@@ -92,13 +94,7 @@ impl NotificationDestination {
         .all(db)
         .await?
         .into_iter()
-        .filter_map(|ucp| {
-          if ucp.allow_sms {
-            ucp.mobile_phone
-          } else {
-            None
-          }
-        })
+        .filter_map(|ucp| sms_destination_for_profile(&ucp).map(str::to_string))
         .collect::<Vec<_>>(),
     );
 
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use async_graphql::Enum;
+use intercode_entities::{notification_preferences, user_con_profiles};
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
+use seawater::ConnectionWrapper;
+
+use crate::{
+  NotificationCategoryConfig, NotificationDestination, NotificationEventConfig,
+  RenderedNotification, NOTIFICATIONS_CONFIG,
+};
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NotificationDeliveryMode {
+  /// Send each notification as soon as it happens
+  #[graphql(name = "immediate")]
+  Immediate,
+  /// Collect notifications and send them in a single email once a day
+  #[graphql(name = "daily_digest")]
+  DailyDigest,
+  /// Don't send this notification
+  #[graphql(name = "off")]
+  Off,
+}
+
+impl NotificationDeliveryMode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      NotificationDeliveryMode::Immediate => "immediate",
+      NotificationDeliveryMode::DailyDigest => "daily_digest",
+      NotificationDeliveryMode::Off => "off",
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "immediate" => Some(NotificationDeliveryMode::Immediate),
+      "daily_digest" => Some(NotificationDeliveryMode::DailyDigest),
+      "off" => Some(NotificationDeliveryMode::Off),
+      _ => None,
+    }
+  }
+}
+
+impl NotificationEventConfig {
+  pub fn allowed_delivery_modes(&self) -> Vec<NotificationDeliveryMode> {
+    let mut modes = vec![NotificationDeliveryMode::Immediate];
+    if self.allows_digest {
+      modes.push(NotificationDeliveryMode::DailyDigest);
+    }
+    if self.allows_opt_out {
+      modes.push(NotificationDeliveryMode::Off);
+    }
+    modes
+  }
+
+  pub fn allows_delivery_mode(&self, mode: NotificationDeliveryMode) -> bool {
+    self.allowed_delivery_modes().contains(&mode)
+  }
+
+  /// Whether recipients have any choice about how this notification is delivered
+  pub fn has_delivery_preferences(&self) -> bool {
+    self.allows_digest || self.allows_opt_out
+  }
+}
+
+/// Looks up a notification event by its qualified key (e.g. "signups/new_signup")
+pub fn find_notification_event(
+  qualified_event_key: &str,
+) -> Option<(
+  &'static NotificationCategoryConfig,
+  &'static NotificationEventConfig,
+)> {
+  let (category_key, event_key) = qualified_event_key.split_once('/')?;
+  let category = NOTIFICATIONS_CONFIG.categories.get(category_key)?;
+  let event = category.events.get(event_key)?;
+  Some((category, event))
+}
+
+/// Loads the delivery mode each of the given profiles has chosen for a notification event.
+/// Profiles that haven't chosen, or whose choice the event no longer allows, get Immediate.
+pub async fn load_delivery_modes(
+  qualified_event_key: &str,
+  event: &NotificationEventConfig,
+  user_con_profile_ids: impl IntoIterator<Item = i64>,
+  db: &ConnectionWrapper,
+) -> Result<HashMap<i64, NotificationDeliveryMode>, DbErr> {
+  let user_con_profile_ids = user_con_profile_ids.into_iter().collect::<Vec<_>>();
+  let mut modes = user_con_profile_ids
+    .iter()
+    .map(|id| (*id, NotificationDeliveryMode::Immediate))
+    .collect::<HashMap<_, _>>();
+
+  if !event.has_delivery_preferences() || user_con_profile_ids.is_empty() {
+    return Ok(modes);
+  }
+
+  let preferences = notification_preferences::Entity::find()
+    .filter(notification_preferences::Column::EventKey.eq(qualified_event_key))
+    .filter(notification_preferences::Column::UserConProfileId.is_in(user_con_profile_ids))
+    .all(db)
+    .await?;
+
+  for preference in preferences {
+    if let Some(mode) = NotificationDeliveryMode::parse(&preference.delivery_mode) {
+      if event.allows_delivery_mode(mode) {
+        modes.insert(preference.user_con_profile_id, mode);
+      }
+    }
+  }
+
+  Ok(modes)
+}
+
+/// Removes destinations that have opted out of a notification or asked for it in their daily
+/// digest.  Returns the profiles that want it in their digest.  Staff position destinations
+/// always receive notifications immediately.
+pub async fn apply_delivery_preferences(
+  rendered: &mut RenderedNotification,
+  qualified_event_key: &str,
+  event: &NotificationEventConfig,
+  db: &ConnectionWrapper,
+) -> Result<Vec<user_con_profiles::Model>, DbErr> {
+  let modes = load_delivery_modes(
+    qualified_event_key,
+    event,
+    rendered
+      .destinations
+      .iter()
+      .filter_map(|destination| match destination {
+        NotificationDestination::UserConProfile(ucp) => Some(ucp.id),
+        NotificationDestination::StaffPosition(_) => None,
+      }),
+    db,
+  )
+  .await?;
+
+  let mut digest_recipients = Vec::new();
+  let destinations = std::mem::take(&mut rendered.destinations);
+  for destination in destinations {
+    let mode = match &destination {
+      NotificationDestination::UserConProfile(ucp) => modes
+        .get(&ucp.id)
+        .copied()
+        .unwrap_or(NotificationDeliveryMode::Immediate),
+      NotificationDestination::StaffPosition(_) => NotificationDeliveryMode::Immediate,
+    };
+
+    match (mode, destination) {
+      (NotificationDeliveryMode::Immediate, destination) => rendered.destinations.push(destination),
+      (NotificationDeliveryMode::DailyDigest, NotificationDestination::UserConProfile(ucp)) => {
+        digest_recipients.push(ucp)
+      }
+      _ => {}
+    }
+  }
+
+  Ok(digest_recipients)
+}
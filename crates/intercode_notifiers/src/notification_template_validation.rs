@@ -0,0 +1,68 @@
+use async_graphql::Error;
+use intercode_graphql_core::liquid_renderer::LiquidRenderer;
+use intercode_liquid::variable_references::find_undefined_variables;
+use intercode_liquid_drops::drops::IntercodeGlobals;
+
+use crate::Notifier;
+
+pub struct NotificationTemplateContent<'a> {
+  pub subject: Option<&'a str>,
+  pub body_html: Option<&'a str>,
+  pub body_text: Option<&'a str>,
+  pub body_sms: Option<&'a str>,
+}
+
+impl<'a> NotificationTemplateContent<'a> {
+  fn fields(&self) -> [(&'static str, Option<&'a str>); 4] {
+    [
+      ("subject", self.subject),
+      ("body_html", self.body_html),
+      ("body_text", self.body_text),
+      ("body_sms", self.body_sms),
+    ]
+  }
+}
+
+/// Checks that every field of a notification template parses as Liquid and only reads variables
+/// the notifier will actually assign.
+pub async fn validate_notification_template(
+  content: &NotificationTemplateContent<'_>,
+  preview_notifier: &dyn Notifier,
+  liquid_renderer: &dyn LiquidRenderer,
+) -> Result<(), Error> {
+  let assigns = preview_notifier.get_liquid_assigns();
+  let defined_variables = IntercodeGlobals::VARIABLE_NAMES
+    .iter()
+    .copied()
+    .chain(assigns.keys().map(|key| key.as_str()))
+    .collect::<Vec<_>>();
+
+  let mut errors: Vec<String> = Vec::new();
+
+  for (field_name, value) in content.fields() {
+    let Some(value) = value else {
+      continue;
+    };
+
+    if let Err(err) = liquid_renderer.parse_liquid(value).await {
+      errors.push(format!("{}: {}", field_name, err.message));
+      continue;
+    }
+
+    let undefined = find_undefined_variables(value, &defined_variables);
+    if !undefined.is_empty() {
+      errors.push(format!(
+        "{}: undefined variable{} {}",
+        field_name,
+        if undefined.len() == 1 { "" } else { "s" },
+        undefined.join(", ")
+      ));
+    }
+  }
+
+  if errors.is_empty() {
+    Ok(())
+  } else {
+    Err(Error::new(errors.join("; ")))
+  }
+}
@@ -8,8 +8,8 @@ use sea_orm::{ColumnTrait, DbErr, ModelTrait, QueryFilter};
 use seawater::ConnectionWrapper;
 
 use crate::{
-  NotificationCategoryConfig, NotificationDestination, NotificationEventConfig,
-  RenderedNotification, NOTIFICATIONS_CONFIG,
+  apply_delivery_preferences, enqueue_digest_items, sms::SmsTransport, NotificationCategoryConfig,
+  NotificationDestination, NotificationEventConfig, RenderedNotification, NOTIFICATIONS_CONFIG,
 };
 
 #[async_trait]
@@ -18,6 +18,12 @@ pub trait Notifier: Send + Sync {
   fn get_category_key(&self) -> &str;
   fn get_event_key(&self) -> &str;
   fn get_liquid_assigns(&self) -> liquid::Object;
+
+  /// The run this notification is about, if any.  Used to group notifications in digests.
+  fn digest_run_id(&self) -> Option<i64> {
+    None
+  }
+
   async fn get_destinations(
     &self,
     db: &ConnectionWrapper,
@@ -80,7 +86,19 @@ pub trait Notifier: Send + Sync {
     liquid_renderer: &dyn LiquidRenderer,
     db: &ConnectionWrapper,
   ) -> Result<RenderedNotification, Error> {
-    let (subject, body_html, body_text, body_sms, destinations) = try_join!(
+    let destinations = self.get_destinations(db).await?;
+    self
+      .render_for_destinations(notification_template, liquid_renderer, destinations)
+      .await
+  }
+
+  async fn render_for_destinations(
+    &self,
+    notification_template: &notification_templates::Model,
+    liquid_renderer: &dyn LiquidRenderer,
+    destinations: Vec<NotificationDestination>,
+  ) -> Result<RenderedNotification, Error> {
+    let (subject, body_html, body_text, body_sms) = try_join!(
       self.render_content(
         notification_template.subject.as_deref().unwrap_or_default(),
         liquid_renderer,
@@ -105,8 +123,7 @@ pub trait Notifier: Send + Sync {
           .as_deref()
           .unwrap_or_default(),
         liquid_renderer,
-      ),
-      self.get_destinations(db)
+      )
     )?;
 
     Ok(RenderedNotification {
@@ -130,7 +147,7 @@ pub trait Notifier: Send + Sync {
     })
   }
 
-  fn should_send_sms(&self) -> bool {
+  fn should_send_sms(&self, sms_transport: &dyn SmsTransport) -> bool {
     let event = self.get_event();
 
     if event.sends_sms {
@@ -138,7 +155,7 @@ pub trait Notifier: Send + Sync {
         return true;
       }
 
-      if env::var("TWILIO_SMS_NUMBER").is_err() {
+      if sms_transport.from_number().is_none() {
         return false;
       }
 
@@ -158,18 +175,33 @@ pub trait Notifier: Send + Sync {
     &self,
     notification_template: &notification_templates::Model,
     liquid_renderer: &dyn LiquidRenderer,
+    sms_transport: &dyn SmsTransport,
     db: &ConnectionWrapper,
   ) -> Result<(), Error> {
     let convention = self.get_convention();
-    let rendered = self
+    let mut rendered = self
       .render(notification_template, liquid_renderer, db)
       .await?;
 
+    let qualified_event_key = self.get_qualified_event_key();
+    let digest_recipients =
+      apply_delivery_preferences(&mut rendered, &qualified_event_key, self.get_event(), db).await?;
+    enqueue_digest_items(
+      &rendered,
+      &digest_recipients,
+      &qualified_event_key,
+      self.digest_run_id(),
+      db,
+    )
+    .await?;
+
+    if rendered.destinations.is_empty() {
+      return Ok(());
+    }
+
     try_join!(rendered.send_email(&convention.email_from, db), async {
-      if self.should_send_sms() {
-        rendered
-          .send_sms(&env::var("TWILIO_SMS_NUMBER").unwrap(), db)
-          .await
+      if self.should_send_sms(sms_transport) {
+        rendered.send_sms(sms_transport, db).await
       } else {
         Ok(())
       }
@@ -1,14 +1,17 @@
 use std::fmt::Display;
 
-use async_graphql::Error;
+use async_graphql::{async_trait::async_trait, Error};
 use intercode_entities::{conventions, events, runs, signup_requests, user_con_profiles, users};
 use intercode_liquid_drops::drops::{
   DropContext, EventDrop, RunDrop, SignupRequestDrop, UserConProfileDrop, UserDrop,
 };
-use liquid::object;
-use seawater::{Context, DropResult, LiquidDrop, ModelBackedDrop};
+use liquid::{model::Value, object};
+use seawater::{ConnectionWrapper, Context, DropResult, LiquidDrop, ModelBackedDrop};
 
-use crate::{signup_requests::RequestAcceptedNotifier, Notifier};
+use crate::{
+  find_notification_event, signup_requests::RequestAcceptedNotifier, NotificationCategoryConfig,
+  NotificationDestination, NotificationEventConfig, Notifier,
+};
 
 pub struct UnknownNotifierKeyError(String);
 
@@ -110,6 +113,45 @@ fn mock_signup_request_drop(ctx: DropContext) -> DropResult<SignupRequestDrop> {
   })
 }
 
+/// A stand-in for notifiers we can't build realistic placeholder data for yet.  It assigns every
+/// variable the event's templates may use, but leaves them all blank.
+pub struct PlaceholderNotifier {
+  convention: conventions::Model,
+  category: &'static NotificationCategoryConfig,
+  event: &'static NotificationEventConfig,
+}
+
+#[async_trait]
+impl Notifier for PlaceholderNotifier {
+  fn get_convention(&self) -> &conventions::Model {
+    &self.convention
+  }
+
+  fn get_category_key(&self) -> &str {
+    &self.category.key
+  }
+
+  fn get_event_key(&self) -> &str {
+    &self.event.key
+  }
+
+  fn get_liquid_assigns(&self) -> liquid::Object {
+    self
+      .event
+      .assigns
+      .iter()
+      .map(|assign| (assign.clone().into(), Value::Nil))
+      .collect()
+  }
+
+  async fn get_destinations(
+    &self,
+    _db: &ConnectionWrapper,
+  ) -> Result<Vec<NotificationDestination>, Error> {
+    Ok(vec![])
+  }
+}
+
 pub fn build_notifier_preview(
   convention: &conventions::Model,
   event_key: &str,
@@ -125,6 +167,35 @@ pub fn build_notifier_preview(
         }),
       )))
     }
-    _ => Err(UnknownNotifierKeyError(event_key.to_string()).into()),
+    _ => {
+      let (category, event) = find_notification_event(event_key)
+        .ok_or_else(|| UnknownNotifierKeyError(event_key.to_string()))?;
+      Ok(Box::new(PlaceholderNotifier {
+        convention: convention.clone(),
+        category,
+        event,
+      }))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn placeholder_notifiers_assign_the_configured_variables() {
+    let (category, event) = find_notification_event("signups/withdrawal").unwrap();
+    let notifier = PlaceholderNotifier {
+      convention: conventions::Model::default(),
+      category,
+      event,
+    };
+
+    let assigns = notifier.get_liquid_assigns();
+    assert_eq!(notifier.get_qualified_event_key(), "signups/withdrawal");
+    assert_eq!(assigns.len(), event.assigns.len());
+    assert!(assigns.contains_key("signup"));
+    assert!(assigns.contains_key("prev_state"));
   }
 }
@@ -0,0 +1,3 @@
+mod notification_preference_type;
+
+pub use notification_preference_type::*;
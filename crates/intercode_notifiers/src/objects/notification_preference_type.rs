@@ -0,0 +1,69 @@
+use async_graphql::SimpleObject;
+use intercode_entities::notification_preferences;
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
+use seawater::ConnectionWrapper;
+
+use crate::{NotificationDeliveryMode, NOTIFICATIONS_CONFIG};
+
+#[derive(SimpleObject)]
+#[graphql(name = "NotificationPreference")]
+pub struct NotificationPreferenceType {
+  /// The qualified key of the notification event, e.g. signups/new_signup
+  #[graphql(name = "event_key")]
+  pub event_key: String,
+  #[graphql(name = "category_name")]
+  pub category_name: String,
+  #[graphql(name = "event_name")]
+  pub event_name: String,
+  #[graphql(name = "delivery_mode")]
+  pub delivery_mode: NotificationDeliveryMode,
+  #[graphql(name = "allowed_delivery_modes")]
+  pub allowed_delivery_modes: Vec<NotificationDeliveryMode>,
+}
+
+impl NotificationPreferenceType {
+  /// Lists every notification event a profile can set a preference for, along with the
+  /// preference they've chosen (or the default).
+  pub async fn load_for_user_con_profile(
+    user_con_profile_id: i64,
+    db: &ConnectionWrapper,
+  ) -> Result<Vec<Self>, DbErr> {
+    let saved_preferences = notification_preferences::Entity::find()
+      .filter(notification_preferences::Column::UserConProfileId.eq(user_con_profile_id))
+      .all(db)
+      .await?;
+
+    let mut categories = NOTIFICATIONS_CONFIG.categories.values().collect::<Vec<_>>();
+    categories.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut preferences = Vec::new();
+    for category in categories {
+      let mut events = category
+        .events
+        .values()
+        .filter(|event| event.has_delivery_preferences())
+        .collect::<Vec<_>>();
+      events.sort_by(|a, b| a.key.cmp(&b.key));
+
+      for event in events {
+        let event_key = format!("{}/{}", category.key, event.key);
+        let delivery_mode = saved_preferences
+          .iter()
+          .find(|preference| preference.event_key == event_key)
+          .and_then(|preference| NotificationDeliveryMode::parse(&preference.delivery_mode))
+          .filter(|mode| event.allows_delivery_mode(*mode))
+          .unwrap_or(NotificationDeliveryMode::Immediate);
+
+        preferences.push(NotificationPreferenceType {
+          event_key,
+          category_name: category.name.clone(),
+          event_name: event.name.clone(),
+          delivery_mode,
+          allowed_delivery_modes: event.allowed_delivery_modes(),
+        });
+      }
+    }
+
+    Ok(preferences)
+  }
+}
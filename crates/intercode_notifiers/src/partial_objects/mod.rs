@@ -1,3 +1,7 @@
 mod convention_notifiers_fields;
+mod mutation_root_notifiers_fields;
+mod user_con_profile_notifiers_fields;
 
 pub use convention_notifiers_fields::*;
+pub use mutation_root_notifiers_fields::*;
+pub use user_con_profile_notifiers_fields::*;
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use async_graphql::*;
+use intercode_cms::api::{objects::NotificationTemplateType, policies::NotificationTemplatePolicy};
+use intercode_entities::{
+  conventions, notification_preferences, notification_templates, user_con_profiles,
+};
+use intercode_graphql_core::{
+  liquid_renderer::LiquidRenderer, query_data::QueryData, schema_data::SchemaData, ModelBackedType,
+};
+use intercode_liquid_drops::drops::DropContext;
+use intercode_policies::{
+  ensure_action_permitted,
+  policies::{UserConProfileAction, UserConProfilePolicy},
+  ReadManageAction,
+};
+use sea_orm::{
+  sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, ModelTrait,
+  QueryFilter, Set, TransactionTrait,
+};
+use seawater::DropStore;
+
+use crate::{
+  build_notifier_preview, find_notification_event,
+  mutations::{
+    SendTestNotificationInput, SendTestNotificationPayload, UpdateNotificationPreferencesInput,
+    UpdateNotificationPreferencesPayload, UpdateNotificationTemplateInput,
+    UpdateNotificationTemplatePayload,
+  },
+  objects::NotificationPreferenceType,
+  sms::SmsTransport,
+  validate_notification_template, NotificationDeliveryMode, NotificationDestination,
+  NotificationTemplateContent,
+};
+
+async fn find_notification_template(
+  ctx: &Context<'_>,
+  event_key: &str,
+) -> Result<(conventions::Model, notification_templates::Model), Error> {
+  let query_data = ctx.data::<QueryData>()?;
+  let convention = query_data
+    .convention()
+    .ok_or_else(|| Error::new("Notification templates can only be managed within a convention"))?;
+
+  let notification_template = convention
+    .find_related(notification_templates::Entity)
+    .filter(notification_templates::Column::EventKey.eq(event_key))
+    .one(query_data.db())
+    .await?
+    .ok_or_else(|| {
+      DbErr::RecordNotFound(format!(
+        "Notification template for {} not found in {}",
+        event_key,
+        convention.name.as_deref().unwrap_or_default()
+      ))
+    })?;
+
+  ensure_action_permitted::<NotificationTemplatePolicy, _>(
+    ctx,
+    &ReadManageAction::Manage,
+    &notification_template,
+  )
+  .await?;
+
+  Ok((convention.clone(), notification_template))
+}
+
+#[derive(Default)]
+pub struct MutationRootNotifiersFields;
+
+#[Object]
+impl MutationRootNotifiersFields {
+  /// Updates the Liquid content of one of the current convention's notification templates.  All
+  /// fields must parse as valid Liquid and may only reference variables provided to that
+  /// notification.
+  async fn update_notification_template(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateNotificationTemplateInput,
+  ) -> Result<UpdateNotificationTemplatePayload, Error> {
+    let schema_data = ctx.data::<SchemaData>()?;
+    let query_data = ctx.data::<QueryData>()?;
+    let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
+    let (convention, notification_template) =
+      find_notification_template(ctx, &input.event_key).await?;
+
+    let store = DropStore::new();
+    let preview_notifier = build_notifier_preview(
+      &convention,
+      &input.event_key,
+      DropContext::new(
+        schema_data.clone(),
+        query_data.clone_ref(),
+        Arc::downgrade(&store),
+      ),
+    )?;
+
+    let fields = input.notification_template;
+    validate_notification_template(
+      &fields.content(),
+      preview_notifier.as_ref(),
+      liquid_renderer.as_ref(),
+    )
+    .await?;
+
+    let mut active_model: notification_templates::ActiveModel = notification_template.into();
+    fields.apply_to(&mut active_model);
+    active_model.updated_at = Set(chrono::Utc::now().naive_utc());
+    let notification_template = active_model.update(query_data.db()).await?;
+
+    Ok(UpdateNotificationTemplatePayload {
+      client_mutation_id: input.client_mutation_id,
+      notification_template: NotificationTemplateType::new(notification_template),
+    })
+  }
+
+  /// Renders a notification template using placeholder data and sends it to the current user
+  /// only, so that staff can see what it will look like when it arrives.
+  async fn send_test_notification(
+    &self,
+    ctx: &Context<'_>,
+    input: SendTestNotificationInput,
+  ) -> Result<SendTestNotificationPayload, Error> {
+    let schema_data = ctx.data::<SchemaData>()?;
+    let query_data = ctx.data::<QueryData>()?;
+    let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
+    let sms_transport = ctx.data::<Arc<dyn SmsTransport>>()?;
+    let (convention, notification_template) =
+      find_notification_template(ctx, &input.event_key).await?;
+    let user_con_profile = query_data
+      .user_con_profile()
+      .ok_or_else(|| Error::new("You must have a profile in this convention to send a test"))?;
+
+    let store = DropStore::new();
+    let notifier = build_notifier_preview(
+      &convention,
+      &input.event_key,
+      DropContext::new(
+        schema_data.clone(),
+        query_data.clone_ref(),
+        Arc::downgrade(&store),
+      ),
+    )?;
+
+    let rendered = notifier
+      .render_for_destinations(
+        &notification_template,
+        liquid_renderer.as_ref(),
+        vec![NotificationDestination::UserConProfile(
+          user_con_profile.clone(),
+        )],
+      )
+      .await?;
+
+    if input.email {
+      rendered
+        .send_email(&convention.email_from, query_data.db())
+        .await?;
+    }
+
+    if input.sms {
+      rendered
+        .send_sms(sms_transport.as_ref(), query_data.db())
+        .await?;
+    }
+
+    Ok(SendTestNotificationPayload {
+      client_mutation_id: input.client_mutation_id,
+    })
+  }
+
+  /// Changes how a profile receives notifications.  Each event can be delivered immediately,
+  /// batched into a daily digest, or turned off, depending on what that event allows.
+  async fn update_notification_preferences(
+    &self,
+    ctx: &Context<'_>,
+    input: UpdateNotificationPreferencesInput,
+  ) -> Result<UpdateNotificationPreferencesPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user_con_profile = match &input.user_con_profile_id {
+      Some(id) => user_con_profiles::Entity::find_by_id(id.parse::<i64>()?)
+        .one(query_data.db())
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("UserConProfile {} not found", id.0)))?,
+      None => query_data
+        .user_con_profile()
+        .cloned()
+        .ok_or_else(|| Error::new("You do not have a profile in this convention"))?,
+    };
+
+    ensure_action_permitted::<UserConProfilePolicy, _>(
+      ctx,
+      &UserConProfileAction::Update,
+      &user_con_profile,
+    )
+    .await?;
+
+    for preference in &input.preferences {
+      let (_, event) = find_notification_event(&preference.event_key).ok_or_else(|| {
+        Error::new(format!(
+          "Unknown notification event: {}",
+          preference.event_key
+        ))
+      })?;
+      if !event.allows_delivery_mode(preference.delivery_mode) {
+        return Err(Error::new(format!(
+          "{} can't be set to {}",
+          event.name,
+          preference.delivery_mode.as_str()
+        )));
+      }
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let txn = query_data.db().begin().await?;
+    for preference in input.preferences {
+      if preference.delivery_mode == NotificationDeliveryMode::Immediate {
+        notification_preferences::Entity::delete_many()
+          .filter(notification_preferences::Column::UserConProfileId.eq(user_con_profile.id))
+          .filter(notification_preferences::Column::EventKey.eq(&preference.event_key))
+          .exec(&txn)
+          .await?;
+        continue;
+      }
+
+      notification_preferences::Entity::insert(notification_preferences::ActiveModel {
+        user_con_profile_id: Set(user_con_profile.id),
+        event_key: Set(preference.event_key),
+        delivery_mode: Set(preference.delivery_mode.as_str().to_string()),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+      })
+      .on_conflict(
+        OnConflict::columns([
+          notification_preferences::Column::UserConProfileId,
+          notification_preferences::Column::EventKey,
+        ])
+        .update_columns([
+          notification_preferences::Column::DeliveryMode,
+          notification_preferences::Column::UpdatedAt,
+        ])
+        .to_owned(),
+      )
+      .exec(&txn)
+      .await?;
+    }
+    txn.commit().await?;
+
+    Ok(UpdateNotificationPreferencesPayload {
+      client_mutation_id: input.client_mutation_id,
+      notification_preferences: NotificationPreferenceType::load_for_user_con_profile(
+        user_con_profile.id,
+        query_data.db(),
+      )
+      .await?,
+    })
+  }
+}
@@ -0,0 +1,27 @@
+use async_graphql::*;
+use intercode_entities::user_con_profiles;
+use intercode_graphql_core::{model_backed_type, query_data::QueryData};
+use intercode_policies::{
+  policies::{UserConProfileAction, UserConProfilePolicy},
+  ModelBackedTypeGuardablePolicy,
+};
+
+use crate::objects::NotificationPreferenceType;
+
+model_backed_type!(UserConProfileNotifiersFields, user_con_profiles::Model);
+
+#[Object]
+impl UserConProfileNotifiersFields {
+  /// How this profile wants to receive each notification that offers a choice
+  #[graphql(
+    name = "notification_preferences",
+    guard = "UserConProfilePolicy::model_guard(UserConProfileAction::Update, self)"
+  )]
+  async fn notification_preferences(
+    &self,
+    ctx: &Context<'_>,
+  ) -> Result<Vec<NotificationPreferenceType>> {
+    let query_data = ctx.data::<QueryData>()?;
+    Ok(NotificationPreferenceType::load_for_user_con_profile(self.model.id, query_data.db()).await?)
+  }
+}
@@ -1,9 +1,10 @@
+use std::env;
+
 use async_graphql::{futures_util::future::try_join_all, Error};
 use aws_sdk_sesv2::types::Destination;
 use seawater::ConnectionWrapper;
-use twilio::OutboundMessage;
 
-use crate::{NotificationDestination, TWILIO_CLIENT};
+use crate::{sms::SmsTransport, NotificationDestination};
 
 pub struct RenderedNotification {
   pub destinations: Vec<NotificationDestination>,
@@ -38,25 +39,29 @@ impl RenderedNotification {
     Ok(())
   }
 
-  pub async fn send_sms(&self, from_sms: &str, db: &ConnectionWrapper) -> Result<(), Error> {
-    let sms_numbers =
-      NotificationDestination::load_sms_numbers(self.destinations.iter(), db).await?;
+  pub async fn send_sms(
+    &self,
+    sms_transport: &dyn SmsTransport,
+    db: &ConnectionWrapper,
+  ) -> Result<(), Error> {
+    let sms_numbers = match env::var("TWILIO_SMS_DEBUG_DESTINATION") {
+      Ok(debug_destination) => vec![debug_destination],
+      Err(_) => NotificationDestination::load_sms_numbers(self.destinations.iter(), db).await?,
+    };
 
     let body = self.body_sms.clone().unwrap_or_else(|| self.body_text());
 
     try_join_all(sms_numbers.iter().map(|sms_number| async {
       let sms_number = phonenumber::parse(None, sms_number.clone()).map_err(Error::from)?;
-      TWILIO_CLIENT
-        .send_message(OutboundMessage::new(
-          from_sms,
+      sms_transport
+        .send_sms(
           &sms_number
             .format()
             .mode(phonenumber::Mode::E164)
             .to_string(),
           &body,
-        ))
+        )
         .await
-        .map_err(Error::from)
     }))
     .await?;
 
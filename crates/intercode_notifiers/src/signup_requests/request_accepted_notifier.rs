@@ -55,6 +55,10 @@ impl Notifier for RequestAcceptedNotifier {
     "request_accepted"
   }
 
+  fn digest_run_id(&self) -> Option<i64> {
+    Some(self.signup_request.target_run_id)
+  }
+
   fn get_liquid_assigns(&self) -> liquid::Object {
     self.liquid_assigns.clone()
   }
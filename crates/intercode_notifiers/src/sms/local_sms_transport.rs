@@ -0,0 +1,93 @@
+use std::{collections::BTreeMap, fs::OpenOptions, io::Write, path::PathBuf};
+
+use async_graphql::{async_trait::async_trait, Error};
+use chrono::{NaiveDateTime, Utc};
+use parking_lot::Mutex;
+use tracing::log::*;
+
+use super::SmsTransport;
+
+pub const LOCAL_SMS_FROM_NUMBER: &str = "+15555550100";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalSentSms {
+  pub sent_at: NaiveDateTime,
+  pub from: String,
+  pub to: String,
+  pub body: String,
+}
+
+/// An SMS transport for development and testing.  Instead of sending anything, it keeps every
+/// message in memory and, if given a path, appends it to a log file.
+pub struct LocalSmsTransport {
+  log_path: Option<PathBuf>,
+  sent_messages: Mutex<Vec<LocalSentSms>>,
+}
+
+impl LocalSmsTransport {
+  pub fn new(log_path: Option<PathBuf>) -> Self {
+    Self {
+      log_path,
+      sent_messages: Default::default(),
+    }
+  }
+
+  pub fn sent_messages(&self) -> Vec<LocalSentSms> {
+    self.sent_messages.lock().clone()
+  }
+
+  pub fn clear(&self) {
+    self.sent_messages.lock().clear();
+  }
+
+  fn append_to_log(&self, message: &LocalSentSms) -> Result<(), Error> {
+    let Some(log_path) = &self.log_path else {
+      return Ok(());
+    };
+
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(log_path)?;
+    writeln!(
+      file,
+      "[{}] SMS from {} to {}\n{}\n",
+      message.sent_at, message.from, message.to, message.body
+    )?;
+
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl SmsTransport for LocalSmsTransport {
+  fn from_number(&self) -> Option<&str> {
+    Some(LOCAL_SMS_FROM_NUMBER)
+  }
+
+  async fn send_sms(&self, to: &str, body: &str) -> Result<(), Error> {
+    let message = LocalSentSms {
+      sent_at: Utc::now().naive_utc(),
+      from: LOCAL_SMS_FROM_NUMBER.to_string(),
+      to: to.to_string(),
+      body: body.to_string(),
+    };
+
+    info!("Local SMS transport sending to {}: {}", to, body);
+    self.append_to_log(&message)?;
+    self.sent_messages.lock().push(message);
+
+    Ok(())
+  }
+
+  fn verify_webhook_signature(
+    &self,
+    _url: &str,
+    _params: &BTreeMap<String, String>,
+    _signature: Option<&str>,
+  ) -> bool {
+    // There's no provider behind this transport, so there's no way to tell a real inbound
+    // message from a forged one.  Accepting them would let anyone change SMS consent.
+    false
+  }
+}
@@ -0,0 +1,102 @@
+mod local_sms_transport;
+mod sms_consent;
+mod sms_keyword;
+mod twilio_sms_transport;
+
+use std::{collections::BTreeMap, env, path::PathBuf, sync::Arc};
+
+use async_graphql::{async_trait::async_trait, Error};
+pub use local_sms_transport::*;
+pub use sms_consent::*;
+pub use sms_keyword::*;
+use tracing::log::*;
+pub use twilio_sms_transport::*;
+
+#[async_trait]
+pub trait SmsTransport: Send + Sync {
+  /// The number outbound messages are sent from.  If this returns None, the transport isn't
+  /// configured well enough to send anything and notifiers will skip SMS delivery entirely.
+  fn from_number(&self) -> Option<&str>;
+
+  /// Sends a single message.  `to` is expected to already be in E.164 format.
+  async fn send_sms(&self, to: &str, body: &str) -> Result<(), Error>;
+
+  /// Checks that an inbound webhook request actually came from this transport's provider.
+  /// Transports that can't tell (because there's no provider) must return false.
+  fn verify_webhook_signature(
+    &self,
+    url: &str,
+    params: &BTreeMap<String, String>,
+    signature: Option<&str>,
+  ) -> bool;
+}
+
+fn local_sms_transport() -> Arc<dyn SmsTransport> {
+  Arc::new(LocalSmsTransport::new(
+    env::var("SMS_LOG_PATH").ok().map(PathBuf::from),
+  ))
+}
+
+/// The transport SMS_TRANSPORT asks for, if it's set.  Anything other than "twilio" or "local"
+/// is a configuration error.
+fn configured_transport_name() -> Result<Option<String>, Error> {
+  match env::var("SMS_TRANSPORT")
+    .ok()
+    .map(|name| name.trim().to_string())
+    .filter(|name| !name.is_empty())
+  {
+    None => Ok(None),
+    Some(name) if name == "twilio" || name == "local" => Ok(Some(name)),
+    Some(other) => Err(Error::new(format!(
+      "Unknown SMS_TRANSPORT {:?} (expected \"twilio\" or \"local\")",
+      other
+    ))),
+  }
+}
+
+/// Checks the SMS configuration without building a transport, so that the server can refuse to
+/// start instead of quietly sending texts nowhere
+pub fn validate_sms_transport_env() -> Result<(), Error> {
+  configured_transport_name().map(|_| ())
+}
+
+/// Builds an SMS transport based on the SMS_TRANSPORT environment variable.  If that isn't set,
+/// we use Twilio when Twilio credentials are present and fall back to the local transport
+/// otherwise.  The local transport rejects every inbound webhook, so consent changes can only
+/// come in through a real provider.
+pub fn build_sms_transport_from_env() -> Result<Arc<dyn SmsTransport>, Error> {
+  let twilio_credentials = match (
+    env::var("TWILIO_ACCOUNT_SID"),
+    env::var("TWILIO_AUTH_TOKEN"),
+  ) {
+    (Ok(sid), Ok(auth_token)) => Some((sid, auth_token)),
+    _ => None,
+  };
+
+  Ok(
+    match (configured_transport_name()?.as_deref(), twilio_credentials) {
+      (Some("twilio") | None, Some((sid, auth_token))) => Arc::new(TwilioSmsTransport::new(
+        &sid,
+        &auth_token,
+        env::var("TWILIO_SMS_NUMBER").ok(),
+      )),
+      (Some("twilio"), None) => {
+        warn!(
+          "Could not get TWILIO_ACCOUNT_SID and/or TWILIO_AUTH_TOKEN from environment, Twilio API operations will fail"
+        );
+        Arc::new(TwilioSmsTransport::new(
+          "",
+          "",
+          env::var("TWILIO_SMS_NUMBER").ok(),
+        ))
+      }
+      (None, None) => {
+        warn!(
+          "Could not get TWILIO_ACCOUNT_SID and/or TWILIO_AUTH_TOKEN from environment, SMS messages will be logged locally instead of sent"
+        );
+        local_sms_transport()
+      }
+      _ => local_sms_transport(),
+    },
+  )
+}
@@ -0,0 +1,93 @@
+use async_graphql::Error;
+use chrono::Utc;
+use intercode_entities::user_con_profiles;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter, QuerySelect};
+use seawater::ConnectionWrapper;
+
+/// Returns the number we're allowed to text for a given profile, if any.  Profiles that have
+/// opted out (or never gave us a mobile number) get None.
+pub fn sms_destination_for_profile(user_con_profile: &user_con_profiles::Model) -> Option<&str> {
+  if !user_con_profile.allow_sms {
+    return None;
+  }
+
+  user_con_profile
+    .mobile_phone
+    .as_deref()
+    .filter(|mobile_phone| !mobile_phone.trim().is_empty())
+}
+
+fn e164(number: &phonenumber::PhoneNumber) -> String {
+  number.format().mode(phonenumber::Mode::E164).to_string()
+}
+
+/// Whether a free-text profile phone number is the given E.164 number.  Profile numbers are
+/// parsed the same way as when we send to them, so a number we'd text is a number that can
+/// opt out.
+fn is_same_number(profile_number: &str, e164_number: &str) -> bool {
+  phonenumber::parse(None, profile_number)
+    .map(|parsed| e164(&parsed) == e164_number)
+    .unwrap_or(false)
+}
+
+/// Updates allow_sms on every profile whose mobile_phone is the given number.  Phone numbers on
+/// profiles are free text, so we narrow the candidates down in SQL by their trailing digits and
+/// then compare full E.164 numbers, which keeps numbers from different countries apart.
+/// Returns the number of profiles updated.
+pub async fn set_sms_consent_for_number(
+  number: &str,
+  allow_sms: bool,
+  db: &ConnectionWrapper,
+) -> Result<u64, Error> {
+  let number = phonenumber::parse(None, number).map_err(Error::from)?;
+  let e164_number = e164(&number);
+  let national_digits = number.national().value().to_string();
+
+  let profile_ids = user_con_profiles::Entity::find()
+    .select_only()
+    .column(user_con_profiles::Column::Id)
+    .column(user_con_profiles::Column::MobilePhone)
+    .filter(Expr::cust_with_values(
+      "regexp_replace(user_con_profiles.mobile_phone, '[^0-9]', '', 'g') LIKE $1",
+      vec![format!("%{}", national_digits)],
+    ))
+    .into_tuple::<(i64, Option<String>)>()
+    .all(db)
+    .await?
+    .into_iter()
+    .filter(|(_, mobile_phone)| {
+      mobile_phone
+        .as_deref()
+        .is_some_and(|mobile_phone| is_same_number(mobile_phone, &e164_number))
+    })
+    .map(|(id, _)| id)
+    .collect::<Vec<_>>();
+
+  if profile_ids.is_empty() {
+    return Ok(0);
+  }
+
+  let result = user_con_profiles::Entity::update_many()
+    .col_expr(user_con_profiles::Column::AllowSms, Expr::value(allow_sms))
+    .col_expr(
+      user_con_profiles::Column::UpdatedAt,
+      Expr::value(Utc::now().naive_utc()),
+    )
+    .filter(user_con_profiles::Column::Id.is_in(profile_ids))
+    .exec(db)
+    .await?;
+
+  Ok(result.rows_affected)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::is_same_number;
+
+  #[test]
+  fn matches_full_international_numbers() {
+    assert!(is_same_number("+1 (617) 555-0123", "+16175550123"));
+    assert!(!is_same_number("+44 617 555 0123", "+16175550123"));
+    assert!(!is_same_number("not a phone number", "+16175550123"));
+  }
+}
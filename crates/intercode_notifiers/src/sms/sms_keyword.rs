@@ -0,0 +1,76 @@
+/// The keywords carriers and Twilio treat as opting out of or back into messages from a number.
+/// A message only counts as a keyword if the keyword is the entire message body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmsKeyword {
+  OptOut,
+  OptIn,
+  Help,
+}
+
+const OPT_OUT_KEYWORDS: &[&str] = &[
+  "STOP",
+  "STOPALL",
+  "STOP ALL",
+  "UNSUBSCRIBE",
+  "CANCEL",
+  "END",
+  "QUIT",
+  "OPTOUT",
+  "REVOKE",
+];
+const OPT_IN_KEYWORDS: &[&str] = &["START", "YES", "UNSTOP"];
+const HELP_KEYWORDS: &[&str] = &["HELP", "INFO"];
+
+impl SmsKeyword {
+  pub fn parse(body: &str) -> Option<Self> {
+    let normalized = body
+      .trim()
+      .trim_end_matches(|c: char| c.is_ascii_punctuation())
+      .to_uppercase();
+
+    if OPT_OUT_KEYWORDS.contains(&normalized.as_str()) {
+      Some(Self::OptOut)
+    } else if OPT_IN_KEYWORDS.contains(&normalized.as_str()) {
+      Some(Self::OptIn)
+    } else if HELP_KEYWORDS.contains(&normalized.as_str()) {
+      Some(Self::Help)
+    } else {
+      None
+    }
+  }
+
+  /// The value allow_sms should be set to after receiving this keyword, if it changes consent at
+  /// all.
+  pub fn allow_sms(&self) -> Option<bool> {
+    match self {
+      Self::OptOut => Some(false),
+      Self::OptIn => Some(true),
+      Self::Help => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::SmsKeyword;
+
+  #[test]
+  fn parses_opt_out_keywords_case_insensitively() {
+    assert_eq!(SmsKeyword::parse("STOP"), Some(SmsKeyword::OptOut));
+    assert_eq!(SmsKeyword::parse(" stop\n"), Some(SmsKeyword::OptOut));
+    assert_eq!(SmsKeyword::parse("Unsubscribe."), Some(SmsKeyword::OptOut));
+    assert_eq!(SmsKeyword::parse("stop all"), Some(SmsKeyword::OptOut));
+  }
+
+  #[test]
+  fn parses_opt_in_keywords() {
+    assert_eq!(SmsKeyword::parse("start"), Some(SmsKeyword::OptIn));
+    assert_eq!(SmsKeyword::parse("UNSTOP"), Some(SmsKeyword::OptIn));
+  }
+
+  #[test]
+  fn ignores_keywords_inside_longer_messages() {
+    assert_eq!(SmsKeyword::parse("please don't stop"), None);
+    assert_eq!(SmsKeyword::parse("stop sending me these"), None);
+  }
+}
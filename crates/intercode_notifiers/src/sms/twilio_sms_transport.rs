@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use async_graphql::{async_trait::async_trait, Error};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use twilio::OutboundMessage;
+
+use super::SmsTransport;
+
+pub struct TwilioSmsTransport {
+  client: twilio::Client,
+  auth_token: String,
+  from_number: Option<String>,
+}
+
+impl TwilioSmsTransport {
+  pub fn new(account_sid: &str, auth_token: &str, from_number: Option<String>) -> Self {
+    Self {
+      client: twilio::Client::new(account_sid, auth_token),
+      auth_token: auth_token.to_string(),
+      from_number,
+    }
+  }
+}
+
+fn twilio_signature_mac(
+  auth_token: &str,
+  url: &str,
+  params: &BTreeMap<String, String>,
+) -> Hmac<Sha1> {
+  let mut mac =
+    Hmac::<Sha1>::new_from_slice(auth_token.as_bytes()).expect("HMAC can take a key of any size");
+  mac.update(url.as_bytes());
+  for (key, value) in params {
+    mac.update(key.as_bytes());
+    mac.update(value.as_bytes());
+  }
+
+  mac
+}
+
+/// Computes the value Twilio sends in the X-Twilio-Signature header: the full request URL with
+/// every POST parameter's name and value appended in sorted order, HMAC-SHA1 signed with the
+/// account's auth token and base64 encoded.
+pub fn compute_twilio_signature(
+  auth_token: &str,
+  url: &str,
+  params: &BTreeMap<String, String>,
+) -> String {
+  base64::engine::general_purpose::STANDARD.encode(
+    twilio_signature_mac(auth_token, url, params)
+      .finalize()
+      .into_bytes(),
+  )
+}
+
+#[async_trait]
+impl SmsTransport for TwilioSmsTransport {
+  fn from_number(&self) -> Option<&str> {
+    self.from_number.as_deref()
+  }
+
+  async fn send_sms(&self, to: &str, body: &str) -> Result<(), Error> {
+    let Some(from_number) = self.from_number.as_deref() else {
+      return Err(Error::new("TWILIO_SMS_NUMBER is not set, can't send SMS"));
+    };
+
+    self
+      .client
+      .send_message(OutboundMessage::new(from_number, to, body))
+      .await
+      .map_err(Error::from)?;
+
+    Ok(())
+  }
+
+  fn verify_webhook_signature(
+    &self,
+    url: &str,
+    params: &BTreeMap<String, String>,
+    signature: Option<&str>,
+  ) -> bool {
+    if self.auth_token.is_empty() {
+      return false;
+    }
+
+    let Some(signature) = signature.and_then(|signature| {
+      base64::engine::general_purpose::STANDARD
+        .decode(signature)
+        .ok()
+    }) else {
+      return false;
+    };
+
+    twilio_signature_mac(&self.auth_token, url, params)
+      .verify_slice(&signature)
+      .is_ok()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::compute_twilio_signature;
+
+  fn example_params() -> BTreeMap<String, String> {
+    [
+      ("CallSid", "CA1234567890ABCDE"),
+      ("Caller", "+12349013030"),
+      ("Digits", "1234"),
+      ("From", "+12349013030"),
+      ("To", "+18005551212"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+  }
+
+  #[test]
+  fn signature_matches_twilio_algorithm() {
+    assert_eq!(
+      compute_twilio_signature(
+        "12345",
+        "https://mycompany.com/myapp.php?foo=1&bar=2",
+        &example_params()
+      ),
+      "0/KCTR6DLpKmkAf8muzZqo1nDgQ="
+    )
+  }
+
+  #[test]
+  fn signature_changes_with_params() {
+    let mut params = example_params();
+    params.insert("Body".to_string(), "STOP".to_string());
+
+    assert_ne!(
+      compute_twilio_signature(
+        "12345",
+        "https://mycompany.com/myapp.php?foo=1&bar=2",
+        &params
+      ),
+      "0/KCTR6DLpKmkAf8muzZqo1nDgQ="
+    )
+  }
+}
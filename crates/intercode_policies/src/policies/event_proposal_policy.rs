@@ -4,12 +4,12 @@ use async_graphql::{Context, Error};
 use async_trait::async_trait;
 use cached::once_cell::sync::Lazy;
 use intercode_entities::{
-  conventions, event_proposals, model_ext::event_proposals::EventProposalStatus,
+  conventions, event_proposals, model_ext::event_proposals::EventProposalStatus, user_con_profiles,
 };
 use intercode_graphql_loaders::LoaderManager;
 use sea_orm::{
   sea_query::{Cond, Expr},
-  ColumnTrait, DbErr, EntityTrait, Iterable, QueryFilter, Select,
+  ColumnTrait, DbErr, EntityTrait, Iterable, QueryFilter, QuerySelect, Select,
 };
 use seawater::loaders::ExpectModel;
 
@@ -242,12 +242,18 @@ impl EntityPolicy<AuthorizationInfo, event_proposals::Model> for EventProposalPo
 
         scope.filter(
           Cond::any()
-            .add_option(
-              principal
-                .user
-                .as_ref()
-                .map(|user| event_proposals::Column::OwnerId.eq(user.id)),
-            )
+            // owner_id is a profile id, not a user id
+            .add_option(principal.user.as_ref().map(|user| {
+              event_proposals::Column::OwnerId.in_subquery(
+                sea_orm::QuerySelect::query(
+                  &mut user_con_profiles::Entity::find()
+                    .select_only()
+                    .column(user_con_profiles::Column::Id)
+                    .filter(user_con_profiles::Column::UserId.eq(user.id)),
+                )
+                .take(),
+              )
+            }))
             .add(
               event_proposals::Column::EventCategoryId
                 .in_subquery(
@@ -44,29 +44,46 @@ pub trait PolicyGuard<'a, P: Policy<AuthorizationInfo, R>, R: Send + Sync, M: Se
 
     match permitted {
       true => Ok(()),
-      false => {
-        let error = async_graphql::Error::new("Permission denied").extend_with(|_err, ext| {
-          ext.set(
-            "code",
-            if ctx
-              .data::<QueryData>()
-              .ok()
-              .and_then(|qd| qd.current_user())
-              .is_none()
-            {
-              "NOT_AUTHENTICATED"
-            } else {
-              "NOT_AUTHORIZED"
-            },
-          )
-        });
-
-        Err(error)
-      }
+      false => Err(permission_denied_error(ctx)),
     }
   }
 }
 
+/// Builds the error a guard returns when a policy check fails, distinguishing between
+/// anonymous and signed-in users
+pub fn permission_denied_error(ctx: &Context<'_>) -> async_graphql::Error {
+  async_graphql::Error::new("Permission denied").extend_with(|_err, ext| {
+    ext.set(
+      "code",
+      if ctx
+        .data::<QueryData>()
+        .ok()
+        .and_then(|qd| qd.current_user())
+        .is_none()
+      {
+        "NOT_AUTHENTICATED"
+      } else {
+        "NOT_AUTHORIZED"
+      },
+    )
+  })
+}
+
+/// Checks a policy from inside a resolver, for cases where the resource isn't known until the
+/// resolver has loaded it (e.g. mutations)
+pub async fn ensure_action_permitted<P: Policy<AuthorizationInfo, R>, R: Send + Sync>(
+  ctx: &Context<'_>,
+  action: &P::Action,
+  resource: &R,
+) -> Result<()> {
+  let principal = ctx.data::<AuthorizationInfo>()?;
+  if P::action_permitted(principal, action, resource).await? {
+    Ok(())
+  } else {
+    Err(permission_denied_error(ctx))
+  }
+}
+
 #[async_trait]
 impl<'a, P: Policy<AuthorizationInfo, R>, R: Send + Sync, M: Send + Sync + 'static> Guard
   for Box<dyn PolicyGuard<'a, P, R, M> + Send + Sync>
@@ -1,3 +1,4 @@
 pub mod actions;
+pub mod mutations;
 pub mod objects;
 pub mod partial_objects;
@@ -0,0 +1,3 @@
+mod send_bulk_email;
+
+pub use send_bulk_email::*;
@@ -0,0 +1,179 @@
+use async_graphql::{Enum, Error, InputObject, SimpleObject, ID};
+use intercode_email::outbox::{new_outbox_email, OUTBOX_STATUS_FAILED};
+use intercode_entities::outbox_emails;
+use intercode_graphql_core::scalars::DateScalar;
+use intercode_policies::policies::ConventionAction;
+use sea_orm::Set;
+use serde_json::json;
+
+use crate::objects::BulkEmailCampaignType;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MailingListKind {
+  /// Everyone who has proposed an event that is still under consideration or accepted
+  #[graphql(name = "event_proposers")]
+  EventProposers,
+  /// Team members of all active events
+  #[graphql(name = "team_members")]
+  TeamMembers,
+  /// Everyone with a ticket
+  #[graphql(name = "ticketed_attendees")]
+  TicketedAttendees,
+  /// Bio-eligible attendees who haven't written a bio
+  #[graphql(name = "users_with_pending_bio")]
+  UsersWithPendingBio,
+  /// Everyone on a waitlist
+  #[graphql(name = "waitlists")]
+  Waitlists,
+  /// Ticketed attendees with no signups in a given timespan who have opted in to these emails
+  #[graphql(name = "whos_free")]
+  WhosFree,
+}
+
+impl MailingListKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      MailingListKind::EventProposers => "event_proposers",
+      MailingListKind::TeamMembers => "team_members",
+      MailingListKind::TicketedAttendees => "ticketed_attendees",
+      MailingListKind::UsersWithPendingBio => "users_with_pending_bio",
+      MailingListKind::Waitlists => "waitlists",
+      MailingListKind::WhosFree => "whos_free",
+    }
+  }
+
+  /// The permission needed to read this list.  Sending to it additionally requires
+  /// ConventionAction::Update.
+  pub fn required_action(&self) -> ConventionAction {
+    match self {
+      MailingListKind::TicketedAttendees | MailingListKind::WhosFree => {
+        ConventionAction::ReadUserConProfilesMailingList
+      }
+      _ => ConventionAction::ReadTeamMembersMailingList,
+    }
+  }
+}
+
+#[derive(InputObject)]
+#[graphql(name = "MailingListSelectorInput")]
+pub struct MailingListSelectorInput {
+  pub list: MailingListKind,
+  /// Required for the whos_free list: the start of the timespan to check.
+  pub start: Option<DateScalar>,
+  /// Required for the whos_free list: the end of the timespan to check.
+  pub finish: Option<DateScalar>,
+  /// For the waitlists list, restricts the recipients to a single run's waitlist.
+  #[graphql(name = "run_id")]
+  pub run_id: Option<ID>,
+}
+
+impl MailingListSelectorInput {
+  pub fn params_json(&self) -> serde_json::Value {
+    let mut params = serde_json::Map::new();
+    if let Some(start) = &self.start {
+      params.insert("start".to_string(), json!(start.0.to_rfc3339()));
+    }
+    if let Some(finish) = &self.finish {
+      params.insert("finish".to_string(), json!(finish.0.to_rfc3339()));
+    }
+    if let Some(run_id) = &self.run_id {
+      params.insert("run_id".to_string(), json!(run_id.0));
+    }
+    serde_json::Value::Object(params)
+  }
+}
+
+#[derive(InputObject)]
+pub struct SendBulkEmailInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// Which mailing list to send to.
+  #[graphql(name = "mailing_list")]
+  pub mailing_list: MailingListSelectorInput,
+  /// The subject line, as a Liquid template.
+  pub subject: String,
+  /// The HTML body, as a Liquid template.  Each recipient's profile is available as `recipient`.
+  pub body: String,
+}
+
+#[derive(SimpleObject)]
+pub struct SendBulkEmailPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "bulk_email_campaign")]
+  pub bulk_email_campaign: BulkEmailCampaignType,
+}
+
+/// Builds the outbox row for one recipient.  If their copy of the email didn't render, the row is
+/// recorded as failed with the Liquid error, so the rest of the list still gets sent and the
+/// campaign shows who was missed.
+pub fn bulk_email_recipient_row(
+  from_address: &str,
+  to_address: &str,
+  user_con_profile_id: i64,
+  rendered: Result<(String, String), Error>,
+) -> outbox_emails::ActiveModel {
+  let mut row = match rendered {
+    Ok((subject, body_html)) => new_outbox_email(
+      from_address,
+      to_address,
+      subject.trim(),
+      Some(body_html),
+      None,
+    ),
+    Err(err) => {
+      let mut row = new_outbox_email(from_address, to_address, "", None, None);
+      row.status = Set(OUTBOX_STATUS_FAILED.to_string());
+      row.last_error = Set(Some(err.message));
+      row
+    }
+  };
+  row.user_con_profile_id = Set(Some(user_con_profile_id));
+  row
+}
+
+#[cfg(test)]
+mod tests {
+  use intercode_email::outbox::OUTBOX_STATUS_PENDING;
+
+  use super::*;
+
+  #[test]
+  fn rendered_recipients_are_queued() {
+    let row = bulk_email_recipient_row(
+      "con@intercode.test",
+      "attendee@intercode.test",
+      12,
+      Ok((
+        " Hello Alice \n".to_string(),
+        "<p>See you there</p>".to_string(),
+      )),
+    );
+
+    assert_eq!(row.status, Set(OUTBOX_STATUS_PENDING.to_string()));
+    assert_eq!(row.subject, Set("Hello Alice".to_string()));
+    assert_eq!(row.body_html, Set(Some("<p>See you there</p>".to_string())));
+    assert_eq!(row.to_address, Set("attendee@intercode.test".to_string()));
+    assert_eq!(row.user_con_profile_id, Set(Some(12)));
+    assert_eq!(row.last_error, sea_orm::ActiveValue::NotSet);
+  }
+
+  #[test]
+  fn recipients_that_fail_to_render_are_recorded_as_failed() {
+    let row = bulk_email_recipient_row(
+      "con@intercode.test",
+      "attendee@intercode.test",
+      12,
+      Err(Error::new("Liquid error: Unknown variable")),
+    );
+
+    assert_eq!(row.status, Set(OUTBOX_STATUS_FAILED.to_string()));
+    assert_eq!(
+      row.last_error,
+      Set(Some("Liquid error: Unknown variable".to_string()))
+    );
+    assert_eq!(row.user_con_profile_id, Set(Some(12)));
+  }
+}
@@ -0,0 +1,190 @@
+use async_graphql::*;
+use intercode_email::outbox::{OUTBOX_STATUS_FAILED, OUTBOX_STATUS_PENDING, OUTBOX_STATUS_SENT};
+use intercode_entities::{bulk_email_campaigns, outbox_emails};
+use intercode_graphql_core::{
+  model_backed_type,
+  query_data::QueryData,
+  scalars::{DateScalar, JsonScalar},
+  ModelBackedType,
+};
+use sea_orm::{
+  ColumnTrait, ConnectionTrait, DbErr, ModelTrait, PaginatorTrait, QueryFilter, QueryOrder,
+};
+
+use super::BulkEmailRecipientType;
+
+model_backed_type!(BulkEmailCampaignType, bulk_email_campaigns::Model);
+
+async fn count_recipients_with_status<C: ConnectionTrait>(
+  campaign: &bulk_email_campaigns::Model,
+  db: &C,
+  status: &str,
+) -> Result<u64, DbErr> {
+  campaign
+    .find_related(outbox_emails::Entity)
+    .filter(outbox_emails::Column::Status.eq(status))
+    .count(db)
+    .await
+}
+
+impl BulkEmailCampaignType {
+  async fn count_recipients_with_status(&self, ctx: &Context<'_>, status: &str) -> Result<u64> {
+    Ok(count_recipients_with_status(&self.model, ctx.data::<QueryData>()?.db(), status).await?)
+  }
+}
+
+#[Object(name = "BulkEmailCampaign")]
+impl BulkEmailCampaignType {
+  async fn id(&self) -> ID {
+    self.model.id.into()
+  }
+
+  async fn body(&self) -> &str {
+    &self.model.body
+  }
+
+  #[graphql(name = "created_at")]
+  async fn created_at(&self) -> Result<DateScalar> {
+    self.model.created_at.try_into()
+  }
+
+  #[graphql(name = "failed_count")]
+  async fn failed_count(&self, ctx: &Context<'_>) -> Result<u64> {
+    self
+      .count_recipients_with_status(ctx, OUTBOX_STATUS_FAILED)
+      .await
+  }
+
+  #[graphql(name = "mailing_list")]
+  async fn mailing_list(&self) -> &str {
+    &self.model.mailing_list
+  }
+
+  #[graphql(name = "mailing_list_params")]
+  async fn mailing_list_params(&self) -> JsonScalar {
+    JsonScalar(self.model.mailing_list_params.clone())
+  }
+
+  #[graphql(name = "pending_count")]
+  async fn pending_count(&self, ctx: &Context<'_>) -> Result<u64> {
+    self
+      .count_recipients_with_status(ctx, OUTBOX_STATUS_PENDING)
+      .await
+  }
+
+  async fn recipients(&self, ctx: &Context<'_>) -> Result<Vec<BulkEmailRecipientType>> {
+    Ok(
+      self
+        .model
+        .find_related(outbox_emails::Entity)
+        .order_by_asc(outbox_emails::Column::Id)
+        .all(ctx.data::<QueryData>()?.db())
+        .await?
+        .into_iter()
+        .map(BulkEmailRecipientType::new)
+        .collect(),
+    )
+  }
+
+  #[graphql(name = "sent_count")]
+  async fn sent_count(&self, ctx: &Context<'_>) -> Result<u64> {
+    self
+      .count_recipients_with_status(ctx, OUTBOX_STATUS_SENT)
+      .await
+  }
+
+  async fn subject(&self) -> &str {
+    &self.model.subject
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Utc;
+  use intercode_email::outbox::new_outbox_email;
+  use intercode_entities::conventions;
+  use intercode_policies::test_helpers::with_test_db;
+  use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn counts_each_recipient_by_their_own_status() {
+    with_test_db(|db| {
+      Box::pin(async move {
+        let now = Utc::now().naive_utc();
+        let convention = conventions::ActiveModel {
+          domain: Set("bulk-email.intercode.test".to_string()),
+          email_from: Set("noreply@intercode.test".to_string()),
+          language: Set("en".to_string()),
+          timezone_mode: Set("user_local".to_string()),
+          ticket_mode: Set("disabled".to_string()),
+          ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let campaign = bulk_email_campaigns::ActiveModel {
+          convention_id: Set(convention.id),
+          mailing_list: Set("ticketed_attendees".to_string()),
+          subject: Set("Hello".to_string()),
+          body: Set("Hello".to_string()),
+          created_at: Set(now),
+          updated_at: Set(now),
+          ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        for (index, status) in [
+          OUTBOX_STATUS_SENT,
+          OUTBOX_STATUS_SENT,
+          OUTBOX_STATUS_PENDING,
+          OUTBOX_STATUS_FAILED,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+          let mut row = new_outbox_email(
+            &convention.email_from,
+            &format!("recipient{}@intercode.test", index),
+            "Hello",
+            None,
+            None,
+          );
+          row.status = Set(status.to_string());
+          row.bulk_email_campaign_id = Set(Some(campaign.id));
+          row.insert(&db).await.unwrap();
+        }
+        // an unrelated email shouldn't count towards the campaign
+        new_outbox_email(
+          &convention.email_from,
+          "someone@intercode.test",
+          "Hi",
+          None,
+          None,
+        )
+        .insert(&db)
+        .await
+        .unwrap();
+
+        for (status, expected) in [
+          (OUTBOX_STATUS_SENT, 2),
+          (OUTBOX_STATUS_PENDING, 1),
+          (OUTBOX_STATUS_FAILED, 1),
+        ] {
+          assert_eq!(
+            count_recipients_with_status(&campaign, &db, status)
+              .await
+              .unwrap(),
+            expected,
+            "{} count",
+            status
+          );
+        }
+      })
+    })
+    .await;
+  }
+}
@@ -0,0 +1,40 @@
+use async_graphql::*;
+use intercode_entities::outbox_emails;
+use intercode_graphql_core::{model_backed_type, scalars::DateScalar};
+
+model_backed_type!(BulkEmailRecipientType, outbox_emails::Model);
+
+#[Object(name = "BulkEmailRecipient")]
+impl BulkEmailRecipientType {
+  async fn id(&self) -> ID {
+    self.model.id.into()
+  }
+
+  async fn attempts(&self) -> i32 {
+    self.model.attempts
+  }
+
+  async fn email(&self) -> &str {
+    &self.model.to_address
+  }
+
+  #[graphql(name = "last_error")]
+  async fn last_error(&self) -> Option<&str> {
+    self.model.last_error.as_deref()
+  }
+
+  #[graphql(name = "sent_at")]
+  async fn sent_at(&self) -> Result<Option<DateScalar>> {
+    self.model.sent_at.map(DateScalar::try_from).transpose()
+  }
+
+  /// pending, sent, or failed
+  async fn status(&self) -> &str {
+    &self.model.status
+  }
+
+  #[graphql(name = "user_con_profile_id")]
+  async fn user_con_profile_id(&self) -> Option<ID> {
+    self.model.user_con_profile_id.map(ID::from)
+  }
+}
@@ -1,3 +1,7 @@
+mod bulk_email_campaign_type;
+mod bulk_email_recipient_type;
 mod contact_email_type;
 
+pub use bulk_email_campaign_type::*;
+pub use bulk_email_recipient_type::*;
 pub use contact_email_type::*;
@@ -2,7 +2,7 @@ use std::{collections::HashMap, fmt};
 
 use async_graphql::*;
 use intercode_entities::{
-  conventions, event_proposals, events,
+  bulk_email_campaigns, conventions, event_proposals, events,
   links::ConventionToSignups,
   model_ext::{
     event_proposals::EventProposalStatus,
@@ -13,7 +13,7 @@ use intercode_entities::{
 };
 use intercode_graphql_core::{
   load_many_by_ids, load_many_by_model_ids, loader_result_map_to_required_map, model_backed_type,
-  query_data::QueryData, scalars::DateScalar,
+  query_data::QueryData, scalars::DateScalar, ModelBackedType,
 };
 use intercode_policies::{
   policies::{ConventionAction, ConventionPolicy},
@@ -27,7 +27,7 @@ use sea_orm::{
 };
 use seawater::loaders::ExpectModel;
 
-use crate::objects::{ContactEmail, ContactEmailType};
+use crate::objects::{BulkEmailCampaignType, ContactEmail, ContactEmailType};
 
 struct TrimFunction;
 
@@ -72,6 +72,18 @@ impl MailingListsResult {
   }
 }
 
+impl MailingListsResult {
+  pub fn into_emails(self) -> Vec<ContactEmailType> {
+    match self {
+      Self::EventProposers(emails)
+      | Self::TeamMembers(emails)
+      | Self::TicketedAttendees(emails)
+      | Self::UsersWithPendingBio(emails)
+      | Self::WhosFree(emails) => emails,
+    }
+  }
+}
+
 model_backed_type!(MailingListsReportingFields, conventions::Model);
 
 pub async fn waitlists<T: From<MailingListsWaitlistsResult>>(
@@ -142,11 +154,30 @@ pub async fn waitlists<T: From<MailingListsWaitlistsResult>>(
 
 #[Object]
 impl MailingListsReportingFields {
+  /// Bulk emails previously sent to this convention's mailing lists, most recent first
+  #[graphql(
+    name = "bulk_email_campaigns",
+    guard = "ConventionPolicy::model_guard(ConventionAction::ReadAnyMailingList, self)"
+  )]
+  async fn bulk_email_campaigns(&self, ctx: &Context<'_>) -> Result<Vec<BulkEmailCampaignType>> {
+    Ok(
+      self
+        .model
+        .find_related(bulk_email_campaigns::Entity)
+        .order_by_desc(bulk_email_campaigns::Column::CreatedAt)
+        .all(ctx.data::<QueryData>()?.db())
+        .await?
+        .into_iter()
+        .map(BulkEmailCampaignType::new)
+        .collect(),
+    )
+  }
+
   #[graphql(
     name = "event_proposers",
     guard = "ConventionPolicy::model_guard(ConventionAction::ReadTeamMembersMailingList, self)"
   )]
-  async fn event_proposers(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
+  pub async fn event_proposers(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
     let query_data = ctx.data::<QueryData>()?;
     let results = self
       .model
@@ -207,7 +238,7 @@ impl MailingListsReportingFields {
     name = "team_members",
     guard = "ConventionPolicy::model_guard(ConventionAction::ReadTeamMembersMailingList, self)"
   )]
-  async fn team_members(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
+  pub async fn team_members(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
     let query_data = ctx.data::<QueryData>()?;
     let results = team_members::Entity::find()
       .inner_join(events::Entity)
@@ -273,7 +304,7 @@ impl MailingListsReportingFields {
     name = "ticketed_attendees",
     guard = "ConventionPolicy::model_guard(ConventionAction::ReadUserConProfilesMailingList, self)"
   )]
-  async fn ticketed_attendees(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
+  pub async fn ticketed_attendees(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
     let query_data = ctx.data::<QueryData>()?;
     let results = self
       .model
@@ -306,7 +337,7 @@ impl MailingListsReportingFields {
     name = "users_with_pending_bio",
     guard = "ConventionPolicy::model_guard(ConventionAction::ReadTeamMembersMailingList, self)"
   )]
-  async fn users_with_pending_bio(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
+  pub async fn users_with_pending_bio(&self, ctx: &Context<'_>) -> Result<MailingListsResult> {
     let query_data = ctx.data::<QueryData>()?;
     let results = self
       .model
@@ -349,7 +380,7 @@ impl MailingListsReportingFields {
     name = "whos_free",
     guard = "ConventionPolicy::model_guard(ConventionAction::ReadUserConProfilesMailingList, self)"
   )]
-  async fn whos_free(
+  pub async fn whos_free(
     &self,
     ctx: &Context<'_>,
     start: DateScalar,
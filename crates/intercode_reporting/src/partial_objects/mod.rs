@@ -1,5 +1,7 @@
 mod ability_reporting_fields;
 mod mailing_lists_reporting_fields;
+mod mutation_root_reporting_fields;
 
 pub use ability_reporting_fields::*;
 pub use mailing_lists_reporting_fields::*;
+pub use mutation_root_reporting_fields::*;
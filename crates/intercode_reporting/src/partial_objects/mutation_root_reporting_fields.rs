@@ -0,0 +1,191 @@
+use std::{collections::HashSet, sync::Arc};
+
+use async_graphql::*;
+use chrono::Utc;
+use intercode_entities::{
+  bulk_email_campaigns, conventions, outbox_emails, user_con_profiles, users,
+};
+use intercode_graphql_core::{
+  liquid_renderer::LiquidRenderer, query_data::QueryData, scalars::DateScalar,
+  schema_data::SchemaData, ModelBackedType,
+};
+use intercode_liquid_drops::drops::{DropContext, UserConProfileDrop};
+use intercode_policies::{
+  ensure_action_permitted,
+  policies::{ConventionAction, ConventionPolicy},
+};
+use liquid::object;
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use seawater::DropStore;
+
+use crate::{
+  mutations::{
+    bulk_email_recipient_row, MailingListKind, MailingListSelectorInput, SendBulkEmailInput,
+    SendBulkEmailPayload,
+  },
+  objects::{BulkEmailCampaignType, ContactEmailType},
+  partial_objects::{waitlists, MailingListsReportingFields, MailingListsWaitlistsResult},
+};
+
+async fn resolve_mailing_list(
+  ctx: &Context<'_>,
+  convention: &conventions::Model,
+  selector: &MailingListSelectorInput,
+) -> Result<Vec<ContactEmailType>> {
+  let mailing_lists = MailingListsReportingFields::new(convention.clone());
+
+  let result = match selector.list {
+    MailingListKind::EventProposers => mailing_lists.event_proposers(ctx).await?,
+    MailingListKind::TeamMembers => mailing_lists.team_members(ctx).await?,
+    MailingListKind::TicketedAttendees => mailing_lists.ticketed_attendees(ctx).await?,
+    MailingListKind::UsersWithPendingBio => mailing_lists.users_with_pending_bio(ctx).await?,
+    MailingListKind::WhosFree => {
+      let (Some(start), Some(finish)) = (&selector.start, &selector.finish) else {
+        return Err(Error::new("The whos_free list requires a start and finish"));
+      };
+      mailing_lists
+        .whos_free(ctx, DateScalar(start.0), DateScalar(finish.0))
+        .await?
+    }
+    MailingListKind::Waitlists => {
+      let run_id = selector
+        .run_id
+        .as_ref()
+        .map(|id| id.parse::<i64>())
+        .transpose()?;
+
+      return Ok(
+        waitlists::<MailingListsWaitlistsResult>(convention, ctx)
+          .await?
+          .into_iter()
+          .filter(|result| run_id.map(|run_id| result.run.id == run_id).unwrap_or(true))
+          .flat_map(|result| result.emails)
+          .collect(),
+      );
+    }
+  };
+
+  Ok(result.into_emails())
+}
+
+#[derive(Default)]
+pub struct MutationRootReportingFields;
+
+#[Object]
+impl MutationRootReportingFields {
+  /// Sends an email to everyone on one of the current convention's mailing lists.  The subject
+  /// and body are rendered separately for each recipient, with their profile available as
+  /// `recipient`.  Messages are queued in the outbox and sent in rate-limited batches; the
+  /// returned campaign tracks the delivery status of each one.
+  async fn send_bulk_email(
+    &self,
+    ctx: &Context<'_>,
+    input: SendBulkEmailInput,
+  ) -> Result<SendBulkEmailPayload, Error> {
+    let schema_data = ctx.data::<SchemaData>()?;
+    let query_data = ctx.data::<QueryData>()?;
+    let liquid_renderer = ctx.data::<Arc<dyn LiquidRenderer>>()?;
+    let convention = query_data
+      .convention()
+      .ok_or_else(|| Error::new("Bulk emails can only be sent within a convention"))?;
+
+    // Reading a mailing list isn't enough to email everyone on it: sending is a convention
+    // management action, and needs a token with write access
+    ensure_action_permitted::<ConventionPolicy, _>(ctx, &ConventionAction::Update, convention)
+      .await?;
+    ensure_action_permitted::<ConventionPolicy, _>(
+      ctx,
+      &input.mailing_list.list.required_action(),
+      convention,
+    )
+    .await?;
+
+    liquid_renderer.parse_liquid(&input.subject).await?;
+    liquid_renderer.parse_liquid(&input.body).await?;
+
+    let contact_emails = resolve_mailing_list(ctx, convention, &input.mailing_list).await?;
+    let emails = contact_emails
+      .into_iter()
+      .map(|contact_email| contact_email.0.email.to_lowercase())
+      .collect::<HashSet<_>>();
+
+    let recipients = user_con_profiles::Entity::find()
+      .filter(user_con_profiles::Column::ConventionId.eq(convention.id))
+      .find_also_related(users::Entity)
+      .filter(
+        Expr::expr(Func::lower(Expr::col((
+          users::Entity,
+          users::Column::Email,
+        ))))
+        .is_in(emails),
+      )
+      .order_by_asc(user_con_profiles::Column::Id)
+      .all(query_data.db())
+      .await?;
+
+    let store = DropStore::new();
+    let drop_context = DropContext::new(
+      schema_data.clone(),
+      query_data.clone_ref(),
+      Arc::downgrade(&store),
+    );
+
+    let mut outbox_rows: Vec<outbox_emails::ActiveModel> = Vec::with_capacity(recipients.len());
+    for (user_con_profile, user) in recipients {
+      let Some(user) = user else {
+        continue;
+      };
+
+      let assigns = || {
+        object!({
+          "recipient": UserConProfileDrop::new(user_con_profile.clone(), drop_context.clone())
+        })
+      };
+      let rendered = futures::try_join!(
+        liquid_renderer.render_liquid(&input.subject, assigns(), None),
+        liquid_renderer.render_liquid(&input.body, assigns(), None),
+      );
+
+      outbox_rows.push(bulk_email_recipient_row(
+        &convention.email_from,
+        &user.email,
+        user_con_profile.id,
+        rendered,
+      ));
+    }
+
+    let now = Utc::now().naive_utc();
+    let txn = query_data.db().begin().await?;
+    let campaign = bulk_email_campaigns::ActiveModel {
+      convention_id: Set(convention.id),
+      sender_user_con_profile_id: Set(query_data.user_con_profile().map(|ucp| ucp.id)),
+      mailing_list: Set(input.mailing_list.list.as_str().to_string()),
+      mailing_list_params: Set(input.mailing_list.params_json()),
+      subject: Set(input.subject),
+      body: Set(input.body),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+
+    if !outbox_rows.is_empty() {
+      outbox_emails::Entity::insert_many(outbox_rows.into_iter().map(|mut row| {
+        row.bulk_email_campaign_id = Set(Some(campaign.id));
+        row
+      }))
+      .exec(&txn)
+      .await?;
+    }
+    txn.commit().await?;
+
+    Ok(SendBulkEmailPayload {
+      client_mutation_id: input.client_mutation_id,
+      bulk_email_campaign: BulkEmailCampaignType::new(campaign),
+    })
+  }
+}
@@ -15,6 +15,7 @@ use tower_http::compression::CompressionLayer;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 
 use crate::{
+  assumed_identity::assumed_identity_request_logging,
   csrf::{csrf_middleware, CsrfConfig},
   db_sessions::SessionWithDbStoreFromTxLayer,
   request_bound_transaction::request_bound_transaction,
@@ -47,6 +48,10 @@ where
     .layer(session_layer);
 
   let app = app
+    .layer(from_fn_with_state(
+      state.clone(),
+      assumed_identity_request_logging,
+    ))
     .layer(axum::middleware::from_fn(csrf_middleware))
     .layer(Extension(csrf_config))
     .layer(session_service)
@@ -0,0 +1,611 @@
+use std::sync::Arc;
+
+use axum::{
+  body::{Body, Bytes},
+  extract::{MatchedPath, Request, State},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use http::{header, HeaderMap, Method, StatusCode};
+use intercode_entities::{assumed_identity_sessions, user_con_profiles, users};
+use sea_orm::{
+  ConnectionTrait, DatabaseBackend, DatabaseConnection, DbErr, EntityTrait, Statement,
+};
+use serde_json::{json, Map, Value};
+use tower_sessions::Session;
+use tracing::log::*;
+
+use crate::client_ip;
+
+/// The session key holding the ID of the assumed_identity_sessions row we're acting under
+pub const ASSUMED_IDENTITY_SESSION_ID_KEY: &str = "assumed_identity_session_id";
+
+// Request bodies bigger than this are logged truncated, though the request still gets all of it
+const MAX_LOGGED_BODY_SIZE: usize = 1024 * 1024;
+const TRUNCATED_BODY_MARKER: &str = "[TRUNCATED]";
+
+const FILTERED_HEADERS: &[&str] = &["authorization", "cookie", "x-csrf-token"];
+// Parameters whose names contain any of these get their values filtered out of the log, like
+// Rails' filter_parameters (so this catches password_confirmation, otp_attempt, recovery codes...)
+const FILTERED_PARAMETERS: &[&str] = &["password", "otp", "code"];
+const FILTERED_VALUE: &str = "[FILTERED]";
+
+/// Who is really behind a request made while assuming someone else's identity.  The effective
+/// user (the one being assumed) is what QueryData's current_user returns; this is the real one.
+#[derive(Clone, Debug)]
+pub struct AssumedIdentity {
+  pub session: assumed_identity_sessions::Model,
+  pub assumer_profile: user_con_profiles::Model,
+  pub assumed_profile: user_con_profiles::Model,
+}
+
+/// Loads the assumed identity session for a signed-in user's cookie session, if there is one and
+/// it's still active and belongs to them.  An identity is only assumed within the convention the
+/// assumed profile belongs to; on any other site, the real user is signed in as themselves.
+pub async fn load_assumed_identity<C: ConnectionTrait>(
+  db: &C,
+  session: &Session,
+  real_user_id: i64,
+  convention_id: Option<i64>,
+) -> Result<Option<AssumedIdentity>, DbErr> {
+  let Ok(Some(assumed_identity_session_id)) = session.get::<i64>(ASSUMED_IDENTITY_SESSION_ID_KEY)
+  else {
+    return Ok(None);
+  };
+
+  let Some(assumed_identity_session) =
+    assumed_identity_sessions::Entity::find_by_id(assumed_identity_session_id)
+      .one(db)
+      .await?
+      .filter(|assumed_identity_session| assumed_identity_session.finished_at.is_none())
+  else {
+    let _ = session.remove::<i64>(ASSUMED_IDENTITY_SESSION_ID_KEY);
+    return Ok(None);
+  };
+
+  let assumer_profile =
+    user_con_profiles::Entity::find_by_id(assumed_identity_session.assumer_profile_id)
+      .one(db)
+      .await?;
+  let assumed_profile =
+    user_con_profiles::Entity::find_by_id(assumed_identity_session.assumed_profile_id)
+      .one(db)
+      .await?;
+
+  match (assumer_profile, assumed_profile) {
+    (Some(assumer_profile), Some(assumed_profile)) if assumer_profile.user_id == real_user_id => {
+      if Some(assumed_profile.convention_id) != convention_id {
+        return Ok(None);
+      }
+
+      Ok(Some(AssumedIdentity {
+        session: assumed_identity_session,
+        assumer_profile,
+        assumed_profile,
+      }))
+    }
+    _ => {
+      let _ = session.remove::<i64>(ASSUMED_IDENTITY_SESSION_ID_KEY);
+      Ok(None)
+    }
+  }
+}
+
+/// Loads the user a profile belongs to
+pub async fn load_profile_user<C: ConnectionTrait>(
+  db: &C,
+  user_con_profile: &user_con_profiles::Model,
+) -> Result<Option<users::Model>, DbErr> {
+  users::Entity::find_by_id(user_con_profile.user_id)
+    .one(db)
+    .await
+}
+
+fn logged_headers(headers: &HeaderMap) -> Value {
+  Value::Object(
+    headers
+      .iter()
+      .map(|(name, value)| {
+        let value = if FILTERED_HEADERS.contains(&name.as_str()) {
+          "[FILTERED]".to_string()
+        } else {
+          String::from_utf8_lossy(value.as_bytes()).to_string()
+        };
+        (name.to_string(), json!(value))
+      })
+      .collect::<Map<_, _>>(),
+  )
+}
+
+fn is_filtered_parameter(name: &str) -> bool {
+  let name = name.to_lowercase();
+  FILTERED_PARAMETERS
+    .iter()
+    .any(|filtered| name.contains(filtered))
+}
+
+/// Strips secrets out of form-encoded bodies, the way Rails' filter_parameters would
+fn filter_form_body(body: &str) -> String {
+  body
+    .split('&')
+    .map(|pair| match pair.split_once('=') {
+      Some((key, _)) if is_filtered_parameter(key) => format!("{}={}", key, FILTERED_VALUE),
+      _ => pair.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join("&")
+}
+
+/// Strips secrets out of JSON, at any depth
+fn filter_json_value(value: Value) -> Value {
+  match value {
+    Value::Object(object) => Value::Object(
+      object
+        .into_iter()
+        .map(|(key, value)| {
+          if is_filtered_parameter(&key) {
+            (key, json!(FILTERED_VALUE))
+          } else {
+            (key, filter_json_value(value))
+          }
+        })
+        .collect(),
+    ),
+    Value::Array(values) => Value::Array(values.into_iter().map(filter_json_value).collect()),
+    value => value,
+  }
+}
+
+fn multipart_boundary(content_type: &str) -> Option<&str> {
+  if !content_type.starts_with("multipart/") {
+    return None;
+  }
+
+  content_type.split(';').find_map(|param| {
+    let (name, value) = param.trim().split_once('=')?;
+    name
+      .eq_ignore_ascii_case("boundary")
+      .then(|| value.trim_matches('"'))
+  })
+}
+
+/// Strips secrets out of multipart bodies.  Secret fields have their contents replaced, and so
+/// do the variables in a GraphQL multipart request's operations field.
+fn filter_multipart_body(body: &str, boundary: &str) -> String {
+  let delimiter = format!("--{}", boundary);
+
+  body
+    .split(delimiter.as_str())
+    .map(|part| {
+      let Some((headers, content)) = part.split_once("\r\n\r\n") else {
+        return part.to_string();
+      };
+      let Some(name) = headers.split(';').find_map(|param| {
+        param
+          .trim()
+          .strip_prefix("name=")
+          .map(|name| name.trim_matches('"'))
+      }) else {
+        return part.to_string();
+      };
+      let (value, trailer) = match content.strip_suffix("\r\n") {
+        Some(value) => (value, "\r\n"),
+        None => (content, ""),
+      };
+
+      let value = if is_filtered_parameter(name) {
+        FILTERED_VALUE.to_string()
+      } else if name == "operations" {
+        match serde_json::from_str::<Value>(value) {
+          Ok(operations) => filter_json_value(operations).to_string(),
+          Err(_) => value.to_string(),
+        }
+      } else {
+        value.to_string()
+      };
+      format!("{}\r\n\r\n{}{}", headers, value, trailer)
+    })
+    .collect::<Vec<_>>()
+    .join(delimiter.as_str())
+}
+
+/// Strips secrets out of a request body, based on its content type
+fn filter_body(content_type: Option<&str>, body: &[u8]) -> String {
+  let body = String::from_utf8_lossy(body);
+
+  match content_type {
+    Some(content_type) if content_type.starts_with("application/json") => {
+      match serde_json::from_str::<Value>(&body) {
+        Ok(value) => filter_json_value(value).to_string(),
+        Err(_) => body.to_string(),
+      }
+    }
+    Some(content_type) => match multipart_boundary(content_type) {
+      Some(boundary) => filter_multipart_body(&body, boundary),
+      None => filter_form_body(&body),
+    },
+    None => filter_form_body(&body),
+  }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct GraphQLLogFields {
+  operation_name: Option<String>,
+  document: Option<String>,
+  variables: Option<Value>,
+}
+
+/// Pulls the operation name, document and variables out of a GraphQL request body.  Batched
+/// requests are logged together: names and documents joined, variables as an array.
+fn graphql_log_fields(body: &[u8]) -> GraphQLLogFields {
+  let operations = match serde_json::from_slice::<Value>(body) {
+    Ok(Value::Array(operations)) => operations,
+    Ok(operation @ Value::Object(_)) => vec![operation],
+    _ => return GraphQLLogFields::default(),
+  };
+
+  let strings = |key: &str| {
+    let values = operations
+      .iter()
+      .filter_map(|operation| operation.get(key).and_then(Value::as_str))
+      .collect::<Vec<_>>();
+    (!values.is_empty()).then_some(values)
+  };
+
+  GraphQLLogFields {
+    operation_name: strings("operationName").map(|names| names.join(", ")),
+    document: strings("query").map(|documents| documents.join("\n\n")),
+    variables: if operations.len() == 1 {
+      operations[0]
+        .get("variables")
+        .cloned()
+        .map(filter_json_value)
+    } else {
+      Some(filter_json_value(Value::Array(
+        operations
+          .iter()
+          .map(|operation| operation.get("variables").cloned().unwrap_or(Value::Null))
+          .collect(),
+      )))
+    },
+  }
+}
+
+/// Rails-style controller and action names for a route.  GraphQL requests and the single page
+/// app keep the names the Rails app logged them under; other routes get a controller named after
+/// their path (without parameters or file extensions) and a RESTful action for their method.
+fn controller_and_action(matched_path: Option<&str>, method: &Method) -> (String, String) {
+  let Some(matched_path) = matched_path else {
+    return ("single_page_app".to_string(), "root".to_string());
+  };
+  if matched_path == "/graphql" {
+    return ("graphql".to_string(), "execute".to_string());
+  }
+
+  let controller = matched_path
+    .split('/')
+    .filter(|segment| !segment.is_empty() && !segment.starts_with([':', '*']))
+    .map(|segment| {
+      let segment = segment.trim_start_matches('.');
+      segment.split_once('.').map_or(segment, |(name, _)| name)
+    })
+    .collect::<Vec<_>>()
+    .join("/");
+  let action = match *method {
+    Method::GET | Method::HEAD => "show".to_string(),
+    Method::POST => "create".to_string(),
+    Method::PUT | Method::PATCH => "update".to_string(),
+    Method::DELETE => "destroy".to_string(),
+    _ => method.as_str().to_lowercase(),
+  };
+
+  (controller, action)
+}
+
+/// Reads up to MAX_LOGGED_BODY_SIZE bytes of a request body to log.  Returns a body with all of
+/// the original in it to pass along, the part to log, and whether that part was truncated.
+async fn read_loggable_body(body: Body) -> Result<(Body, Bytes, bool), axum::Error> {
+  let mut data_stream = body.into_data_stream();
+  let mut buffered = Vec::new();
+
+  while buffered.len() <= MAX_LOGGED_BODY_SIZE {
+    match data_stream.next().await {
+      Some(chunk) => buffered.extend_from_slice(&chunk?),
+      None => {
+        let buffered = Bytes::from(buffered);
+        return Ok((Body::from(buffered.clone()), buffered, false));
+      }
+    }
+  }
+
+  let buffered = Bytes::from(buffered);
+  let logged = buffered.slice(..MAX_LOGGED_BODY_SIZE);
+  let body = Body::from_stream(
+    stream::once(async move { Ok::<_, axum::Error>(buffered) }).chain(data_stream),
+  );
+  Ok((body, logged, true))
+}
+
+async fn insert_request_log(
+  db: &DatabaseConnection,
+  assumed_identity_session_id: i64,
+  request: &Request,
+  body: &[u8],
+  body_truncated: bool,
+) -> Result<(), DbErr> {
+  let matched_path = request
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|matched_path| matched_path.as_str());
+  let path = matched_path.unwrap_or_else(|| request.uri().path());
+  let (controller_name, action_name) = controller_and_action(matched_path, request.method());
+  let content_type = request
+    .headers()
+    .get(header::CONTENT_TYPE)
+    .and_then(|content_type| content_type.to_str().ok());
+  let is_json = content_type
+    .map(|content_type| content_type.starts_with("application/json"))
+    .unwrap_or(false);
+
+  let graphql = if path == "/graphql" && is_json {
+    graphql_log_fields(body)
+  } else {
+    GraphQLLogFields::default()
+  };
+  let http_body = if body.is_empty() || graphql.document.is_some() {
+    None
+  } else if body_truncated {
+    Some(format!(
+      "{}{}",
+      filter_body(content_type, body),
+      TRUNCATED_BODY_MARKER
+    ))
+  } else {
+    Some(filter_body(content_type, body))
+  };
+  let now = Utc::now().naive_utc();
+
+  // ip_address is an inet column, which needs an explicit cast from the text we bind
+  db.execute(Statement::from_sql_and_values(
+    DatabaseBackend::Postgres,
+    "INSERT INTO assumed_identity_request_logs (assumed_identity_session_id, controller_name, \
+     action_name, http_method, url, ip_address, http_headers, http_body, graphql_operation_name, \
+     graphql_document, graphql_variables, created_at, updated_at) \
+     VALUES ($1, $2, $3, $4, $5, CAST($6 AS inet), $7, $8, $9, $10, $11, $12, $12)",
+    [
+      assumed_identity_session_id.into(),
+      controller_name.into(),
+      action_name.into(),
+      request.method().as_str().into(),
+      request.uri().to_string().into(),
+      client_ip(request.headers())
+        .unwrap_or_else(|| "127.0.0.1".to_string())
+        .into(),
+      logged_headers(request.headers()).into(),
+      http_body.into(),
+      graphql.operation_name.into(),
+      graphql.document.into(),
+      graphql.variables.into(),
+      now.into(),
+    ],
+  ))
+  .await?;
+
+  Ok(())
+}
+
+/// Logs every request made while assuming someone else's identity to
+/// assumed_identity_request_logs.  The log is written outside the request's transaction, so
+/// requests that fail are logged too.
+pub async fn assumed_identity_request_logging(
+  State(db): State<Arc<DatabaseConnection>>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let assumed_identity_session_id = request.extensions().get::<Session>().and_then(|session| {
+    session
+      .get::<i64>(ASSUMED_IDENTITY_SESSION_ID_KEY)
+      .ok()
+      .flatten()
+  });
+  let Some(assumed_identity_session_id) = assumed_identity_session_id else {
+    return next.run(request).await;
+  };
+
+  match assumed_identity_sessions::Entity::find_by_id(assumed_identity_session_id)
+    .one(db.as_ref())
+    .await
+  {
+    Ok(Some(assumed_identity_session)) if assumed_identity_session.finished_at.is_none() => {}
+    Ok(_) => return next.run(request).await,
+    Err(err) => {
+      error!("Couldn't load assumed identity session: {:?}", err);
+      return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+  }
+
+  let (parts, body) = request.into_parts();
+  let (body, logged_body, body_truncated) = match read_loggable_body(body).await {
+    Ok(read) => read,
+    Err(err) => {
+      warn!("Couldn't read request body: {:?}", err);
+      return StatusCode::BAD_REQUEST.into_response();
+    }
+  };
+  let request = Request::from_parts(parts, body);
+
+  if let Err(err) = insert_request_log(
+    &db,
+    assumed_identity_session_id,
+    &request,
+    &logged_body,
+    body_truncated,
+  )
+  .await
+  {
+    // a request we can't audit shouldn't go through
+    error!("Couldn't log assumed identity request: {:?}", err);
+    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+  }
+
+  next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn extracts_graphql_fields() {
+    let fields = graphql_log_fields(
+      br#"{"operationName":"CurrentUser","query":"query CurrentUser { currentUser { id } }","variables":{"a":1}}"#,
+    );
+    assert_eq!(fields.operation_name.as_deref(), Some("CurrentUser"));
+    assert_eq!(
+      fields.document.as_deref(),
+      Some("query CurrentUser { currentUser { id } }")
+    );
+    assert_eq!(fields.variables, Some(json!({ "a": 1 })));
+  }
+
+  #[test]
+  fn extracts_batched_graphql_fields() {
+    let fields = graphql_log_fields(
+      br#"[{"operationName":"A","query":"query A { a }"},{"operationName":"B","query":"query B { b }","variables":{"b":2}}]"#,
+    );
+    assert_eq!(fields.operation_name.as_deref(), Some("A, B"));
+    assert_eq!(
+      fields.document.as_deref(),
+      Some("query A { a }\n\nquery B { b }")
+    );
+    assert_eq!(fields.variables, Some(json!([null, { "b": 2 }])));
+  }
+
+  #[test]
+  fn names_controllers_and_actions_rails_style() {
+    let name = |path, method| {
+      let (controller, action) = controller_and_action(path, &method);
+      format!("{}#{}", controller, action)
+    };
+
+    assert_eq!(name(Some("/graphql"), Method::POST), "graphql#execute");
+    assert_eq!(name(None, Method::GET), "single_page_app#root");
+    assert_eq!(
+      name(Some("/users/sign_in"), Method::POST),
+      "users/sign_in#create"
+    );
+    assert_eq!(
+      name(Some("/users/password"), Method::PATCH),
+      "users/password#update"
+    );
+    assert_eq!(
+      name(
+        Some("/reports/user_con_profiles/:user_con_profile_id"),
+        Method::GET
+      ),
+      "reports/user_con_profiles#show"
+    );
+    assert_eq!(
+      name(Some("/feeds/events.rss"), Method::GET),
+      "feeds/events#show"
+    );
+    assert_eq!(
+      name(Some("/.well-known/openid-configuration"), Method::GET),
+      "well-known/openid-configuration#show"
+    );
+  }
+
+  #[tokio::test]
+  async fn passes_along_bodies_too_big_to_log() {
+    let original = vec![b'a'; MAX_LOGGED_BODY_SIZE + 10];
+    let (body, logged, truncated) = read_loggable_body(Body::from(original.clone()))
+      .await
+      .unwrap();
+
+    assert!(truncated);
+    assert_eq!(logged.len(), MAX_LOGGED_BODY_SIZE);
+    assert_eq!(
+      axum::body::to_bytes(body, usize::MAX).await.unwrap(),
+      Bytes::from(original)
+    );
+  }
+
+  #[test]
+  fn filters_passwords_from_form_bodies() {
+    assert_eq!(
+      filter_form_body("user%5Bemail%5D=a%40b.com&user%5Bpassword%5D=hunter2"),
+      "user%5Bemail%5D=a%40b.com&user%5Bpassword%5D=[FILTERED]"
+    );
+    assert_eq!(
+      filter_form_body("otp_attempt=123456&remember=1"),
+      "otp_attempt=[FILTERED]&remember=1"
+    );
+  }
+
+  #[test]
+  fn filters_secrets_from_graphql_variables_at_any_depth() {
+    let fields = graphql_log_fields(
+      br#"{"query":"mutation { a }","variables":{"email":"a@b.com","Password":"hunter2","input":{"otpCode":"123456","profiles":[{"name":"x","recovery_code":"abc"}]}}}"#,
+    );
+    assert_eq!(
+      fields.variables,
+      Some(json!({
+        "email": "a@b.com",
+        "Password": "[FILTERED]",
+        "input": {
+          "otpCode": "[FILTERED]",
+          "profiles": [{ "name": "x", "recovery_code": "[FILTERED]" }],
+        },
+      }))
+    );
+
+    let fields = graphql_log_fields(
+      br#"[{"query":"query A { a }"},{"query":"mutation B { b }","variables":{"password":"x"}}]"#,
+    );
+    assert_eq!(
+      fields.variables,
+      Some(json!([null, { "password": "[FILTERED]" }]))
+    );
+  }
+
+  #[test]
+  fn filters_secrets_from_multipart_bodies() {
+    let body = "--XyZ\r\n\
+Content-Disposition: form-data; name=\"user[email]\"\r\n\r\n\
+a@b.com\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"user[password]\"\r\n\r\n\
+hunter2\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"operations\"\r\n\r\n\
+{\"query\":\"mutation { a }\",\"variables\":{\"otp\":\"123456\",\"file\":null}}\r\n\
+--XyZ--\r\n";
+
+    assert_eq!(
+      filter_body(Some("multipart/form-data; boundary=XyZ"), body.as_bytes()),
+      "--XyZ\r\n\
+Content-Disposition: form-data; name=\"user[email]\"\r\n\r\n\
+a@b.com\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"user[password]\"\r\n\r\n\
+[FILTERED]\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"operations\"\r\n\r\n\
+{\"query\":\"mutation { a }\",\"variables\":{\"file\":null,\"otp\":\"[FILTERED]\"}}\r\n\
+--XyZ--\r\n"
+    );
+  }
+
+  #[test]
+  fn filters_secrets_from_json_bodies() {
+    assert_eq!(
+      filter_body(
+        Some("application/json"),
+        br#"{"client_id":"abc","code":"xyz","code_verifier":"123"}"#
+      ),
+      r#"{"client_id":"abc","code":"[FILTERED]","code_verifier":"[FILTERED]"}"#
+    );
+  }
+}
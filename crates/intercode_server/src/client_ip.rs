@@ -0,0 +1,19 @@
+use http::HeaderMap;
+
+/// The address of the client that made a request.  We only ever listen on localhost behind a
+/// reverse proxy, so this comes from the headers the proxy sets.  Proxies append the address they
+/// received the request from to X-Forwarded-For, so the last entry is the one we can trust;
+/// anything before it was supplied by the client.
+pub fn client_ip(headers: &HeaderMap) -> Option<String> {
+  headers
+    .get("x-forwarded-for")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.rsplit(',').next())
+    .or_else(|| {
+      headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+    })
+    .map(|ip| ip.trim().to_string())
+    .filter(|ip| !ip.is_empty())
+}
@@ -5,7 +5,9 @@ use chrono::Utc;
 use futures::future::BoxFuture;
 use http::Request;
 use intercode_entities::sessions;
-use sea_orm::{sea_query::OnConflict, ColumnTrait, DbErr, EntityTrait, QueryFilter};
+use sea_orm::{
+  sea_query::OnConflict, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+};
 use seawater::ConnectionWrapper;
 use tower::{Layer, Service};
 use tower_sessions::{
@@ -65,6 +67,16 @@ impl From<serde_json::Error> for DbSessionError {
   }
 }
 
+/// Session keys that tie a session to a user: the signed-in user, or someone who's entered their
+/// password and is partway through two-factor sign-in
+const SESSION_USER_ID_KEYS: &[&str] = &["current_user_id", "two_factor_pending_user_id"];
+
+fn session_user_id(session: &Session) -> Option<i64> {
+  SESSION_USER_ID_KEYS
+    .iter()
+    .find_map(|key| session.get::<i64>(key).ok().flatten())
+}
+
 #[async_trait]
 impl SessionStore for DbSessionStore {
   type Error = DbSessionError;
@@ -78,11 +90,19 @@ impl SessionStore for DbSessionStore {
       updated_at: sea_orm::ActiveValue::Set(Some(Utc::now().naive_utc())),
       session_id: sea_orm::ActiveValue::Set(session_id),
       data: sea_orm::ActiveValue::Set(Some(encoded_data)),
+      user_id: sea_orm::ActiveValue::Set(session_user_id(session)),
     };
     sessions::Entity::insert(model)
       .on_conflict(
         OnConflict::column(sessions::Column::SessionId)
-          .update_columns(vec![sessions::Column::UpdatedAt, sessions::Column::Data].into_iter())
+          .update_columns(
+            vec![
+              sessions::Column::UpdatedAt,
+              sessions::Column::Data,
+              sessions::Column::UserId,
+            ]
+            .into_iter(),
+          )
           .to_owned(),
       )
       .exec(self.db.as_ref())
@@ -113,6 +133,20 @@ impl SessionStore for DbSessionStore {
   }
 }
 
+/// Signs a user out everywhere by deleting every stored session that belongs to them, including
+/// ones where they're partway through signing in
+pub async fn delete_sessions_for_user<C: ConnectionTrait>(
+  db: &C,
+  user_id: i64,
+) -> Result<u64, DbSessionError> {
+  let result = sessions::Entity::delete_many()
+    .filter(sessions::Column::UserId.eq(user_id))
+    .exec(db)
+    .await?;
+
+  Ok(result.rows_affected)
+}
+
 #[derive(Clone)]
 pub struct SessionWithDbStoreFromTxLayer;
 
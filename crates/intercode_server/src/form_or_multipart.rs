@@ -6,6 +6,7 @@ use axum::{
 use http::StatusCode;
 use once_cell::sync::Lazy;
 use regex::bytes::Regex;
+use serde::de::DeserializeOwned;
 
 static CONTENT_TYPE_MIME_TYPE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^([^;]+)").unwrap());
 
@@ -14,6 +15,32 @@ pub enum FormOrMultipart<T> {
   Multipart(Multipart),
 }
 
+impl<T: DeserializeOwned> FormOrMultipart<T> {
+  /// Deserializes the submitted fields, whichever way they were encoded.  Multipart fields are
+  /// all read as text.
+  pub async fn into_params(self) -> Result<T, StatusCode> {
+    match self {
+      FormOrMultipart::Form(Form(params)) => Ok(params),
+      FormOrMultipart::Multipart(mut multipart) => {
+        let mut fields = serde_json::Map::new();
+
+        while let Some(field) = multipart
+          .next_field()
+          .await
+          .map_err(|_err| StatusCode::BAD_REQUEST)?
+        {
+          let name = field.name().unwrap_or_default().to_string();
+          let value = field.text().await.map_err(|_err| StatusCode::BAD_REQUEST)?;
+          fields.insert(name, serde_json::Value::String(value));
+        }
+
+        serde_json::from_value(serde_json::Value::Object(fields))
+          .map_err(|_err| StatusCode::BAD_REQUEST)
+      }
+    }
+  }
+}
+
 #[async_trait]
 impl<T, S: Send + Sync> FromRequest<S> for FormOrMultipart<T>
 where
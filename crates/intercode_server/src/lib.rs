@@ -1,5 +1,7 @@
 pub mod actions;
 mod app;
+mod assumed_identity;
+mod client_ip;
 mod csrf;
 mod db_sessions;
 mod form_or_multipart;
@@ -10,6 +12,9 @@ mod server;
 
 pub use crate::csrf::*;
 pub use app::*;
+pub use assumed_identity::*;
+pub use client_ip::client_ip;
+pub use db_sessions::{delete_sessions_for_user, DbSessionStore};
 pub use form_or_multipart::*;
 pub use middleware::*;
 pub use server::*;
@@ -2,19 +2,27 @@ use axum::{
   async_trait,
   extract::{FromRequestParts, Host},
 };
-use http::{request::Parts, StatusCode};
+use chrono::{Duration, Utc};
+use http::{header::AUTHORIZATION, request::Parts, StatusCode};
 use intercode_entities::{
-  cms_parent::CmsParent, conventions, root_sites, user_con_profiles, users,
+  cms_parent::CmsParent,
+  conventions,
+  model_ext::{oauth::OAUTH_DEFAULT_SCOPES, personal_access_tokens::PERSONAL_ACCESS_TOKEN_PREFIX},
+  oauth_access_tokens, personal_access_tokens, root_sites, user_con_profiles, users,
 };
 use intercode_graphql_core::query_data::{ArcQueryData, OwnedQueryData, QueryData};
 use intercode_policies::AuthorizationInfo;
 use once_cell::sync::Lazy;
+use oxide_auth::endpoint::Scope;
 use regex::Regex;
-use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, DbErr, EntityTrait, QueryFilter};
 use seawater::ConnectionWrapper;
+use sha2::{Digest, Sha256};
 use tower_sessions::Session;
 use tracing::{error, warn};
 
+use crate::{client_ip, load_assumed_identity, AssumedIdentity};
+
 static PORT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(":\\d+$").unwrap());
 
 async fn convention_from_request_parts(
@@ -57,6 +65,139 @@ async fn cms_parent_from_request_parts(
   Ok((cms_parent, convention))
 }
 
+/// The OAuth access token a request was authenticated with, if it came with an Authorization
+/// header rather than a session cookie.  QueryDataFromRequest puts this in the request
+/// extensions so that later extractors can see which scopes were granted.
+#[derive(Clone, Debug)]
+pub struct BearerTokenAuthorization(pub oauth_access_tokens::Model);
+
+impl BearerTokenAuthorization {
+  pub fn scope(&self) -> Scope {
+    // anything unparseable gets the most limited scope rather than none at all, since no scope
+    // means a cookie session
+    self
+      .0
+      .scope_string()
+      .parse()
+      .unwrap_or_else(|_| OAUTH_DEFAULT_SCOPES.parse().unwrap())
+  }
+}
+
+/// The personal access token a request was authenticated with.  These work like OAuth bearer
+/// tokens, except that users create them for themselves rather than granting them to an app.
+#[derive(Clone, Debug)]
+pub struct PersonalAccessTokenAuthorization(pub personal_access_tokens::Model);
+
+impl PersonalAccessTokenAuthorization {
+  pub fn scope(&self) -> Scope {
+    self
+      .0
+      .scopes
+      .parse()
+      .unwrap_or_else(|_| OAUTH_DEFAULT_SCOPES.parse().unwrap())
+  }
+}
+
+/// Personal access tokens are stored as a SHA-256 digest; they're long and random enough that
+/// nothing slower is needed
+pub fn personal_access_token_digest(token: &str) -> String {
+  hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// how stale last_used_at can get before a request updates it
+const PERSONAL_ACCESS_TOKEN_LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+enum BearerCredential {
+  OAuth(oauth_access_tokens::Model),
+  PersonalAccessToken(personal_access_tokens::Model),
+}
+
+fn invalid_bearer_token() -> (StatusCode, String) {
+  (
+    StatusCode::UNAUTHORIZED,
+    "The access token is invalid, expired or revoked".to_string(),
+  )
+}
+
+async fn personal_access_token_from_request_parts(
+  parts: &Parts,
+  db: &ConnectionWrapper,
+  token: &str,
+) -> Result<personal_access_tokens::Model, (StatusCode, String)> {
+  let now = Utc::now().naive_utc();
+  let personal_access_token = personal_access_tokens::Entity::find()
+    .filter(personal_access_tokens::Column::TokenDigest.eq(personal_access_token_digest(token)))
+    .one(db.as_ref())
+    .await
+    .map_err(|db_err| {
+      error!("Error finding personal access token: {:?}", db_err);
+      (StatusCode::INTERNAL_SERVER_ERROR, db_err.to_string())
+    })?
+    .filter(|personal_access_token| personal_access_token.is_accessible(now))
+    .ok_or_else(invalid_bearer_token)?;
+
+  let last_used_is_stale = personal_access_token
+    .last_used_at
+    .map(|last_used_at| {
+      last_used_at + Duration::seconds(PERSONAL_ACCESS_TOKEN_LAST_USED_RESOLUTION_SECONDS) <= now
+    })
+    .unwrap_or(true);
+  if !last_used_is_stale {
+    return Ok(personal_access_token);
+  }
+
+  personal_access_tokens::ActiveModel {
+    id: ActiveValue::Unchanged(personal_access_token.id),
+    last_used_at: ActiveValue::Set(Some(now)),
+    last_used_ip: ActiveValue::Set(client_ip(&parts.headers)),
+    ..Default::default()
+  }
+  .update(db.as_ref())
+  .await
+  .map_err(|db_err| {
+    error!("Error updating personal access token: {:?}", db_err);
+    (StatusCode::INTERNAL_SERVER_ERROR, db_err.to_string())
+  })
+}
+
+async fn bearer_credential_from_request_parts(
+  parts: &Parts,
+  db: &ConnectionWrapper,
+) -> Result<Option<BearerCredential>, (StatusCode, String)> {
+  let Some(header) = parts.headers.get(AUTHORIZATION) else {
+    return Ok(None);
+  };
+  let Some(token) = header
+    .to_str()
+    .ok()
+    .and_then(|header| header.strip_prefix("Bearer "))
+    .map(str::trim)
+  else {
+    return Ok(None);
+  };
+
+  if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+    return personal_access_token_from_request_parts(parts, db, token)
+      .await
+      .map(|personal_access_token| {
+        Some(BearerCredential::PersonalAccessToken(personal_access_token))
+      });
+  }
+
+  let access_token = oauth_access_tokens::Entity::find()
+    .filter(oauth_access_tokens::Column::Token.eq(token))
+    .one(db.as_ref())
+    .await
+    .map_err(|db_err| {
+      error!("Error finding access token: {:?}", db_err);
+      (StatusCode::INTERNAL_SERVER_ERROR, db_err.to_string())
+    })?
+    .filter(|access_token| access_token.is_accessible(Utc::now().naive_utc()))
+    .ok_or_else(invalid_bearer_token)?;
+
+  Ok(Some(BearerCredential::OAuth(access_token)))
+}
+
 pub struct QueryDataFromRequest(pub QueryData);
 
 #[async_trait]
@@ -78,7 +219,7 @@ impl<S: Sync> FromRequestParts<S> for QueryDataFromRequest {
     let (cms_parent, convention) = cms_parent_from_request_parts(parts, &db)
       .await
       .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-    let session = parts.extensions.get::<Session>().unwrap();
+    let session = parts.extensions.get::<Session>().unwrap().clone();
 
     let Some(cms_parent) = cms_parent else {
       return Err((
@@ -92,9 +233,54 @@ impl<S: Sync> FromRequestParts<S> for QueryDataFromRequest {
       .get("X-Intercode-User-Timezone")
       .and_then(|header| header.to_str().ok());
 
-    let current_user_id: Option<i64> = session
-      .get("current_user_id")
-      .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    let bearer_credential = bearer_credential_from_request_parts(parts, &db).await?;
+    let current_user_id: Option<i64> = if let Some(bearer_credential) = bearer_credential {
+      match bearer_credential {
+        BearerCredential::OAuth(bearer_token) => {
+          let resource_owner_id = bearer_token.resource_owner_id;
+          parts
+            .extensions
+            .insert(BearerTokenAuthorization(bearer_token));
+          resource_owner_id
+        }
+        BearerCredential::PersonalAccessToken(personal_access_token) => {
+          let user_id = personal_access_token.user_id;
+          parts
+            .extensions
+            .insert(PersonalAccessTokenAuthorization(personal_access_token));
+          Some(user_id)
+        }
+      }
+    } else {
+      let real_user_id: Option<i64> = session
+        .get("current_user_id")
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+      let assumed_identity = if let Some(real_user_id) = real_user_id {
+        load_assumed_identity(
+          &db,
+          &session,
+          real_user_id,
+          convention.as_ref().map(|convention| convention.id),
+        )
+        .await
+        .map_err(|db_err| {
+          error!("Error finding assumed identity session: {:?}", db_err);
+          (http::StatusCode::INTERNAL_SERVER_ERROR, db_err.to_string())
+        })?
+      } else {
+        None
+      };
+
+      // while assuming someone's identity, they're the current user; the real user is kept
+      // around in the AssumedIdentity for authorization and auditing
+      if let Some(assumed_identity) = assumed_identity {
+        let assumed_user_id = assumed_identity.assumed_profile.user_id;
+        parts.extensions.insert(assumed_identity);
+        Some(assumed_user_id)
+      } else {
+        real_user_id
+      }
+    };
     let current_user = if let Some(current_user_id) = current_user_id {
       users::Entity::find_by_id(current_user_id)
         .one(db.as_ref())
@@ -170,10 +356,20 @@ impl<S: Sync> FromRequestParts<S> for AuthorizationInfoAndQueryDataFromRequest {
       AuthorizationInfo::new(
         query_data.db().clone(),
         query_data.current_user().cloned(),
-        // TODO figure out how to get oauth scopes
-        Default::default(),
-        // TODO figure out how to do assumed identity stuff
-        None,
+        parts
+          .extensions
+          .get::<BearerTokenAuthorization>()
+          .map(BearerTokenAuthorization::scope)
+          .or_else(|| {
+            parts
+              .extensions
+              .get::<PersonalAccessTokenAuthorization>()
+              .map(PersonalAccessTokenAuthorization::scope)
+          }),
+        parts
+          .extensions
+          .get::<AssumedIdentity>()
+          .map(|assumed_identity| assumed_identity.assumer_profile.clone()),
       ),
       query_data,
     ))
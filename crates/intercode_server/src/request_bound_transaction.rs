@@ -6,6 +6,7 @@ use axum::{
   response::{IntoResponse, Response},
 };
 use http::StatusCode;
+use intercode_graphql_core::after_commit::AfterCommitHooks;
 use sea_orm::{DatabaseConnection, DbErr, TransactionTrait};
 use seawater::ConnectionWrapper;
 
@@ -17,10 +18,12 @@ async fn run_request_bound_transaction(
   let extensions_mut = request.extensions_mut();
   let tx = db.begin().await?;
   let tx_arc = Arc::new(tx);
+  let after_commit_hooks = AfterCommitHooks::default();
 
   extensions_mut.insert(ConnectionWrapper::DatabaseTransaction(Arc::downgrade(
     &tx_arc,
   )));
+  extensions_mut.insert(after_commit_hooks.clone());
   let response = next.run(request).await;
   let tx = Arc::try_unwrap(tx_arc).map_err(|arc| {
     DbErr::Custom(format!(
@@ -31,6 +34,7 @@ async fn run_request_bound_transaction(
 
   if response.status().is_success() {
     tx.commit().await?;
+    after_commit_hooks.run();
   } else {
     tx.rollback().await?;
   }
@@ -0,0 +1,40 @@
+use aws_sdk_sesv2::types::Destination;
+use http::StatusCode;
+use intercode_graphql_core::query_data::QueryData;
+use tracing::log::*;
+
+/// Where account emails come from: the current convention's address if there is one, otherwise
+/// a no-reply address on the current host
+pub(crate) fn account_email_from(query_data: &QueryData, host: &str) -> String {
+  match query_data.convention() {
+    Some(convention) => convention.email_from.clone(),
+    None => format!("noreply@{}", host.split(':').next().unwrap_or(host)),
+  }
+}
+
+pub(crate) fn account_url(host: &str, path_and_query: &str) -> String {
+  format!("https://{}{}", host, path_and_query)
+}
+
+pub(crate) async fn send_account_email(
+  from: &str,
+  to: &str,
+  subject: &str,
+  body_html: &str,
+  body_text: &str,
+) -> Result<(), StatusCode> {
+  intercode_email::send_email(
+    from,
+    Destination::builder().to_addresses(to).build(),
+    subject,
+    Some(body_html),
+    Some(body_text),
+  )
+  .await
+  .map_err(|err| {
+    error!("Error sending account email to {}: {}", to, err);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+
+  Ok(())
+}
@@ -0,0 +1,162 @@
+use axum::{
+  debug_handler,
+  response::{self, IntoResponse, Response},
+  Extension,
+};
+use chrono::Utc;
+use http::StatusCode;
+use intercode_entities::{assumed_identity_sessions, user_con_profiles};
+use intercode_policies::{
+  policies::{UserConProfileAction, UserConProfilePolicy},
+  Policy,
+};
+use intercode_server::{
+  enforce_csrf, AssumedIdentity, AuthorizationInfoAndQueryDataFromRequest, CsrfData,
+  FormOrMultipart, ASSUMED_IDENTITY_SESSION_ID_KEY,
+};
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use serde_json::json;
+use tower_sessions::Session;
+use tracing::log::*;
+
+#[derive(Deserialize, Debug)]
+pub struct StartAssumedIdentitySessionParams {
+  user_con_profile_id: i64,
+  justification: String,
+}
+
+fn assumed_identity_error(status: StatusCode, message: &str) -> Response {
+  (status, response::Json(json!({ "error": message }))).into_response()
+}
+
+fn internal_error(err: impl std::fmt::Debug) -> Response {
+  error!("Error in assumed identity session: {:?}", err);
+  StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Starts acting as another user's profile in the current convention.  Every request made until
+/// the session is stopped gets logged against it.
+#[debug_handler]
+pub async fn start_assumed_identity_session(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
+  assumed_identity: Option<Extension<AssumedIdentity>>,
+  session: Session,
+  form: FormOrMultipart<StartAssumedIdentitySessionParams>,
+) -> Result<impl IntoResponse, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  if assumed_identity.is_some() {
+    return Err(assumed_identity_error(
+      StatusCode::CONFLICT,
+      "You're already assuming someone else's identity.  Stop that session first.",
+    ));
+  }
+
+  let params = form
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let justification = params.justification.trim();
+  if justification.is_empty() {
+    return Err(assumed_identity_error(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      "A justification is required to assume someone else's identity.",
+    ));
+  }
+
+  let (Some(user), Some(convention)) = (query_data.current_user(), query_data.convention()) else {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  };
+  let db = query_data.db();
+
+  let assumer_profile = user_con_profiles::Entity::find()
+    .filter(user_con_profiles::Column::UserId.eq(user.id))
+    .filter(user_con_profiles::Column::ConventionId.eq(convention.id))
+    .one(db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| StatusCode::FORBIDDEN.into_response())?;
+
+  let assumed_profile = user_con_profiles::Entity::find_by_id(params.user_con_profile_id)
+    .filter(user_con_profiles::Column::ConventionId.eq(convention.id))
+    .one(db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+
+  if assumed_profile.id == assumer_profile.id
+    || !UserConProfilePolicy::action_permitted(
+      &authorization_info,
+      &UserConProfileAction::Become,
+      &assumed_profile,
+    )
+    .await
+    .map_err(internal_error)?
+  {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  let now = Utc::now().naive_utc();
+  let assumed_identity_session = assumed_identity_sessions::ActiveModel {
+    assumer_profile_id: ActiveValue::Set(assumer_profile.id),
+    assumed_profile_id: ActiveValue::Set(assumed_profile.id),
+    justification: ActiveValue::Set(justification.to_string()),
+    started_at: ActiveValue::Set(now),
+    created_at: ActiveValue::Set(now),
+    updated_at: ActiveValue::Set(now),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+  .map_err(internal_error)?;
+
+  session
+    .insert(ASSUMED_IDENTITY_SESSION_ID_KEY, assumed_identity_session.id)
+    .map_err(internal_error)?;
+
+  Ok(response::Json(json!({
+    "status": "success",
+    "assumed_identity_session": {
+      "id": assumed_identity_session.id.to_string(),
+      "assumed_profile_id": assumed_profile.id.to_string(),
+      "started_at": assumed_identity_session.started_at,
+    },
+  })))
+}
+
+/// Stops assuming someone else's identity and goes back to being ourselves
+#[debug_handler]
+pub async fn stop_assumed_identity_session(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(_, query_data): AuthorizationInfoAndQueryDataFromRequest,
+  assumed_identity: Option<Extension<AssumedIdentity>>,
+  session: Session,
+) -> Result<impl IntoResponse, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  let Some(Extension(assumed_identity)) = assumed_identity else {
+    return Err(assumed_identity_error(
+      StatusCode::NOT_FOUND,
+      "You aren't assuming anyone's identity.",
+    ));
+  };
+
+  let now = Utc::now().naive_utc();
+  assumed_identity_sessions::ActiveModel {
+    id: ActiveValue::Unchanged(assumed_identity.session.id),
+    finished_at: ActiveValue::Set(Some(now)),
+    updated_at: ActiveValue::Set(now),
+    ..Default::default()
+  }
+  .update(query_data.db())
+  .await
+  .map_err(internal_error)?;
+
+  session
+    .remove::<i64>(ASSUMED_IDENTITY_SESSION_ID_KEY)
+    .map_err(internal_error)?;
+
+  Ok(response::Json(json!({ "status": "success" })))
+}
@@ -1,21 +1,28 @@
-use std::collections::HashMap;
-
 use axum::{
   debug_handler,
-  response::{self, IntoResponse},
-  Form,
+  response::{self, IntoResponse, Response},
 };
-use http::StatusCode;
+use chrono::{NaiveDateTime, Utc};
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode};
 use intercode_entities::users;
-use intercode_server::{enforce_csrf, CsrfData, FormOrMultipart, QueryDataFromRequest};
-use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use intercode_server::{
+  client_ip, enforce_csrf, AuthorizationInfoAndQueryDataFromRequest, CsrfData, DbSessionStore,
+  FormOrMultipart, QueryDataFromRequest,
+};
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter,
+};
 use serde::Deserialize;
 use serde_json::json;
-use tower_sessions::Session;
+use tower_sessions::{Session, SessionStore};
+use tracing::log::*;
 
-use crate::legacy_passwords::{verify_legacy_md5_password, verify_legacy_sha1_password};
-
-pub const BCRYPT_COST: u32 = 10;
+use super::two_factor::{begin_two_factor_sign_in, pending_two_factor_step};
+use crate::{
+  password_hashing::PASSWORD_HASHER,
+  sign_in_protection::{LOCKOUT_CONFIG, SIGN_IN_THROTTLE},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct SignInParams {
@@ -25,78 +32,251 @@ pub struct SignInParams {
   password: String,
 }
 
+fn sign_in_error(status: StatusCode, message: &str) -> Response {
+  (status, response::Json(json!({ "error": message }))).into_response()
+}
+
+/// The same response whether the email is unknown, the password is wrong or the account is
+/// locked, so that failed sign-ins don't reveal which accounts exist
+fn invalid_credentials() -> Response {
+  sign_in_error(StatusCode::UNAUTHORIZED, "Invalid email or password.")
+}
+
+/// Counts a failed sign-in attempt against the account, locking it if there have been too many
+pub(crate) async fn record_failed_sign_in<C: ConnectionTrait>(
+  db: &C,
+  user: &users::Model,
+  now: NaiveDateTime,
+) -> Result<(), StatusCode> {
+  // a lock that has expired starts the count over
+  let failed_attempts = if user.locked_at.is_some() {
+    1
+  } else {
+    user.failed_attempts + 1
+  };
+  let locked_at = if failed_attempts >= LOCKOUT_CONFIG.maximum_attempts {
+    warn!(
+      "Locking user {} after {} failed sign-in attempts",
+      user.id, failed_attempts
+    );
+    Some(now)
+  } else {
+    None
+  };
+
+  users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    failed_attempts: ActiveValue::Set(failed_attempts),
+    locked_at: ActiveValue::Set(locked_at),
+    ..Default::default()
+  }
+  .update(db)
+  .await
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  Ok(())
+}
+
+/// Devise's trackable bookkeeping, plus clearing out any failed attempts
+pub(crate) fn track_sign_in(
+  update: &mut users::ActiveModel,
+  user: &users::Model,
+  ip: Option<String>,
+  now: NaiveDateTime,
+) {
+  update.sign_in_count = ActiveValue::Set(Some(user.sign_in_count.unwrap_or(0) + 1));
+  update.last_sign_in_at = ActiveValue::Set(user.current_sign_in_at.or(Some(now)));
+  update.current_sign_in_at = ActiveValue::Set(Some(now));
+  update.last_sign_in_ip = ActiveValue::Set(user.current_sign_in_ip.clone().or_else(|| ip.clone()));
+  update.current_sign_in_ip = ActiveValue::Set(ip);
+  update.failed_attempts = ActiveValue::Set(0);
+  update.locked_at = ActiveValue::Set(None);
+}
+
+/// Marks the session as signed in as the given user.  The session gets a new id first, so that
+/// an id someone planted in the browser beforehand (session fixation) doesn't come out of this
+/// signed in.
+pub(crate) fn start_signed_in_session(session: &Session, user_id: i64) -> Result<(), StatusCode> {
+  session.cycle_id();
+  session
+    .insert("current_user_id", user_id)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 #[debug_handler]
 pub async fn sign_in(
   token: CsrfData,
   QueryDataFromRequest(query_data): QueryDataFromRequest,
   session: Session,
+  headers: HeaderMap,
   form_or_multipart: FormOrMultipart<SignInParams>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
   enforce_csrf(token)?;
 
-  let params = match form_or_multipart {
-    FormOrMultipart::Form(form) => form,
-    FormOrMultipart::Multipart(mut multipart) => {
-      let mut mp_params: HashMap<String, String> = Default::default();
+  let params = form_or_multipart.into_params().await?;
+  let email = params.email.trim().to_lowercase();
+  let ip = client_ip(&headers);
 
-      while let Some(field) = multipart.next_field().await.unwrap() {
-        let name = field.name().unwrap().to_string();
-        let value = field.text().await.unwrap();
-        mp_params.insert(name, value);
-      }
-
-      let mp_value = serde_json::to_value(mp_params).unwrap();
-      Form(serde_json::from_value::<SignInParams>(mp_value).unwrap())
-    }
-  };
+  if let Some(retry_after) = SIGN_IN_THROTTLE.hit(ip.as_deref(), &email) {
+    let mut response = sign_in_error(
+      StatusCode::TOO_MANY_REQUESTS,
+      "Too many sign-in attempts.  Please wait a few minutes and try again.",
+    );
+    response
+      .headers_mut()
+      .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs().max(1)));
+    return Ok(response);
+  }
 
   let user = users::Entity::find()
-    .filter(users::Column::Email.eq(params.email.as_str()))
+    .filter(Expr::expr(Func::lower(Expr::col(users::Column::Email))).eq(email.as_str()))
     .one(query_data.db().as_ref())
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
-
-  let password_matches = if !user.encrypted_password.is_empty() {
-    bcrypt::verify(&params.password, &user.encrypted_password)
-      .map_err(|_| StatusCode::NOT_ACCEPTABLE)?
-  } else if let (Some(legacy_password_sha1), Some(legacy_password_sha1_salt)) =
-    (user.legacy_password_sha1, user.legacy_password_sha1_salt)
-  {
-    verify_legacy_sha1_password(
-      &params.password,
-      &legacy_password_sha1,
-      &legacy_password_sha1_salt,
-    )
-  } else if let Some(legacy_password_md5) = user.legacy_password_md5 {
-    verify_legacy_md5_password(&params.password, &legacy_password_md5)
-  } else {
-    false
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  let Some(user) = user else {
+    PASSWORD_HASHER.verify_dummy(&params.password);
+    return Ok(invalid_credentials());
   };
 
-  if !password_matches {
-    return Err(StatusCode::NOT_ACCEPTABLE);
+  let now = Utc::now().naive_utc();
+  // check the password even for locked accounts, so that how long the response takes doesn't
+  // give away which accounts are locked
+  let password_valid = PASSWORD_HASHER.verify(&user, &params.password);
+  if user.is_access_locked(now, LOCKOUT_CONFIG.unlock_in) {
+    return Ok(invalid_credentials());
+  }
+
+  if !password_valid {
+    record_failed_sign_in(query_data.db().as_ref(), &user, now).await?;
+    return Ok(invalid_credentials());
+  }
+
+  if !user.is_confirmed() {
+    return Ok(sign_in_error(
+      StatusCode::FORBIDDEN,
+      "You have to confirm your email address before continuing.",
+    ));
   }
 
-  if user.encrypted_password.is_empty() {
+  let mut update = users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    ..Default::default()
+  };
+
+  if PASSWORD_HASHER.needs_rehash(&user) {
     // upgrade the password while we have it in RAM
-    let upgrade = users::ActiveModel {
-      encrypted_password: ActiveValue::Set(bcrypt::hash(&params.password, BCRYPT_COST).unwrap()),
-      legacy_password_md5: ActiveValue::Set(None),
-      legacy_password_sha1: ActiveValue::Set(None),
-      legacy_password_sha1_salt: ActiveValue::Set(None),
-      ..Default::default()
-    };
-
-    users::Entity::update(upgrade)
-      .exec(query_data.db().as_ref())
-      .await
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    update.encrypted_password = ActiveValue::Set(
+      PASSWORD_HASHER
+        .hash(&params.password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    );
+    update.legacy_password_md5 = ActiveValue::Set(None);
+    update.legacy_password_sha1 = ActiveValue::Set(None);
+    update.legacy_password_sha1_salt = ActiveValue::Set(None);
   }
 
-  session
-    .insert("current_user_id", user.id)
+  // users who need a second factor (or need to set one up) are only partway signed in until
+  // they've provided it
+  if let Some(enrollment_required) = pending_two_factor_step(query_data.db(), &user).await? {
+    if update.is_changed() {
+      update
+        .update(query_data.db().as_ref())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    return begin_two_factor_sign_in(&session, user.id, enrollment_required, now);
+  }
+
+  track_sign_in(&mut update, &user, ip, now);
+  update
+    .update(query_data.db().as_ref())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  SIGN_IN_THROTTLE.reset_account(&email);
+  start_signed_in_session(&session, user.id)?;
+
+  Ok(response::Json(json!({ "status": "success" })).into_response())
+}
+
+#[debug_handler]
+pub async fn sign_out(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
+  session: Session,
+) -> Result<impl IntoResponse, StatusCode> {
+  enforce_csrf(token)?;
+  // signing out ends a browser session; a bearer token has no business doing that
+  if authorization_info.oauth_scope.is_some() {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  DbSessionStore::new(query_data.db().clone())
+    .delete(&session.id())
+    .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  session.delete();
 
   Ok(response::Json(json!({ "status": "success" })))
 }
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::post, Router};
+  use http::{header, Request};
+  use tower::ServiceExt;
+  use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+  use super::*;
+
+  fn session_cookie(response: &Response) -> Option<String> {
+    response
+      .headers()
+      .get_all(header::SET_COOKIE)
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .find(|value| value.starts_with("id="))
+      .and_then(|value| value.split(';').next())
+      .map(str::to_string)
+  }
+
+  #[tokio::test]
+  async fn signing_in_issues_a_new_session_id() {
+    let app = Router::new()
+      .route(
+        "/visit",
+        post(|session: Session| async move {
+          session
+            .insert("visited", true)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        }),
+      )
+      .route(
+        "/sign_in",
+        post(|session: Session| async move { start_signed_in_session(&session, 1) }),
+      )
+      .layer(SessionManagerLayer::new(MemoryStore::default()).with_secure(false));
+
+    let response = app
+      .clone()
+      .oneshot(Request::post("/visit").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let anonymous_cookie = session_cookie(&response).expect("anonymous session cookie");
+
+    let response = app
+      .oneshot(
+        Request::post("/sign_in")
+          .header(header::COOKIE, &anonymous_cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let signed_in_cookie = session_cookie(&response).expect("signed-in session cookie");
+
+    assert_ne!(signed_in_cookie, anonymous_cookie);
+  }
+}
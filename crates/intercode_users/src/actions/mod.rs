@@ -1,3 +1,18 @@
+mod account_emails;
+mod assumed_identity;
 mod authentication;
+mod oauth;
+mod openid_connect;
+mod passwords;
+mod personal_data;
+mod registration;
+mod two_factor;
 
+pub use assumed_identity::*;
 pub use authentication::*;
+pub use oauth::*;
+pub use openid_connect::*;
+pub use passwords::*;
+pub use personal_data::*;
+pub use registration::*;
+pub use two_factor::*;
@@ -0,0 +1,565 @@
+use axum::{
+  debug_handler,
+  extract::{Host, Query},
+  response::{self, IntoResponse, Response},
+};
+use chrono::Utc;
+use http::{HeaderMap, StatusCode};
+use intercode_entities::{
+  conventions, oauth_access_grants, oauth_access_tokens, oauth_applications, oauth_openid_requests,
+  users,
+};
+use intercode_policies::AuthorizationInfo;
+use intercode_server::{
+  enforce_csrf, AuthorizationInfoAndQueryDataFromRequest, CsrfData, FormOrMultipart,
+  QueryDataFromRequest,
+};
+use sea_orm::{
+  sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, EntityTrait,
+  QueryFilter,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::log::*;
+use url::Url;
+
+use crate::{
+  oauth::{
+    authenticate_client, client_credentials, generate_oauth_token, issue_access_token,
+    resolve_requested_scopes, revoke_access_token, scopes_subset, verify_code_challenge,
+    OAuthError, TokenResponse, ACCESS_GRANT_EXPIRES_IN,
+  },
+  openid_connect::{build_id_token, has_openid_scope, issuer_for_host, OIDC_KEY_SET},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct AuthorizationParams {
+  response_type: String,
+  client_id: String,
+  redirect_uri: Option<String>,
+  scope: Option<String>,
+  state: Option<String>,
+  code_challenge: Option<String>,
+  code_challenge_method: Option<String>,
+  nonce: Option<String>,
+}
+
+/// An authorization request that has been checked against the application it's for
+struct PreAuthorization {
+  application: oauth_applications::Model,
+  redirect_uri: String,
+  scopes: String,
+  state: Option<String>,
+  code_challenge: Option<String>,
+  code_challenge_method: Option<String>,
+  nonce: Option<String>,
+}
+
+impl PreAuthorization {
+  fn redirect_with(&self, params: &[(&str, &str)]) -> Result<String, OAuthError> {
+    let mut url = Url::parse(&self.redirect_uri)
+      .map_err(|_| OAuthError::invalid_request("The redirect URI is invalid"))?;
+    {
+      let mut query = url.query_pairs_mut();
+      for (key, value) in params {
+        query.append_pair(key, value);
+      }
+      if let Some(state) = &self.state {
+        query.append_pair("state", state);
+      }
+    }
+
+    Ok(url.to_string())
+  }
+}
+
+async fn pre_authorize<C: ConnectionTrait>(
+  db: &C,
+  params: AuthorizationParams,
+) -> Result<PreAuthorization, OAuthError> {
+  let application = oauth_applications::Entity::find()
+    .filter(oauth_applications::Column::Uid.eq(params.client_id.as_str()))
+    .one(db)
+    .await?
+    .ok_or_else(OAuthError::invalid_client)?;
+
+  let redirect_uri = match params.redirect_uri {
+    Some(redirect_uri) => application
+      .redirect_uris()
+      .any(|registered| registered == redirect_uri)
+      .then_some(redirect_uri),
+    None => {
+      let registered = application.redirect_uris().collect::<Vec<_>>();
+      (registered.len() == 1).then(|| registered[0].to_string())
+    }
+  }
+  .ok_or_else(|| {
+    OAuthError::invalid_request("The redirect URI doesn't match any registered for this client")
+  })?;
+
+  if params.response_type != "code" {
+    return Err(OAuthError::new(
+      StatusCode::BAD_REQUEST,
+      "unsupported_response_type",
+      "The authorization server does not support this response type.",
+    ));
+  }
+
+  let scopes = resolve_requested_scopes(&application, params.scope.as_deref())?;
+  if has_openid_scope(&scopes) && OIDC_KEY_SET.is_none() {
+    return Err(OAuthError::new(
+      StatusCode::BAD_REQUEST,
+      "invalid_scope",
+      "OpenID Connect is not configured on this server.",
+    ));
+  }
+
+  match (
+    &params.code_challenge,
+    params.code_challenge_method.as_deref(),
+  ) {
+    (None, _) if !application.confidential => {
+      return Err(OAuthError::invalid_request(
+        "Public clients must use PKCE (code_challenge)",
+      ));
+    }
+    (Some(_), Some(method)) if method != "plain" && method != "S256" => {
+      return Err(OAuthError::invalid_request(
+        "code_challenge_method must be plain or S256",
+      ));
+    }
+    _ => {}
+  }
+
+  Ok(PreAuthorization {
+    application,
+    redirect_uri,
+    scopes,
+    state: params.state,
+    nonce: params.nonce,
+    code_challenge_method: params.code_challenge.as_ref().map(|_| {
+      params
+        .code_challenge_method
+        .unwrap_or_else(|| "plain".to_string())
+    }),
+    code_challenge: params.code_challenge,
+  })
+}
+
+/// Consent can only be given (or refused) by the user themselves, signed in with a cookie: an
+/// access token can't be used to authorize more applications
+fn ensure_browser_session(authorization_info: &AuthorizationInfo) -> Result<(), StatusCode> {
+  if authorization_info.oauth_scope.is_some() {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  Ok(())
+}
+
+/// Describes an authorization request so that the consent screen can show it
+#[debug_handler]
+pub async fn oauth_pre_authorization(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Query(params): Query<AuthorizationParams>,
+) -> Result<impl IntoResponse, OAuthError> {
+  let pre_auth = pre_authorize(query_data.db(), params).await?;
+
+  Ok(response::Json(json!({
+    "client_id": pre_auth.application.uid,
+    "client_name": pre_auth.application.name,
+    "redirect_uri": pre_auth.redirect_uri,
+    "state": pre_auth.state,
+    "response_type": "code",
+    "scope": pre_auth.scopes,
+    "code_challenge": pre_auth.code_challenge,
+    "code_challenge_method": pre_auth.code_challenge_method,
+  })))
+}
+
+/// The signed-in user approved an authorization request: issue a code and send them back to the
+/// application
+#[debug_handler]
+pub async fn oauth_authorize(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
+  form_or_multipart: FormOrMultipart<AuthorizationParams>,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+  ensure_browser_session(&authorization_info).map_err(IntoResponse::into_response)?;
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let Some(current_user) = query_data.current_user() else {
+    return Err(StatusCode::UNAUTHORIZED.into_response());
+  };
+
+  let pre_auth = pre_authorize(query_data.db(), params)
+    .await
+    .map_err(IntoResponse::into_response)?;
+
+  let grant = oauth_access_grants::ActiveModel {
+    resource_owner_id: ActiveValue::Set(current_user.id),
+    application_id: ActiveValue::Set(pre_auth.application.id),
+    token: ActiveValue::Set(generate_oauth_token()),
+    expires_in: ActiveValue::Set(ACCESS_GRANT_EXPIRES_IN),
+    redirect_uri: ActiveValue::Set(pre_auth.redirect_uri.clone()),
+    scopes: ActiveValue::Set(Some(pre_auth.scopes.clone())),
+    code_challenge: ActiveValue::Set(pre_auth.code_challenge.clone()),
+    code_challenge_method: ActiveValue::Set(pre_auth.code_challenge_method.clone()),
+    created_at: ActiveValue::Set(Utc::now().naive_utc()),
+    ..Default::default()
+  }
+  .insert(query_data.db())
+  .await
+  .map_err(|err| OAuthError::from(err).into_response())?;
+
+  if let Some(nonce) = pre_auth
+    .nonce
+    .as_ref()
+    .filter(|_| has_openid_scope(&pre_auth.scopes))
+  {
+    oauth_openid_requests::ActiveModel {
+      access_grant_id: ActiveValue::Set(grant.id),
+      nonce: ActiveValue::Set(nonce.clone()),
+      ..Default::default()
+    }
+    .insert(query_data.db())
+    .await
+    .map_err(|err| OAuthError::from(err).into_response())?;
+  }
+
+  let redirect_uri = pre_auth
+    .redirect_with(&[("code", &grant.token)])
+    .map_err(IntoResponse::into_response)?;
+
+  Ok(response::Json(json!({ "status": "redirect", "redirect_uri": redirect_uri })).into_response())
+}
+
+/// The signed-in user turned down an authorization request: send them back to the application
+/// with an access_denied error
+#[debug_handler]
+pub async fn oauth_deny(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
+  form_or_multipart: FormOrMultipart<AuthorizationParams>,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+  ensure_browser_session(&authorization_info).map_err(IntoResponse::into_response)?;
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+
+  let pre_auth = pre_authorize(query_data.db(), params)
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let redirect_uri = pre_auth
+    .redirect_with(&[
+      ("error", "access_denied"),
+      (
+        "error_description",
+        "The resource owner or authorization server denied the request.",
+      ),
+    ])
+    .map_err(IntoResponse::into_response)?;
+
+  Ok(response::Json(json!({ "status": "redirect", "redirect_uri": redirect_uri })).into_response())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenParams {
+  grant_type: String,
+  code: Option<String>,
+  redirect_uri: Option<String>,
+  code_verifier: Option<String>,
+  refresh_token: Option<String>,
+  scope: Option<String>,
+  client_id: Option<String>,
+  client_secret: Option<String>,
+}
+
+async fn exchange_authorization_code<C: ConnectionTrait>(
+  db: &C,
+  application: &oauth_applications::Model,
+  params: &TokenParams,
+) -> Result<(oauth_access_tokens::Model, oauth_access_grants::Model), OAuthError> {
+  let now = Utc::now().naive_utc();
+  let code = params
+    .code
+    .as_deref()
+    .ok_or_else(|| OAuthError::invalid_request("Missing code"))?;
+  let grant = oauth_access_grants::Entity::find()
+    .filter(oauth_access_grants::Column::Token.eq(code))
+    .filter(oauth_access_grants::Column::ApplicationId.eq(application.id))
+    .one(db)
+    .await?
+    .filter(|grant| grant.is_accessible(now))
+    .ok_or_else(OAuthError::invalid_grant)?;
+
+  if params.redirect_uri.as_deref() != Some(grant.redirect_uri.as_str()) {
+    return Err(OAuthError::invalid_grant());
+  }
+
+  let pkce_ok = match (&grant.code_challenge, &params.code_verifier) {
+    (Some(challenge), Some(verifier)) => {
+      verify_code_challenge(challenge, grant.code_challenge_method.as_deref(), verifier)
+    }
+    (None, None) => true,
+    _ => false,
+  };
+  if !pkce_ok {
+    return Err(OAuthError::invalid_grant());
+  }
+
+  // codes are single-use; if two requests race to use one, only one of them wins
+  let revoked = oauth_access_grants::Entity::update_many()
+    .col_expr(oauth_access_grants::Column::RevokedAt, Expr::value(now))
+    .filter(oauth_access_grants::Column::Id.eq(grant.id))
+    .filter(oauth_access_grants::Column::RevokedAt.is_null())
+    .exec(db)
+    .await?;
+  if revoked.rows_affected != 1 {
+    return Err(OAuthError::invalid_grant());
+  }
+
+  let access_token = issue_access_token(
+    db,
+    application.id,
+    Some(grant.resource_owner_id),
+    grant.scope_string(),
+    true,
+    None,
+    now,
+  )
+  .await?;
+
+  Ok((access_token, grant))
+}
+
+/// Signs an id_token for an authorization code exchange that asked for the openid scope, using
+/// up the nonce from the authorization request if there was one
+async fn id_token_for_grant<C: ConnectionTrait>(
+  db: &C,
+  issuer: &str,
+  application: &oauth_applications::Model,
+  grant: &oauth_access_grants::Model,
+  convention: Option<&conventions::Model>,
+) -> Result<Option<String>, OAuthError> {
+  let Some(key_set) = OIDC_KEY_SET.as_ref() else {
+    return Ok(None);
+  };
+  if !has_openid_scope(grant.scope_string()) {
+    return Ok(None);
+  }
+
+  let user = users::Entity::find_by_id(grant.resource_owner_id)
+    .one(db)
+    .await?
+    .ok_or_else(OAuthError::invalid_grant)?;
+  let openid_request = oauth_openid_requests::Entity::find()
+    .filter(oauth_openid_requests::Column::AccessGrantId.eq(grant.id))
+    .one(db)
+    .await?;
+  if let Some(openid_request) = &openid_request {
+    oauth_openid_requests::Entity::delete_by_id(openid_request.id)
+      .exec(db)
+      .await?;
+  }
+
+  let id_token = build_id_token(
+    db,
+    key_set,
+    issuer,
+    &application.uid,
+    &user,
+    convention,
+    grant.scope_string(),
+    openid_request
+      .as_ref()
+      .map(|request| request.nonce.as_str()),
+    grant.created_at,
+    Utc::now().naive_utc(),
+  )
+  .await
+  .map_err(|err| {
+    error!("Error signing id_token: {}", err);
+    OAuthError::server_error()
+  })?;
+
+  Ok(Some(id_token))
+}
+
+async fn exchange_refresh_token<C: ConnectionTrait>(
+  db: &C,
+  application: &oauth_applications::Model,
+  params: &TokenParams,
+) -> Result<oauth_access_tokens::Model, OAuthError> {
+  let refresh_token = params
+    .refresh_token
+    .as_deref()
+    .ok_or_else(|| OAuthError::invalid_request("Missing refresh_token"))?;
+  let previous_token = oauth_access_tokens::Entity::find()
+    .filter(oauth_access_tokens::Column::RefreshToken.eq(refresh_token))
+    .filter(oauth_access_tokens::Column::ApplicationId.eq(application.id))
+    .one(db)
+    .await?
+    .filter(|access_token| access_token.revoked_at.is_none())
+    .ok_or_else(OAuthError::invalid_grant)?;
+
+  let scopes = match params
+    .scope
+    .as_deref()
+    .filter(|scope| !scope.trim().is_empty())
+  {
+    Some(scope) => {
+      if !scopes_subset(scope, previous_token.scope_string()) {
+        return Err(OAuthError::invalid_scope());
+      }
+      scope.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+    None => previous_token.scope_string().to_string(),
+  };
+
+  // refresh tokens are single-use too; if two requests race to use one, only one of them wins
+  let revoked = oauth_access_tokens::Entity::update_many()
+    .col_expr(
+      oauth_access_tokens::Column::RevokedAt,
+      Expr::value(Utc::now().naive_utc()),
+    )
+    .filter(oauth_access_tokens::Column::Id.eq(previous_token.id))
+    .filter(oauth_access_tokens::Column::RevokedAt.is_null())
+    .exec(db)
+    .await?;
+  if revoked.rows_affected != 1 {
+    return Err(OAuthError::invalid_grant());
+  }
+
+  Ok(
+    issue_access_token(
+      db,
+      application.id,
+      previous_token.resource_owner_id,
+      &scopes,
+      true,
+      previous_token.refresh_token.clone(),
+      Utc::now().naive_utc(),
+    )
+    .await?,
+  )
+}
+
+/// The token endpoint (RFC 6749 section 3.2).  Supports the authorization_code (with optional
+/// PKCE), refresh_token and client_credentials grants.
+#[debug_handler]
+pub async fn oauth_token(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Host(host): Host,
+  headers: HeaderMap,
+  form_or_multipart: FormOrMultipart<TokenParams>,
+) -> Result<TokenResponse, OAuthError> {
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(|_| OAuthError::invalid_request("Couldn't parse the token request"))?;
+  let db = query_data.db();
+  let credentials = client_credentials(
+    &headers,
+    params.client_id.as_deref(),
+    params.client_secret.as_deref(),
+  );
+
+  let mut id_token = None;
+  let access_token = match params.grant_type.as_str() {
+    "authorization_code" => {
+      let application = authenticate_client(db, credentials, false).await?;
+      let (access_token, grant) = exchange_authorization_code(db, &application, &params).await?;
+      id_token = id_token_for_grant(
+        db,
+        &issuer_for_host(&host),
+        &application,
+        &grant,
+        query_data.convention(),
+      )
+      .await?;
+      access_token
+    }
+    "refresh_token" => {
+      let application = authenticate_client(db, credentials, false).await?;
+      exchange_refresh_token(db, &application, &params).await?
+    }
+    "client_credentials" => {
+      let application = authenticate_client(db, credentials, true).await?;
+      let scopes = resolve_requested_scopes(&application, params.scope.as_deref())?;
+      issue_access_token(
+        db,
+        application.id,
+        None,
+        &scopes,
+        false,
+        None,
+        Utc::now().naive_utc(),
+      )
+      .await?
+    }
+    _ => {
+      return Err(OAuthError::new(
+        StatusCode::BAD_REQUEST,
+        "unsupported_grant_type",
+        "The authorization grant type is not supported by the authorization server.",
+      ))
+    }
+  };
+
+  let mut response = TokenResponse::from(&access_token);
+  response.id_token = id_token;
+  Ok(response)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RevokeParams {
+  token: String,
+  client_id: Option<String>,
+  client_secret: Option<String>,
+}
+
+/// Token revocation (RFC 7009).  Accepts either an access token or a refresh token, and responds
+/// the same way whether or not the token existed.
+#[debug_handler]
+pub async fn oauth_revoke(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  headers: HeaderMap,
+  form_or_multipart: FormOrMultipart<RevokeParams>,
+) -> Result<impl IntoResponse, OAuthError> {
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(|_| OAuthError::invalid_request("Couldn't parse the revocation request"))?;
+  let db = query_data.db();
+  let application = authenticate_client(
+    db,
+    client_credentials(
+      &headers,
+      params.client_id.as_deref(),
+      params.client_secret.as_deref(),
+    ),
+    false,
+  )
+  .await?;
+
+  let access_token = oauth_access_tokens::Entity::find()
+    .filter(
+      oauth_access_tokens::Column::Token
+        .eq(params.token.as_str())
+        .or(oauth_access_tokens::Column::RefreshToken.eq(params.token.as_str())),
+    )
+    .filter(oauth_access_tokens::Column::ApplicationId.eq(application.id))
+    .one(db)
+    .await?;
+
+  if let Some(access_token) = access_token {
+    revoke_access_token(db, &access_token).await?;
+  }
+
+  Ok(response::Json(json!({})))
+}
@@ -0,0 +1,95 @@
+use axum::{
+  debug_handler,
+  extract::Host,
+  response::{self, IntoResponse, Response},
+  Extension,
+};
+use http::{header::WWW_AUTHENTICATE, HeaderValue, StatusCode};
+use intercode_server::{BearerTokenAuthorization, QueryDataFromRequest};
+use serde_json::Value;
+
+use crate::{
+  oauth::OAuthError,
+  openid_connect::{
+    discovery_document, has_openid_scope, issuer_for_host, user_claims, OIDC_KEY_SET,
+  },
+};
+
+fn oidc_not_configured() -> OAuthError {
+  OAuthError::new(
+    StatusCode::NOT_FOUND,
+    "not_found",
+    "OpenID Connect is not configured on this server.",
+  )
+}
+
+/// OpenID Connect discovery (OpenID Connect Discovery 1.0 section 4)
+#[debug_handler]
+pub async fn openid_configuration(Host(host): Host) -> Result<impl IntoResponse, OAuthError> {
+  let key_set = OIDC_KEY_SET.as_ref().ok_or_else(oidc_not_configured)?;
+  Ok(response::Json(discovery_document(
+    &issuer_for_host(&host),
+    key_set,
+  )))
+}
+
+/// The public keys id_tokens are signed with, as a JWK set
+#[debug_handler]
+pub async fn oauth_discovery_keys() -> Result<impl IntoResponse, OAuthError> {
+  let key_set = OIDC_KEY_SET.as_ref().ok_or_else(oidc_not_configured)?;
+  Ok(response::Json(key_set.jwks()))
+}
+
+fn bearer_error(status: StatusCode, error: &'static str, description: &str) -> Response {
+  let mut response = OAuthError::new(status, error, description).into_response();
+  response.headers_mut().insert(
+    WWW_AUTHENTICATE,
+    HeaderValue::from_str(&format!(
+      "Bearer error=\"{}\", error_description=\"{}\"",
+      error, description
+    ))
+    .unwrap(),
+  );
+  response
+}
+
+/// Claims about the user an access token was issued for (OpenID Connect Core 1.0 section 5.3).
+/// The token needs the openid scope.
+#[debug_handler]
+pub async fn oauth_userinfo(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  bearer_token: Option<Extension<BearerTokenAuthorization>>,
+) -> Result<response::Json<Value>, Response> {
+  let Some(Extension(bearer_token)) = bearer_token else {
+    return Err(bearer_error(
+      StatusCode::UNAUTHORIZED,
+      "invalid_token",
+      "An access token is required",
+    ));
+  };
+  if !has_openid_scope(bearer_token.0.scope_string()) {
+    return Err(bearer_error(
+      StatusCode::FORBIDDEN,
+      "insufficient_scope",
+      "The access token wasn't granted the openid scope",
+    ));
+  }
+  let Some(user) = query_data.current_user() else {
+    return Err(bearer_error(
+      StatusCode::FORBIDDEN,
+      "insufficient_scope",
+      "The access token wasn't issued on behalf of a user",
+    ));
+  };
+
+  let claims = user_claims(
+    query_data.db(),
+    user,
+    query_data.convention(),
+    bearer_token.0.scope_string(),
+  )
+  .await
+  .map_err(|err| OAuthError::from(err).into_response())?;
+
+  Ok(response::Json(Value::Object(claims)))
+}
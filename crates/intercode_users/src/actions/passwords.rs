@@ -0,0 +1,189 @@
+use axum::{
+  debug_handler,
+  extract::Host,
+  response::{self, IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use http::StatusCode;
+use intercode_entities::users;
+use intercode_server::{enforce_csrf, CsrfData, FormOrMultipart, QueryDataFromRequest};
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tower_sessions::Session;
+use tracing::log::*;
+
+use super::{
+  account_emails::{account_email_from, account_url, send_account_email},
+  authentication::start_signed_in_session,
+  registration::{validate_new_password, validation_errors_response},
+  two_factor::{begin_two_factor_sign_in, pending_two_factor_step},
+};
+use crate::{devise_tokens::DeviseTokenGenerator, password_hashing::PASSWORD_HASHER};
+
+const RESET_PASSWORD_TOKEN_COLUMN: &str = "reset_password_token";
+
+/// Same as Devise's default reset_password_within
+pub fn reset_password_within() -> Duration {
+  Duration::hours(6)
+}
+
+fn token_generator() -> Result<DeviseTokenGenerator, StatusCode> {
+  DeviseTokenGenerator::from_env().map_err(|err| {
+    error!(
+      "Can't generate password reset tokens without SECRET_KEY_BASE: {}",
+      err
+    );
+    StatusCode::INTERNAL_SERVER_ERROR
+  })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RequestPasswordResetParams {
+  #[serde(rename(deserialize = "user[email]"))]
+  email: String,
+}
+
+/// Emails a password reset link to the given address, if there's an account for it.  Responds
+/// the same way whether or not there is, so that this can't be used to find out who has an
+/// account.
+#[debug_handler]
+pub async fn request_password_reset(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Host(host): Host,
+  form_or_multipart: FormOrMultipart<RequestPasswordResetParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+  enforce_csrf(token)?;
+  let params = form_or_multipart.into_params().await?;
+  let email = params.email.trim().to_lowercase();
+
+  let user = users::Entity::find()
+    .filter(Expr::expr(Func::lower(Expr::col(users::Column::Email))).eq(email.as_str()))
+    .one(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  if let Some(user) = user {
+    let (raw_token, digested_token) = token_generator()?.generate(RESET_PASSWORD_TOKEN_COLUMN);
+    let now = Utc::now().naive_utc();
+    users::ActiveModel {
+      id: ActiveValue::Unchanged(user.id),
+      reset_password_token: ActiveValue::Set(Some(digested_token)),
+      reset_password_sent_at: ActiveValue::Set(Some(now)),
+      updated_at: ActiveValue::Set(Some(now)),
+      ..Default::default()
+    }
+    .update(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let reset_url = account_url(
+      &host,
+      &format!("/users/password/edit?reset_password_token={}", raw_token),
+    );
+    send_account_email(
+      &account_email_from(&query_data, &host),
+      &user.email,
+      "Reset password instructions",
+      &format!(
+        "<p>Hello {}!</p><p>Someone has requested a link to change your password. You can do this through the link below.</p><p><a href=\"{}\">Change my password</a></p><p>If you didn't request this, please ignore this email. Your password won't change until you access the link above and create a new one.</p>",
+        html_escape::encode_text(&user.first_name),
+        reset_url
+      ),
+      &format!(
+        "Hello {}!\n\nSomeone has requested a link to change your password. You can do this through the link below.\n\n{}\n\nIf you didn't request this, please ignore this email. Your password won't change until you access the link above and create a new one.\n",
+        user.first_name, reset_url
+      ),
+    )
+    .await?;
+  }
+
+  Ok(response::Json(json!({ "status": "success" })))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetPasswordParams {
+  #[serde(rename(deserialize = "user[reset_password_token]"))]
+  reset_password_token: String,
+  #[serde(rename(deserialize = "user[password]"))]
+  password: String,
+  #[serde(rename(deserialize = "user[password_confirmation]"))]
+  password_confirmation: String,
+}
+
+/// Sets a new password using the token from a password reset email, then signs the user in
+#[debug_handler]
+pub async fn reset_password(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+  form_or_multipart: FormOrMultipart<ResetPasswordParams>,
+) -> Result<Response, StatusCode> {
+  enforce_csrf(token)?;
+  let params = form_or_multipart.into_params().await?;
+
+  let digested_token =
+    token_generator()?.digest(RESET_PASSWORD_TOKEN_COLUMN, &params.reset_password_token);
+  let user = users::Entity::find()
+    .filter(users::Column::ResetPasswordToken.eq(digested_token))
+    .one(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  let now = Utc::now().naive_utc();
+  let Some(user) = user.filter(|user| {
+    user
+      .reset_password_sent_at
+      .map(|sent_at| sent_at + reset_password_within() > now)
+      .unwrap_or(false)
+  }) else {
+    return Ok(validation_errors_response(vec![
+      "Reset password token is invalid or has expired".to_string(),
+    ]));
+  };
+
+  let errors = validate_new_password(&params.password, &params.password_confirmation);
+  if !errors.is_empty() {
+    return Ok(validation_errors_response(errors));
+  }
+
+  users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    encrypted_password: ActiveValue::Set(
+      PASSWORD_HASHER
+        .hash(&params.password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    ),
+    legacy_password_md5: ActiveValue::Set(None),
+    legacy_password_sha1: ActiveValue::Set(None),
+    legacy_password_sha1_salt: ActiveValue::Set(None),
+    reset_password_token: ActiveValue::Set(None),
+    reset_password_sent_at: ActiveValue::Set(None),
+    failed_attempts: ActiveValue::Set(0),
+    locked_at: ActiveValue::Set(None),
+    // following the emailed link proves they own the address
+    confirmed_at: if user.confirmed_at.is_none() {
+      ActiveValue::Set(Some(now))
+    } else {
+      ActiveValue::NotSet
+    },
+    updated_at: ActiveValue::Set(Some(now)),
+    ..Default::default()
+  }
+  .update(query_data.db())
+  .await
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  // a reset password doesn't get anyone past two-factor authentication
+  if let Some(enrollment_required) = pending_two_factor_step(query_data.db(), &user).await? {
+    return begin_two_factor_sign_in(&session, user.id, enrollment_required, now);
+  }
+
+  start_signed_in_session(&session, user.id)?;
+
+  Ok(response::Json(json!({ "status": "success" })).into_response())
+}
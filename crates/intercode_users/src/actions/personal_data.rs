@@ -0,0 +1,76 @@
+use axum::{
+  debug_handler,
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use http::{
+  header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+  StatusCode,
+};
+use intercode_server::{enforce_csrf, AuthorizationInfoAndQueryDataFromRequest, CsrfData};
+use tracing::log::*;
+
+use crate::personal_data::{
+  export_personal_data, export_summary, record_personal_data_request, PERSONAL_DATA_EXPORT,
+};
+
+/// Downloads everything we hold about the signed-in user as a JSON file.  Like the
+/// exportPersonalData mutation, this only works from the user's own browser session, and every
+/// download is recorded in the personal data request log.
+#[debug_handler]
+pub async fn download_personal_data_export(
+  token: CsrfData,
+  AuthorizationInfoAndQueryDataFromRequest(authorization_info, query_data): AuthorizationInfoAndQueryDataFromRequest,
+) -> Result<Response, StatusCode> {
+  enforce_csrf(token)?;
+
+  if authorization_info.oauth_scope.is_some()
+    || authorization_info.assumed_identity_from_profile.is_some()
+  {
+    return Err(StatusCode::FORBIDDEN);
+  }
+  let Some(user) = authorization_info.user.as_ref() else {
+    return Err(StatusCode::UNAUTHORIZED);
+  };
+
+  let personal_data = export_personal_data(query_data.db(), user)
+    .await
+    .map_err(|err| {
+      error!("Error exporting personal data: {:?}", err);
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+  record_personal_data_request(
+    query_data.db(),
+    user.id,
+    PERSONAL_DATA_EXPORT,
+    export_summary(&personal_data),
+  )
+  .await
+  .map_err(|err| {
+    error!("Error recording personal data request: {:?}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+
+  let body = serde_json::to_vec_pretty(&personal_data).map_err(|err| {
+    error!("Error serializing personal data export: {:?}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+  let filename = format!(
+    "personal-data-{}.json",
+    Utc::now().date_naive().format("%Y-%m-%d")
+  );
+
+  Ok(
+    (
+      [
+        (CONTENT_TYPE, "application/json".to_string()),
+        (
+          CONTENT_DISPOSITION,
+          format!("attachment; filename=\"{}\"", filename),
+        ),
+      ],
+      body,
+    )
+      .into_response(),
+  )
+}
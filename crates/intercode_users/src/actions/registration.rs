@@ -0,0 +1,180 @@
+use axum::{
+  debug_handler,
+  extract::{Host, Query},
+  response::{self, IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use http::StatusCode;
+use intercode_entities::users;
+use intercode_server::{enforce_csrf, CsrfData, FormOrMultipart, QueryDataFromRequest};
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::account_emails::{account_email_from, account_url, send_account_email};
+use crate::{devise_tokens::friendly_token, password_hashing::PASSWORD_HASHER};
+
+pub const MIN_PASSWORD_LENGTH: usize = 6;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+pub(crate) fn validate_new_password(password: &str, password_confirmation: &str) -> Vec<String> {
+  let mut errors = Vec::new();
+  if password.len() < MIN_PASSWORD_LENGTH || password.len() > MAX_PASSWORD_LENGTH {
+    errors.push(format!(
+      "Password must be between {} and {} characters",
+      MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+    ));
+  }
+  if password != password_confirmation {
+    errors.push("Password confirmation doesn't match password".to_string());
+  }
+  errors
+}
+
+pub(crate) fn validation_errors_response(errors: Vec<String>) -> Response {
+  (
+    StatusCode::UNPROCESSABLE_ENTITY,
+    response::Json(json!({ "errors": errors })),
+  )
+    .into_response()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SignUpParams {
+  #[serde(rename(deserialize = "user[email]"))]
+  email: String,
+  #[serde(rename(deserialize = "user[first_name]"))]
+  first_name: String,
+  #[serde(rename(deserialize = "user[last_name]"))]
+  last_name: String,
+  #[serde(rename(deserialize = "user[password]"))]
+  password: String,
+  #[serde(rename(deserialize = "user[password_confirmation]"))]
+  password_confirmation: String,
+}
+
+/// Creates an account and emails the new user a link to confirm their address.  They can't sign
+/// in until they've followed it.
+#[debug_handler]
+pub async fn sign_up(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Host(host): Host,
+  form_or_multipart: FormOrMultipart<SignUpParams>,
+) -> Result<Response, StatusCode> {
+  enforce_csrf(token)?;
+  let params = form_or_multipart.into_params().await?;
+  let email = params.email.trim().to_lowercase();
+
+  let mut errors = Vec::new();
+  if params.first_name.trim().is_empty() {
+    errors.push("First name can't be blank".to_string());
+  }
+  if params.last_name.trim().is_empty() {
+    errors.push("Last name can't be blank".to_string());
+  }
+  if !email.contains('@') || email.contains(char::is_whitespace) {
+    errors.push("Email is invalid".to_string());
+  }
+  errors.extend(validate_new_password(
+    &params.password,
+    &params.password_confirmation,
+  ));
+
+  let existing_user = users::Entity::find()
+    .filter(Expr::expr(Func::lower(Expr::col(users::Column::Email))).eq(email.as_str()))
+    .one(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  if existing_user.is_some() {
+    errors.push("Email has already been taken".to_string());
+  }
+
+  if !errors.is_empty() {
+    return Ok(validation_errors_response(errors));
+  }
+
+  let now = Utc::now().naive_utc();
+  let confirmation_token = friendly_token();
+  let user = users::ActiveModel {
+    email: ActiveValue::Set(email),
+    first_name: ActiveValue::Set(params.first_name.trim().to_string()),
+    last_name: ActiveValue::Set(params.last_name.trim().to_string()),
+    encrypted_password: ActiveValue::Set(
+      PASSWORD_HASHER
+        .hash(&params.password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    ),
+    confirmation_token: ActiveValue::Set(Some(confirmation_token.clone())),
+    confirmation_sent_at: ActiveValue::Set(Some(now)),
+    sign_in_count: ActiveValue::Set(Some(0)),
+    created_at: ActiveValue::Set(Some(now)),
+    updated_at: ActiveValue::Set(Some(now)),
+    ..Default::default()
+  }
+  .insert(query_data.db())
+  .await
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  let confirmation_url = account_url(
+    &host,
+    &format!(
+      "/users/confirmation?confirmation_token={}",
+      confirmation_token
+    ),
+  );
+  send_account_email(
+    &account_email_from(&query_data, &host),
+    &user.email,
+    "Confirmation instructions",
+    &format!(
+      "<p>Welcome, {}!</p><p>You can confirm your account email through the link below:</p><p><a href=\"{}\">Confirm my account</a></p>",
+      html_escape::encode_text(&user.first_name),
+      confirmation_url
+    ),
+    &format!(
+      "Welcome, {}!\n\nYou can confirm your account email through the link below:\n\n{}\n",
+      user.first_name, confirmation_url
+    ),
+  )
+  .await?;
+
+  Ok(response::Json(json!({ "status": "confirmation_required" })).into_response())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmationParams {
+  confirmation_token: String,
+}
+
+/// Confirms an account's email address using the link from its confirmation email
+#[debug_handler]
+pub async fn confirm_email(
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  Query(params): Query<ConfirmationParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+  let user = users::Entity::find()
+    .filter(users::Column::ConfirmationToken.eq(params.confirmation_token.as_str()))
+    .one(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+  if user.confirmed_at.is_none() {
+    let now = Utc::now().naive_utc();
+    users::ActiveModel {
+      id: ActiveValue::Unchanged(user.id),
+      confirmed_at: ActiveValue::Set(Some(now)),
+      updated_at: ActiveValue::Set(Some(now)),
+      ..Default::default()
+    }
+    .update(query_data.db())
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  }
+
+  Ok(Redirect::to("/users/sign_in"))
+}
@@ -0,0 +1,514 @@
+use axum::{
+  debug_handler,
+  response::{self, IntoResponse, Response},
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode};
+use intercode_entities::{otp_recovery_codes, users};
+use intercode_server::{client_ip, enforce_csrf, CsrfData, FormOrMultipart, QueryDataFromRequest};
+use sea_orm::{
+  ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tower_sessions::Session;
+use tracing::log::*;
+
+use super::authentication::{record_failed_sign_in, start_signed_in_session, track_sign_in};
+use crate::{
+  devise_tokens::DeviseTokenGenerator,
+  password_hashing::PASSWORD_HASHER,
+  sign_in_protection::{LOCKOUT_CONFIG, SIGN_IN_THROTTLE},
+  two_factor::{
+    consume_otp_timestep, consume_recovery_code, decrypt_otp_secret, encrypt_otp_secret,
+    generate_otp_secret, provisioning_uri, regenerate_recovery_codes, two_factor_required,
+    verify_totp,
+  },
+};
+
+const TWO_FACTOR_PENDING_USER_ID_KEY: &str = "two_factor_pending_user_id";
+const TWO_FACTOR_PENDING_SINCE_KEY: &str = "two_factor_pending_since";
+const TWO_FACTOR_ENROLLMENT_REQUIRED_KEY: &str = "two_factor_enrollment_required";
+
+/// How long someone who's entered their password has to finish signing in
+fn two_factor_pending_within() -> Duration {
+  Duration::minutes(10)
+}
+
+fn two_factor_error(status: StatusCode, message: &str) -> Response {
+  (status, response::Json(json!({ "error": message }))).into_response()
+}
+
+fn token_generator() -> Result<DeviseTokenGenerator, StatusCode> {
+  DeviseTokenGenerator::from_env().map_err(|err| {
+    error!(
+      "Can't encrypt two-factor secrets without SECRET_KEY_BASE: {}",
+      err
+    );
+    StatusCode::INTERNAL_SERVER_ERROR
+  })
+}
+
+/// Whether a user who's just proven their password still has a second step to go: Some(false)
+/// if they need to enter a code, Some(true) if they need to set up two-factor authentication
+/// first, None if they're done
+pub(crate) async fn pending_two_factor_step<C: ConnectionTrait>(
+  db: &C,
+  user: &users::Model,
+) -> Result<Option<bool>, StatusCode> {
+  if user.otp_enabled_at.is_some() {
+    return Ok(Some(false));
+  }
+
+  let required = two_factor_required(db, user).await.map_err(|err| {
+    error!("Error checking two-factor requirements: {:?}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+  Ok(required.then_some(true))
+}
+
+/// Leaves the session partially authenticated: it remembers who's signing in, but they aren't
+/// the current user until they finish the second step
+pub(crate) fn begin_two_factor_sign_in(
+  session: &Session,
+  user_id: i64,
+  enrollment_required: bool,
+  now: NaiveDateTime,
+) -> Result<Response, StatusCode> {
+  session
+    .remove::<i64>("current_user_id")
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  session
+    .insert(TWO_FACTOR_PENDING_USER_ID_KEY, user_id)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  session
+    .insert(TWO_FACTOR_PENDING_SINCE_KEY, now.timestamp())
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  session
+    .insert(TWO_FACTOR_ENROLLMENT_REQUIRED_KEY, enrollment_required)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  Ok(
+    response::Json(json!({
+      "status": if enrollment_required {
+        "two_factor_enrollment_required"
+      } else {
+        "two_factor_required"
+      }
+    }))
+    .into_response(),
+  )
+}
+
+fn clear_pending_two_factor_sign_in(session: &Session) {
+  let _ = session.remove::<i64>(TWO_FACTOR_PENDING_USER_ID_KEY);
+  let _ = session.remove::<i64>(TWO_FACTOR_PENDING_SINCE_KEY);
+  let _ = session.remove::<bool>(TWO_FACTOR_ENROLLMENT_REQUIRED_KEY);
+}
+
+/// The user partway through signing in on this session, if they haven't taken too long about it,
+/// and whether they still need to enroll
+fn pending_two_factor_sign_in(session: &Session, now: NaiveDateTime) -> Option<(i64, bool)> {
+  let user_id = session
+    .get::<i64>(TWO_FACTOR_PENDING_USER_ID_KEY)
+    .ok()
+    .flatten()?;
+  let since = session
+    .get::<i64>(TWO_FACTOR_PENDING_SINCE_KEY)
+    .ok()
+    .flatten()
+    .and_then(|since| NaiveDateTime::from_timestamp_opt(since, 0))?;
+  if since + two_factor_pending_within() < now {
+    clear_pending_two_factor_sign_in(session);
+    return None;
+  }
+  let enrollment_required = session
+    .get::<bool>(TWO_FACTOR_ENROLLMENT_REQUIRED_KEY)
+    .ok()
+    .flatten()
+    .unwrap_or(false);
+
+  Some((user_id, enrollment_required))
+}
+
+fn sign_in_expired() -> Response {
+  two_factor_error(
+    StatusCode::UNAUTHORIZED,
+    "Your sign-in has expired.  Please enter your email and password again.",
+  )
+}
+
+async fn load_user<C: ConnectionTrait>(db: &C, user_id: i64) -> Result<users::Model, Response> {
+  users::Entity::find_by_id(user_id)
+    .one(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?
+    .ok_or_else(sign_in_expired)
+}
+
+/// The user managing their two-factor settings: whoever is signed in on this session, or someone
+/// partway through signing in who has to enroll before they can finish
+async fn enrolling_user<C: ConnectionTrait>(
+  db: &C,
+  session: &Session,
+  now: NaiveDateTime,
+) -> Result<(users::Model, bool), Response> {
+  if let Some(user_id) = session
+    .get::<i64>("current_user_id")
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?
+  {
+    return Ok((load_user(db, user_id).await?, false));
+  }
+
+  match pending_two_factor_sign_in(session, now) {
+    Some((user_id, true)) => Ok((load_user(db, user_id).await?, true)),
+    _ => Err(StatusCode::UNAUTHORIZED.into_response()),
+  }
+}
+
+/// Checks a code from the user's authenticator app, returning the timestep to record if it's good
+fn verify_otp_attempt(
+  token_generator: &DeviseTokenGenerator,
+  user: &users::Model,
+  otp_attempt: &str,
+  now: NaiveDateTime,
+) -> Result<Option<i64>, StatusCode> {
+  let Some(ciphertext) = &user.otp_secret_ciphertext else {
+    return Ok(None);
+  };
+  let secret = decrypt_otp_secret(token_generator, ciphertext).map_err(|err| {
+    error!("Couldn't decrypt OTP secret for user {}: {}", user.id, err);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+
+  Ok(verify_totp(
+    &secret,
+    otp_attempt,
+    now.timestamp(),
+    user.otp_consumed_timestep,
+  ))
+}
+
+fn invalid_current_password() -> Response {
+  two_factor_error(StatusCode::UNAUTHORIZED, "Current password is incorrect.")
+}
+
+fn invalid_otp_attempt() -> Response {
+  two_factor_error(StatusCode::UNAUTHORIZED, "Invalid two-factor code.")
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TwoFactorSignInParams {
+  #[serde(rename(deserialize = "user[otp_attempt]"))]
+  otp_attempt: String,
+}
+
+/// The second step of signing in: a code from an authenticator app, or one of the recovery codes
+#[debug_handler]
+pub async fn sign_in_two_factor(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+  headers: HeaderMap,
+  form_or_multipart: FormOrMultipart<TwoFactorSignInParams>,
+) -> Result<Response, StatusCode> {
+  enforce_csrf(token)?;
+
+  let params = form_or_multipart.into_params().await?;
+  let now = Utc::now().naive_utc();
+  let Some((user_id, false)) = pending_two_factor_sign_in(&session, now) else {
+    return Ok(sign_in_expired());
+  };
+  let db = query_data.db();
+  let user = match load_user(db, user_id).await {
+    Ok(user) => user,
+    Err(response) => return Ok(response),
+  };
+
+  let ip = client_ip(&headers);
+  if let Some(retry_after) = SIGN_IN_THROTTLE.hit(ip.as_deref(), &user.email.to_lowercase()) {
+    let mut response = two_factor_error(
+      StatusCode::TOO_MANY_REQUESTS,
+      "Too many sign-in attempts.  Please wait a few minutes and try again.",
+    );
+    response
+      .headers_mut()
+      .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs().max(1)));
+    return Ok(response);
+  }
+
+  if user.is_access_locked(now, LOCKOUT_CONFIG.unlock_in) {
+    clear_pending_two_factor_sign_in(&session);
+    return Ok(invalid_otp_attempt());
+  }
+
+  let token_generator = token_generator()?;
+  let mut update = users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    ..Default::default()
+  };
+
+  let consumed = match verify_otp_attempt(&token_generator, &user, &params.otp_attempt, now)? {
+    Some(timestep) => consume_otp_timestep(db, user.id, timestep).await,
+    None => consume_recovery_code(db, &token_generator, user.id, &params.otp_attempt, now).await,
+  }
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+  if !consumed {
+    record_failed_sign_in(db, &user, now).await?;
+    return Ok(invalid_otp_attempt());
+  }
+
+  track_sign_in(&mut update, &user, ip, now);
+  update
+    .update(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+  SIGN_IN_THROTTLE.reset_account(&user.email.to_lowercase());
+  clear_pending_two_factor_sign_in(&session);
+  start_signed_in_session(&session, user.id)?;
+
+  Ok(response::Json(json!({ "status": "success" })).into_response())
+}
+
+/// Starts setting up two-factor authentication by generating a secret.  It doesn't take effect
+/// until a code generated from it has been confirmed.
+#[debug_handler]
+pub async fn begin_two_factor_enrollment(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  let now = Utc::now().naive_utc();
+  let db = query_data.db();
+  let (user, _) = enrolling_user(db, &session, now).await?;
+  if user.otp_enabled_at.is_some() {
+    return Err(two_factor_error(
+      StatusCode::CONFLICT,
+      "Two-factor authentication is already enabled.",
+    ));
+  }
+
+  let token_generator = token_generator().map_err(IntoResponse::into_response)?;
+  let secret = generate_otp_secret();
+  let ciphertext = encrypt_otp_secret(&token_generator, &secret).map_err(|err| {
+    error!("Couldn't encrypt OTP secret: {}", err);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+  })?;
+
+  users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    otp_secret_ciphertext: ActiveValue::Set(Some(ciphertext)),
+    otp_consumed_timestep: ActiveValue::Set(None),
+    updated_at: ActiveValue::Set(Some(now)),
+    ..Default::default()
+  }
+  .update(db)
+  .await
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  Ok(
+    response::Json(json!({
+      "secret": secret,
+      "provisioning_uri": provisioning_uri(&secret, &user.email),
+    }))
+    .into_response(),
+  )
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmTwoFactorEnrollmentParams {
+  #[serde(rename(deserialize = "user[otp_attempt]"))]
+  otp_attempt: String,
+  #[serde(default, rename(deserialize = "user[current_password]"))]
+  current_password: Option<String>,
+}
+
+/// Turns on two-factor authentication once the user has shown their authenticator app works, and
+/// hands back their recovery codes.  Signed-in users have to give their current password too;
+/// users enrolling as part of signing in have just given it, and are signed in once this succeeds.
+#[debug_handler]
+pub async fn confirm_two_factor_enrollment(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+  headers: HeaderMap,
+  form_or_multipart: FormOrMultipart<ConfirmTwoFactorEnrollmentParams>,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let now = Utc::now().naive_utc();
+  let db = query_data.db();
+  let (user, signing_in) = enrolling_user(db, &session, now).await?;
+
+  if user.otp_enabled_at.is_some() {
+    return Err(two_factor_error(
+      StatusCode::CONFLICT,
+      "Two-factor authentication is already enabled.",
+    ));
+  }
+  if !signing_in
+    && !params
+      .current_password
+      .as_deref()
+      .map(|password| PASSWORD_HASHER.verify(&user, password))
+      .unwrap_or(false)
+  {
+    return Err(invalid_current_password());
+  }
+
+  let token_generator = token_generator().map_err(IntoResponse::into_response)?;
+  let Some(timestep) = verify_otp_attempt(&token_generator, &user, &params.otp_attempt, now)
+    .map_err(IntoResponse::into_response)?
+  else {
+    return Err(invalid_otp_attempt());
+  };
+  if !consume_otp_timestep(db, user.id, timestep)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?
+  {
+    return Err(invalid_otp_attempt());
+  }
+
+  let mut update = users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    otp_enabled_at: ActiveValue::Set(Some(now)),
+    updated_at: ActiveValue::Set(Some(now)),
+    ..Default::default()
+  };
+  if signing_in {
+    track_sign_in(&mut update, &user, client_ip(&headers), now);
+  }
+  update
+    .update(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  let recovery_codes = regenerate_recovery_codes(db, &token_generator, user.id, now)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  if signing_in {
+    clear_pending_two_factor_sign_in(&session);
+    start_signed_in_session(&session, user.id).map_err(IntoResponse::into_response)?;
+  }
+
+  Ok(
+    response::Json(json!({
+      "status": "success",
+      "recovery_codes": recovery_codes,
+    }))
+    .into_response(),
+  )
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ManageTwoFactorParams {
+  #[serde(rename(deserialize = "user[current_password]"))]
+  current_password: String,
+}
+
+/// Replaces the signed-in user's recovery codes, e.g. because they've used most of them
+#[debug_handler]
+pub async fn regenerate_two_factor_recovery_codes(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+  form_or_multipart: FormOrMultipart<ManageTwoFactorParams>,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let now = Utc::now().naive_utc();
+  let db = query_data.db();
+  let (user, false) = enrolling_user(db, &session, now).await? else {
+    return Err(StatusCode::UNAUTHORIZED.into_response());
+  };
+
+  if user.otp_enabled_at.is_none() {
+    return Err(two_factor_error(
+      StatusCode::NOT_FOUND,
+      "Two-factor authentication isn't enabled.",
+    ));
+  }
+  if !PASSWORD_HASHER.verify(&user, &params.current_password) {
+    return Err(invalid_current_password());
+  }
+
+  let token_generator = token_generator().map_err(IntoResponse::into_response)?;
+  let recovery_codes = regenerate_recovery_codes(db, &token_generator, user.id, now)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  Ok(
+    response::Json(json!({
+      "status": "success",
+      "recovery_codes": recovery_codes,
+    }))
+    .into_response(),
+  )
+}
+
+/// Turns off two-factor authentication for the signed-in user, unless something requires them to
+/// have it
+#[debug_handler]
+pub async fn disable_two_factor(
+  token: CsrfData,
+  QueryDataFromRequest(query_data): QueryDataFromRequest,
+  session: Session,
+  form_or_multipart: FormOrMultipart<ManageTwoFactorParams>,
+) -> Result<Response, Response> {
+  enforce_csrf(token).map_err(IntoResponse::into_response)?;
+
+  let params = form_or_multipart
+    .into_params()
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let now = Utc::now().naive_utc();
+  let db = query_data.db();
+  let (user, false) = enrolling_user(db, &session, now).await? else {
+    return Err(StatusCode::UNAUTHORIZED.into_response());
+  };
+
+  if !PASSWORD_HASHER.verify(&user, &params.current_password) {
+    return Err(invalid_current_password());
+  }
+  if two_factor_required(db, &user)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?
+  {
+    return Err(two_factor_error(
+      StatusCode::FORBIDDEN,
+      "Your permissions require two-factor authentication, so it can't be turned off.",
+    ));
+  }
+
+  users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    otp_secret_ciphertext: ActiveValue::Set(None),
+    otp_enabled_at: ActiveValue::Set(None),
+    otp_consumed_timestep: ActiveValue::Set(None),
+    updated_at: ActiveValue::Set(Some(now)),
+    ..Default::default()
+  }
+  .update(db)
+  .await
+  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  otp_recovery_codes::Entity::delete_many()
+    .filter(otp_recovery_codes::Column::UserId.eq(user.id))
+    .exec(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+  Ok(response::Json(json!({ "status": "success" })).into_response())
+}
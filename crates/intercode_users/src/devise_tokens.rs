@@ -0,0 +1,146 @@
+//! Tokens compatible with the ones Devise generates, so that a link emailed by either the Rails
+//! app or this server works with both.
+
+use std::env;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::Sha256;
+
+// ActiveSupport::KeyGenerator's defaults
+const KEY_GENERATOR_ITERATIONS: u32 = 65536;
+const KEY_SIZE: usize = 64;
+
+/// Generates a random 20-character token, the same way Devise.friendly_token does
+pub fn friendly_token() -> String {
+  let mut bytes = [0u8; 15];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  URL_SAFE_NO_PAD
+    .encode(bytes)
+    .chars()
+    .map(|c| match c {
+      'l' => 's',
+      'I' => 'x',
+      'O' => 'y',
+      '0' => 'z',
+      c => c,
+    })
+    .collect()
+}
+
+/// The hash function Rails' key generator uses to derive keys from secret_key_base.  Rails 7.0
+/// switched the default from SHA1 to SHA256, but apps upgraded from older versions may still be
+/// using SHA1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyGeneratorDigest {
+  Sha1,
+  Sha256,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviseTokenGenerator {
+  secret_key_base: Vec<u8>,
+  key_generator_digest: KeyGeneratorDigest,
+}
+
+impl DeviseTokenGenerator {
+  pub fn new(
+    secret_key_base: impl Into<Vec<u8>>,
+    key_generator_digest: KeyGeneratorDigest,
+  ) -> Self {
+    Self {
+      secret_key_base: secret_key_base.into(),
+      key_generator_digest,
+    }
+  }
+
+  /// Reads SECRET_KEY_BASE (shared with the Rails app) and, optionally,
+  /// KEY_GENERATOR_HASH_DIGEST ("sha1" or "sha256", defaulting to sha1)
+  pub fn from_env() -> Result<Self, env::VarError> {
+    let secret_key_base = env::var("SECRET_KEY_BASE")?;
+    let key_generator_digest = match env::var("KEY_GENERATOR_HASH_DIGEST")
+      .unwrap_or_default()
+      .to_lowercase()
+      .as_str()
+    {
+      "sha256" => KeyGeneratorDigest::Sha256,
+      _ => KeyGeneratorDigest::Sha1,
+    };
+
+    Ok(Self::new(secret_key_base, key_generator_digest))
+  }
+
+  fn key_for(&self, column: &str) -> [u8; KEY_SIZE] {
+    self.derive_key(&format!("Devise {}", column))
+  }
+
+  /// Derives a key from secret_key_base the way ActiveSupport::KeyGenerator#generate_key does
+  pub fn derive_key(&self, salt: &str) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    match self.key_generator_digest {
+      KeyGeneratorDigest::Sha1 => pbkdf2::pbkdf2_hmac::<Sha1>(
+        &self.secret_key_base,
+        salt.as_bytes(),
+        KEY_GENERATOR_ITERATIONS,
+        &mut key,
+      ),
+      KeyGeneratorDigest::Sha256 => pbkdf2::pbkdf2_hmac::<Sha256>(
+        &self.secret_key_base,
+        salt.as_bytes(),
+        KEY_GENERATOR_ITERATIONS,
+        &mut key,
+      ),
+    }
+    key
+  }
+
+  /// Hashes a raw token for storage in (or lookup by) the given column
+  pub fn digest(&self, column: &str, value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&self.key_for(column)).unwrap();
+    mac.update(value.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+  }
+
+  /// Generates a new token for the given column, returning the raw token (to send to the user)
+  /// and its digest (to store in the database)
+  pub fn generate(&self, column: &str) -> (String, String) {
+    let raw = friendly_token();
+    let digested = self.digest(column, &raw);
+    (raw, digested)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn friendly_tokens_look_like_devise_tokens() {
+    let token = friendly_token();
+    assert_eq!(token.len(), 20);
+    assert!(token
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    assert!(!token.contains(['l', 'I', 'O', '0']));
+  }
+
+  #[test]
+  fn digests_match_devise() {
+    // Expected values computed independently, the way ActiveSupport::KeyGenerator and
+    // Devise::TokenGenerator do it: HMAC-SHA256 keyed by PBKDF2(secret_key_base, "Devise <column>")
+    let secret_key_base = "a".repeat(128);
+
+    assert_eq!(
+      DeviseTokenGenerator::new(secret_key_base.clone(), KeyGeneratorDigest::Sha1)
+        .digest("reset_password_token", "Ke3gs2oKxkLxYxvMzsw5"),
+      "1d513f381dd92295e8b0ed9751c93fdb7272bb7a5f66494a07b1b42a2bd4e31b"
+    );
+    assert_eq!(
+      DeviseTokenGenerator::new(secret_key_base, KeyGeneratorDigest::Sha256)
+        .digest("reset_password_token", "Ke3gs2oKxkLxYxvMzsw5"),
+      "9888681c4c80456d09223390e4e85e60d9c054a946934c131c5754b09b8ed4b2"
+    );
+  }
+}
@@ -1,5 +1,16 @@
 pub mod actions;
+pub mod devise_tokens;
 pub mod legacy_passwords;
+pub mod mutations;
+pub mod oauth;
+pub mod objects;
+pub mod openid_connect;
 pub mod partial_objects;
+pub mod password_hashing;
+pub mod personal_access_tokens;
+pub mod personal_data;
 pub mod policies;
 pub mod query_builders;
+pub mod sign_in_protection;
+pub mod two_factor;
+pub mod user_merge;
@@ -0,0 +1,30 @@
+use async_graphql::{InputObject, SimpleObject};
+use intercode_graphql_core::scalars::DateScalar;
+
+use crate::objects::PersonalAccessTokenType;
+
+#[derive(InputObject)]
+pub struct CreatePersonalAccessTokenInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// A name to remember the token by, e.g. what script uses it
+  pub name: String,
+  /// The scopes to grant the token.  Requests made with it can only do what these allow.
+  pub scopes: Vec<String>,
+  /// When the token should stop working.  Leave this out for a token that never expires.
+  #[graphql(name = "expires_at")]
+  pub expires_at: Option<DateScalar>,
+}
+
+#[derive(SimpleObject)]
+pub struct CreatePersonalAccessTokenPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "personal_access_token")]
+  pub personal_access_token: PersonalAccessTokenType,
+  /// The token to send as "Authorization: Bearer <token>".  This is the only time it's available,
+  /// since only a digest of it is stored.
+  pub token: String,
+}
@@ -0,0 +1,17 @@
+use async_graphql::{InputObject, SimpleObject};
+
+#[derive(InputObject)]
+pub struct DeleteAccountInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "current_password")]
+  pub current_password: String,
+}
+
+#[derive(SimpleObject)]
+pub struct DeleteAccountPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+}
@@ -0,0 +1,20 @@
+use async_graphql::{InputObject, SimpleObject};
+use intercode_graphql_core::scalars::JsonScalar;
+
+#[derive(InputObject)]
+pub struct ExportPersonalDataInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct ExportPersonalDataPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// Everything we hold about the current user: their account, profiles, signups, orders,
+  /// tickets, event ratings, event proposals and form response history
+  #[graphql(name = "personal_data")]
+  pub personal_data: JsonScalar,
+}
@@ -0,0 +1,31 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::user_merge::{MergeUsersReport, ProfileConflictStrategy};
+
+#[derive(InputObject)]
+pub struct MergeUsersInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  /// The account to keep
+  #[graphql(name = "winning_user_id")]
+  pub winning_user_id: ID,
+  /// The account to merge into the winning one and then delete
+  #[graphql(name = "losing_user_id")]
+  pub losing_user_id: ID,
+  /// What to do when both users have a profile in the same convention
+  #[graphql(name = "profile_conflict_strategy")]
+  pub profile_conflict_strategy: ProfileConflictStrategy,
+  /// Report what the merge would do without doing it.  Defaults to true, so that merging takes a
+  /// deliberate second request.
+  #[graphql(name = "dry_run", default = true)]
+  pub dry_run: bool,
+}
+
+#[derive(SimpleObject)]
+pub struct MergeUsersPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub report: MergeUsersReport,
+}
@@ -0,0 +1,11 @@
+mod create_personal_access_token;
+mod delete_account;
+mod export_personal_data;
+mod merge_users;
+mod revoke_personal_access_token;
+
+pub use create_personal_access_token::*;
+pub use delete_account::*;
+pub use export_personal_data::*;
+pub use merge_users::*;
+pub use revoke_personal_access_token::*;
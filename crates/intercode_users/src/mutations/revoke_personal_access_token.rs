@@ -0,0 +1,20 @@
+use async_graphql::{InputObject, SimpleObject, ID};
+
+use crate::objects::PersonalAccessTokenType;
+
+#[derive(InputObject)]
+pub struct RevokePersonalAccessTokenInput {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  pub id: ID,
+}
+
+#[derive(SimpleObject)]
+pub struct RevokePersonalAccessTokenPayload {
+  /// A unique identifier for the client performing the mutation.
+  #[graphql(name = "clientMutationId")]
+  pub client_mutation_id: Option<String>,
+  #[graphql(name = "personal_access_token")]
+  pub personal_access_token: PersonalAccessTokenType,
+}
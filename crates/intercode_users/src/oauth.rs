@@ -0,0 +1,341 @@
+//! An OAuth 2.0 authorization server that reads and writes the same tables as Doorkeeper, so
+//! applications and tokens carry over from the Rails app.
+
+use axum::response::{IntoResponse, Response};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{NaiveDateTime, Utc};
+use http::{
+  header::{AUTHORIZATION, CACHE_CONTROL},
+  HeaderMap, HeaderValue, StatusCode,
+};
+use intercode_entities::{oauth_access_tokens, oauth_applications};
+use rand::RngCore;
+use sea_orm::{
+  ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::log::*;
+
+/// Doorkeeper's default access_token_expires_in
+pub const ACCESS_TOKEN_EXPIRES_IN: i32 = 7200;
+/// Doorkeeper's default authorization_code_expires_in
+pub const ACCESS_GRANT_EXPIRES_IN: i32 = 600;
+
+/// An error response as described in RFC 6749 section 5.2
+#[derive(Debug)]
+pub struct OAuthError {
+  pub status: StatusCode,
+  pub error: &'static str,
+  pub description: String,
+}
+
+impl OAuthError {
+  pub fn new(status: StatusCode, error: &'static str, description: impl Into<String>) -> Self {
+    Self {
+      status,
+      error,
+      description: description.into(),
+    }
+  }
+
+  pub fn invalid_request(description: impl Into<String>) -> Self {
+    Self::new(StatusCode::BAD_REQUEST, "invalid_request", description)
+  }
+
+  pub fn invalid_client() -> Self {
+    Self::new(
+      StatusCode::UNAUTHORIZED,
+      "invalid_client",
+      "Client authentication failed due to unknown client, no client authentication included, or unsupported authentication method.",
+    )
+  }
+
+  pub fn invalid_grant() -> Self {
+    Self::new(
+      StatusCode::BAD_REQUEST,
+      "invalid_grant",
+      "The provided authorization grant is invalid, expired, revoked, does not match the redirection URI used in the authorization request, or was issued to another client.",
+    )
+  }
+
+  pub fn invalid_scope() -> Self {
+    Self::new(
+      StatusCode::BAD_REQUEST,
+      "invalid_scope",
+      "The requested scope is invalid, unknown, or malformed.",
+    )
+  }
+
+  pub fn server_error() -> Self {
+    Self::new(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      "server_error",
+      "The authorization server encountered an unexpected condition which prevented it from fulfilling the request.",
+    )
+  }
+}
+
+impl From<DbErr> for OAuthError {
+  fn from(err: DbErr) -> Self {
+    error!("Database error in OAuth flow: {:?}", err);
+    Self::server_error()
+  }
+}
+
+impl IntoResponse for OAuthError {
+  fn into_response(self) -> Response {
+    (
+      self.status,
+      axum::Json(json!({ "error": self.error, "error_description": self.description })),
+    )
+      .into_response()
+  }
+}
+
+/// Generates a random token the way Doorkeeper's UniqueToken does
+pub fn generate_oauth_token() -> String {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Checks a PKCE code verifier against the challenge from the authorization request (RFC 7636)
+pub fn verify_code_challenge(challenge: &str, method: Option<&str>, verifier: &str) -> bool {
+  match method.unwrap_or("plain") {
+    "plain" => challenge == verifier,
+    "S256" => URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == challenge,
+    _ => false,
+  }
+}
+
+/// Works out which scopes to grant for a request.  Asking for nothing gets the default scopes;
+/// asking for anything the application isn't allowed is an error.
+pub fn resolve_requested_scopes(
+  application: &oauth_applications::Model,
+  requested: Option<&str>,
+) -> Result<String, OAuthError> {
+  let requested = requested
+    .map(str::trim)
+    .filter(|requested| !requested.is_empty())
+    .unwrap_or(intercode_entities::model_ext::oauth::OAUTH_DEFAULT_SCOPES);
+  let allowed_scopes = application.allowed_scopes();
+
+  if requested
+    .split_whitespace()
+    .all(|scope| allowed_scopes.contains(&scope))
+  {
+    Ok(requested.split_whitespace().collect::<Vec<_>>().join(" "))
+  } else {
+    Err(OAuthError::invalid_scope())
+  }
+}
+
+/// Whether every scope in `requested` was also in `granted`
+pub fn scopes_subset(requested: &str, granted: &str) -> bool {
+  let granted = granted.split_whitespace().collect::<Vec<_>>();
+  requested
+    .split_whitespace()
+    .all(|scope| granted.contains(&scope))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+  a.len() == b.len()
+    && a
+      .bytes()
+      .zip(b.bytes())
+      .fold(0, |acc, (a, b)| acc | (a ^ b))
+      == 0
+}
+
+/// Client credentials from either HTTP Basic authentication or the request body, in that order
+/// of preference (RFC 6749 section 2.3.1)
+pub fn client_credentials(
+  headers: &HeaderMap,
+  client_id: Option<&str>,
+  client_secret: Option<&str>,
+) -> Option<(String, Option<String>)> {
+  let basic = headers
+    .get(AUTHORIZATION)
+    .and_then(|header| header.to_str().ok())
+    .and_then(|header| header.strip_prefix("Basic "))
+    .and_then(|encoded| {
+      base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()
+    })
+    .and_then(|decoded| String::from_utf8(decoded).ok())
+    .and_then(|decoded| {
+      decoded
+        .split_once(':')
+        .map(|(id, secret)| (id.to_string(), Some(secret.to_string())))
+    });
+
+  basic.or_else(|| {
+    client_id.map(|client_id| {
+      (
+        client_id.to_string(),
+        client_secret.map(|secret| secret.to_string()),
+      )
+    })
+  })
+}
+
+/// Finds the application making a token request.  Confidential applications must authenticate
+/// with their secret; public ones (e.g. native apps using PKCE) only have to identify themselves.
+pub async fn authenticate_client<C: ConnectionTrait>(
+  db: &C,
+  credentials: Option<(String, Option<String>)>,
+  require_secret: bool,
+) -> Result<oauth_applications::Model, OAuthError> {
+  let (client_id, client_secret) = credentials.ok_or_else(OAuthError::invalid_client)?;
+  let application = oauth_applications::Entity::find()
+    .filter(oauth_applications::Column::Uid.eq(client_id))
+    .one(db)
+    .await?
+    .ok_or_else(OAuthError::invalid_client)?;
+
+  match client_secret {
+    Some(secret) => {
+      if !constant_time_eq(&secret, &application.secret) {
+        return Err(OAuthError::invalid_client());
+      }
+    }
+    None => {
+      if application.confidential || require_secret {
+        return Err(OAuthError::invalid_client());
+      }
+    }
+  }
+
+  Ok(application)
+}
+
+pub async fn issue_access_token<C: ConnectionTrait>(
+  db: &C,
+  application_id: i64,
+  resource_owner_id: Option<i64>,
+  scopes: &str,
+  use_refresh_token: bool,
+  previous_refresh_token: Option<String>,
+  now: NaiveDateTime,
+) -> Result<oauth_access_tokens::Model, DbErr> {
+  oauth_access_tokens::ActiveModel {
+    application_id: ActiveValue::Set(Some(application_id)),
+    resource_owner_id: ActiveValue::Set(resource_owner_id),
+    token: ActiveValue::Set(generate_oauth_token()),
+    refresh_token: ActiveValue::Set(use_refresh_token.then(generate_oauth_token)),
+    expires_in: ActiveValue::Set(Some(ACCESS_TOKEN_EXPIRES_IN)),
+    scopes: ActiveValue::Set(Some(scopes.to_string())),
+    previous_refresh_token: ActiveValue::Set(previous_refresh_token.unwrap_or_default()),
+    created_at: ActiveValue::Set(now),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+}
+
+/// Revokes a token now, unless it's already been revoked
+pub async fn revoke_access_token<C: ConnectionTrait>(
+  db: &C,
+  access_token: &oauth_access_tokens::Model,
+) -> Result<(), DbErr> {
+  if access_token.revoked_at.is_none() {
+    oauth_access_tokens::ActiveModel {
+      id: ActiveValue::Unchanged(access_token.id),
+      revoked_at: ActiveValue::Set(Some(Utc::now().naive_utc())),
+      ..Default::default()
+    }
+    .update(db)
+    .await?;
+  }
+
+  Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenResponse {
+  pub access_token: String,
+  pub token_type: &'static str,
+  pub expires_in: Option<i32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub refresh_token: Option<String>,
+  pub scope: String,
+  pub created_at: i64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id_token: Option<String>,
+}
+
+impl From<&oauth_access_tokens::Model> for TokenResponse {
+  fn from(access_token: &oauth_access_tokens::Model) -> Self {
+    Self {
+      access_token: access_token.token.clone(),
+      token_type: "Bearer",
+      expires_in: access_token.expires_in,
+      refresh_token: access_token.refresh_token.clone(),
+      scope: access_token.scope_string().to_string(),
+      created_at: access_token.created_at.timestamp(),
+      id_token: None,
+    }
+  }
+}
+
+impl IntoResponse for TokenResponse {
+  fn into_response(self) -> Response {
+    let mut response = axum::Json(self).into_response();
+    response
+      .headers_mut()
+      .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verifies_s256_code_challenges() {
+    let verifier = "dBjftJeZ4CK-ZTrM-ocd2xXnEr4pYz6T2L9K7aSX2JA";
+    let challenge = "Lp-Mb-UWp0f9mQZewWeMyCj56hfkXbj7KA1FeTbmVno";
+    assert!(verify_code_challenge(challenge, Some("S256"), verifier));
+    assert!(!verify_code_challenge(challenge, Some("S256"), "wrong"));
+    assert!(!verify_code_challenge(challenge, Some("plain"), verifier));
+    assert!(verify_code_challenge(verifier, None, verifier));
+  }
+
+  #[test]
+  fn resolves_scopes_against_the_application() {
+    let application = oauth_applications::Model {
+      scopes: "public read_profile".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(
+      resolve_requested_scopes(&application, None).unwrap(),
+      "public"
+    );
+    assert_eq!(
+      resolve_requested_scopes(&application, Some("read_profile  public")).unwrap(),
+      "read_profile public"
+    );
+    assert!(resolve_requested_scopes(&application, Some("manage_profile")).is_err());
+  }
+
+  #[test]
+  fn reads_basic_client_credentials() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      AUTHORIZATION,
+      HeaderValue::from_static("Basic Y2xpZW50OnNlY3JldA=="),
+    );
+    assert_eq!(
+      client_credentials(&headers, Some("other"), None),
+      Some(("client".to_string(), Some("secret".to_string())))
+    );
+    assert_eq!(
+      client_credentials(&HeaderMap::new(), Some("other"), None),
+      Some(("other".to_string(), None))
+    );
+  }
+}
@@ -0,0 +1,90 @@
+use async_graphql::*;
+
+use crate::user_merge::{
+  MergeUsersChange, MergeUsersChangeKind, MergeUsersProfileResolution, MergeUsersReport,
+};
+
+/// What merging one user into another did, or would do in a dry run
+#[Object(name = "MergeUsersReport")]
+impl MergeUsersReport {
+  #[graphql(name = "winning_user_id")]
+  async fn winning_user_id(&self) -> ID {
+    self.winning_user_id.into()
+  }
+
+  #[graphql(name = "losing_user_id")]
+  async fn losing_user_id(&self) -> ID {
+    self.losing_user_id.into()
+  }
+
+  #[graphql(name = "dry_run")]
+  async fn dry_run(&self) -> bool {
+    self.dry_run
+  }
+
+  /// True if the losing user has actually been merged away
+  async fn merged(&self) -> bool {
+    self.merged
+  }
+
+  /// Where each of the losing user's profiles ends up
+  async fn profiles(&self) -> &[MergeUsersProfileResolution] {
+    &self.profiles
+  }
+
+  /// Row counts, by table and column, of everything the merge changed
+  async fn changes(&self) -> &[MergeUsersChange] {
+    &self.changes
+  }
+
+  /// Problems that have to be sorted out by hand before these users can be merged
+  async fn blockers(&self) -> &[String] {
+    &self.blockers
+  }
+}
+
+#[Object(name = "MergeUsersProfileResolution")]
+impl MergeUsersProfileResolution {
+  #[graphql(name = "convention_id")]
+  async fn convention_id(&self) -> ID {
+    self.convention_id.into()
+  }
+
+  #[graphql(name = "convention_name")]
+  async fn convention_name(&self) -> Option<&str> {
+    self.convention_name.as_deref()
+  }
+
+  #[graphql(name = "kept_profile_id")]
+  async fn kept_profile_id(&self) -> ID {
+    self.kept_profile_id.into()
+  }
+
+  /// The profile that was folded into the kept one, if both users had a profile in this
+  /// convention
+  #[graphql(name = "removed_profile_id")]
+  async fn removed_profile_id(&self) -> Option<ID> {
+    self.removed_profile_id.map(ID::from)
+  }
+}
+
+#[Object(name = "MergeUsersChange")]
+impl MergeUsersChange {
+  #[graphql(name = "table_name")]
+  async fn table_name(&self) -> &str {
+    self.table
+  }
+
+  #[graphql(name = "column_name")]
+  async fn column_name(&self) -> &str {
+    self.column
+  }
+
+  async fn kind(&self) -> MergeUsersChangeKind {
+    self.kind
+  }
+
+  async fn count(&self) -> u64 {
+    self.count
+  }
+}
@@ -0,0 +1,4 @@
+mod merge_users_report_type;
+mod personal_access_token_type;
+
+pub use personal_access_token_type::*;
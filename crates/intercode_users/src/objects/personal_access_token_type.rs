@@ -0,0 +1,52 @@
+use async_graphql::*;
+use intercode_entities::personal_access_tokens;
+use intercode_graphql_core::{model_backed_type, scalars::DateScalar};
+
+model_backed_type!(PersonalAccessTokenType, personal_access_tokens::Model);
+
+/// A token a user has created for scripting against the API.  The token itself is only ever
+/// shown once, when it's created; this is everything else about it.
+#[Object(name = "PersonalAccessToken")]
+impl PersonalAccessTokenType {
+  async fn id(&self) -> ID {
+    self.model.id.into()
+  }
+
+  async fn name(&self) -> &str {
+    &self.model.name
+  }
+
+  /// The first few characters of the token, to tell tokens apart by
+  #[graphql(name = "token_hint")]
+  async fn token_hint(&self) -> &str {
+    &self.model.token_hint
+  }
+
+  async fn scopes(&self) -> Vec<&str> {
+    self.model.scope_list()
+  }
+
+  #[graphql(name = "expires_at")]
+  async fn expires_at(&self) -> Result<Option<DateScalar>> {
+    self.model.expires_at.map(DateScalar::try_from).transpose()
+  }
+
+  #[graphql(name = "last_used_at")]
+  async fn last_used_at(&self) -> Result<Option<DateScalar>> {
+    self
+      .model
+      .last_used_at
+      .map(DateScalar::try_from)
+      .transpose()
+  }
+
+  #[graphql(name = "last_used_ip")]
+  async fn last_used_ip(&self) -> Option<&str> {
+    self.model.last_used_ip.as_deref()
+  }
+
+  #[graphql(name = "created_at")]
+  async fn created_at(&self) -> Result<DateScalar> {
+    self.model.created_at.try_into()
+  }
+}
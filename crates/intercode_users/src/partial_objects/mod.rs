@@ -1,5 +1,6 @@
 mod ability_users_fields;
 mod convention_users_fields;
+mod mutation_root_users_fields;
 mod permission_users_fields;
 mod query_root_users_fields;
 mod staff_position_users_fields;
@@ -8,6 +9,7 @@ mod user_users_fields;
 
 pub use ability_users_fields::*;
 pub use convention_users_fields::*;
+pub use mutation_root_users_fields::*;
 pub use permission_users_fields::*;
 pub use query_root_users_fields::*;
 pub use staff_position_users_fields::*;
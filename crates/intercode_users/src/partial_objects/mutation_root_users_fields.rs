@@ -0,0 +1,249 @@
+use async_graphql::*;
+use chrono::{NaiveDateTime, Utc};
+use intercode_entities::{personal_access_tokens, users};
+use intercode_graphql_core::{
+  lax_id::LaxId, query_data::QueryData, scalars::JsonScalar, ModelBackedType,
+};
+use intercode_policies::{ensure_action_permitted, AuthorizationInfo};
+use intercode_server::personal_access_token_digest;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::{
+  mutations::{
+    CreatePersonalAccessTokenInput, CreatePersonalAccessTokenPayload, DeleteAccountInput,
+    DeleteAccountPayload, ExportPersonalDataInput, ExportPersonalDataPayload, MergeUsersInput,
+    MergeUsersPayload, RevokePersonalAccessTokenInput, RevokePersonalAccessTokenPayload,
+  },
+  objects::PersonalAccessTokenType,
+  password_hashing::PASSWORD_HASHER,
+  personal_access_tokens::{
+    generate_personal_access_token, normalize_personal_access_token_scopes, token_hint,
+  },
+  personal_data::{
+    anonymize_user, export_personal_data, export_summary, record_personal_data_request,
+    PERSONAL_DATA_ANONYMIZATION, PERSONAL_DATA_EXPORT,
+  },
+  policies::{UserAction, UserPolicy},
+  user_merge::merge_users,
+};
+
+/// Personal access tokens can only be managed by the real user, signed in with a cookie: a token
+/// can't be used to mint more tokens, and nobody can make tokens for someone whose identity
+/// they've assumed.
+pub(crate) fn personal_access_token_owner(ctx: &Context<'_>) -> Result<users::Model, Error> {
+  let authorization_info = ctx.data::<AuthorizationInfo>()?;
+  if authorization_info.oauth_scope.is_some() {
+    return Err(Error::new(
+      "Personal access tokens can only be managed from a signed-in browser session",
+    ));
+  }
+  if authorization_info.assumed_identity_from_profile.is_some() {
+    return Err(Error::new(
+      "Personal access tokens can't be managed while assuming someone else's identity",
+    ));
+  }
+
+  authorization_info
+    .user
+    .clone()
+    .ok_or_else(|| Error::new("You must be signed in to manage personal access tokens"))
+}
+
+/// Data subject requests can only be made by the real user, from their own browser session
+fn personal_data_subject(ctx: &Context<'_>) -> Result<users::Model, Error> {
+  let authorization_info = ctx.data::<AuthorizationInfo>()?;
+  if authorization_info.oauth_scope.is_some() {
+    return Err(Error::new(
+      "Personal data requests can only be made from a signed-in browser session",
+    ));
+  }
+  if authorization_info.assumed_identity_from_profile.is_some() {
+    return Err(Error::new(
+      "Personal data requests can't be made while assuming someone else's identity",
+    ));
+  }
+
+  authorization_info
+    .user
+    .clone()
+    .ok_or_else(|| Error::new("You must be signed in to make a personal data request"))
+}
+
+#[derive(Default)]
+pub struct MutationRootUsersFields;
+
+#[Object]
+impl MutationRootUsersFields {
+  /// Creates a personal access token for the current user.  The token is returned in the payload
+  /// and can't be retrieved again afterwards.
+  async fn create_personal_access_token(
+    &self,
+    ctx: &Context<'_>,
+    input: CreatePersonalAccessTokenInput,
+  ) -> Result<CreatePersonalAccessTokenPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user = personal_access_token_owner(ctx)?;
+
+    let name = input.name.trim();
+    if name.is_empty() {
+      return Err(Error::new("Personal access tokens need a name"));
+    }
+    let scopes = normalize_personal_access_token_scopes(&input.scopes).map_err(Error::new)?;
+    let now = Utc::now().naive_utc();
+    let expires_at = input.expires_at.map(NaiveDateTime::from);
+    if expires_at.is_some_and(|expires_at| expires_at <= now) {
+      return Err(Error::new("Expiration time must be in the future"));
+    }
+
+    let token = generate_personal_access_token();
+    let personal_access_token = personal_access_tokens::ActiveModel {
+      user_id: Set(user.id),
+      name: Set(name.to_string()),
+      token_digest: Set(personal_access_token_digest(&token)),
+      token_hint: Set(token_hint(&token)),
+      scopes: Set(scopes),
+      expires_at: Set(expires_at),
+      created_at: Set(now),
+      updated_at: Set(now),
+      ..Default::default()
+    }
+    .insert(query_data.db())
+    .await?;
+
+    Ok(CreatePersonalAccessTokenPayload {
+      client_mutation_id: input.client_mutation_id,
+      personal_access_token: PersonalAccessTokenType::new(personal_access_token),
+      token,
+    })
+  }
+
+  /// Revokes one of the current user's personal access tokens.  Requests made with it will be
+  /// refused from then on.
+  async fn revoke_personal_access_token(
+    &self,
+    ctx: &Context<'_>,
+    input: RevokePersonalAccessTokenInput,
+  ) -> Result<RevokePersonalAccessTokenPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user = personal_access_token_owner(ctx)?;
+
+    let personal_access_token =
+      personal_access_tokens::Entity::find_by_id(LaxId::parse(input.id.clone())?)
+        .filter(personal_access_tokens::Column::UserId.eq(user.id))
+        .one(query_data.db())
+        .await?
+        .ok_or_else(|| {
+          DbErr::RecordNotFound(format!("Personal access token {} not found", input.id.0))
+        })?;
+
+    let personal_access_token = if personal_access_token.revoked_at.is_some() {
+      personal_access_token
+    } else {
+      let now = Utc::now().naive_utc();
+      let mut active_model: personal_access_tokens::ActiveModel = personal_access_token.into();
+      active_model.revoked_at = Set(Some(now));
+      active_model.updated_at = Set(now);
+      active_model.update(query_data.db()).await?
+    };
+
+    Ok(RevokePersonalAccessTokenPayload {
+      client_mutation_id: input.client_mutation_id,
+      personal_access_token: PersonalAccessTokenType::new(personal_access_token),
+    })
+  }
+
+  /// Merges a duplicate account into another one.  Runs as a dry run unless dry_run is set to
+  /// false, so the report can be reviewed before anything changes.
+  async fn merge_users(
+    &self,
+    ctx: &Context<'_>,
+    input: MergeUsersInput,
+  ) -> Result<MergeUsersPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let winning_user_id = LaxId::parse(input.winning_user_id)?;
+    let losing_user_id = LaxId::parse(input.losing_user_id)?;
+
+    for user_id in [winning_user_id, losing_user_id] {
+      let user = users::Entity::find_by_id(user_id)
+        .one(query_data.db())
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("User {} not found", user_id)))?;
+      ensure_action_permitted::<UserPolicy, _>(ctx, &UserAction::Merge, &user).await?;
+    }
+
+    let report = merge_users(
+      query_data.db(),
+      winning_user_id,
+      losing_user_id,
+      input.profile_conflict_strategy,
+      input.dry_run,
+    )
+    .await?;
+
+    Ok(MergeUsersPayload {
+      client_mutation_id: input.client_mutation_id,
+      report,
+    })
+  }
+
+  /// Exports everything we hold about the current user as a JSON document.  Each export is
+  /// recorded in the personal data request log.  POST /users/personal_data_export returns the same
+  /// document as a file download.
+  async fn export_personal_data(
+    &self,
+    ctx: &Context<'_>,
+    input: ExportPersonalDataInput,
+  ) -> Result<ExportPersonalDataPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user = personal_data_subject(ctx)?;
+
+    let personal_data = export_personal_data(query_data.db(), &user).await?;
+    record_personal_data_request(
+      query_data.db(),
+      user.id,
+      PERSONAL_DATA_EXPORT,
+      export_summary(&personal_data),
+    )
+    .await?;
+
+    Ok(ExportPersonalDataPayload {
+      client_mutation_id: input.client_mutation_id,
+      personal_data: JsonScalar(personal_data),
+    })
+  }
+
+  /// Deletes the current user's account.  Personal information is scrubbed from the account and
+  /// all its profiles, and the user is signed out everywhere; signups, tickets and orders are
+  /// kept, anonymized, so that conventions' historical numbers stay the same.
+  async fn delete_account(
+    &self,
+    ctx: &Context<'_>,
+    input: DeleteAccountInput,
+  ) -> Result<DeleteAccountPayload, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user = personal_data_subject(ctx)?;
+
+    if !PASSWORD_HASHER.verify(&user, &input.current_password) {
+      return Err(Error::new("Current password is incorrect"));
+    }
+    if user.site_admin.unwrap_or(false) {
+      return Err(Error::new(
+        "Site admins can't delete their own accounts.  Ask another site admin to remove your \
+         admin access first.",
+      ));
+    }
+
+    let details = anonymize_user(query_data.db(), &user).await?;
+    record_personal_data_request(
+      query_data.db(),
+      user.id,
+      PERSONAL_DATA_ANONYMIZATION,
+      details,
+    )
+    .await?;
+
+    Ok(DeleteAccountPayload {
+      client_mutation_id: input.client_mutation_id,
+    })
+  }
+}
@@ -1,20 +1,23 @@
 use std::sync::Arc;
 
 use async_graphql::*;
-use intercode_entities::{oauth_applications, users};
+use intercode_entities::{oauth_applications, personal_access_tokens, users};
 use intercode_graphql_core::{
   lax_id::LaxId, query_data::QueryData, ModelBackedType, ModelPaginator,
 };
 use intercode_policies::{AuthorizationInfo, AuthorizedFromQueryBuilder};
 use intercode_query_builders::sort_input::SortInput;
-use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
 
 use crate::{
+  objects::PersonalAccessTokenType,
   policies::UserPolicy,
   query_builders::{UserFiltersInput, UsersQueryBuilder},
 };
 
-use super::{AbilityUsersFields, UserConProfileUsersFields, UserUsersFields};
+use super::{
+  personal_access_token_owner, AbilityUsersFields, UserConProfileUsersFields, UserUsersFields,
+};
 
 #[derive(Default)]
 pub struct QueryRootUsersFields;
@@ -113,4 +116,25 @@ impl QueryRootUsersFields {
       .await?;
     Ok(count > 0)
   }
+
+  /// The current user's personal access tokens, not including revoked ones
+  async fn personal_access_tokens(
+    &self,
+    ctx: &Context<'_>,
+  ) -> Result<Vec<PersonalAccessTokenType>, Error> {
+    let query_data = ctx.data::<QueryData>()?;
+    let user = personal_access_token_owner(ctx)?;
+
+    Ok(
+      personal_access_tokens::Entity::find()
+        .filter(personal_access_tokens::Column::UserId.eq(user.id))
+        .filter(personal_access_tokens::Column::RevokedAt.is_null())
+        .order_by_desc(personal_access_tokens::Column::CreatedAt)
+        .all(query_data.db())
+        .await?
+        .into_iter()
+        .map(PersonalAccessTokenType::new)
+        .collect(),
+    )
+  }
 }
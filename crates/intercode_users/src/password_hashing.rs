@@ -0,0 +1,357 @@
+//! Password hashing.  New passwords are hashed with Argon2id by default; bcrypt hashes (which is
+//! what Devise writes) and the legacy schemes in [crate::legacy_passwords] still verify, and any
+//! stored hash that isn't in the configured format with the configured parameters gets replaced
+//! the next time its owner signs in successfully.
+
+use std::{env, str::FromStr};
+
+use argon2::{
+  password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString},
+  Algorithm, Argon2, Params, Version,
+};
+use intercode_entities::users;
+use once_cell::sync::{Lazy, OnceCell};
+use rand::RngCore;
+use tracing::error;
+
+use crate::legacy_passwords::{verify_legacy_md5_password, verify_legacy_sha1_password};
+
+pub const DEFAULT_BCRYPT_COST: u32 = 10;
+// the range the bcrypt crate accepts
+const BCRYPT_COSTS: std::ops::RangeInclusive<u32> = 4..=31;
+
+pub static PASSWORD_HASHER: Lazy<PasswordHasher> = Lazy::new(|| {
+  PasswordHasherConfig::from_env()
+    .and_then(PasswordHasher::new)
+    .unwrap_or_else(|err| {
+      // the server refuses to start with a bad configuration (see validate_password_hashing_env),
+      // so only tools that skip that check can end up here
+      error!(
+        "{}; hashing passwords with the default settings instead",
+        err
+      );
+      PasswordHasher::with_params(PasswordHasherConfig::default(), Params::default())
+    })
+});
+
+/// Checks the password hashing configuration, so that the server can refuse to start instead of
+/// failing (or quietly using settings nobody chose) the first time someone signs in
+pub fn validate_password_hashing_env() -> Result<(), String> {
+  PasswordHasherConfig::from_env()
+    .and_then(PasswordHasher::new)
+    .map(|_| ())
+}
+
+fn parse_or<T: FromStr>(name: &str, value: Option<String>, default: T) -> Result<T, String> {
+  match value {
+    Some(value) => value
+      .trim()
+      .parse()
+      .map_err(|_| format!("{} is set to {:?}, which isn't valid", name, value)),
+    None => Ok(default),
+  }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String> {
+  parse_or(name, env::var(name).ok(), default)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordHashAlgorithm {
+  Argon2id,
+  Bcrypt,
+}
+
+impl FromStr for PasswordHashAlgorithm {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "argon2id" => Ok(Self::Argon2id),
+      "bcrypt" => Ok(Self::Bcrypt),
+      _ => Err(format!("Unknown password hash algorithm: {}", s)),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordHasherConfig {
+  pub algorithm: PasswordHashAlgorithm,
+  pub argon2_memory_kib: u32,
+  pub argon2_iterations: u32,
+  pub argon2_parallelism: u32,
+  pub bcrypt_cost: u32,
+}
+
+impl PasswordHasherConfig {
+  /// Reads PASSWORD_HASH_ALGORITHM (argon2id or bcrypt, default argon2id), ARGON2_MEMORY_KIB
+  /// (default 19456), ARGON2_ITERATIONS (default 2), ARGON2_PARALLELISM (default 1) and
+  /// BCRYPT_COST (default 10).  The Argon2 defaults are OWASP's minimum recommendation.  Anything
+  /// that still needs to read hashes the way Devise does should stick with bcrypt.  Values that
+  /// are set but can't be parsed are errors, rather than being replaced with the defaults.
+  pub fn from_env() -> Result<Self, String> {
+    let default = Self::default();
+
+    Ok(Self {
+      algorithm: env_or("PASSWORD_HASH_ALGORITHM", default.algorithm)?,
+      argon2_memory_kib: env_or("ARGON2_MEMORY_KIB", default.argon2_memory_kib)?,
+      argon2_iterations: env_or("ARGON2_ITERATIONS", default.argon2_iterations)?,
+      argon2_parallelism: env_or("ARGON2_PARALLELISM", default.argon2_parallelism)?,
+      bcrypt_cost: env_or("BCRYPT_COST", default.bcrypt_cost)?,
+    })
+  }
+}
+
+impl Default for PasswordHasherConfig {
+  fn default() -> Self {
+    Self {
+      algorithm: PasswordHashAlgorithm::Argon2id,
+      argon2_memory_kib: Params::DEFAULT_M_COST,
+      argon2_iterations: Params::DEFAULT_T_COST,
+      argon2_parallelism: Params::DEFAULT_P_COST,
+      bcrypt_cost: DEFAULT_BCRYPT_COST,
+    }
+  }
+}
+
+pub struct PasswordHasher {
+  config: PasswordHasherConfig,
+  argon2: Argon2<'static>,
+  dummy_hash: OnceCell<String>,
+}
+
+impl PasswordHasher {
+  /// Builds a hasher, or explains what's wrong with the configuration
+  pub fn new(config: PasswordHasherConfig) -> Result<Self, String> {
+    let params = Params::new(
+      config.argon2_memory_kib,
+      config.argon2_iterations,
+      config.argon2_parallelism,
+      None,
+    )
+    .map_err(|err| format!("Invalid Argon2 parameters: {}", err))?;
+    if !BCRYPT_COSTS.contains(&config.bcrypt_cost) {
+      return Err(format!(
+        "Invalid bcrypt cost {}: it must be between {} and {}",
+        config.bcrypt_cost,
+        BCRYPT_COSTS.start(),
+        BCRYPT_COSTS.end()
+      ));
+    }
+
+    Ok(Self::with_params(config, params))
+  }
+
+  fn with_params(config: PasswordHasherConfig, params: Params) -> Self {
+    Self {
+      argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+      config,
+      dummy_hash: OnceCell::new(),
+    }
+  }
+
+  pub fn hash(&self, password: &str) -> Result<String, String> {
+    match self.config.algorithm {
+      PasswordHashAlgorithm::Argon2id => {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|err| err.to_string())?;
+        self
+          .argon2
+          .hash_password(password.as_bytes(), &salt)
+          .map(|hash| hash.to_string())
+          .map_err(|err| err.to_string())
+      }
+      PasswordHashAlgorithm::Bcrypt => {
+        bcrypt::hash(password, self.config.bcrypt_cost).map_err(|err| err.to_string())
+      }
+    }
+  }
+
+  /// Checks a password against a single Argon2 or bcrypt hash
+  pub fn verify_hash(&self, password: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+      // the hash carries its own parameters, so the configured ones don't matter here
+      PasswordHash::new(hash)
+        .map(|parsed| {
+          Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+        })
+        .unwrap_or(false)
+    } else {
+      bcrypt::verify(password, hash).unwrap_or(false)
+    }
+  }
+
+  /// Checks a password against whatever the user has stored, falling back to the legacy schemes
+  /// for users who haven't signed in since they were imported
+  pub fn verify(&self, user: &users::Model, password: &str) -> bool {
+    if !user.encrypted_password.is_empty() {
+      self.verify_hash(password, &user.encrypted_password)
+    } else if let (Some(legacy_password_sha1), Some(legacy_password_sha1_salt)) =
+      (&user.legacy_password_sha1, &user.legacy_password_sha1_salt)
+    {
+      verify_legacy_sha1_password(password, legacy_password_sha1, legacy_password_sha1_salt)
+    } else if let Some(legacy_password_md5) = &user.legacy_password_md5 {
+      verify_legacy_md5_password(password, legacy_password_md5)
+    } else {
+      false
+    }
+  }
+
+  /// Burns about as much time as checking a real password would, so that sign-ins for accounts
+  /// that don't exist can't be told apart by how long they take
+  pub fn verify_dummy(&self, password: &str) {
+    let dummy_hash = self
+      .dummy_hash
+      .get_or_init(|| self.hash("not a real password").unwrap_or_default());
+    let _ = self.verify_hash(password, dummy_hash);
+  }
+
+  /// Whether a hash was made with the configured algorithm and parameters
+  pub fn hash_is_current(&self, hash: &str) -> bool {
+    match self.config.algorithm {
+      PasswordHashAlgorithm::Argon2id => {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+          return false;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+          return false;
+        };
+
+        parsed.algorithm == argon2::ARGON2ID_IDENT
+          && parsed.version == Some(Version::V0x13.into())
+          && params.m_cost() == self.config.argon2_memory_kib
+          && params.t_cost() == self.config.argon2_iterations
+          && params.p_cost() == self.config.argon2_parallelism
+      }
+      PasswordHashAlgorithm::Bcrypt => hash
+        .parse::<bcrypt::HashParts>()
+        .map(|parts| parts.get_cost() == self.config.bcrypt_cost)
+        .unwrap_or(false),
+    }
+  }
+
+  /// Whether the user's password should be rehashed next time we have it in plain text
+  pub fn needs_rehash(&self, user: &users::Model) -> bool {
+    user.encrypted_password.is_empty() || !self.hash_is_current(&user.encrypted_password)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // from the Argon2 reference implementation's test suite
+  const ARGON2ID_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+
+  // from the OpenBSD/crypt_blowfish test vectors
+  const BCRYPT_PASSWORD_HASH: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+
+  fn hasher(algorithm: PasswordHashAlgorithm) -> PasswordHasher {
+    PasswordHasher::new(PasswordHasherConfig {
+      algorithm,
+      argon2_memory_kib: 65536,
+      argon2_iterations: 2,
+      argon2_parallelism: 1,
+      bcrypt_cost: 5,
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn verify_correct_argon2id_password_is_valid() {
+    assert!(hasher(PasswordHashAlgorithm::Argon2id).verify_hash("password", ARGON2ID_PASSWORD_HASH))
+  }
+
+  #[test]
+  fn verify_incorrect_argon2id_password_is_invalid() {
+    assert!(!hasher(PasswordHashAlgorithm::Argon2id).verify_hash("Password", ARGON2ID_PASSWORD_HASH))
+  }
+
+  #[test]
+  fn verify_correct_bcrypt_password_is_valid() {
+    assert!(hasher(PasswordHashAlgorithm::Argon2id).verify_hash("U*U", BCRYPT_PASSWORD_HASH))
+  }
+
+  #[test]
+  fn verify_incorrect_bcrypt_password_is_invalid() {
+    assert!(!hasher(PasswordHashAlgorithm::Argon2id).verify_hash("U*U*", BCRYPT_PASSWORD_HASH))
+  }
+
+  #[test]
+  fn hashes_with_other_parameters_are_outdated() {
+    let argon2id = hasher(PasswordHashAlgorithm::Argon2id);
+    assert!(argon2id.hash_is_current(ARGON2ID_PASSWORD_HASH));
+    assert!(!argon2id.hash_is_current(BCRYPT_PASSWORD_HASH));
+
+    let weaker_argon2id = PasswordHasher::new(PasswordHasherConfig {
+      argon2_memory_kib: 19456,
+      ..argon2id.config.clone()
+    })
+    .unwrap();
+    assert!(!weaker_argon2id.hash_is_current(ARGON2ID_PASSWORD_HASH));
+
+    let bcrypt_hasher = hasher(PasswordHashAlgorithm::Bcrypt);
+    assert!(bcrypt_hasher.hash_is_current(BCRYPT_PASSWORD_HASH));
+    assert!(!bcrypt_hasher.hash_is_current(ARGON2ID_PASSWORD_HASH));
+
+    let costlier_bcrypt = PasswordHasher::new(PasswordHasherConfig {
+      bcrypt_cost: 10,
+      ..bcrypt_hasher.config.clone()
+    })
+    .unwrap();
+    assert!(!costlier_bcrypt.hash_is_current(BCRYPT_PASSWORD_HASH));
+  }
+
+  #[test]
+  fn new_hashes_verify_and_are_current() {
+    let hasher = PasswordHasher::new(PasswordHasherConfig {
+      algorithm: PasswordHashAlgorithm::Argon2id,
+      argon2_memory_kib: 256,
+      argon2_iterations: 1,
+      argon2_parallelism: 1,
+      bcrypt_cost: 5,
+    })
+    .unwrap();
+    let hash = hasher.hash("hello").unwrap();
+
+    assert!(hash.starts_with("$argon2id$v=19$m=256,t=1,p=1$"));
+    assert!(hasher.verify_hash("hello", &hash));
+    assert!(!hasher.verify_hash("Hello", &hash));
+    assert!(hasher.hash_is_current(&hash));
+  }
+
+  #[test]
+  fn rejects_invalid_configuration() {
+    assert!(PasswordHasher::new(PasswordHasherConfig {
+      argon2_memory_kib: 1,
+      ..Default::default()
+    })
+    .is_err());
+    assert!(PasswordHasher::new(PasswordHasherConfig {
+      bcrypt_cost: 40,
+      ..Default::default()
+    })
+    .is_err());
+    assert!(PasswordHasher::new(PasswordHasherConfig::default()).is_ok());
+  }
+
+  #[test]
+  fn unparsable_settings_are_errors() {
+    assert_eq!(parse_or("ARGON2_ITERATIONS", None, 2u32), Ok(2));
+    assert_eq!(
+      parse_or("ARGON2_ITERATIONS", Some(" 3 ".to_string()), 2u32),
+      Ok(3)
+    );
+    assert!(parse_or("ARGON2_ITERATIONS", Some("three".to_string()), 2u32).is_err());
+    assert!(parse_or(
+      "PASSWORD_HASH_ALGORITHM",
+      Some("scrypt".to_string()),
+      PasswordHashAlgorithm::Argon2id
+    )
+    .is_err());
+  }
+}
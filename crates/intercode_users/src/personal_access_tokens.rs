@@ -0,0 +1,77 @@
+//! Personal access tokens, which users create for themselves to script against the GraphQL API
+//! without having to register an OAuth application.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use intercode_entities::model_ext::personal_access_tokens::{
+  personal_access_token_scopes, PERSONAL_ACCESS_TOKEN_PREFIX,
+};
+use rand::RngCore;
+
+// how much of the random part of a token to keep around so users can tell their tokens apart
+const TOKEN_HINT_LENGTH: usize = 4;
+
+pub fn generate_personal_access_token() -> String {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  format!(
+    "{}{}",
+    PERSONAL_ACCESS_TOKEN_PREFIX,
+    URL_SAFE_NO_PAD.encode(bytes)
+  )
+}
+
+pub fn token_hint(token: &str) -> String {
+  token
+    .chars()
+    .take(PERSONAL_ACCESS_TOKEN_PREFIX.len() + TOKEN_HINT_LENGTH)
+    .collect()
+}
+
+/// Checks the requested scopes and turns them into the space-separated form tokens store
+pub fn normalize_personal_access_token_scopes(scopes: &[String]) -> Result<String, String> {
+  let mut normalized: Vec<&str> = vec![];
+  for scope in scopes.iter().flat_map(|scope| scope.split_whitespace()) {
+    if !personal_access_token_scopes().any(|allowed| allowed == scope) {
+      return Err(format!("Unknown scope: {}", scope));
+    }
+    if !normalized.contains(&scope) {
+      normalized.push(scope);
+    }
+  }
+
+  if normalized.is_empty() {
+    return Err("Personal access tokens need at least one scope".to_string());
+  }
+
+  Ok(normalized.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn generates_prefixed_tokens() {
+    let token = generate_personal_access_token();
+    assert!(token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX));
+    assert_eq!(token.len(), PERSONAL_ACCESS_TOKEN_PREFIX.len() + 43);
+    assert_eq!(
+      token_hint(&token),
+      &token[0..PERSONAL_ACCESS_TOKEN_PREFIX.len() + TOKEN_HINT_LENGTH]
+    );
+  }
+
+  #[test]
+  fn normalizes_scopes() {
+    assert_eq!(
+      normalize_personal_access_token_scopes(&[
+        "read_events".to_string(),
+        "read_profile read_events".to_string()
+      ])
+      .unwrap(),
+      "read_events read_profile"
+    );
+    assert!(normalize_personal_access_token_scopes(&["openid".to_string()]).is_err());
+    assert!(normalize_personal_access_token_scopes(&[]).is_err());
+  }
+}
@@ -0,0 +1,582 @@
+//! Self-service handling of data subject requests: exporting everything we hold about a user,
+//! and anonymizing their account when they ask for it to be deleted.
+//!
+//! Anonymization keeps the user and profile rows (scrubbed of anything personal) rather than
+//! deleting them, so that signups, tickets and orders stay attached to something and historical
+//! counts don't change.
+
+use chrono::Utc;
+use intercode_email::outbox::{OUTBOX_STATUS_FAILED, OUTBOX_STATUS_PENDING};
+use intercode_entities::{
+  ahoy_events, ahoy_visits, assumed_identity_request_logs, assumed_identity_sessions, conventions,
+  event_proposals, event_ratings, form_response_changes, notification_destinations,
+  notification_preferences, oauth_access_grants, oauth_access_tokens, order_entries, orders,
+  otp_recovery_codes, outbox_emails, personal_access_tokens, personal_data_requests, signups,
+  team_members, tickets, user_activity_alerts, user_con_profiles, users,
+};
+use intercode_full_text_search::search_index::{reindex_records, SearchableType};
+use sea_orm::{
+  sea_query::{Expr, Func},
+  ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+  QueryFilter, QuerySelect, Set,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{devise_tokens::friendly_token, user_merge::delete_openid_requests_for_grants_of};
+
+pub const PERSONAL_DATA_EXPORT: &str = "export";
+pub const PERSONAL_DATA_ANONYMIZATION: &str = "anonymization";
+
+// Credentials and internal bookkeeping that don't belong in an export, even the user's own
+const REDACTED_USER_COLUMNS: &[&str] = &[
+  "encrypted_password",
+  "reset_password_token",
+  "legacy_password_md5",
+  "legacy_password_sha1",
+  "legacy_password_sha1_salt",
+  "confirmation_token",
+  "otp_secret_ciphertext",
+  "otp_consumed_timestep",
+];
+const REDACTED_PROFILE_COLUMNS: &[&str] = &["ical_secret"];
+const REDACTED_EVENT_PROPOSAL_COLUMNS: &[&str] = &["admin_notes"];
+
+fn redact(mut value: Value, columns: &[&str]) -> Value {
+  if let Value::Object(object) = &mut value {
+    for column in columns {
+      object.remove(*column);
+    }
+  }
+  value
+}
+
+fn ids(rows: &[Value]) -> Vec<i64> {
+  rows.iter().filter_map(|row| row["id"].as_i64()).collect()
+}
+
+/// Everything we hold about a user, as a single JSON document
+pub async fn export_personal_data<C: ConnectionTrait>(
+  db: &C,
+  user: &users::Model,
+) -> Result<Value, DbErr> {
+  let user_json = users::Entity::find_by_id(user.id)
+    .into_json()
+    .one(db)
+    .await?
+    .map(|value| redact(value, REDACTED_USER_COLUMNS))
+    .unwrap_or_default();
+
+  let profiles = user_con_profiles::Entity::find()
+    .filter(user_con_profiles::Column::UserId.eq(user.id))
+    .into_json()
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|value| redact(value, REDACTED_PROFILE_COLUMNS))
+    .collect::<Vec<_>>();
+  let profile_ids = ids(&profiles);
+  let convention_ids = profiles
+    .iter()
+    .filter_map(|profile| profile["convention_id"].as_i64())
+    .collect::<Vec<_>>();
+
+  let conventions = conventions::Entity::find()
+    .select_only()
+    .columns([
+      conventions::Column::Id,
+      conventions::Column::Name,
+      conventions::Column::Domain,
+    ])
+    .filter(conventions::Column::Id.is_in(convention_ids))
+    .into_json()
+    .all(db)
+    .await?;
+
+  let orders = orders::Entity::find()
+    .filter(orders::Column::UserConProfileId.is_in(profile_ids.clone()))
+    .into_json()
+    .all(db)
+    .await?;
+  let order_entries = order_entries::Entity::find()
+    .filter(order_entries::Column::OrderId.is_in(ids(&orders)))
+    .into_json()
+    .all(db)
+    .await?;
+
+  let event_proposals = event_proposals::Entity::find()
+    .filter(event_proposals::Column::OwnerId.is_in(profile_ids.clone()))
+    .into_json()
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|value| redact(value, REDACTED_EVENT_PROPOSAL_COLUMNS))
+    .collect::<Vec<_>>();
+
+  Ok(json!({
+    "exported_at": Utc::now().to_rfc3339(),
+    "user": user_json,
+    "conventions": conventions,
+    "user_con_profiles": profiles,
+    "signups": signups::Entity::find()
+      .filter(signups::Column::UserConProfileId.is_in(profile_ids.clone()))
+      .into_json()
+      .all(db)
+      .await?,
+    "orders": orders,
+    "order_entries": order_entries,
+    "tickets": tickets::Entity::find()
+      .filter(tickets::Column::UserConProfileId.is_in(profile_ids.clone()))
+      .into_json()
+      .all(db)
+      .await?,
+    "event_ratings": event_ratings::Entity::find()
+      .filter(event_ratings::Column::UserConProfileId.is_in(profile_ids.clone()))
+      .into_json()
+      .all(db)
+      .await?,
+    "event_proposals": event_proposals,
+    "form_response_changes": form_response_changes::Entity::find()
+      .filter(form_response_changes::Column::UserConProfileId.is_in(profile_ids))
+      .into_json()
+      .all(db)
+      .await?,
+  }))
+}
+
+/// A summary of an export for the audit log: how many rows of each kind went out, but none of
+/// the data itself
+pub fn export_summary(export: &Value) -> Value {
+  let counts = export
+    .as_object()
+    .into_iter()
+    .flatten()
+    .filter_map(|(key, value)| {
+      value
+        .as_array()
+        .map(|rows| (key.clone(), json!(rows.len())))
+    })
+    .collect::<Map<_, _>>();
+
+  json!({ "row_counts": counts })
+}
+
+pub fn anonymized_email(user_id: i64) -> String {
+  format!("deleted-user-{}@deleted.invalid", user_id)
+}
+
+/// Mail to a user is tied to them either through a profile or just by address
+fn outbox_emails_for(user: &users::Model, profile_ids: &[i64]) -> Condition {
+  Condition::any()
+    .add(outbox_emails::Column::UserConProfileId.is_in(profile_ids.to_vec()))
+    .add(
+      Expr::expr(Func::lower(Expr::col(outbox_emails::Column::ToAddress)))
+        .eq(user.email.to_lowercase()),
+    )
+}
+
+/// Scrubs everything personal from a user's account and signs them out everywhere.  Returns a
+/// summary of what was done, for the audit log.
+pub async fn anonymize_user<C: ConnectionTrait>(
+  db: &C,
+  user: &users::Model,
+) -> Result<Value, DbErr> {
+  let now = Utc::now().naive_utc();
+  let profile_ids = user_con_profiles::Entity::find()
+    .filter(user_con_profiles::Column::UserId.eq(user.id))
+    .select_only()
+    .column(user_con_profiles::Column::Id)
+    .into_tuple::<i64>()
+    .all(db)
+    .await?;
+
+  users::ActiveModel {
+    id: ActiveValue::Unchanged(user.id),
+    first_name: Set("Deleted".to_string()),
+    last_name: Set("User".to_string()),
+    email: Set(anonymized_email(user.id)),
+    site_admin: Set(Some(false)),
+    encrypted_password: Set("".to_string()),
+    reset_password_token: Set(None),
+    reset_password_sent_at: Set(None),
+    remember_created_at: Set(None),
+    current_sign_in_ip: Set(None),
+    last_sign_in_ip: Set(None),
+    legacy_password_md5: Set(None),
+    legacy_password_sha1: Set(None),
+    legacy_password_sha1_salt: Set(None),
+    confirmation_token: Set(None),
+    otp_secret_ciphertext: Set(None),
+    otp_enabled_at: Set(None),
+    otp_consumed_timestep: Set(None),
+    updated_at: Set(Some(now)),
+    ..Default::default()
+  }
+  .update(db)
+  .await?;
+
+  let scrubbed_profiles = user_con_profiles::Entity::update_many()
+    .col_expr(user_con_profiles::Column::FirstName, Expr::value("Deleted"))
+    .col_expr(user_con_profiles::Column::LastName, Expr::value("User"))
+    .col_expr(
+      user_con_profiles::Column::Nickname,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::BirthDate,
+      Expr::value(Option::<chrono::NaiveDate>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::Gender,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::Address,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::City,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::State,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::Zipcode,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::Country,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::DayPhone,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::EveningPhone,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::MobilePhone,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::BestCallTime,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::PreferredContact,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::Bio,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::ShowNicknameInBio,
+      Expr::value(false),
+    )
+    .col_expr(
+      user_con_profiles::Column::AdditionalInfo,
+      Expr::value(Option::<Value>::None),
+    )
+    .col_expr(
+      user_con_profiles::Column::GravatarEnabled,
+      Expr::value(false),
+    )
+    .col_expr(
+      user_con_profiles::Column::ReceiveWhosFreeEmails,
+      Expr::value(false),
+    )
+    .col_expr(user_con_profiles::Column::AllowSms, Expr::value(false))
+    .col_expr(
+      user_con_profiles::Column::IcalSecret,
+      Expr::value(friendly_token()),
+    )
+    .col_expr(user_con_profiles::Column::UpdatedAt, Expr::value(now))
+    .filter(user_con_profiles::Column::UserId.eq(user.id))
+    .exec(db)
+    .await?
+    .rows_affected;
+
+  // proposals stay, since they're part of the event's history, but the contact address goes
+  event_proposals::Entity::update_many()
+    .col_expr(
+      event_proposals::Column::Email,
+      Expr::value(Option::<String>::None),
+    )
+    .filter(event_proposals::Column::OwnerId.is_in(profile_ids.clone()))
+    .exec(db)
+    .await?;
+
+  // search documents carry copies of names and email addresses, so they need scrubbing too
+  let owned_proposal_ids = event_proposals::Entity::find()
+    .filter(event_proposals::Column::OwnerId.is_in(profile_ids.clone()))
+    .select_only()
+    .column(event_proposals::Column::Id)
+    .into_tuple::<i64>()
+    .all(db)
+    .await?;
+  let team_member_event_ids = team_members::Entity::find()
+    .filter(team_members::Column::UserConProfileId.is_in(profile_ids.clone()))
+    .filter(team_members::Column::EventId.is_not_null())
+    .select_only()
+    .column(team_members::Column::EventId)
+    .into_tuple::<i64>()
+    .all(db)
+    .await?;
+  reindex_records(db, SearchableType::UserConProfile, profile_ids.clone()).await?;
+  reindex_records(db, SearchableType::EventProposal, owned_proposal_ids).await?;
+  reindex_records(db, SearchableType::Event, team_member_event_ids).await?;
+
+  let mut scrubbed = Map::new();
+
+  // queued mail would otherwise still go out to the old address, and sent mail keeps a copy of
+  // what we said to them
+  outbox_emails::Entity::update_many()
+    .col_expr(
+      outbox_emails::Column::Status,
+      Expr::value(OUTBOX_STATUS_FAILED),
+    )
+    .col_expr(
+      outbox_emails::Column::LastError,
+      Expr::value("The recipient deleted their account"),
+    )
+    .filter(outbox_emails::Column::Status.eq(OUTBOX_STATUS_PENDING))
+    .filter(outbox_emails_for(user, &profile_ids))
+    .exec(db)
+    .await?;
+  let result = outbox_emails::Entity::update_many()
+    .col_expr(
+      outbox_emails::Column::ToAddress,
+      Expr::value(anonymized_email(user.id)),
+    )
+    .col_expr(outbox_emails::Column::Subject, Expr::value(""))
+    .col_expr(
+      outbox_emails::Column::BodyHtml,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      outbox_emails::Column::BodyText,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(outbox_emails::Column::UpdatedAt, Expr::value(now))
+    .filter(outbox_emails_for(user, &profile_ids))
+    .exec(db)
+    .await?;
+  scrubbed.insert("outbox_emails".to_string(), json!(result.rows_affected));
+
+  // the audit trail of assumed identity sessions stays, but not the request contents, which can
+  // include anything either user submitted
+  let assumed_identity_session_ids = assumed_identity_sessions::Entity::find()
+    .filter(
+      Condition::any()
+        .add(assumed_identity_sessions::Column::AssumedProfileId.is_in(profile_ids.clone()))
+        .add(assumed_identity_sessions::Column::AssumerProfileId.is_in(profile_ids.clone())),
+    )
+    .select_only()
+    .column(assumed_identity_sessions::Column::Id)
+    .into_tuple::<i64>()
+    .all(db)
+    .await?;
+  let result = assumed_identity_request_logs::Entity::update_many()
+    .col_expr(
+      assumed_identity_request_logs::Column::IpAddress,
+      Expr::cust("'0.0.0.0'::inet"),
+    )
+    .col_expr(
+      assumed_identity_request_logs::Column::HttpHeaders,
+      Expr::value(json!({})),
+    )
+    .col_expr(
+      assumed_identity_request_logs::Column::HttpBody,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      assumed_identity_request_logs::Column::GraphqlVariables,
+      Expr::value(Option::<Value>::None),
+    )
+    .col_expr(
+      assumed_identity_request_logs::Column::UpdatedAt,
+      Expr::value(now),
+    )
+    .filter(
+      assumed_identity_request_logs::Column::AssumedIdentitySessionId
+        .is_in(assumed_identity_session_ids),
+    )
+    .exec(db)
+    .await?;
+  scrubbed.insert(
+    "assumed_identity_request_logs".to_string(),
+    json!(result.rows_affected),
+  );
+
+  // visits keep their aggregate-friendly fields (browser, OS, campaign) but lose anything that
+  // could identify or locate the person
+  let result = ahoy_visits::Entity::update_many()
+    .col_expr(
+      ahoy_visits::Column::UserId,
+      Expr::value(Option::<i64>::None),
+    )
+    .col_expr(ahoy_visits::Column::Ip, Expr::value(Option::<String>::None))
+    .col_expr(
+      ahoy_visits::Column::UserAgent,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::Referrer,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::LandingPage,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::Region,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::City,
+      Expr::value(Option::<String>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::Latitude,
+      Expr::value(Option::<f64>::None),
+    )
+    .col_expr(
+      ahoy_visits::Column::Longitude,
+      Expr::value(Option::<f64>::None),
+    )
+    .filter(ahoy_visits::Column::UserId.eq(user.id))
+    .exec(db)
+    .await?;
+  scrubbed.insert("ahoy_visits".to_string(), json!(result.rows_affected));
+  let result = ahoy_events::Entity::update_many()
+    .col_expr(
+      ahoy_events::Column::UserId,
+      Expr::value(Option::<i64>::None),
+    )
+    .filter(ahoy_events::Column::UserId.eq(user.id))
+    .exec(db)
+    .await?;
+  scrubbed.insert("ahoy_events".to_string(), json!(result.rows_affected));
+
+  let mut deleted = Map::new();
+  macro_rules! delete_rows {
+    ($entity:ident, $filter:expr) => {
+      let result = $entity::Entity::delete_many()
+        .filter($filter)
+        .exec(db)
+        .await?;
+      deleted.insert(stringify!($entity).to_string(), json!(result.rows_affected));
+    };
+  }
+
+  delete_rows!(
+    event_ratings,
+    event_ratings::Column::UserConProfileId.is_in(profile_ids.clone())
+  );
+  delete_rows!(
+    form_response_changes,
+    form_response_changes::Column::UserConProfileId.is_in(profile_ids.clone())
+  );
+  delete_rows!(
+    notification_destinations,
+    notification_destinations::Column::UserConProfileId.is_in(profile_ids.clone())
+  );
+  delete_rows!(
+    notification_preferences,
+    notification_preferences::Column::UserConProfileId.is_in(profile_ids)
+  );
+  // OpenID nonces reference grants without cascading, so they go first
+  deleted.insert(
+    "oauth_openid_requests".to_string(),
+    json!(delete_openid_requests_for_grants_of(db, user.id).await?),
+  );
+  delete_rows!(
+    oauth_access_grants,
+    oauth_access_grants::Column::ResourceOwnerId.eq(user.id)
+  );
+  delete_rows!(
+    oauth_access_tokens,
+    oauth_access_tokens::Column::ResourceOwnerId.eq(user.id)
+  );
+  delete_rows!(
+    otp_recovery_codes,
+    otp_recovery_codes::Column::UserId.eq(user.id)
+  );
+  delete_rows!(
+    personal_access_tokens,
+    personal_access_tokens::Column::UserId.eq(user.id)
+  );
+  delete_rows!(
+    user_activity_alerts,
+    user_activity_alerts::Column::UserId.eq(user.id)
+  );
+
+  let signed_out_sessions = intercode_server::delete_sessions_for_user(db, user.id)
+    .await
+    .map_err(|err| DbErr::Custom(err.to_string()))?;
+
+  Ok(json!({
+    "scrubbed_profiles": scrubbed_profiles,
+    "scrubbed_rows": scrubbed,
+    "deleted_rows": deleted,
+    "signed_out_sessions": signed_out_sessions,
+  }))
+}
+
+pub async fn record_personal_data_request<C: ConnectionTrait>(
+  db: &C,
+  user_id: i64,
+  request_kind: &str,
+  details: Value,
+) -> Result<personal_data_requests::Model, DbErr> {
+  let now = Utc::now().naive_utc();
+  personal_data_requests::ActiveModel {
+    user_id: Set(user_id),
+    request_kind: Set(request_kind.to_string()),
+    details: Set(details),
+    created_at: Set(now),
+    updated_at: Set(now),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn redact_removes_secrets_and_keeps_everything_else() {
+    let user = json!({
+      "id": 1,
+      "email": "someone@example.com",
+      "encrypted_password": "$argon2id$...",
+      "otp_secret_ciphertext": "abc",
+    });
+
+    assert_eq!(
+      redact(user, REDACTED_USER_COLUMNS),
+      json!({ "id": 1, "email": "someone@example.com" })
+    );
+  }
+
+  #[test]
+  fn export_summary_counts_rows_without_including_them() {
+    let export = json!({
+      "exported_at": "2026-10-19T00:00:00",
+      "user": { "id": 1 },
+      "signups": [{ "id": 1 }, { "id": 2 }],
+      "tickets": [],
+    });
+
+    assert_eq!(
+      export_summary(&export),
+      json!({ "row_counts": { "signups": 2, "tickets": 0 } })
+    );
+  }
+}
@@ -69,6 +69,7 @@ impl Policy<AuthorizationInfo, users::Model> for UserPolicy {
                 > 0),
         )
       }
+      UserAction::Merge => Ok(principal.site_admin_manage()),
       _ => todo!(),
     }
   }
@@ -0,0 +1,208 @@
+//! Protection against password guessing: rate limits on sign-in attempts per client IP and per
+//! account, and Devise-style temporary lockout of accounts after repeated failures.
+
+use std::{
+  collections::HashMap,
+  env,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+pub static SIGN_IN_THROTTLE: Lazy<SignInThrottle> =
+  Lazy::new(|| SignInThrottle::new(SignInThrottleConfig::from_env()));
+
+pub static LOCKOUT_CONFIG: Lazy<LockoutConfig> = Lazy::new(LockoutConfig::from_env);
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+  env::var(name)
+    .ok()
+    .and_then(|value| value.parse().ok())
+    .unwrap_or(default)
+}
+
+#[derive(Debug, Clone)]
+pub struct SignInThrottleConfig {
+  pub max_attempts_per_ip: u32,
+  pub max_attempts_per_account: u32,
+  pub window: Duration,
+}
+
+impl SignInThrottleConfig {
+  /// Reads SIGN_IN_MAX_ATTEMPTS_PER_IP (default 20), SIGN_IN_MAX_ATTEMPTS_PER_ACCOUNT (default
+  /// 10) and SIGN_IN_THROTTLE_WINDOW_SECONDS (default 300)
+  pub fn from_env() -> Self {
+    Self {
+      max_attempts_per_ip: env_or("SIGN_IN_MAX_ATTEMPTS_PER_IP", 20),
+      max_attempts_per_account: env_or("SIGN_IN_MAX_ATTEMPTS_PER_ACCOUNT", 10),
+      window: Duration::from_secs(env_or("SIGN_IN_THROTTLE_WINDOW_SECONDS", 300)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AttemptWindow {
+  started_at: Instant,
+  attempts: u32,
+}
+
+/// Fixed-window counters of sign-in attempts, kept in memory.  Each server process counts
+/// separately, so the effective limit is multiplied by the number of processes; account lockout
+/// is what protects individual accounts across the whole deployment.
+#[derive(Debug)]
+pub struct SignInThrottle {
+  config: SignInThrottleConfig,
+  windows: Mutex<HashMap<String, AttemptWindow>>,
+}
+
+impl SignInThrottle {
+  pub fn new(config: SignInThrottleConfig) -> Self {
+    Self {
+      config,
+      windows: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Counts a sign-in attempt from the given IP address for the given email.  If either has made
+  /// too many attempts recently, returns how long the client should wait before trying again.
+  pub fn hit(&self, ip: Option<&str>, email: &str) -> Option<Duration> {
+    self.hit_at(ip, email, Instant::now())
+  }
+
+  fn hit_at(&self, ip: Option<&str>, email: &str, now: Instant) -> Option<Duration> {
+    let mut windows = self.windows.lock().unwrap();
+    if windows.len() > 10_000 {
+      windows.retain(|_, window| now.duration_since(window.started_at) < self.config.window);
+    }
+
+    let ip_retry_after = ip.and_then(|ip| {
+      self.hit_key(
+        &mut windows,
+        format!("ip:{}", ip),
+        self.config.max_attempts_per_ip,
+        now,
+      )
+    });
+    let account_retry_after = self.hit_key(
+      &mut windows,
+      format!("account:{}", email.to_lowercase()),
+      self.config.max_attempts_per_account,
+      now,
+    );
+
+    ip_retry_after.max(account_retry_after)
+  }
+
+  fn hit_key(
+    &self,
+    windows: &mut HashMap<String, AttemptWindow>,
+    key: String,
+    max_attempts: u32,
+    now: Instant,
+  ) -> Option<Duration> {
+    let window = windows.entry(key).or_insert(AttemptWindow {
+      started_at: now,
+      attempts: 0,
+    });
+    if now.duration_since(window.started_at) >= self.config.window {
+      *window = AttemptWindow {
+        started_at: now,
+        attempts: 0,
+      };
+    }
+
+    window.attempts += 1;
+    if window.attempts > max_attempts {
+      Some(self.config.window - now.duration_since(window.started_at))
+    } else {
+      None
+    }
+  }
+
+  /// Forgets the attempts made against an account, after someone signs into it successfully
+  pub fn reset_account(&self, email: &str) {
+    self
+      .windows
+      .lock()
+      .unwrap()
+      .remove(&format!("account:{}", email.to_lowercase()));
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockoutConfig {
+  pub maximum_attempts: i32,
+  pub unlock_in: chrono::Duration,
+}
+
+impl LockoutConfig {
+  /// Reads LOCKOUT_MAXIMUM_ATTEMPTS (default 20) and LOCKOUT_UNLOCK_IN_MINUTES (default 60), the
+  /// same defaults as Devise's lockable module
+  pub fn from_env() -> Self {
+    Self {
+      maximum_attempts: env_or("LOCKOUT_MAXIMUM_ATTEMPTS", 20),
+      unlock_in: chrono::Duration::minutes(env_or("LOCKOUT_UNLOCK_IN_MINUTES", 60)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn throttle() -> SignInThrottle {
+    SignInThrottle::new(SignInThrottleConfig {
+      max_attempts_per_ip: 3,
+      max_attempts_per_account: 2,
+      window: Duration::from_secs(60),
+    })
+  }
+
+  #[test]
+  fn throttles_repeated_attempts_on_one_account() {
+    let throttle = throttle();
+    let now = Instant::now();
+    assert_eq!(throttle.hit_at(Some("1.1.1.1"), "a@example.com", now), None);
+    assert_eq!(throttle.hit_at(Some("2.2.2.2"), "A@example.com", now), None);
+    assert_eq!(
+      throttle.hit_at(
+        Some("3.3.3.3"),
+        "a@example.com",
+        now + Duration::from_secs(15)
+      ),
+      Some(Duration::from_secs(45))
+    );
+    assert_eq!(
+      throttle.hit_at(
+        Some("3.3.3.3"),
+        "a@example.com",
+        now + Duration::from_secs(60)
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn throttles_repeated_attempts_from_one_ip() {
+    let throttle = throttle();
+    let now = Instant::now();
+    for email in ["a@example.com", "b@example.com", "c@example.com"] {
+      assert_eq!(throttle.hit_at(Some("1.1.1.1"), email, now), None);
+    }
+    assert!(throttle
+      .hit_at(Some("1.1.1.1"), "d@example.com", now)
+      .is_some());
+    assert_eq!(throttle.hit_at(Some("2.2.2.2"), "d@example.com", now), None);
+  }
+
+  #[test]
+  fn successful_sign_in_resets_account_counter() {
+    let throttle = throttle();
+    let now = Instant::now();
+    throttle.hit_at(None, "a@example.com", now);
+    throttle.hit_at(None, "a@example.com", now);
+    throttle.reset_account("a@example.com");
+    assert_eq!(throttle.hit_at(None, "a@example.com", now), None);
+  }
+}
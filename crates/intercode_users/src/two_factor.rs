@@ -0,0 +1,419 @@
+//! TOTP two-factor authentication (RFC 6238) with the same parameters authenticator apps
+//! default to: HMAC-SHA1, 30 second steps and 6 digit codes.  Secrets are stored encrypted with a
+//! key derived from SECRET_KEY_BASE, and recovery codes are stored as digests.
+
+use std::collections::HashSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use intercode_entities::{
+  conventions, otp_recovery_codes, permissions, root_sites, staff_positions, users,
+};
+use intercode_policies::user_permission_scope;
+use rand::{Rng, RngCore};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use sea_orm::{
+  sea_query::Expr, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+  QueryFilter,
+};
+use serde_json::Value;
+use sha1::Sha1;
+
+use crate::devise_tokens::DeviseTokenGenerator;
+
+pub const OTP_ISSUER: &str = "Intercode";
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const TIME_STEP_SECONDS: i64 = 30;
+const CODE_DIGITS: u32 = 6;
+// how many steps either side of now we accept, to allow for clock drift
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const SECRET_BYTES: usize = 20;
+const ENCRYPTION_KEY_SALT: &str = "intercode otp_secret";
+const RECOVERY_CODE_DIGEST_COLUMN: &str = "otp_recovery_code";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, which is what otpauth:// URIs expect
+pub fn base32_encode(bytes: &[u8]) -> String {
+  let mut output = String::with_capacity((bytes.len() * 8 + 4) / 5);
+  let mut buffer: u32 = 0;
+  let mut bits = 0;
+
+  for byte in bytes {
+    buffer = (buffer << 8) | u32::from(*byte);
+    bits += 8;
+    while bits >= 5 {
+      bits -= 5;
+      output.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+    }
+  }
+
+  if bits > 0 {
+    output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+  }
+
+  output
+}
+
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+  let mut output = Vec::with_capacity(encoded.len() * 5 / 8);
+  let mut buffer: u32 = 0;
+  let mut bits = 0;
+
+  for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+    let value = BASE32_ALPHABET
+      .iter()
+      .position(|letter| *letter as char == c.to_ascii_uppercase())?;
+    buffer = (buffer << 5) | value as u32;
+    bits += 5;
+    if bits >= 8 {
+      bits -= 8;
+      output.push((buffer >> bits) as u8);
+    }
+  }
+
+  Some(output)
+}
+
+/// Generates a new random secret, base32-encoded
+pub fn generate_otp_secret() -> String {
+  let mut bytes = [0u8; SECRET_BYTES];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  base32_encode(&bytes)
+}
+
+/// RFC 4226 HOTP
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+  let mut mac = Hmac::<Sha1>::new_from_slice(secret).unwrap();
+  mac.update(&counter.to_be_bytes());
+  let hash = mac.finalize().into_bytes();
+  let offset = (hash[hash.len() - 1] & 0xf) as usize;
+  let truncated = u32::from_be_bytes([
+    hash[offset] & 0x7f,
+    hash[offset + 1],
+    hash[offset + 2],
+    hash[offset + 3],
+  ]);
+  truncated % 10_u32.pow(CODE_DIGITS)
+}
+
+pub fn totp_timestep(unix_time: i64) -> i64 {
+  unix_time.div_euclid(TIME_STEP_SECONDS)
+}
+
+fn format_code(code: u32) -> String {
+  format!("{:0width$}", code, width = CODE_DIGITS as usize)
+}
+
+/// Checks a code against a secret.  Returns the timestep the code was for, so that the caller can
+/// record it and refuse the same code (or an older one) next time.
+pub fn verify_totp(
+  secret: &str,
+  code: &str,
+  unix_time: i64,
+  last_consumed_timestep: Option<i64>,
+) -> Option<i64> {
+  let code = code
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<String>();
+  if code.len() != CODE_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  let secret = base32_decode(secret)?;
+  let current_timestep = totp_timestep(unix_time);
+
+  (current_timestep - ALLOWED_DRIFT_STEPS..=current_timestep + ALLOWED_DRIFT_STEPS)
+    .filter(|timestep| *timestep >= 0)
+    .filter(|timestep| last_consumed_timestep.map_or(true, |last| *timestep > last))
+    .find(|timestep| format_code(hotp(&secret, *timestep as u64)) == code)
+}
+
+fn percent_encode(value: &str) -> String {
+  value
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+        (byte as char).to_string()
+      }
+      _ => format!("%{:02X}", byte),
+    })
+    .collect()
+}
+
+/// The otpauth:// URI authenticator apps read out of the enrollment QR code
+pub fn provisioning_uri(secret: &str, account_name: &str) -> String {
+  format!(
+    "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+    percent_encode(OTP_ISSUER),
+    percent_encode(account_name),
+    secret,
+    percent_encode(OTP_ISSUER),
+    CODE_DIGITS,
+    TIME_STEP_SECONDS
+  )
+}
+
+fn encryption_key(token_generator: &DeviseTokenGenerator) -> LessSafeKey {
+  let key = token_generator.derive_key(ENCRYPTION_KEY_SALT);
+  LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key[0..32]).unwrap())
+}
+
+/// Encrypts a secret for storage in users.otp_secret_ciphertext as base64(nonce || ciphertext)
+pub fn encrypt_otp_secret(
+  token_generator: &DeviseTokenGenerator,
+  secret: &str,
+) -> Result<String, String> {
+  let mut nonce = [0u8; NONCE_LEN];
+  rand::thread_rng().fill_bytes(&mut nonce);
+  let mut in_out = secret.as_bytes().to_vec();
+  encryption_key(token_generator)
+    .seal_in_place_append_tag(
+      Nonce::assume_unique_for_key(nonce),
+      Aad::empty(),
+      &mut in_out,
+    )
+    .map_err(|err| err.to_string())?;
+
+  Ok(STANDARD.encode([nonce.as_slice(), &in_out].concat()))
+}
+
+pub fn decrypt_otp_secret(
+  token_generator: &DeviseTokenGenerator,
+  ciphertext: &str,
+) -> Result<String, String> {
+  let decoded = STANDARD.decode(ciphertext).map_err(|err| err.to_string())?;
+  if decoded.len() < NONCE_LEN {
+    return Err("OTP secret ciphertext is too short".to_string());
+  }
+  let (nonce, sealed) = decoded.split_at(NONCE_LEN);
+  let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|err| err.to_string())?;
+  let mut in_out = sealed.to_vec();
+  let plaintext = encryption_key(token_generator)
+    .open_in_place(nonce, Aad::empty(), &mut in_out)
+    .map_err(|err| err.to_string())?;
+
+  String::from_utf8(plaintext.to_vec()).map_err(|err| err.to_string())
+}
+
+/// Recovery codes look like "k3j9-x8q2-m4n7-p5w1"; they're compared ignoring case and dashes
+pub fn generate_recovery_code() -> String {
+  const ALPHABET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+  let mut rng = rand::thread_rng();
+  (0..4)
+    .map(|_| {
+      (0..4)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect::<String>()
+    })
+    .collect::<Vec<_>>()
+    .join("-")
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+  code
+    .chars()
+    .filter(|c| c.is_ascii_alphanumeric())
+    .map(|c| c.to_ascii_lowercase())
+    .collect()
+}
+
+pub fn recovery_code_digest(token_generator: &DeviseTokenGenerator, code: &str) -> String {
+  token_generator.digest(RECOVERY_CODE_DIGEST_COLUMN, &normalize_recovery_code(code))
+}
+
+/// Replaces a user's recovery codes with a fresh set, returning the new codes.  This is the only
+/// time they're available in the clear.
+pub async fn regenerate_recovery_codes<C: ConnectionTrait>(
+  db: &C,
+  token_generator: &DeviseTokenGenerator,
+  user_id: i64,
+  now: NaiveDateTime,
+) -> Result<Vec<String>, DbErr> {
+  otp_recovery_codes::Entity::delete_many()
+    .filter(otp_recovery_codes::Column::UserId.eq(user_id))
+    .exec(db)
+    .await?;
+
+  let codes = (0..RECOVERY_CODE_COUNT)
+    .map(|_| generate_recovery_code())
+    .collect::<Vec<_>>();
+  otp_recovery_codes::Entity::insert_many(codes.iter().map(|code| {
+    otp_recovery_codes::ActiveModel {
+      user_id: ActiveValue::Set(user_id),
+      code_digest: ActiveValue::Set(recovery_code_digest(token_generator, code)),
+      created_at: ActiveValue::Set(now),
+      updated_at: ActiveValue::Set(now),
+      ..Default::default()
+    }
+  }))
+  .exec(db)
+  .await?;
+
+  Ok(codes)
+}
+
+/// Uses up one of a user's recovery codes, if the given code is one of them and hasn't been used.
+/// The check and the update are one statement, so two requests racing to use the same code can't
+/// both succeed.
+pub async fn consume_recovery_code<C: ConnectionTrait>(
+  db: &C,
+  token_generator: &DeviseTokenGenerator,
+  user_id: i64,
+  code: &str,
+  now: NaiveDateTime,
+) -> Result<bool, DbErr> {
+  let result = otp_recovery_codes::Entity::update_many()
+    .col_expr(otp_recovery_codes::Column::UsedAt, Expr::value(now))
+    .col_expr(otp_recovery_codes::Column::UpdatedAt, Expr::value(now))
+    .filter(otp_recovery_codes::Column::UserId.eq(user_id))
+    .filter(otp_recovery_codes::Column::CodeDigest.eq(recovery_code_digest(token_generator, code)))
+    .filter(otp_recovery_codes::Column::UsedAt.is_null())
+    .exec(db)
+    .await?;
+
+  Ok(result.rows_affected > 0)
+}
+
+/// Records that a TOTP code from the given timestep has been used, so that it (and anything
+/// earlier) can't be used again.  Returns false if that timestep or a later one was already
+/// used, which means someone else got there first.
+pub async fn consume_otp_timestep<C: ConnectionTrait>(
+  db: &C,
+  user_id: i64,
+  timestep: i64,
+) -> Result<bool, DbErr> {
+  let result = users::Entity::update_many()
+    .col_expr(users::Column::OtpConsumedTimestep, Expr::value(timestep))
+    .filter(users::Column::Id.eq(user_id))
+    .filter(
+      Condition::any()
+        .add(users::Column::OtpConsumedTimestep.is_null())
+        .add(users::Column::OtpConsumedTimestep.lt(timestep)),
+    )
+    .exec(db)
+    .await?;
+
+  Ok(result.rows_affected == 1)
+}
+
+fn permission_names(value: &Value) -> HashSet<&str> {
+  value
+    .as_array()
+    .map(|permissions| permissions.iter().filter_map(Value::as_str).collect())
+    .unwrap_or_default()
+}
+
+/// Whether a user has to use two-factor authentication to sign in.  That's the case if they're a
+/// site admin and the root site requires it of site admins, if they hold any permission the root
+/// site lists, or if they hold a permission that the convention it applies to lists.
+pub async fn two_factor_required<C: ConnectionTrait>(
+  db: &C,
+  user: &users::Model,
+) -> Result<bool, DbErr> {
+  let root_site = root_sites::Entity::find().one(db).await?;
+  if let Some(root_site) = &root_site {
+    if root_site.two_factor_required_for_site_admins && user.site_admin == Some(true) {
+      return Ok(true);
+    }
+  }
+
+  let user_permissions = user_permission_scope(Some(user.id)).all(db).await?;
+  if user_permissions.is_empty() {
+    return Ok(false);
+  }
+
+  let site_wide_permissions = root_site
+    .as_ref()
+    .map(|root_site| permission_names(&root_site.two_factor_required_permissions))
+    .unwrap_or_default();
+  if user_permissions
+    .iter()
+    .any(|permission| site_wide_permissions.contains(permission.permission.as_str()))
+  {
+    return Ok(true);
+  }
+
+  let staff_position_ids = user_permissions
+    .iter()
+    .filter_map(|permission| permission.staff_position_id)
+    .collect::<HashSet<_>>();
+  let staff_positions = staff_positions::Entity::find()
+    .filter(staff_positions::Column::Id.is_in(staff_position_ids))
+    .all(db)
+    .await?;
+  let convention_id_for_permission = |permission: &permissions::Model| {
+    permission.convention_id.or_else(|| {
+      staff_positions
+        .iter()
+        .find(|staff_position| Some(staff_position.id) == permission.staff_position_id)
+        .and_then(|staff_position| staff_position.convention_id)
+    })
+  };
+
+  let convention_ids = user_permissions
+    .iter()
+    .filter_map(convention_id_for_permission)
+    .collect::<HashSet<_>>();
+  let conventions = conventions::Entity::find()
+    .filter(conventions::Column::Id.is_in(convention_ids))
+    .all(db)
+    .await?;
+
+  Ok(user_permissions.iter().any(|permission| {
+    convention_id_for_permission(permission)
+      .and_then(|convention_id| {
+        conventions
+          .iter()
+          .find(|convention| convention.id == convention_id)
+      })
+      .map(|convention| {
+        permission_names(&convention.two_factor_required_permissions)
+          .contains(permission.permission.as_str())
+      })
+      .unwrap_or(false)
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // RFC 6238 appendix B's SHA1 secret
+  const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+  #[test]
+  fn generates_rfc_6238_codes() {
+    let secret = base32_encode(RFC_SECRET);
+    for (time, code) in [
+      (59, "287082"),
+      (1111111109, "081804"),
+      (1111111111, "050471"),
+      (1234567890, "005924"),
+      (2000000000, "279037"),
+    ] {
+      assert_eq!(
+        verify_totp(&secret, code, time, None),
+        Some(totp_timestep(time))
+      );
+    }
+  }
+
+  #[test]
+  fn allows_one_step_of_drift_and_refuses_replays() {
+    let secret = base32_encode(RFC_SECRET);
+    assert_eq!(verify_totp(&secret, "287082", 59 + 30, None), Some(1));
+    assert_eq!(verify_totp(&secret, "287082", 59 + 60, None), None);
+    assert_eq!(verify_totp(&secret, "287082", 59, Some(1)), None);
+    assert_eq!(verify_totp(&secret, "28708", 59, None), None);
+  }
+
+  #[test]
+  fn round_trips_base32() {
+    assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+    assert_eq!(base32_decode("mzxw 6ytb oi======").unwrap(), b"foobar");
+    assert!(base32_decode("not base32!").is_none());
+  }
+}
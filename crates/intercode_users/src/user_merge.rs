@@ -0,0 +1,630 @@
+//! Merging duplicate user accounts.  Everything that belongs to the losing user (and to their
+//! profiles) gets re-pointed at the winning user, and then the losing user is deleted.  When both
+//! users have a profile in the same convention, a [ProfileConflictStrategy] decides which one
+//! survives.
+//!
+//! A merge always runs in its own transaction; dry runs do all the same work and then roll it
+//! back, so the report they produce is exactly what a real merge would do.
+
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  fmt::Display,
+  str::FromStr,
+};
+
+use async_graphql::Enum;
+use intercode_entities::{conventions, signups, tickets, user_con_profiles, users};
+use intercode_full_text_search::search_index::{
+  reindex_records, remove_from_search_index, SearchableType,
+};
+use sea_orm::{
+  ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Statement, TransactionTrait,
+};
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "MergeUsersProfileConflictStrategy")]
+pub enum ProfileConflictStrategy {
+  /// Refuse to merge users who both have a profile in the same convention
+  #[graphql(name = "abort")]
+  Abort,
+  /// Keep the winning user's profile and move everything from the losing user's profile onto it
+  #[graphql(name = "keep_winning_profile")]
+  KeepWinningProfile,
+  /// Keep the losing user's profile (reassigned to the winning user) and move everything from the
+  /// winning user's profile onto it
+  #[graphql(name = "keep_losing_profile")]
+  KeepLosingProfile,
+}
+
+impl ProfileConflictStrategy {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ProfileConflictStrategy::Abort => "abort",
+      ProfileConflictStrategy::KeepWinningProfile => "keep_winning_profile",
+      ProfileConflictStrategy::KeepLosingProfile => "keep_losing_profile",
+    }
+  }
+}
+
+impl FromStr for ProfileConflictStrategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.replace('-', "_").as_str() {
+      "abort" => Ok(ProfileConflictStrategy::Abort),
+      "keep_winning_profile" => Ok(ProfileConflictStrategy::KeepWinningProfile),
+      "keep_losing_profile" => Ok(ProfileConflictStrategy::KeepLosingProfile),
+      _ => Err(format!(
+        "Unknown profile conflict strategy {}; expected abort, keep_winning_profile or \
+         keep_losing_profile",
+        s
+      )),
+    }
+  }
+}
+
+/// A column that points at a user or a profile.  If rows are only allowed to appear once per
+/// owner for some other column (e.g. one rating per event), that column is `unique_with`, and the
+/// losing side's duplicates are deleted rather than moved.
+struct Reference {
+  table: &'static str,
+  column: &'static str,
+  unique_with: Option<&'static str>,
+}
+
+const fn reference(table: &'static str, column: &'static str) -> Reference {
+  Reference {
+    table,
+    column,
+    unique_with: None,
+  }
+}
+
+const fn unique_reference(
+  table: &'static str,
+  column: &'static str,
+  unique_with: &'static str,
+) -> Reference {
+  Reference {
+    table,
+    column,
+    unique_with: Some(unique_with),
+  }
+}
+
+const PROFILE_REFERENCES: &[Reference] = &[
+  reference("assumed_identity_sessions", "assumed_profile_id"),
+  reference("assumed_identity_sessions", "assumer_profile_id"),
+  reference("bulk_email_campaigns", "sender_user_con_profile_id"),
+  reference("event_proposals", "owner_id"),
+  unique_reference("event_ratings", "user_con_profile_id", "event_id"),
+  reference("form_response_changes", "user_con_profile_id"),
+  reference("notification_destinations", "user_con_profile_id"),
+  reference("notification_digest_items", "user_con_profile_id"),
+  unique_reference(
+    "notification_preferences",
+    "user_con_profile_id",
+    "event_key",
+  ),
+  reference("orders", "user_con_profile_id"),
+  reference("outbox_emails", "user_con_profile_id"),
+  reference("signup_changes", "user_con_profile_id"),
+  reference("signup_requests", "user_con_profile_id"),
+  reference("signups", "user_con_profile_id"),
+  unique_reference(
+    "staff_positions_user_con_profiles",
+    "user_con_profile_id",
+    "staff_position_id",
+  ),
+  unique_reference("team_members", "user_con_profile_id", "event_id"),
+  reference("tickets", "user_con_profile_id"),
+];
+
+const USER_REFERENCES: &[Reference] = &[
+  reference("ahoy_events", "user_id"),
+  reference("ahoy_visits", "user_id"),
+  reference("cms_content_revisions", "author_id"),
+  reference("cms_files", "uploader_id"),
+  reference("conventions", "updated_by_id"),
+  reference("events", "owner_id"),
+  reference("events", "updated_by_id"),
+  unique_reference(
+    "organization_roles_users",
+    "user_id",
+    "organization_role_id",
+  ),
+  reference("personal_data_requests", "user_id"),
+  reference("runs", "updated_by_id"),
+  reference("signup_changes", "updated_by_id"),
+  reference("signup_requests", "updated_by_id"),
+  reference("signups", "updated_by_id"),
+  reference("team_members", "updated_by_id"),
+  reference("user_activity_alerts", "user_id"),
+  reference("user_con_profiles", "user_id"),
+];
+
+// Credentials are tied to the account they were issued for, so they go away with it instead of
+// being handed to the winner
+const USER_CREDENTIALS: &[Reference] = &[
+  reference("oauth_access_grants", "resource_owner_id"),
+  reference("oauth_access_tokens", "resource_owner_id"),
+  reference("otp_recovery_codes", "user_id"),
+  reference("personal_access_tokens", "user_id"),
+];
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MergeUsersChangeKind {
+  /// Rows that now point at the winning user or the kept profile
+  #[graphql(name = "reassigned")]
+  Reassigned,
+  /// Rows that were deleted, either because they duplicated something the winner already had or
+  /// because they can't be carried over
+  #[graphql(name = "deleted")]
+  Deleted,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeUsersChange {
+  pub table: &'static str,
+  pub column: &'static str,
+  pub kind: MergeUsersChangeKind,
+  pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeUsersProfileResolution {
+  pub convention_id: i64,
+  pub convention_name: Option<String>,
+  pub kept_profile_id: i64,
+  /// Set when both users had a profile in this convention
+  pub removed_profile_id: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeUsersReport {
+  pub winning_user_id: i64,
+  pub losing_user_id: i64,
+  pub dry_run: bool,
+  /// Whether the merge actually happened: false for dry runs and for merges with blockers
+  pub merged: bool,
+  pub profiles: Vec<MergeUsersProfileResolution>,
+  pub changes: Vec<MergeUsersChange>,
+  /// Reasons the merge can't go ahead.  If there are any, nothing is changed.
+  pub blockers: Vec<String>,
+}
+
+impl Display for MergeUsersReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let verb = if self.merged { "Merged" } else { "Would merge" };
+    writeln!(
+      f,
+      "{} user {} into user {}",
+      verb, self.losing_user_id, self.winning_user_id
+    )?;
+
+    if !self.blockers.is_empty() {
+      writeln!(f, "\nCan't merge until these are resolved:")?;
+      for blocker in &self.blockers {
+        writeln!(f, "  - {}", blocker)?;
+      }
+      return Ok(());
+    }
+
+    if !self.profiles.is_empty() {
+      writeln!(f, "\nProfiles:")?;
+      for profile in &self.profiles {
+        let convention = profile
+          .convention_name
+          .clone()
+          .unwrap_or_else(|| format!("convention {}", profile.convention_id));
+        match profile.removed_profile_id {
+          Some(removed_profile_id) => writeln!(
+            f,
+            "  {}: keep profile {}, fold in profile {}",
+            convention, profile.kept_profile_id, removed_profile_id
+          )?,
+          None => writeln!(
+            f,
+            "  {}: move profile {}",
+            convention, profile.kept_profile_id
+          )?,
+        }
+      }
+    }
+
+    writeln!(f, "\nChanges:")?;
+    for change in &self.changes {
+      let action = match change.kind {
+        MergeUsersChangeKind::Reassigned => "reassign",
+        MergeUsersChangeKind::Deleted => "delete",
+      };
+      writeln!(
+        f,
+        "  {} {} {}.{}",
+        action, change.count, change.table, change.column
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct ChangeCounter(BTreeMap<(&'static str, &'static str, MergeUsersChangeKind), u64>);
+
+impl ChangeCounter {
+  fn record(&mut self, reference: &Reference, kind: MergeUsersChangeKind, count: u64) {
+    if count > 0 {
+      *self
+        .0
+        .entry((reference.table, reference.column, kind))
+        .or_default() += count;
+    }
+  }
+
+  fn into_changes(self) -> Vec<MergeUsersChange> {
+    self
+      .0
+      .into_iter()
+      .map(|((table, column, kind), count)| MergeUsersChange {
+        table,
+        column,
+        kind,
+        count,
+      })
+      .collect()
+  }
+}
+
+async fn execute<C: ConnectionTrait>(
+  db: &C,
+  sql: String,
+  from: i64,
+  to: i64,
+) -> Result<u64, DbErr> {
+  db.execute(Statement::from_sql_and_values(
+    db.get_database_backend(),
+    &sql,
+    [from.into(), to.into()],
+  ))
+  .await
+  .map(|result| result.rows_affected())
+}
+
+async fn reassign<C: ConnectionTrait>(
+  db: &C,
+  reference: &Reference,
+  from: i64,
+  to: i64,
+  counter: &mut ChangeCounter,
+) -> Result<(), DbErr> {
+  let Reference {
+    table,
+    column,
+    unique_with,
+  } = reference;
+
+  if let Some(key) = unique_with {
+    let deleted = execute(
+      db,
+      format!(
+        "DELETE FROM {table} WHERE {column} = $1 AND {key} IN \
+         (SELECT {key} FROM {table} WHERE {column} = $2)"
+      ),
+      from,
+      to,
+    )
+    .await?;
+    counter.record(reference, MergeUsersChangeKind::Deleted, deleted);
+  }
+
+  let reassigned = execute(
+    db,
+    format!("UPDATE {table} SET {column} = $2 WHERE {column} = $1"),
+    from,
+    to,
+  )
+  .await?;
+  counter.record(reference, MergeUsersChangeKind::Reassigned, reassigned);
+
+  Ok(())
+}
+
+async fn delete_owned<C: ConnectionTrait>(
+  db: &C,
+  reference: &Reference,
+  owner_id: i64,
+  counter: &mut ChangeCounter,
+) -> Result<(), DbErr> {
+  let Reference { table, column, .. } = reference;
+  let deleted = db
+    .execute(Statement::from_sql_and_values(
+      db.get_database_backend(),
+      &format!("DELETE FROM {table} WHERE {column} = $1"),
+      [owner_id.into()],
+    ))
+    .await?
+    .rows_affected();
+  counter.record(reference, MergeUsersChangeKind::Deleted, deleted);
+
+  Ok(())
+}
+
+/// OpenID Connect nonces hang off authorization grants without cascading deletes, so they have to
+/// go before the grants they belong to can be deleted
+pub(crate) async fn delete_openid_requests_for_grants_of<C: ConnectionTrait>(
+  db: &C,
+  resource_owner_id: i64,
+) -> Result<u64, DbErr> {
+  db.execute(Statement::from_sql_and_values(
+    db.get_database_backend(),
+    "DELETE FROM oauth_openid_requests WHERE access_grant_id IN \
+     (SELECT id FROM oauth_access_grants WHERE resource_owner_id = $1)",
+    [resource_owner_id.into()],
+  ))
+  .await
+  .map(|result| result.rows_affected())
+}
+
+/// Things that would make merging two profiles in the same convention lose or double up data
+async fn profile_conflict_blockers<C: ConnectionTrait>(
+  db: &C,
+  convention_name: &str,
+  kept: &user_con_profiles::Model,
+  removed: &user_con_profiles::Model,
+) -> Result<Vec<String>, DbErr> {
+  let mut blockers = vec![];
+
+  let ticketed_profile_ids = tickets::Entity::find()
+    .filter(tickets::Column::UserConProfileId.is_in([kept.id, removed.id]))
+    .all(db)
+    .await?
+    .into_iter()
+    .filter_map(|ticket| ticket.user_con_profile_id)
+    .collect::<HashSet<_>>();
+  if ticketed_profile_ids.len() > 1 {
+    blockers.push(format!(
+      "Both profiles in {} have tickets; refund or transfer one of them first",
+      convention_name
+    ));
+  }
+
+  let active_signups = signups::Entity::find()
+    .filter(signups::Column::UserConProfileId.is_in([kept.id, removed.id]))
+    .filter(signups::Column::State.ne("withdrawn"))
+    .all(db)
+    .await?;
+  let mut profiles_by_run: HashMap<i64, Vec<i64>> = HashMap::new();
+  for signup in active_signups {
+    profiles_by_run
+      .entry(signup.run_id)
+      .or_default()
+      .push(signup.user_con_profile_id);
+  }
+  let mut doubled_run_ids = profiles_by_run
+    .into_iter()
+    .filter(|(_, profile_ids)| profile_ids.contains(&kept.id) && profile_ids.contains(&removed.id))
+    .map(|(run_id, _)| run_id)
+    .collect::<Vec<_>>();
+  doubled_run_ids.sort();
+  for run_id in doubled_run_ids {
+    blockers.push(format!(
+      "Both profiles in {} are signed up for run {}; withdraw one of the signups first",
+      convention_name, run_id
+    ));
+  }
+
+  Ok(blockers)
+}
+
+async fn perform_merge<C: ConnectionTrait>(
+  db: &C,
+  winning_user: &users::Model,
+  losing_user: &users::Model,
+  strategy: ProfileConflictStrategy,
+  report: &mut MergeUsersReport,
+) -> Result<(), DbErr> {
+  let profiles = user_con_profiles::Entity::find()
+    .filter(user_con_profiles::Column::UserId.is_in([winning_user.id, losing_user.id]))
+    .all(db)
+    .await?;
+  let convention_names = conventions::Entity::find()
+    .filter(conventions::Column::Id.is_in(profiles.iter().map(|profile| profile.convention_id)))
+    .all(db)
+    .await?
+    .into_iter()
+    .map(|convention| (convention.id, convention.name))
+    .collect::<HashMap<_, _>>();
+  let convention_label = |convention_id: i64| {
+    convention_names
+      .get(&convention_id)
+      .cloned()
+      .flatten()
+      .unwrap_or_else(|| format!("convention {}", convention_id))
+  };
+
+  let winning_profiles = profiles
+    .iter()
+    .filter(|profile| profile.user_id == winning_user.id)
+    .map(|profile| (profile.convention_id, profile))
+    .collect::<HashMap<_, _>>();
+
+  // (kept, removed) pairs for conventions where both users have a profile
+  let mut conflicts = vec![];
+  for losing_profile in profiles
+    .iter()
+    .filter(|profile| profile.user_id == losing_user.id)
+  {
+    let convention_id = losing_profile.convention_id;
+    let Some(winning_profile) = winning_profiles.get(&convention_id) else {
+      report.profiles.push(MergeUsersProfileResolution {
+        convention_id,
+        convention_name: convention_names.get(&convention_id).cloned().flatten(),
+        kept_profile_id: losing_profile.id,
+        removed_profile_id: None,
+      });
+      continue;
+    };
+
+    let (kept, removed) = match strategy {
+      ProfileConflictStrategy::Abort => {
+        report.blockers.push(format!(
+          "Both users have a profile in {}",
+          convention_label(convention_id)
+        ));
+        continue;
+      }
+      ProfileConflictStrategy::KeepWinningProfile => (*winning_profile, losing_profile),
+      ProfileConflictStrategy::KeepLosingProfile => (losing_profile, *winning_profile),
+    };
+
+    report.blockers.extend(
+      profile_conflict_blockers(db, &convention_label(convention_id), kept, removed).await?,
+    );
+    report.profiles.push(MergeUsersProfileResolution {
+      convention_id,
+      convention_name: convention_names.get(&convention_id).cloned().flatten(),
+      kept_profile_id: kept.id,
+      removed_profile_id: Some(removed.id),
+    });
+    conflicts.push((kept.id, removed.id));
+  }
+
+  if !report.blockers.is_empty() {
+    return Ok(());
+  }
+
+  let mut counter = ChangeCounter::default();
+  let profile_row = reference("user_con_profiles", "id");
+  for (kept_id, removed_id) in conflicts {
+    for profile_reference in PROFILE_REFERENCES {
+      reassign(db, profile_reference, removed_id, kept_id, &mut counter).await?;
+    }
+    delete_owned(db, &profile_row, removed_id, &mut counter).await?;
+  }
+
+  for user_reference in USER_REFERENCES {
+    reassign(
+      db,
+      user_reference,
+      losing_user.id,
+      winning_user.id,
+      &mut counter,
+    )
+    .await?;
+  }
+  let deleted_openid_requests = delete_openid_requests_for_grants_of(db, losing_user.id).await?;
+  counter.record(
+    &reference("oauth_openid_requests", "access_grant_id"),
+    MergeUsersChangeKind::Deleted,
+    deleted_openid_requests,
+  );
+  for credential in USER_CREDENTIALS {
+    delete_owned(db, credential, losing_user.id, &mut counter).await?;
+  }
+  delete_owned(db, &reference("users", "id"), losing_user.id, &mut counter).await?;
+
+  // removed profiles have to leave the search index, and kept ones are now found by a different
+  // user's email address
+  remove_from_search_index(
+    db,
+    SearchableType::UserConProfile,
+    report
+      .profiles
+      .iter()
+      .filter_map(|resolution| resolution.removed_profile_id),
+  )
+  .await?;
+  reindex_records(
+    db,
+    SearchableType::UserConProfile,
+    report
+      .profiles
+      .iter()
+      .map(|resolution| resolution.kept_profile_id)
+      .collect(),
+  )
+  .await?;
+
+  report.changes = counter.into_changes();
+  Ok(())
+}
+
+async fn find_user<C: ConnectionTrait>(db: &C, id: i64) -> Result<users::Model, DbErr> {
+  users::Entity::find_by_id(id)
+    .one(db)
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound(format!("User {} not found", id)))
+}
+
+/// Merges the losing user into the winning one.  With `dry_run`, reports what would happen
+/// without changing anything.  Merges with blockers don't change anything either, dry run or not.
+pub async fn merge_users<C: ConnectionTrait + TransactionTrait>(
+  db: &C,
+  winning_user_id: i64,
+  losing_user_id: i64,
+  strategy: ProfileConflictStrategy,
+  dry_run: bool,
+) -> Result<MergeUsersReport, DbErr> {
+  if winning_user_id == losing_user_id {
+    return Err(DbErr::Custom(
+      "Can't merge a user into themselves".to_string(),
+    ));
+  }
+
+  let txn = db.begin().await?;
+  let winning_user = find_user(&txn, winning_user_id).await?;
+  let losing_user = find_user(&txn, losing_user_id).await?;
+
+  let mut report = MergeUsersReport {
+    winning_user_id,
+    losing_user_id,
+    dry_run,
+    merged: false,
+    profiles: vec![],
+    changes: vec![],
+    blockers: vec![],
+  };
+  perform_merge(&txn, &winning_user, &losing_user, strategy, &mut report).await?;
+
+  if dry_run || !report.blockers.is_empty() {
+    txn.rollback().await?;
+  } else {
+    txn.commit().await?;
+    report.merged = true;
+  }
+
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_strategies_from_the_command_line() {
+    assert_eq!(
+      "keep-winning-profile".parse::<ProfileConflictStrategy>(),
+      Ok(ProfileConflictStrategy::KeepWinningProfile)
+    );
+    assert_eq!(
+      "keep_losing_profile".parse::<ProfileConflictStrategy>(),
+      Ok(ProfileConflictStrategy::KeepLosingProfile)
+    );
+    assert!("keep-both".parse::<ProfileConflictStrategy>().is_err());
+  }
+
+  #[test]
+  fn change_counter_combines_and_skips_empty_changes() {
+    let mut counter = ChangeCounter::default();
+    let signups = reference("signups", "user_con_profile_id");
+    counter.record(&signups, MergeUsersChangeKind::Reassigned, 2);
+    counter.record(&signups, MergeUsersChangeKind::Reassigned, 3);
+    counter.record(&signups, MergeUsersChangeKind::Deleted, 0);
+
+    let changes = counter.into_changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, MergeUsersChangeKind::Reassigned);
+    assert_eq!(changes[0].count, 5);
+  }
+}
@@ -12,6 +12,7 @@ pub mod loaders;
 mod model_backed_drop;
 mod optional_value_view;
 pub mod preloaders;
+pub mod render_budget;
 mod renderer;
 mod result_value_view;
 
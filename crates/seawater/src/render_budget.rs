@@ -0,0 +1,360 @@
+use std::{
+  cell::RefCell,
+  env,
+  fmt::Display,
+  io::{self, Write},
+  str::FromStr,
+  sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// Limits on how much work a single Liquid render may do.  Templates are written by CMS authors,
+/// so a render that goes over budget fails with an error explaining which limit it hit rather
+/// than tying up a worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderBudget {
+  pub max_wall_time: Duration,
+  pub max_output_bytes: u64,
+  /// Loop work is metered in steps: every pass through a `for` or `tablerow` body counts, as does
+  /// every element rendered inside one, so loops with heavy bodies run out sooner
+  pub max_loop_iterations: u64,
+  pub max_graphql_queries: u32,
+  pub max_include_depth: u32,
+}
+
+impl Default for RenderBudget {
+  fn default() -> Self {
+    Self {
+      max_wall_time: Duration::from_secs(10),
+      max_output_bytes: 5 * 1024 * 1024,
+      max_loop_iterations: 1_000_000,
+      max_graphql_queries: 25,
+      max_include_depth: 20,
+    }
+  }
+}
+
+fn env_limit<T: FromStr>(name: &str) -> Option<T> {
+  env::var(name)
+    .ok()
+    .and_then(|value| value.trim().parse().ok())
+}
+
+impl RenderBudget {
+  /// The default budget, with any limits overridden by LIQUID_RENDER_MAX_SECONDS,
+  /// LIQUID_RENDER_MAX_OUTPUT_BYTES, LIQUID_RENDER_MAX_LOOP_ITERATIONS,
+  /// LIQUID_RENDER_MAX_GRAPHQL_QUERIES and LIQUID_RENDER_MAX_INCLUDE_DEPTH
+  pub fn from_env() -> Self {
+    let default = Self::default();
+
+    Self {
+      max_wall_time: env_limit("LIQUID_RENDER_MAX_SECONDS")
+        .map(Duration::from_secs)
+        .unwrap_or(default.max_wall_time),
+      max_output_bytes: env_limit("LIQUID_RENDER_MAX_OUTPUT_BYTES")
+        .unwrap_or(default.max_output_bytes),
+      max_loop_iterations: env_limit("LIQUID_RENDER_MAX_LOOP_ITERATIONS")
+        .unwrap_or(default.max_loop_iterations),
+      max_graphql_queries: env_limit("LIQUID_RENDER_MAX_GRAPHQL_QUERIES")
+        .unwrap_or(default.max_graphql_queries),
+      max_include_depth: env_limit("LIQUID_RENDER_MAX_INCLUDE_DEPTH")
+        .unwrap_or(default.max_include_depth),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderBudgetExceeded {
+  WallTime(Duration),
+  OutputSize(u64),
+  LoopIterations(u64),
+  GraphQLQueries(u32),
+  IncludeDepth(u32),
+}
+
+impl Display for RenderBudgetExceeded {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Liquid render budget exceeded: ")?;
+    match self {
+      RenderBudgetExceeded::WallTime(limit) => write!(
+        f,
+        "rendering took longer than {:.1} seconds",
+        limit.as_secs_f64()
+      ),
+      RenderBudgetExceeded::OutputSize(limit) => {
+        write!(f, "output was larger than {} bytes", limit)
+      }
+      RenderBudgetExceeded::LoopIterations(limit) => {
+        write!(f, "loops ran for more than {} iterations", limit)
+      }
+      RenderBudgetExceeded::GraphQLQueries(limit) => {
+        write!(f, "more than {} GraphQL queries were run", limit)
+      }
+      RenderBudgetExceeded::IncludeDepth(limit) => {
+        write!(f, "partials were included more than {} levels deep", limit)
+      }
+    }
+  }
+}
+
+impl std::error::Error for RenderBudgetExceeded {}
+
+impl From<RenderBudgetExceeded> for liquid_core::Error {
+  fn from(exceeded: RenderBudgetExceeded) -> Self {
+    liquid_core::Error::with_msg(exceeded.to_string())
+  }
+}
+
+/// Tracks one render's spending against its budget.  The first limit to be exceeded is
+/// remembered, and every charge after that fails with the same error, so a render stops at the
+/// next checkpoint no matter which part of the template notices.
+#[derive(Debug)]
+pub struct RenderBudgetTracker {
+  budget: RenderBudget,
+  started_at: Instant,
+  output_bytes: AtomicU64,
+  loop_iterations: AtomicU64,
+  graphql_queries: AtomicU32,
+  include_depth: AtomicU32,
+  exceeded: Mutex<Option<RenderBudgetExceeded>>,
+}
+
+impl RenderBudgetTracker {
+  pub fn new(budget: RenderBudget) -> Self {
+    Self {
+      budget,
+      started_at: Instant::now(),
+      output_bytes: AtomicU64::new(0),
+      loop_iterations: AtomicU64::new(0),
+      graphql_queries: AtomicU32::new(0),
+      include_depth: AtomicU32::new(0),
+      exceeded: Mutex::new(None),
+    }
+  }
+
+  pub fn budget(&self) -> &RenderBudget {
+    &self.budget
+  }
+
+  pub fn exceeded(&self) -> Option<RenderBudgetExceeded> {
+    self.exceeded.lock().clone()
+  }
+
+  /// Marks the budget as exceeded (keeping the first reason if there already was one) and
+  /// returns the error the render should fail with
+  pub fn exceed(&self, reason: RenderBudgetExceeded) -> RenderBudgetExceeded {
+    self.exceeded.lock().get_or_insert(reason).clone()
+  }
+
+  /// How much longer the render may run
+  pub fn remaining_time(&self) -> Duration {
+    self
+      .budget
+      .max_wall_time
+      .saturating_sub(self.started_at.elapsed())
+  }
+
+  /// Fails if the budget has already been exceeded or the render has run out of time
+  pub fn check(&self) -> Result<(), RenderBudgetExceeded> {
+    if let Some(exceeded) = self.exceeded() {
+      return Err(exceeded);
+    }
+
+    if self.started_at.elapsed() > self.budget.max_wall_time {
+      return Err(self.exceed(RenderBudgetExceeded::WallTime(self.budget.max_wall_time)));
+    }
+
+    Ok(())
+  }
+
+  pub fn charge_output(&self, bytes: usize) -> Result<(), RenderBudgetExceeded> {
+    self.check()?;
+    let total = self.output_bytes.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+    if total > self.budget.max_output_bytes {
+      return Err(self.exceed(RenderBudgetExceeded::OutputSize(
+        self.budget.max_output_bytes,
+      )));
+    }
+    Ok(())
+  }
+
+  pub fn charge_loop_iteration(&self) -> Result<(), RenderBudgetExceeded> {
+    self.check()?;
+    if self.loop_iterations.fetch_add(1, Ordering::Relaxed) + 1 > self.budget.max_loop_iterations {
+      return Err(self.exceed(RenderBudgetExceeded::LoopIterations(
+        self.budget.max_loop_iterations,
+      )));
+    }
+    Ok(())
+  }
+
+  pub fn charge_graphql_query(&self) -> Result<(), RenderBudgetExceeded> {
+    self.check()?;
+    if self.graphql_queries.fetch_add(1, Ordering::Relaxed) + 1 > self.budget.max_graphql_queries {
+      return Err(self.exceed(RenderBudgetExceeded::GraphQLQueries(
+        self.budget.max_graphql_queries,
+      )));
+    }
+    Ok(())
+  }
+
+  /// Records that rendering has gone one partial deeper.  The depth goes back down when the
+  /// returned guard is dropped.
+  pub fn enter_include(self: &Arc<Self>) -> Result<IncludeDepthGuard, RenderBudgetExceeded> {
+    self.check()?;
+    let depth = self.include_depth.fetch_add(1, Ordering::Relaxed) + 1;
+    let guard = IncludeDepthGuard {
+      tracker: self.clone(),
+    };
+    if depth > self.budget.max_include_depth {
+      return Err(self.exceed(RenderBudgetExceeded::IncludeDepth(
+        self.budget.max_include_depth,
+      )));
+    }
+    Ok(guard)
+  }
+}
+
+#[derive(Debug)]
+pub struct IncludeDepthGuard {
+  tracker: Arc<RenderBudgetTracker>,
+}
+
+impl Drop for IncludeDepthGuard {
+  fn drop(&mut self) {
+    self.tracker.include_depth.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+thread_local! {
+  static CURRENT_TRACKER: RefCell<Option<Arc<RenderBudgetTracker>>> = RefCell::new(None);
+}
+
+/// Runs a synchronous render with a budget tracker available to the tags and blocks inside it
+/// via current_render_budget.  Renders happen on a single blocking thread, so a thread-local is
+/// enough to find the right tracker.
+pub fn with_render_budget<T>(tracker: Arc<RenderBudgetTracker>, f: impl FnOnce() -> T) -> T {
+  let previous = CURRENT_TRACKER.with(|current| current.replace(Some(tracker)));
+  let result = f();
+  CURRENT_TRACKER.with(|current| current.replace(previous));
+  result
+}
+
+/// The budget tracker for the render happening on this thread, if there is one
+pub fn current_render_budget() -> Option<Arc<RenderBudgetTracker>> {
+  CURRENT_TRACKER.with(|current| current.borrow().clone())
+}
+
+/// Output for a budgeted render.  Every write is charged against the output limit and checks
+/// the clock, so runaway templates stop as soon as they try to produce more output.
+pub struct BudgetedWriter<'a, W: Write> {
+  inner: W,
+  tracker: &'a RenderBudgetTracker,
+}
+
+impl<'a, W: Write> BudgetedWriter<'a, W> {
+  pub fn new(inner: W, tracker: &'a RenderBudgetTracker) -> Self {
+    Self { inner, tracker }
+  }
+}
+
+impl<'a, W: Write> Write for BudgetedWriter<'a, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self
+      .tracker
+      .charge_output(buf.len())
+      .map_err(|exceeded| io::Error::new(io::ErrorKind::Other, exceeded))?;
+    self.inner.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tracker(budget: RenderBudget) -> Arc<RenderBudgetTracker> {
+    Arc::new(RenderBudgetTracker::new(budget))
+  }
+
+  #[test]
+  fn stops_writing_past_the_output_limit() {
+    let tracker = tracker(RenderBudget {
+      max_output_bytes: 8,
+      ..Default::default()
+    });
+    let mut output = Vec::new();
+    let mut writer = BudgetedWriter::new(&mut output, &tracker);
+
+    assert!(writer.write_all(b"12345678").is_ok());
+    assert!(writer.write_all(b"9").is_err());
+    assert_eq!(
+      tracker.exceeded(),
+      Some(RenderBudgetExceeded::OutputSize(8))
+    );
+  }
+
+  #[test]
+  fn remembers_the_first_limit_exceeded() {
+    let tracker = tracker(RenderBudget {
+      max_graphql_queries: 1,
+      ..Default::default()
+    });
+
+    assert!(tracker.charge_graphql_query().is_ok());
+    assert_eq!(
+      tracker.charge_graphql_query(),
+      Err(RenderBudgetExceeded::GraphQLQueries(1))
+    );
+    assert_eq!(
+      tracker.charge_loop_iteration(),
+      Err(RenderBudgetExceeded::GraphQLQueries(1))
+    );
+  }
+
+  #[test]
+  fn include_depth_unwinds() {
+    let tracker = tracker(RenderBudget {
+      max_include_depth: 2,
+      ..Default::default()
+    });
+
+    {
+      let _outer = tracker.enter_include().unwrap();
+      let _inner = tracker.enter_include().unwrap();
+    }
+    let _outer = tracker.enter_include().unwrap();
+    let _inner = tracker.enter_include().unwrap();
+    assert_eq!(
+      tracker.enter_include().unwrap_err(),
+      RenderBudgetExceeded::IncludeDepth(2)
+    );
+  }
+
+  #[test]
+  fn times_out() {
+    let tracker = tracker(RenderBudget {
+      max_wall_time: Duration::ZERO,
+      ..Default::default()
+    });
+    std::thread::sleep(Duration::from_millis(1));
+
+    assert_eq!(
+      tracker.check(),
+      Err(RenderBudgetExceeded::WallTime(Duration::ZERO))
+    );
+    assert!(current_render_budget().is_none());
+    with_render_budget(tracker.clone(), || {
+      assert!(current_render_budget().is_some());
+    });
+    assert!(current_render_budget().is_none());
+  }
+}
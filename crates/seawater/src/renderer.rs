@@ -1,4 +1,9 @@
-use crate::{Context, DropResult, DropResultTrait, DropStore};
+use crate::{
+  render_budget::{
+    with_render_budget, BudgetedWriter, RenderBudget, RenderBudgetExceeded, RenderBudgetTracker,
+  },
+  Context, DropResult, DropResultTrait, DropStore,
+};
 use liquid::Parser;
 use std::{
   fmt::Debug,
@@ -34,6 +39,7 @@ pub struct Renderer<C: Context, G: liquid::ObjectView + Clone + DropResultTrait<
   parser: Parser,
   build_context: Pin<Box<dyn BuildContextFn<C>>>,
   build_globals: Pin<Box<dyn BuildGlobalsFn<C, G>>>,
+  budget: RenderBudget,
 }
 
 impl<C: Context, G: liquid::ObjectView + Clone + DropResultTrait<G> + 'static> Debug
@@ -42,6 +48,7 @@ impl<C: Context, G: liquid::ObjectView + Clone + DropResultTrait<G> + 'static> D
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("Renderer")
       .field("context_type", &std::any::type_name::<C>())
+      .field("budget", &self.budget)
       .finish_non_exhaustive()
   }
 }
@@ -56,9 +63,15 @@ impl<C: Context, G: liquid::ObjectView + Clone + DropResultTrait<G> + 'static> R
       parser,
       build_context: Box::pin(build_context),
       build_globals: Box::pin(build_globals),
+      budget: RenderBudget::default(),
     }
   }
 
+  pub fn with_budget(mut self, budget: RenderBudget) -> Self {
+    self.budget = budget;
+    self
+  }
+
   pub async fn render_liquid(
     &self,
     content: &str,
@@ -71,17 +84,41 @@ impl<C: Context, G: liquid::ObjectView + Clone + DropResultTrait<G> + 'static> R
 
     let template = self.parser.parse(content)?;
     let store_clone = store.clone();
+    let tracker = Arc::new(RenderBudgetTracker::new(self.budget.clone()));
+    let render_tracker = tracker.clone();
 
-    let result = tokio::task::spawn_blocking(move || {
-      let result = template.render(&globals_with_builtins);
+    let render_handle = tokio::task::spawn_blocking(move || {
+      let mut output = Vec::new();
+      let result = with_render_budget(render_tracker.clone(), || {
+        template.render_to(
+          &mut BudgetedWriter::new(&mut output, &render_tracker),
+          &globals_with_builtins,
+        )
+      });
 
       drop(store_clone);
-      result
-    })
-    .await?;
+      result.map(|_| output)
+    });
+
+    // The render itself checks the clock as it goes, but a template can get stuck somewhere that
+    // never reaches a checkpoint.  Don't make the caller wait for it; once it's flagged as over
+    // budget, it'll stop at its next checkpoint.
+    let result = match tokio::time::timeout(self.budget.max_wall_time, render_handle).await {
+      Ok(result) => result?,
+      Err(_) => {
+        let exceeded = tracker.exceed(RenderBudgetExceeded::WallTime(self.budget.max_wall_time));
+        return Err(async_graphql::Error::new(exceeded.to_string()));
+      }
+    };
+
+    if let Some(exceeded) = tracker.exceeded() {
+      return Err(async_graphql::Error::new(exceeded.to_string()));
+    }
 
     match result {
-      Ok(content) => Ok(content),
+      Ok(output) => {
+        String::from_utf8(output).map_err(|error| async_graphql::Error::new(error.to_string()))
+      }
       Err(error) => Err(async_graphql::Error::new(error.to_string())),
     }
   }
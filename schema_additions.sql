@@ -0,0 +1,380 @@
+-- Tables and columns the Rust backend needs that the Intercode Rails app's migrations don't
+-- create yet.  structure.sql is a dump of the Rails schema, so it doesn't include any of this.
+--
+-- Apply this after loading structure.sql (as CI does for the test database), or to an existing
+-- Intercode database before deploying:
+--
+--   psql "$DATABASE_URL" < schema_additions.sql
+--
+-- It runs in one transaction, so it either applies completely or not at all.  As each of these
+-- changes lands as a Rails migration (and so in structure.sql), remove it from here.
+
+BEGIN;
+
+CREATE TABLE public.bulk_email_campaigns (
+    id bigint NOT NULL,
+    convention_id bigint NOT NULL,
+    sender_user_con_profile_id bigint,
+    mailing_list character varying NOT NULL,
+    mailing_list_params jsonb DEFAULT '{}'::jsonb NOT NULL,
+    subject text NOT NULL,
+    body text NOT NULL,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.bulk_email_campaigns_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.bulk_email_campaigns_id_seq OWNED BY public.bulk_email_campaigns.id;
+
+CREATE TABLE public.cms_content_revisions (
+    id bigint NOT NULL,
+    parent_type character varying,
+    parent_id bigint,
+    content_type character varying NOT NULL,
+    content_id bigint NOT NULL,
+    author_id bigint,
+    snapshot jsonb DEFAULT '{}'::jsonb NOT NULL,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.cms_content_revisions_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.cms_content_revisions_id_seq OWNED BY public.cms_content_revisions.id;
+
+CREATE TABLE public.cms_rendered_outputs (
+    id bigint NOT NULL,
+    cache_key character varying NOT NULL,
+    parent_type character varying,
+    parent_id bigint,
+    content_type character varying NOT NULL,
+    content_id bigint NOT NULL,
+    output text NOT NULL,
+    expires_at timestamp(6) without time zone NOT NULL,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.cms_rendered_outputs_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.cms_rendered_outputs_id_seq OWNED BY public.cms_rendered_outputs.id;
+
+ALTER TABLE public.conventions
+    ADD COLUMN two_factor_required_permissions jsonb DEFAULT '[]'::jsonb NOT NULL;
+
+CREATE TABLE public.inbound_email_deliveries (
+    id bigint NOT NULL,
+    message_key character varying NOT NULL,
+    recipient character varying NOT NULL,
+    forwarded_at timestamp(6) without time zone,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.inbound_email_deliveries_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.inbound_email_deliveries_id_seq OWNED BY public.inbound_email_deliveries.id;
+
+CREATE TABLE public.notification_digest_items (
+    id bigint NOT NULL,
+    user_con_profile_id bigint NOT NULL,
+    event_key character varying NOT NULL,
+    run_id bigint,
+    subject text NOT NULL,
+    body_html text,
+    body_text text,
+    delivered_at timestamp(6) without time zone,
+    outbox_email_id bigint,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.notification_digest_items_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.notification_digest_items_id_seq OWNED BY public.notification_digest_items.id;
+
+CREATE TABLE public.notification_preferences (
+    id bigint NOT NULL,
+    user_con_profile_id bigint NOT NULL,
+    event_key character varying NOT NULL,
+    delivery_mode character varying NOT NULL,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.notification_preferences_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.notification_preferences_id_seq OWNED BY public.notification_preferences.id;
+
+ALTER TABLE public.oauth_access_grants
+    ADD COLUMN code_challenge character varying,
+    ADD COLUMN code_challenge_method character varying;
+
+CREATE TABLE public.otp_recovery_codes (
+    id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    code_digest text NOT NULL,
+    used_at timestamp without time zone,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.otp_recovery_codes_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.otp_recovery_codes_id_seq OWNED BY public.otp_recovery_codes.id;
+
+CREATE TABLE public.outbox_emails (
+    id bigint NOT NULL,
+    from_address text NOT NULL,
+    to_address text NOT NULL,
+    subject text NOT NULL,
+    body_html text,
+    body_text text,
+    status character varying DEFAULT 'pending'::character varying NOT NULL,
+    attempts integer DEFAULT 0 NOT NULL,
+    last_error text,
+    send_after timestamp(6) without time zone,
+    sent_at timestamp(6) without time zone,
+    bulk_email_campaign_id bigint,
+    user_con_profile_id bigint,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.outbox_emails_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.outbox_emails_id_seq OWNED BY public.outbox_emails.id;
+
+CREATE TABLE public.personal_access_tokens (
+    id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    name text NOT NULL,
+    token_digest text NOT NULL,
+    token_hint text NOT NULL,
+    scopes text NOT NULL,
+    expires_at timestamp without time zone,
+    last_used_at timestamp without time zone,
+    last_used_ip character varying,
+    revoked_at timestamp without time zone,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.personal_access_tokens_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.personal_access_tokens_id_seq OWNED BY public.personal_access_tokens.id;
+
+CREATE TABLE public.personal_data_requests (
+    id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    request_kind character varying NOT NULL,
+    details jsonb DEFAULT '{}'::jsonb NOT NULL,
+    created_at timestamp(6) without time zone NOT NULL,
+    updated_at timestamp(6) without time zone NOT NULL
+);
+
+CREATE SEQUENCE public.personal_data_requests_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.personal_data_requests_id_seq OWNED BY public.personal_data_requests.id;
+
+ALTER TABLE public.root_sites
+    ADD COLUMN two_factor_required_for_site_admins boolean DEFAULT false NOT NULL,
+    ADD COLUMN two_factor_required_permissions jsonb DEFAULT '[]'::jsonb NOT NULL;
+
+ALTER TABLE public.sessions
+    ADD COLUMN user_id bigint;
+
+ALTER TABLE public.users
+    ADD COLUMN confirmation_token character varying,
+    ADD COLUMN confirmed_at timestamp without time zone,
+    ADD COLUMN confirmation_sent_at timestamp without time zone,
+    ADD COLUMN failed_attempts integer DEFAULT 0 NOT NULL,
+    ADD COLUMN locked_at timestamp without time zone,
+    ADD COLUMN otp_secret_ciphertext text,
+    ADD COLUMN otp_enabled_at timestamp without time zone,
+    ADD COLUMN otp_consumed_timestep bigint;
+
+ALTER TABLE ONLY public.bulk_email_campaigns ALTER COLUMN id SET DEFAULT nextval('public.bulk_email_campaigns_id_seq'::regclass);
+
+ALTER TABLE ONLY public.cms_content_revisions ALTER COLUMN id SET DEFAULT nextval('public.cms_content_revisions_id_seq'::regclass);
+
+ALTER TABLE ONLY public.cms_rendered_outputs ALTER COLUMN id SET DEFAULT nextval('public.cms_rendered_outputs_id_seq'::regclass);
+
+ALTER TABLE ONLY public.inbound_email_deliveries ALTER COLUMN id SET DEFAULT nextval('public.inbound_email_deliveries_id_seq'::regclass);
+
+ALTER TABLE ONLY public.notification_digest_items ALTER COLUMN id SET DEFAULT nextval('public.notification_digest_items_id_seq'::regclass);
+
+ALTER TABLE ONLY public.notification_preferences ALTER COLUMN id SET DEFAULT nextval('public.notification_preferences_id_seq'::regclass);
+
+ALTER TABLE ONLY public.otp_recovery_codes ALTER COLUMN id SET DEFAULT nextval('public.otp_recovery_codes_id_seq'::regclass);
+
+ALTER TABLE ONLY public.outbox_emails ALTER COLUMN id SET DEFAULT nextval('public.outbox_emails_id_seq'::regclass);
+
+ALTER TABLE ONLY public.personal_access_tokens ALTER COLUMN id SET DEFAULT nextval('public.personal_access_tokens_id_seq'::regclass);
+
+ALTER TABLE ONLY public.personal_data_requests ALTER COLUMN id SET DEFAULT nextval('public.personal_data_requests_id_seq'::regclass);
+
+ALTER TABLE ONLY public.bulk_email_campaigns
+    ADD CONSTRAINT bulk_email_campaigns_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.cms_content_revisions
+    ADD CONSTRAINT cms_content_revisions_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.cms_rendered_outputs
+    ADD CONSTRAINT cms_rendered_outputs_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.inbound_email_deliveries
+    ADD CONSTRAINT inbound_email_deliveries_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.notification_digest_items
+    ADD CONSTRAINT notification_digest_items_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.notification_preferences
+    ADD CONSTRAINT notification_preferences_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.otp_recovery_codes
+    ADD CONSTRAINT otp_recovery_codes_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.outbox_emails
+    ADD CONSTRAINT outbox_emails_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.personal_access_tokens
+    ADD CONSTRAINT personal_access_tokens_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.personal_data_requests
+    ADD CONSTRAINT personal_data_requests_pkey PRIMARY KEY (id);
+
+CREATE INDEX index_bulk_email_campaigns_on_convention_id ON public.bulk_email_campaigns USING btree (convention_id);
+
+CREATE INDEX index_bulk_email_campaigns_on_sender_user_con_profile_id ON public.bulk_email_campaigns USING btree (sender_user_con_profile_id);
+
+CREATE INDEX index_cms_content_revisions_on_author_id ON public.cms_content_revisions USING btree (author_id);
+
+CREATE INDEX index_cms_content_revisions_on_content ON public.cms_content_revisions USING btree (content_type, content_id);
+
+CREATE INDEX index_cms_content_revisions_on_parent_type_and_parent_id ON public.cms_content_revisions USING btree (parent_type, parent_id);
+
+CREATE UNIQUE INDEX index_cms_rendered_outputs_on_cache_key ON public.cms_rendered_outputs USING btree (cache_key);
+
+CREATE INDEX index_cms_rendered_outputs_on_content ON public.cms_rendered_outputs USING btree (content_type, content_id);
+
+CREATE INDEX index_cms_rendered_outputs_on_expires_at ON public.cms_rendered_outputs USING btree (expires_at);
+
+CREATE INDEX index_cms_rendered_outputs_on_parent_type_and_parent_id ON public.cms_rendered_outputs USING btree (parent_type, parent_id);
+
+CREATE UNIQUE INDEX index_inbound_email_deliveries_on_message_key_and_recipient ON public.inbound_email_deliveries USING btree (message_key, recipient);
+
+CREATE INDEX index_notification_digest_items_on_outbox_email_id ON public.notification_digest_items USING btree (outbox_email_id);
+
+CREATE INDEX index_notification_digest_items_on_run_id ON public.notification_digest_items USING btree (run_id);
+
+CREATE INDEX index_notification_digest_items_on_ucp_id_and_delivered_at ON public.notification_digest_items USING btree (user_con_profile_id, delivered_at);
+
+CREATE UNIQUE INDEX index_notification_preferences_on_ucp_id_and_event_key ON public.notification_preferences USING btree (user_con_profile_id, event_key);
+
+CREATE UNIQUE INDEX index_otp_recovery_codes_on_user_id_and_code_digest ON public.otp_recovery_codes USING btree (user_id, code_digest);
+
+CREATE INDEX index_outbox_emails_on_bulk_email_campaign_id ON public.outbox_emails USING btree (bulk_email_campaign_id);
+
+CREATE INDEX index_outbox_emails_on_status_and_send_after ON public.outbox_emails USING btree (status, send_after);
+
+CREATE INDEX index_outbox_emails_on_user_con_profile_id ON public.outbox_emails USING btree (user_con_profile_id);
+
+CREATE UNIQUE INDEX index_personal_access_tokens_on_token_digest ON public.personal_access_tokens USING btree (token_digest);
+
+CREATE INDEX index_personal_access_tokens_on_user_id ON public.personal_access_tokens USING btree (user_id);
+
+CREATE INDEX index_personal_data_requests_on_user_id ON public.personal_data_requests USING btree (user_id);
+
+CREATE INDEX index_sessions_on_user_id ON public.sessions USING btree (user_id);
+
+CREATE UNIQUE INDEX index_users_on_confirmation_token ON public.users USING btree (confirmation_token);
+
+ALTER TABLE ONLY public.personal_access_tokens
+    ADD CONSTRAINT fk_rails_08903b8f38 FOREIGN KEY (user_id) REFERENCES public.users(id);
+
+ALTER TABLE ONLY public.notification_digest_items
+    ADD CONSTRAINT fk_rails_098a6d0740 FOREIGN KEY (run_id) REFERENCES public.runs(id);
+
+ALTER TABLE ONLY public.bulk_email_campaigns
+    ADD CONSTRAINT fk_rails_1744c8a341 FOREIGN KEY (sender_user_con_profile_id) REFERENCES public.user_con_profiles(id);
+
+ALTER TABLE ONLY public.notification_digest_items
+    ADD CONSTRAINT fk_rails_4fa879e52d FOREIGN KEY (user_con_profile_id) REFERENCES public.user_con_profiles(id);
+
+ALTER TABLE ONLY public.notification_digest_items
+    ADD CONSTRAINT fk_rails_68c387210f FOREIGN KEY (outbox_email_id) REFERENCES public.outbox_emails(id);
+
+ALTER TABLE ONLY public.outbox_emails
+    ADD CONSTRAINT fk_rails_81c434d6d3 FOREIGN KEY (bulk_email_campaign_id) REFERENCES public.bulk_email_campaigns(id);
+
+ALTER TABLE ONLY public.cms_content_revisions
+    ADD CONSTRAINT fk_rails_94fa57eb16 FOREIGN KEY (author_id) REFERENCES public.users(id);
+
+ALTER TABLE ONLY public.otp_recovery_codes
+    ADD CONSTRAINT fk_rails_a5fff4c1d9 FOREIGN KEY (user_id) REFERENCES public.users(id);
+
+ALTER TABLE ONLY public.personal_data_requests
+    ADD CONSTRAINT fk_rails_d2e4e340f0 FOREIGN KEY (user_id) REFERENCES public.users(id);
+
+ALTER TABLE ONLY public.outbox_emails
+    ADD CONSTRAINT fk_rails_d3ef1a7956 FOREIGN KEY (user_con_profile_id) REFERENCES public.user_con_profiles(id);
+
+ALTER TABLE ONLY public.notification_preferences
+    ADD CONSTRAINT fk_rails_e38087ba45 FOREIGN KEY (user_con_profile_id) REFERENCES public.user_con_profiles(id);
+
+ALTER TABLE ONLY public.bulk_email_campaigns
+    ADD CONSTRAINT fk_rails_f4c6f3fde6 FOREIGN KEY (convention_id) REFERENCES public.conventions(id);
+
+COMMIT;
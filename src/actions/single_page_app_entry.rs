@@ -82,7 +82,8 @@ pub async fn single_page_app_entry(
   ));
 
   let cms_rendering_context =
-    CmsRenderingContext::new(object!({}), &query_data, liquid_renderer.as_ref());
+    CmsRenderingContext::new(object!({}), &query_data, liquid_renderer.as_ref())
+      .with_output_cache(&schema_data.cms_output_cache);
   // TODO
   let page_title = "TODO";
 
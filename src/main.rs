@@ -15,16 +15,26 @@ use clap::{command, FromArgMatches, Parser, Subcommand};
 use database::connect_database;
 use dotenv::dotenv;
 use indicatif::ProgressBar;
+use intercode_cms::cms_content_sets::{
+  export_cms_content_set, import_cms_content_set, read_content_set, write_content_set,
+  CmsContentSetConflictStrategy,
+};
+use intercode_entities::{cms_parent::CmsParent, conventions, root_sites};
+use intercode_full_text_search::search_index::rebuild_search_index;
 use intercode_graphql::{build_intercode_graphql_schema, build_intercode_graphql_schema_minimal};
-use intercode_graphql_core::schema_data::SchemaData;
+use intercode_graphql_core::{cms_output_cache::CmsOutputCache, schema_data::SchemaData};
 use intercode_liquid_drops::check_liquid::LiquidChecker;
 use intercode_server::i18n::build_language_loader;
 use intercode_server::serve;
+use intercode_users::user_merge::{merge_users, ProfileConflictStrategy};
 use opentelemetry::sdk::trace::{config, Tracer};
 use opentelemetry::sdk::Resource;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
+use sea_orm::{ConnectionTrait, EntityTrait, TransactionTrait};
+use seawater::render_budget::RenderBudget;
 use std::env;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::runtime::Runtime;
 use tonic::metadata::{AsciiMetadataValue, MetadataMap};
@@ -154,6 +164,75 @@ enum Subcommands {
   Serve,
   CheckLiquid,
   ExportSchema,
+  /// Merge a duplicate user account into another one.  Only reports what would happen unless
+  /// --execute is given.
+  MergeUsers {
+    /// The account to keep
+    #[arg(long)]
+    winning_user_id: i64,
+    /// The account to merge into the winning one and then delete
+    #[arg(long)]
+    losing_user_id: i64,
+    /// What to do when both users have a profile in the same convention: abort,
+    /// keep-winning-profile or keep-losing-profile
+    #[arg(long)]
+    profile_conflict_strategy: ProfileConflictStrategy,
+    /// Actually do the merge
+    #[arg(long)]
+    execute: bool,
+  },
+  /// Export a convention's CMS content (or the root site's, if no convention is given) as a
+  /// content set
+  ExportCmsContent {
+    #[arg(long)]
+    convention_id: Option<i64>,
+    /// Where to write the content set: a .json or .zip file, or a new directory
+    #[arg(long)]
+    output: PathBuf,
+  },
+  /// Import a content set into a convention (or the root site, if no convention is given).  Only
+  /// reports what would happen unless --execute is given.
+  ImportCmsContent {
+    #[arg(long)]
+    convention_id: Option<i64>,
+    /// A content set in a .json or .zip file, or a directory
+    #[arg(long)]
+    input: PathBuf,
+    /// What to do with content that has the same name as existing content: skip, overwrite or
+    /// rename
+    #[arg(long)]
+    conflict_strategy: CmsContentSetConflictStrategy,
+    /// Actually do the import
+    #[arg(long)]
+    execute: bool,
+  },
+  /// Rebuild the site search index for a convention, or for everything if no convention is given
+  RebuildSearchIndex {
+    #[arg(long)]
+    convention_id: Option<i64>,
+  },
+}
+
+async fn find_cms_parent<C: ConnectionTrait>(
+  db: &C,
+  convention_id: Option<i64>,
+) -> Result<CmsParent> {
+  match convention_id {
+    Some(convention_id) => Ok(
+      conventions::Entity::find_by_id(convention_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::new(format!("Convention {} not found", convention_id)))?
+        .into(),
+    ),
+    None => Ok(
+      root_sites::Entity::find()
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::new("Root site not found"))?
+        .into(),
+    ),
+  }
 }
 
 fn build_runtime() -> Runtime {
@@ -209,6 +288,8 @@ fn main() -> Result<()> {
 
         let schema_data = SchemaData {
           language_loader: Arc::new(build_language_loader()?),
+          cms_output_cache: CmsOutputCache::disabled(),
+          render_budget: RenderBudget::from_env(),
         };
         let checker = LiquidChecker::new(
           build_intercode_graphql_schema(schema_data.clone()),
@@ -218,6 +299,98 @@ fn main() -> Result<()> {
         checker.check_liquid(startup_bar).await
       })?;
     }
+    Subcommands::MergeUsers {
+      winning_user_id,
+      losing_user_id,
+      profile_conflict_strategy,
+      execute,
+    } => {
+      build_runtime().block_on(async {
+        setup_tracing(EnvFilter::new("error"));
+
+        let db = connect_database().await?;
+        let report = merge_users(
+          &db,
+          winning_user_id,
+          losing_user_id,
+          profile_conflict_strategy,
+          !execute,
+        )
+        .await?;
+        print!("{}", report);
+        if !report.merged && report.blockers.is_empty() {
+          println!("\nThis was a dry run.  Run again with --execute to merge.");
+        }
+
+        Ok::<_, Error>(())
+      })?;
+    }
+    Subcommands::ExportCmsContent {
+      convention_id,
+      output,
+    } => {
+      build_runtime().block_on(async {
+        setup_tracing(EnvFilter::new("error"));
+
+        let db = connect_database().await?;
+        let cms_parent = find_cms_parent(&db, convention_id).await?;
+        let content_set = export_cms_content_set(&db, &cms_parent).await?;
+        write_content_set(&output, &content_set)?;
+        println!(
+          "Exported {} pages, {} partials, {} layouts and {} files to {}",
+          content_set.pages.len(),
+          content_set.partials.len(),
+          content_set.layouts.len(),
+          content_set.files.len(),
+          output.display()
+        );
+
+        Ok::<_, Error>(())
+      })?;
+    }
+    Subcommands::ImportCmsContent {
+      convention_id,
+      input,
+      conflict_strategy,
+      execute,
+    } => {
+      build_runtime().block_on(async {
+        setup_tracing(EnvFilter::new("error"));
+
+        let db = connect_database().await?;
+        let cms_parent = find_cms_parent(&db, convention_id).await?;
+        let content_set = read_content_set(&input)?;
+        let report = import_cms_content_set(
+          &db,
+          &cms_parent,
+          &content_set,
+          conflict_strategy,
+          None,
+          !execute,
+        )
+        .await?;
+        print!("{}", report);
+        if !report.imported {
+          println!("\nThis was a dry run.  Run again with --execute to import.");
+        }
+
+        Ok::<_, Error>(())
+      })?;
+    }
+    Subcommands::RebuildSearchIndex { convention_id } => {
+      build_runtime().block_on(async {
+        setup_tracing(EnvFilter::new("error"));
+
+        let db = connect_database().await?;
+        // in a transaction, so searches keep working off the old index until the new one is done
+        let txn = db.begin().await?;
+        let report = rebuild_search_index(&txn, convention_id).await?;
+        txn.commit().await?;
+        print!("{}", report);
+
+        Ok::<_, Error>(())
+      })?;
+    }
   }
 
   Ok(())
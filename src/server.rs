@@ -2,15 +2,20 @@ use crate::actions;
 use crate::database::connect_database;
 use async_graphql_axum::{GraphQLBatchRequest, GraphQLResponse};
 use axum::extract::{FromRef, State};
-use axum::routing::{get, post, IntoMakeService};
-use axum::Router;
+use axum::routing::{delete, get, post, IntoMakeService};
+use axum::{Extension, Router};
+use intercode_email::outbox::{run_outbox_worker, OutboxConfig};
 use intercode_graphql::actions::{graphql_handler_inner, IntercodeSchema};
 use intercode_graphql::build_intercode_graphql_schema;
+use intercode_graphql_core::after_commit::AfterCommitHooks;
+use intercode_graphql_core::cms_output_cache::CmsOutputCache;
 use intercode_graphql_core::liquid_renderer::LiquidRendererFromRequest;
 use intercode_graphql_core::schema_data::SchemaData;
+use intercode_notifiers::run_digest_worker;
 use intercode_server::i18n::build_language_loader;
 use intercode_server::AuthorizationInfoAndQueryDataFromRequest;
 use sea_orm::DatabaseConnection;
+use seawater::render_budget::RenderBudget;
 use std::sync::Arc;
 
 #[derive(Clone, FromRef)]
@@ -25,25 +30,34 @@ async fn graphql_handler(
   State(state): State<AppState>,
   authorization_info_and_query_data_from_request: AuthorizationInfoAndQueryDataFromRequest,
   liquid_renderer_from_request: LiquidRendererFromRequest,
+  Extension(after_commit_hooks): Extension<AfterCommitHooks>,
   req: GraphQLBatchRequest,
 ) -> GraphQLResponse {
   graphql_handler_inner(
     state.schema,
     authorization_info_and_query_data_from_request,
     liquid_renderer_from_request,
+    after_commit_hooks,
     req,
   )
   .await
 }
 
 pub async fn bootstrap_app() -> Result<IntoMakeService<Router>, async_graphql::Error> {
+  intercode_notifiers::sms::validate_sms_transport_env()?;
+  intercode_users::password_hashing::validate_password_hashing_env()?;
   let db_conn = Arc::new(connect_database().await?);
   let language_loader_arc = Arc::new(build_language_loader()?);
   let schema_data = SchemaData {
     language_loader: language_loader_arc,
+    cms_output_cache: CmsOutputCache::from_env(),
+    render_budget: RenderBudget::from_env(),
   };
   let graphql_schema = build_intercode_graphql_schema(schema_data.clone());
 
+  tokio::spawn(run_outbox_worker(db_conn.clone(), OutboxConfig::from_env()));
+  tokio::spawn(run_digest_worker(db_conn.clone()));
+
   let app_state = AppState {
     schema: graphql_schema,
     schema_data,
@@ -56,19 +70,106 @@ pub async fn bootstrap_app() -> Result<IntoMakeService<Router>, async_graphql::E
         "/graphql",
         get(intercode_graphql::actions::graphql_playground).post(graphql_handler),
       )
+      .route(
+        "/assumed_identity_session",
+        post(intercode_users::actions::start_assumed_identity_session)
+          .delete(intercode_users::actions::stop_assumed_identity_session),
+      )
       .route(
         "/authenticity_tokens",
         get(intercode_server::actions::authenticity_tokens),
       )
+      .route(
+        "/.well-known/openid-configuration",
+        get(intercode_users::actions::openid_configuration),
+      )
+      .route(
+        "/oauth/authorize",
+        get(intercode_users::actions::oauth_pre_authorization)
+          .post(intercode_users::actions::oauth_authorize)
+          .delete(intercode_users::actions::oauth_deny),
+      )
+      .route(
+        "/oauth/discovery/keys",
+        get(intercode_users::actions::oauth_discovery_keys),
+      )
+      .route(
+        "/oauth/revoke",
+        post(intercode_users::actions::oauth_revoke),
+      )
+      .route("/oauth/token", post(intercode_users::actions::oauth_token))
+      .route(
+        "/oauth/userinfo",
+        get(intercode_users::actions::oauth_userinfo)
+          .post(intercode_users::actions::oauth_userinfo),
+      )
+      .route("/users", post(intercode_users::actions::sign_up))
+      .route(
+        "/users/confirmation",
+        get(intercode_users::actions::confirm_email),
+      )
+      .route(
+        "/users/password",
+        post(intercode_users::actions::request_password_reset)
+          .put(intercode_users::actions::reset_password)
+          .patch(intercode_users::actions::reset_password),
+      )
+      .route(
+        "/users/personal_data_export",
+        post(intercode_users::actions::download_personal_data_export),
+      )
       .route("/users/sign_in", post(intercode_users::actions::sign_in))
+      .route(
+        "/users/sign_in/two_factor",
+        post(intercode_users::actions::sign_in_two_factor),
+      )
+      .route(
+        "/users/sign_out",
+        delete(intercode_users::actions::sign_out),
+      )
+      .route(
+        "/users/two_factor",
+        post(intercode_users::actions::begin_two_factor_enrollment)
+          .delete(intercode_users::actions::disable_two_factor),
+      )
+      .route(
+        "/users/two_factor/confirm",
+        post(intercode_users::actions::confirm_two_factor_enrollment),
+      )
+      .route(
+        "/users/two_factor/recovery_codes",
+        post(intercode_users::actions::regenerate_two_factor_recovery_codes),
+      )
       .route(
         "/reports/user_con_profiles/:user_con_profile_id",
         get(intercode_reporting::actions::single_user_printable),
       )
+      .route(
+        "/email/inbound",
+        post(intercode_email::actions::inbound_email),
+      )
+      .route(
+        "/email/sns",
+        post(intercode_email::actions::inbound_email_sns),
+      )
+      .route(
+        "/sms/webhook",
+        post(intercode_notifiers::actions::sms_webhook),
+      )
       .route(
         "/calendars/user_schedule/:ical_secret",
         get(intercode_signups::actions::user_schedule),
       )
+      .route("/sitemap.xml", get(intercode_cms::actions::sitemap))
+      .route("/robots.txt", get(intercode_cms::actions::robots))
+      .route(
+        "/feeds/events.atom",
+        get(intercode_events::actions::events_atom_feed),
+      )
+      .route(
+        "/feeds/events.rss",
+        get(intercode_events::actions::events_rss_feed),
+      )
       .fallback(actions::single_page_app_entry::single_page_app_entry)
   })
 }